@@ -1,10 +1,13 @@
 use crate::io;
+use crate::io::vtk_format::{VtkEncoding, VtkPrecision};
+use crate::reconstruction::CoordinateConventionArg;
 use anyhow::anyhow;
 use anyhow::Context;
 use log::info;
-use splashsurf_lib::mesh::MeshWithData;
+use splashsurf_lib::mesh::{AttributeData, MeshWithData};
 use splashsurf_lib::nalgebra::Vector3;
 use splashsurf_lib::{nalgebra, profile, AxisAlignedBoundingBox3d};
+use std::collections::HashMap;
 use std::path::PathBuf;
 use structopt::StructOpt;
 
@@ -13,7 +16,7 @@ use structopt::StructOpt;
 /// Command line arguments for the `convert` subcommand
 #[derive(Clone, Debug, StructOpt)]
 pub struct ConvertSubcommandArgs {
-    /// Path to the input file with particles to read (supported formats: .vtk, .bgeo, .ply, .xyz, .json)
+    /// Path to the input file with particles to read (supported formats: .vtk, .bgeo, .ply, .xyz, .json, .csv)
     #[structopt(
         long = "--particles",
         parse(from_os_str),
@@ -27,12 +30,15 @@ pub struct ConvertSubcommandArgs {
         conflicts_with = "input_particles"
     )]
     input_mesh: Option<PathBuf>,
-    /// Path to the output file (supported formats for particles: .vtk, for meshes: .obj)
+    /// Path to the output file (supported formats for particles: .vtk, for meshes: .obj, .ply)
     #[structopt(short = "-o", parse(from_os_str))]
     output_file: PathBuf,
     /// Whether to overwrite existing files without asking
     #[structopt(long)]
     overwrite: bool,
+    /// Whether to also read the real valued scalar and vector point data attributes of VTK particle input files (or the velocities of BGEO files) and write them to the output file
+    #[structopt(long)]
+    keep_attributes: bool,
     /// Lower corner of the domain of particles to keep, format: domain-min=x_min;y_min;z_min (requires domain-max to be specified)
     #[structopt(
         long,
@@ -49,6 +55,16 @@ pub struct ConvertSubcommandArgs {
         requires = "domain-min"
     )]
     domain_max: Option<Vec<f64>>,
+    /// Coordinate convention to convert the particles or mesh to, the input is assumed to be right-handed with the z-axis pointing up.
+    /// The triangle winding of meshes is reversed if the handedness changes. Particles are filtered by the domain before the conversion. The conventions are right-handed z-up (zup), right-handed y-up (yup) and left-handed y-up (yuplh).
+    #[structopt(long, default_value = "zup", possible_values = &["zup", "yup", "yuplh"], case_insensitive = true)]
+    coordinate_convention: CoordinateConventionArg,
+    /// Encoding of output VTK files, binary files are written in big endian byte order (the byte order of the VTK specification) by default
+    #[structopt(long, default_value = "binary", possible_values = &["binary", "binary-le", "ascii"], case_insensitive = true)]
+    vtk_encoding: String,
+    /// Floating point precision of the coordinates and attributes in output VTK files, by default the precision of the data is kept
+    #[structopt(long, possible_values = &["f32", "f64"], case_insensitive = true)]
+    vtk_precision: Option<String>,
 }
 
 /// Executes the `convert` subcommand
@@ -66,24 +82,57 @@ pub fn convert_subcommand(cmd_args: &ConvertSubcommandArgs) -> Result<(), anyhow
     Ok(())
 }
 
+/// Returns the file format parameters selected by the command line arguments
+fn format_parameters(
+    cmd_args: &ConvertSubcommandArgs,
+) -> Result<io::FormatParameters, anyhow::Error> {
+    let vtk_encoding = match cmd_args.vtk_encoding.to_lowercase().as_str() {
+        "binary" => VtkEncoding::BinaryBigEndian,
+        "binary-le" => VtkEncoding::BinaryLittleEndian,
+        "ascii" => VtkEncoding::Ascii,
+        encoding => return Err(anyhow!("Invalid VTK encoding \"{}\"", encoding)),
+    };
+    let vtk_precision = match cmd_args.vtk_precision.as_deref().map(str::to_lowercase) {
+        None => None,
+        Some(precision) if precision == "f32" => Some(VtkPrecision::F32),
+        Some(precision) if precision == "f64" => Some(VtkPrecision::F64),
+        Some(precision) => return Err(anyhow!("Invalid VTK precision \"{}\"", precision)),
+    };
+
+    Ok(io::FormatParameters {
+        input: io::InputFormatParameters::default(),
+        output: io::OutputFormatParameters {
+            vtk_precision,
+            vtk_encoding,
+        },
+    })
+}
+
 fn convert_particles(cmd_args: &ConvertSubcommandArgs) -> Result<(), anyhow::Error> {
     profile!("particle file conversion cli");
 
-    let io_params = io::FormatParameters::default();
+    let io_params = format_parameters(cmd_args)?;
     let input_file = cmd_args.input_particles.as_ref().unwrap();
     let output_file = &cmd_args.output_file;
-
-    // Read particles
-    let particle_positions: Vec<Vector3<f32>> =
-        io::read_particle_positions(input_file.as_path(), &io_params.input).with_context(|| {
+    let coordinate_convention = cmd_args.coordinate_convention.into_convention();
+
+    // Read particles and optionally their attributes
+    let (particle_positions, mut attributes): (Vec<Vector3<f32>>, _) =
+        if cmd_args.keep_attributes {
+            io::read_particles(input_file.as_path(), &io_params.input)
+        } else {
+            io::read_particle_positions(input_file.as_path(), &io_params.input)
+                .map(|particle_positions| (particle_positions, HashMap::new()))
+        }
+        .with_context(|| {
             format!(
-                "Failed to load particle positions from file \"{}\"",
+                "Failed to load particles from file \"{}\"",
                 input_file.as_path().display()
             )
         })?;
 
-    // Filter particles by user specified domain
-    let particle_positions = if let (Some(min), Some(max)) =
+    // Filter particles and their attributes by user specified domain
+    let mut particle_positions = if let (Some(min), Some(max)) =
         (cmd_args.domain_min.clone(), cmd_args.domain_max.clone())
     {
         let min = nalgebra::convert(Vector3::from_iterator(min));
@@ -91,17 +140,37 @@ fn convert_particles(cmd_args: &ConvertSubcommandArgs) -> Result<(), anyhow::Err
         let aabb = AxisAlignedBoundingBox3d::new(min, max);
         info!("Filtering out particles outside of {:?}", aabb);
 
+        let keep: Vec<bool> = particle_positions
+            .iter()
+            .map(|p| aabb.contains_point(p))
+            .collect();
+        for data in attributes.values_mut() {
+            match data {
+                AttributeData::ScalarU64(values) => retain_by_mask(values, &keep),
+                AttributeData::ScalarReal(values) => retain_by_mask(values, &keep),
+                AttributeData::Vector3Real(values) => retain_by_mask(values, &keep),
+            }
+        }
+
+        let mut particle_positions = particle_positions;
+        retain_by_mask(&mut particle_positions, &keep);
         particle_positions
-            .into_iter()
-            .filter(|p| aabb.contains_point(p))
-            .collect()
     } else {
         particle_positions
     };
 
+    // Convert particles and vector valued attributes (e.g. velocities) to the requested coordinate convention
+    coordinate_convention.convert_points(&mut particle_positions);
+    for data in attributes.values_mut() {
+        if let AttributeData::Vector3Real(vectors) = data {
+            coordinate_convention.convert_points(vectors);
+        }
+    }
+
     // Write particles
-    io::write_particle_positions(
+    io::write_particles(
         particle_positions.as_slice(),
+        &attributes,
         output_file.as_path(),
         &io_params.output,
     )?;
@@ -112,25 +181,35 @@ fn convert_particles(cmd_args: &ConvertSubcommandArgs) -> Result<(), anyhow::Err
 fn convert_mesh(cmd_args: &ConvertSubcommandArgs) -> Result<(), anyhow::Error> {
     profile!("mesh file conversion cli");
 
-    let io_params = io::FormatParameters::default();
+    let io_params = format_parameters(cmd_args)?;
     let input_file = cmd_args.input_mesh.as_ref().unwrap();
     let output_file = &cmd_args.output_file;
+    let coordinate_convention = cmd_args.coordinate_convention.into_convention();
 
     // Try to load surface mesh
-    let mesh: MeshWithData<f32, _> = io::read_surface_mesh(input_file.as_path(), &io_params.input)
-        .with_context(|| {
+    let mut mesh: MeshWithData<f32, _> =
+        io::read_surface_mesh(input_file.as_path(), &io_params.input).with_context(|| {
             format!(
                 "Failed to load surface mesh from file \"{}\"",
                 input_file.as_path().display()
             )
         })?;
 
+    // Convert mesh and attributes (e.g. normals) to the requested coordinate convention
+    coordinate_convention.convert_mesh_with_data(&mut mesh);
+
     // Write mesh
     io::write_mesh(&mesh, output_file.as_path(), &io_params.output)?;
 
     Ok(())
 }
 
+/// Keeps only the values for which the corresponding entry of the mask is `true`
+fn retain_by_mask<T>(values: &mut Vec<T>, keep: &[bool]) {
+    let mut keep = keep.iter();
+    values.retain(|_| *keep.next().unwrap_or(&false));
+}
+
 /// Returns an error if the file already exists but overwrite is disabled
 fn overwrite_check(cmd_args: &ConvertSubcommandArgs) -> Result<(), anyhow::Error> {
     if !cmd_args.overwrite {
@@ -1,17 +1,21 @@
 use anyhow::{anyhow, Context};
 use log::info;
-use splashsurf_lib::mesh::{Mesh3d, MeshWithData, TriMesh3d};
+use splashsurf_lib::mesh::{AttributeData, Mesh3d, MeshWithData, TriMesh3d};
 use splashsurf_lib::nalgebra::Vector3;
 use splashsurf_lib::profile;
 use splashsurf_lib::Real;
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::{BufWriter, Write};
 use std::path::Path;
 
+#[cfg(feature = "bgeo")]
 pub mod bgeo_format;
 pub mod json_format;
 pub mod obj_format;
 pub mod ply_format;
+pub mod sequence;
+pub mod tiles;
 pub mod vtk_format;
 pub mod xyz_format;
 
@@ -34,11 +38,19 @@ impl Default for InputFormatParameters {
 
 /// File format parameters for output files
 #[derive(Clone, Debug)]
-pub struct OutputFormatParameters {}
+pub struct OutputFormatParameters {
+    /// Precision of the floating point buffers written to VTK files, `None` keeps the precision of the data
+    pub vtk_precision: Option<vtk_format::VtkPrecision>,
+    /// Encoding (ASCII or binary with either byte order) of written VTK files
+    pub vtk_encoding: vtk_format::VtkEncoding,
+}
 
 impl Default for OutputFormatParameters {
     fn default() -> Self {
-        Self {}
+        Self {
+            vtk_precision: None,
+            vtk_encoding: vtk_format::VtkEncoding::BinaryBigEndian,
+        }
     }
 }
 
@@ -61,11 +73,15 @@ pub fn read_particle_positions<R: Real, P: AsRef<Path>>(
             .ok_or(anyhow!("Invalid extension of input file"))?;
 
         match extension.to_lowercase().as_str() {
-            "vtk" => vtk_format::particles_from_vtk(&input_file)?,
-            "xyz" => xyz_format::particles_from_xyz(&input_file)?,
+            "vtk" => splashsurf_lib::io::particles_from_vtk(input_file)
+                .context("Unable to read VTK file")?,
+            "xyz" => splashsurf_lib::io::particles_from_xyz(input_file)
+                .context("Unable to read XYZ file")?,
             "ply" => ply_format::particles_from_ply(&input_file)?,
+            #[cfg(feature = "bgeo")]
             "bgeo" => bgeo_format::particles_from_bgeo(&input_file)?,
             "json" => json_format::particles_from_json(&input_file)?,
+            "csv" => read_xyz(input_file, Some(','))?,
             _ => {
                 return Err(anyhow!(
                     "Unsupported file format extension \"{}\" for reading particles",
@@ -87,16 +103,75 @@ pub fn read_particle_positions<R: Real, P: AsRef<Path>>(
     Ok(particle_positions)
 }
 
-/// Writes particles positions to the given file path, automatically detects the file format
-pub fn write_particle_positions<R: Real, P: AsRef<Path>>(
+/// Loads particles positions together with their per particle attributes from the given file path, automatically detects the file format
+///
+/// Attributes are read from VTK files (see [`vtk_format::particles_with_attributes_from_vtk`]) and the
+/// velocity attribute (`"v"`) of BGEO files (see [`bgeo_format::particles_with_velocities_from_bgeo`]),
+/// for all other formats only the particle positions are read and the returned map is empty.
+pub fn read_particles<R: Real, P: AsRef<Path>>(
+    input_file: P,
+    format_params: &InputFormatParameters,
+) -> Result<vtk_format::ParticlesWithAttributes<R>, anyhow::Error> {
+    let input_file = input_file.as_ref();
+    let extension = input_file
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_lowercase());
+
+    let read_attributes = match extension.as_deref() {
+        Some("vtk") => vtk_format::particles_with_attributes_from_vtk,
+        #[cfg(feature = "bgeo")]
+        Some("bgeo") => bgeo_format::particles_with_attributes_from_bgeo,
+        _ => {
+            return Ok((
+                read_particle_positions(input_file, format_params)?,
+                HashMap::new(),
+            ))
+        }
+    };
+
+    info!(
+        "Reading particle dataset with attributes from \"{}\"...",
+        input_file.display()
+    );
+
+    let (particle_positions, attributes) = {
+        profile!("loading particles with attributes");
+        read_attributes(input_file)?
+    };
+
+    info!(
+        "Successfully read dataset with {} particle positions and {} attribute(s).",
+        particle_positions.len(),
+        attributes.len()
+    );
+
+    Ok((particle_positions, attributes))
+}
+
+/// Loads particle positions from a text file with the coordinates of one particle per line
+///
+/// The coordinates of a line are separated by the given delimiter or by whitespace and/or commas if no delimiter is
+/// specified. Blank lines and comments starting with `#` are skipped.
+pub fn read_xyz<R: Real, P: AsRef<Path>>(
+    input_file: P,
+    delimiter: Option<char>,
+) -> Result<Vec<Vector3<R>>, anyhow::Error> {
+    xyz_format::particles_from_text_xyz(input_file, delimiter)
+}
+
+/// Writes particles positions together with per particle attributes to the given file path, automatically detects the file format
+pub fn write_particles<R: Real, P: AsRef<Path>>(
     particles: &[Vector3<R>],
+    attributes: &HashMap<String, AttributeData<R>>,
     output_file: P,
-    _format_params: &OutputFormatParameters,
+    format_params: &OutputFormatParameters,
 ) -> Result<(), anyhow::Error> {
     let output_file = output_file.as_ref();
     info!(
-        "Writing {} particles to \"{}\"...",
+        "Writing {} particles with {} attribute(s) to \"{}\"...",
         particles.len(),
+        attributes.len(),
         output_file.display()
     );
 
@@ -108,7 +183,7 @@ pub fn write_particle_positions<R: Real, P: AsRef<Path>>(
             .ok_or(anyhow!("Invalid extension of output file"))?;
 
         match extension.to_lowercase().as_str() {
-            "vtk" => vtk_format::particles_to_vtk(particles, &output_file)?,
+            "vtk" => write_particles_vtk(particles, attributes, output_file, format_params)?,
             _ => {
                 return Err(anyhow!(
                     "Unsupported file format extension \"{}\" for writing particles",
@@ -126,6 +201,25 @@ pub fn write_particle_positions<R: Real, P: AsRef<Path>>(
     Ok(())
 }
 
+/// Writes particles positions together with per particle attributes as vertex cells to the given VTK file path
+///
+/// The attributes are written in the order of their names. Precision and encoding of the file are taken from the format parameters.
+pub fn write_particles_vtk<R: Real, P: AsRef<Path>>(
+    particles: &[Vector3<R>],
+    attributes: &HashMap<String, AttributeData<R>>,
+    output_file: P,
+    format_params: &OutputFormatParameters,
+) -> Result<(), anyhow::Error> {
+    profile!("writing particles to vtk");
+    vtk_format::particles_with_attributes_to_vtk(
+        particles,
+        attributes,
+        output_file,
+        format_params.vtk_precision,
+        format_params.vtk_encoding,
+    )
+}
+
 /// Loads a surface mesh from the given file path, automatically detects the file format
 pub fn read_surface_mesh<R: Real, P: AsRef<Path>>(
     input_file: P,
@@ -189,6 +283,7 @@ pub fn write_mesh<R: Real, M: Mesh3d<R>, P: AsRef<Path>>(
 
         match extension.to_lowercase().as_str() {
             "obj" => obj_format::mesh_to_obj(mesh, &output_file)?,
+            "ply" => ply_format::mesh_to_ply(mesh, output_file, true)?,
             _ => {
                 return Err(anyhow!(
                     "Unsupported file format extension \"{}\" for writing meshes",
@@ -3,43 +3,121 @@ use std::fs::File;
 use std::io::Read;
 use std::path::Path;
 
-use anyhow::Context;
+use anyhow::{anyhow, Context};
 use flate2::read::GzDecoder;
 use nom::{Finish, Parser};
+use splashsurf_lib::mesh::AttributeData;
 use splashsurf_lib::nalgebra::Vector3;
 use splashsurf_lib::Real;
 
+use super::vtk_format::ParticlesWithAttributes;
 use parser::bgeo_parser;
 
+/// Name of the point attribute of the particle velocities in BGEO files written by Houdini
+pub const VELOCITY_ATTRIBUTE: &str = "v";
+
+/// Particle positions together with the particle velocities if present in the file
+pub type ParticlesWithVelocities<R> = (Vec<Vector3<R>>, Option<Vec<Vector3<R>>>);
+
 // TODO: Find out why there is a 1.0 float value between position vector and id int in splishsplash output
 // TODO: Better error messages, skip nom errors
 
 pub fn particles_from_bgeo<R: Real, P: AsRef<Path>>(
     bgeo_file: P,
 ) -> Result<Vec<Vector3<R>>, anyhow::Error> {
-    // Load positions from BGEO file
-    let position_storage = {
-        let mut bgeo_file = load_bgeo_file(bgeo_file).context("Error while loading BGEO file")?;
-
-        //println!("header: {:?}", bgeo_file.header);
-        //println!("attrs: {:?}", bgeo_file.point_attributes);
-
-        let storage = bgeo_file
-            .points
-            .remove("position")
-            .expect("Positions should always be in BGEO file");
-
-        if let AttributeStorage::Vector(dim, storage) = storage {
-            assert_eq!(dim, 3);
-            assert_eq!(storage.len() % dim, 0);
-            storage
-        } else {
-            panic!("Positions are not stored as vectors");
+    let mut bgeo_file = load_bgeo_file(bgeo_file).context("Error while loading BGEO file")?;
+
+    //println!("header: {:?}", bgeo_file.header);
+    //println!("attrs: {:?}", bgeo_file.point_attributes);
+
+    positions_from_bgeo_file(&mut bgeo_file)
+}
+
+/// Loads particle positions and the velocities stored in the point attribute with the given name (e.g. `"v"` for Houdini files) from a BGEO file
+///
+/// Returns `None` for the velocities if the file does not contain a point attribute with the given name.
+pub fn particles_with_velocities_from_bgeo<R: Real, P: AsRef<Path>>(
+    bgeo_file: P,
+    velocity_attribute: &str,
+) -> Result<ParticlesWithVelocities<R>, anyhow::Error> {
+    let mut bgeo_file = load_bgeo_file(bgeo_file).context("Error while loading BGEO file")?;
+
+    let positions = positions_from_bgeo_file(&mut bgeo_file)?;
+    let velocities = bgeo_file
+        .points
+        .remove(velocity_attribute)
+        .map(|storage| {
+            vectors_from_storage(storage).with_context(|| {
+                format!(
+                    "Invalid storage of velocity attribute \"{}\"",
+                    velocity_attribute
+                )
+            })
+        })
+        .transpose()?;
+
+    if let Some(velocities) = velocities.as_ref() {
+        if velocities.len() != positions.len() {
+            return Err(anyhow!(
+                "Number of values of velocity attribute \"{}\" ({}) does not match the number of particles ({})",
+                velocity_attribute,
+                velocities.len(),
+                positions.len()
+            ));
         }
+    }
+
+    Ok((positions, velocities))
+}
+
+/// Loads particle positions and the Houdini velocity attribute (`"v"`) if present as particle attribute from a BGEO file
+pub fn particles_with_attributes_from_bgeo<R: Real, P: AsRef<Path>>(
+    bgeo_file: P,
+) -> Result<ParticlesWithAttributes<R>, anyhow::Error> {
+    let (positions, velocities) =
+        particles_with_velocities_from_bgeo(bgeo_file, VELOCITY_ATTRIBUTE)?;
+
+    let mut attributes = HashMap::new();
+    if let Some(velocities) = velocities {
+        attributes.insert(
+            VELOCITY_ATTRIBUTE.to_string(),
+            AttributeData::Vector3Real(velocities),
+        );
+    }
+
+    Ok((positions, attributes))
+}
+
+/// Removes the position attribute from a parsed BGEO file and converts it into individual vectors
+fn positions_from_bgeo_file<R: Real>(
+    bgeo_file: &mut BgeoFile,
+) -> Result<Vec<Vector3<R>>, anyhow::Error> {
+    let storage = bgeo_file
+        .points
+        .remove("position")
+        .expect("Positions should always be in BGEO file");
+
+    vectors_from_storage(storage).context("Invalid storage of positions")
+}
+
+/// Converts the storage of a vector attribute with three components into individual vectors
+fn vectors_from_storage<R: Real>(
+    storage: AttributeStorage,
+) -> Result<Vec<Vector3<R>>, anyhow::Error> {
+    let storage = match storage {
+        AttributeStorage::Vector(3, storage) => storage,
+        AttributeStorage::Vector(dim, _) => {
+            return Err(anyhow!(
+                "Expected vectors with 3 components but vectors have {} components",
+                dim
+            ))
+        }
+        _ => return Err(anyhow!("Attribute is not stored as vectors")),
     };
+    assert_eq!(storage.len() % 3, 0);
 
     // Convert the array storage into individual vectors
-    let positions: Vec<_> = position_storage
+    let vectors = storage
         .chunks(3)
         .map(|p| {
             Vector3::new(
@@ -50,7 +128,7 @@ pub fn particles_from_bgeo<R: Real, P: AsRef<Path>>(
         })
         .collect();
 
-    Ok(positions)
+    Ok(vectors)
 }
 
 /// Loads and parses a BGEO file to memory
@@ -648,3 +726,90 @@ mod error {
         }
     }
 }
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use std::io::Write;
+
+    /// Writes an uncompressed BGEO file with positions and a velocity attribute `"v"` for testing
+    fn write_test_bgeo_file(path: &Path, positions: &[[f32; 3]], velocities: &[[f32; 3]]) {
+        let mut buf = Vec::new();
+
+        // Header: magic bytes, version, number of points, prims, point groups, prim groups, point attributes, vertex attributes, prim attributes, attributes
+        buf.extend_from_slice(b"BgeoV");
+        for value in [5, positions.len() as i32, 0, 0, 0, 1, 0, 0, 0].iter() {
+            buf.extend_from_slice(&value.to_be_bytes());
+        }
+
+        // Definition of the velocity attribute: name, size, type and default values
+        buf.extend_from_slice(&1u16.to_be_bytes());
+        buf.extend_from_slice(b"v");
+        buf.extend_from_slice(&3u16.to_be_bytes());
+        buf.extend_from_slice(&5i32.to_be_bytes());
+        for _ in 0..3 {
+            buf.extend_from_slice(&0i32.to_be_bytes());
+        }
+
+        // Point data: position, homogeneous coordinate, velocity
+        for (p, v) in positions.iter().zip(velocities.iter()) {
+            for value in p.iter().chain(std::iter::once(&1.0)).chain(v.iter()) {
+                buf.extend_from_slice(&value.to_be_bytes());
+            }
+        }
+
+        File::create(path).unwrap().write_all(&buf).unwrap();
+    }
+
+    #[test]
+    fn test_bgeo_read_positions_and_velocities() {
+        let positions = [[0.5, -1.0, 2.0], [1.0, 2.0, 3.0], [-0.25, 0.0, 4.5]];
+        let velocities = [[1.0, 0.0, 0.0], [0.0, -2.0, 0.0], [0.5, 0.5, 0.5]];
+
+        let path = std::env::temp_dir().join("splashsurf_test_bgeo_velocities.bgeo");
+        write_test_bgeo_file(&path, &positions, &velocities);
+
+        let read_positions = particles_from_bgeo::<f64, _>(&path).unwrap();
+        assert_eq!(read_positions.len(), 3);
+        assert_eq!(read_positions[0], Vector3::new(0.5, -1.0, 2.0));
+
+        let (read_positions, read_velocities) =
+            particles_with_velocities_from_bgeo::<f64, _>(&path, "v").unwrap();
+        let read_velocities = read_velocities.expect("velocities should be present");
+        assert_eq!(read_positions.len(), 3);
+        assert_eq!(read_velocities.len(), 3);
+        assert_eq!(read_positions[2], Vector3::new(-0.25, 0.0, 4.5));
+        assert_eq!(read_velocities[1], Vector3::new(0.0, -2.0, 0.0));
+
+        let (_, missing_velocities) =
+            particles_with_velocities_from_bgeo::<f64, _>(&path, "velocity").unwrap();
+        assert!(missing_velocities.is_none());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_bgeo_read_particles_with_attributes() {
+        let positions = [[0.5, -1.0, 2.0], [1.0, 2.0, 3.0]];
+        let velocities = [[1.0, 0.0, 0.0], [0.0, -2.0, 0.0]];
+
+        let path = std::env::temp_dir().join("splashsurf_test_bgeo_attributes.bgeo");
+        write_test_bgeo_file(&path, &positions, &velocities);
+
+        let (read_positions, attributes) =
+            crate::io::read_particles::<f32, _>(&path, &Default::default()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(read_positions[1], Vector3::new(1.0, 2.0, 3.0));
+        assert_eq!(attributes.len(), 1);
+        match attributes.get(VELOCITY_ATTRIBUTE) {
+            Some(AttributeData::Vector3Real(values)) => {
+                assert_eq!(
+                    values,
+                    &vec![Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, -2.0, 0.0)]
+                );
+            }
+            _ => panic!("velocity should be read as vector attribute"),
+        }
+    }
+}
@@ -1,12 +1,11 @@
 use anyhow::Context;
-use splashsurf_lib::mesh::{CellConnectivity, Mesh3d, MeshWithData};
+use splashsurf_lib::mesh::{AttributeData, CellConnectivity, Mesh3d, MeshWithData};
 use splashsurf_lib::Real;
 use std::fs;
 use std::io::{BufWriter, Write};
 use std::path::Path;
 
-// TODO: Support for mesh data, e.g. normals?
-
+/// Writes the mesh to an OBJ file, vertex normals are written if the mesh has a point attribute called "normals"
 pub fn mesh_to_obj<R: Real, M: Mesh3d<R>, P: AsRef<Path>>(
     mesh: &MeshWithData<R, M>,
     filename: P,
@@ -15,20 +14,93 @@ pub fn mesh_to_obj<R: Real, M: Mesh3d<R>, P: AsRef<Path>>(
         .read(true)
         .write(true)
         .create(true)
+        .truncate(true)
         .open(filename)
         .context("Failed to open file handle for writing OBJ file")?;
     let mut writer = BufWriter::with_capacity(100000, file);
 
+    let normals = mesh
+        .point_attributes
+        .iter()
+        .find(|a| a.name == "normals")
+        .and_then(|a| match &a.data {
+            AttributeData::Vector3Real(normals) if normals.len() == mesh.mesh.vertices().len() => {
+                Some(normals)
+            }
+            _ => None,
+        });
+
     let mesh = &mesh.mesh;
     for v in mesh.vertices() {
-        write!(&mut writer, "v {} {} {}\n", v.x, v.y, v.z)?;
+        writeln!(&mut writer, "v {} {} {}", v.x, v.y, v.z)?;
+    }
+
+    if let Some(normals) = normals {
+        for n in normals {
+            writeln!(&mut writer, "vn {} {} {}", n.x, n.y, n.z)?;
+        }
     }
 
     for f in mesh.cells() {
         write!(writer, "f")?;
-        f.try_for_each_vertex(|v| write!(writer, " {}", v + 1))?;
-        write!(writer, "\n")?;
+        if normals.is_some() {
+            f.try_for_each_vertex(|v| write!(writer, " {}//{}", v + 1, v + 1))?;
+        } else {
+            f.try_for_each_vertex(|v| write!(writer, " {}", v + 1))?;
+        }
+        writeln!(writer)?;
     }
 
+    writer.flush()?;
     Ok(())
 }
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use splashsurf_lib::mesh::{MeshAttribute, TriMesh3d};
+    use splashsurf_lib::nalgebra::Vector3;
+
+    #[test]
+    fn test_write_obj() -> Result<(), anyhow::Error> {
+        let mesh = MeshWithData::new(TriMesh3d {
+            vertices: vec![
+                Vector3::new(0.0, 0.0, 0.0),
+                Vector3::new(1.0, 0.0, 0.0),
+                Vector3::new(1.0, 1.0, 0.0),
+                Vector3::new(0.0, 1.0, 0.0),
+            ],
+            triangles: vec![[0, 1, 2], [0, 2, 3]],
+        });
+        let mesh_with_normals = mesh
+            .clone()
+            .with_point_data(MeshAttribute::new_real_vector3(
+                "normals",
+                vec![Vector3::<f64>::z(); 4],
+            ));
+
+        let write_lines = |mesh: &MeshWithData<f64, TriMesh3d<f64>>, name: &str| {
+            let output_file = std::env::temp_dir().join(name);
+            mesh_to_obj(mesh, &output_file)?;
+            let content = fs::read_to_string(&output_file);
+            fs::remove_file(&output_file)?;
+            Ok::<_, anyhow::Error>(content?.lines().map(str::to_string).collect::<Vec<_>>())
+        };
+        let count =
+            |lines: &[String], prefix: &str| lines.iter().filter(|l| l.starts_with(prefix)).count();
+
+        let lines = write_lines(&mesh, "splashsurf_test_write_obj.obj")?;
+        assert_eq!(count(&lines, "v "), 4);
+        assert_eq!(count(&lines, "vn "), 0);
+        assert_eq!(count(&lines, "f "), 2);
+        assert!(lines.contains(&"f 1 3 4".to_string()));
+
+        let lines = write_lines(&mesh_with_normals, "splashsurf_test_write_obj_normals.obj")?;
+        assert_eq!(count(&lines, "v "), 4);
+        assert_eq!(count(&lines, "vn "), 4);
+        assert_eq!(count(&lines, "f "), 2);
+        assert!(lines.contains(&"f 1//1 3//3 4//4".to_string()));
+
+        Ok(())
+    }
+}
@@ -1,3 +1,6 @@
+use std::convert::TryFrom;
+use std::fs;
+use std::io::{BufWriter, Write};
 use std::path::Path;
 
 use anyhow::{anyhow, Context};
@@ -5,6 +8,8 @@ use ply_rs::parser::Parser as PlyParser;
 use ply_rs::ply::{DefaultElement, Ply, Property};
 
 use splashsurf_lib::mesh::AttributeData;
+use splashsurf_lib::mesh::CellConnectivity;
+use splashsurf_lib::mesh::Mesh3d;
 use splashsurf_lib::mesh::MeshAttribute;
 use splashsurf_lib::mesh::MeshWithData;
 use splashsurf_lib::mesh::TriMesh3d;
@@ -31,6 +36,113 @@ pub fn surface_mesh_from_ply<R: Real, P: AsRef<Path>>(
     parse_mesh_from_ply(&ply)
 }
 
+/// Writes the mesh to a PLY file in ASCII or binary little endian encoding
+///
+/// The vertex coordinates are written with the precision of `R` (`float` or `double`). If the mesh has a
+/// point attribute called "normals", the normals are written as vertex properties `nx`, `ny`, `nz`. Cells
+/// with any number of vertices (e.g. the mixed faces of a quad mesh) are written as faces.
+pub fn mesh_to_ply<R: Real, M: Mesh3d<R>, P: AsRef<Path>>(
+    mesh: &MeshWithData<R, M>,
+    filename: P,
+    binary: bool,
+) -> Result<(), anyhow::Error> {
+    let file =
+        fs::File::create(filename).context("Failed to open file handle for writing PLY file")?;
+    let mut writer = BufWriter::with_capacity(100000, file);
+
+    let vertices = mesh.mesh.vertices();
+    let cells = mesh.mesh.cells();
+    let normals = mesh
+        .point_attributes
+        .iter()
+        .find(|a| a.name == "normals")
+        .and_then(|a| match &a.data {
+            AttributeData::Vector3Real(normals) if normals.len() == vertices.len() => {
+                Some(normals.as_slice())
+            }
+            _ => None,
+        });
+
+    let is_double = std::mem::size_of::<R>() > std::mem::size_of::<f32>();
+    let scalar_type = if is_double { "double" } else { "float" };
+
+    writeln!(writer, "ply")?;
+    if binary {
+        writeln!(writer, "format binary_little_endian 1.0")?;
+    } else {
+        writeln!(writer, "format ascii 1.0")?;
+    }
+    writeln!(writer, "element vertex {}", vertices.len())?;
+    for name in ["x", "y", "z"].iter() {
+        writeln!(writer, "property {} {}", scalar_type, name)?;
+    }
+    if normals.is_some() {
+        for name in ["nx", "ny", "nz"].iter() {
+            writeln!(writer, "property {} {}", scalar_type, name)?;
+        }
+    }
+    writeln!(writer, "element face {}", cells.len())?;
+    writeln!(writer, "property list uchar uint vertex_indices")?;
+    writeln!(writer, "end_header")?;
+
+    let conversion_error = || anyhow!("Unable to convert value to output precision");
+    let write_vector = |writer: &mut BufWriter<fs::File>, v: &Vector3<R>| {
+        if !binary {
+            write!(writer, "{} {} {}", v.x, v.y, v.z)?;
+        } else if is_double {
+            for &c in v.iter() {
+                writer.write_all(&c.to_f64().ok_or_else(conversion_error)?.to_le_bytes())?;
+            }
+        } else {
+            for &c in v.iter() {
+                writer.write_all(&c.to_f32().ok_or_else(conversion_error)?.to_le_bytes())?;
+            }
+        }
+        Ok::<_, anyhow::Error>(())
+    };
+
+    for (i, v) in vertices.iter().enumerate() {
+        write_vector(&mut writer, v)?;
+        if let Some(normals) = normals {
+            if !binary {
+                write!(writer, " ")?;
+            }
+            write_vector(&mut writer, &normals[i])?;
+        }
+        if !binary {
+            writeln!(writer)?;
+        }
+    }
+
+    for cell in cells {
+        let num_vertices = u8::try_from(cell.cell_num_vertices())
+            .map_err(|_| anyhow!("Too many vertices per cell for a PLY file"))?;
+        if binary {
+            writer.write_all(&[num_vertices])?;
+        } else {
+            write!(writer, "{}", num_vertices)?;
+        }
+
+        cell.try_for_each_vertex(|v| {
+            let index =
+                u32::try_from(v).map_err(|_| anyhow!("Too many vertices for a PLY file"))?;
+            if binary {
+                writer.write_all(&index.to_le_bytes())?;
+            } else {
+                write!(writer, " {}", index)?;
+            }
+            Ok::<_, anyhow::Error>(())
+        })?;
+
+        if !binary {
+            writeln!(writer)?;
+        }
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
 /// Tries to extract particle positions from the given PLY structure
 fn parse_particles_from_ply<R: Real>(
     ply_file: &Ply<DefaultElement>,
@@ -175,6 +287,56 @@ fn parse_mesh_from_ply<R: Real>(
 pub mod test {
     use super::*;
 
+    #[test]
+    fn test_write_ply() -> Result<(), anyhow::Error> {
+        let mesh = MeshWithData::new(TriMesh3d {
+            vertices: vec![
+                Vector3::new(0.0f32, 0.0, 0.0),
+                Vector3::new(1.0, 0.0, 0.0),
+                Vector3::new(1.0, 1.0, 0.0),
+                Vector3::new(0.0, 1.0, 0.0),
+            ],
+            triangles: vec![[0, 1, 2], [0, 2, 3]],
+        });
+        let normals = vec![Vector3::z(); 4];
+        let mesh_with_normals = mesh
+            .clone()
+            .with_point_data(MeshAttribute::new_real_vector3("normals", normals.clone()));
+
+        for &binary in [false, true].iter() {
+            let output_file =
+                std::env::temp_dir().join(format!("splashsurf_test_write_ply_{}.ply", binary));
+            mesh_to_ply(&mesh_with_normals, &output_file, binary)?;
+
+            // Check the declared element counts in the header
+            let header = {
+                let content = fs::read(&output_file)?;
+                let end = b"end_header\n";
+                let header_len = content
+                    .windows(end.len())
+                    .position(|w| w == end)
+                    .expect("PLY file has no end of header");
+                String::from_utf8(content[..header_len].to_vec())?
+            };
+            assert!(header.contains("element vertex 4\n"));
+            assert!(header.contains("element face 2\n"));
+            assert!(header.contains("property float nx\n"));
+
+            let read_mesh = surface_mesh_from_ply::<f32, _>(&output_file);
+            fs::remove_file(&output_file)?;
+            let read_mesh = read_mesh?;
+
+            assert_eq!(read_mesh.mesh.vertices, mesh.mesh.vertices);
+            assert_eq!(read_mesh.mesh.triangles, mesh.mesh.triangles);
+            match &read_mesh.point_attributes[0].data {
+                AttributeData::Vector3Real(read_normals) => assert_eq!(read_normals, &normals),
+                _ => panic!("normals have wrong attribute type"),
+            }
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn test_read_ply_cube() -> Result<(), anyhow::Error> {
         let input_file = Path::new("../data/cube.ply");
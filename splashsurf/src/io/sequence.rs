@@ -0,0 +1,158 @@
+//! Helpers for exporting reconstructed meshes of input sequences as numbered file sequences
+//!
+//! Pipelines that assemble animated geometry (e.g. converters to Alembic or USD) typically consume one mesh file
+//! per frame with a consistent naming scheme. The output files of a sequence are named by replacing the `{}`
+//! placeholder of the output filename with the frame number, optionally zero-padded to a fixed number of digits
+//! (e.g. `fluid_surface_0001.obj`, `fluid_surface_0002.obj`, ...).
+//!
+//! Optionally, a sidecar JSON metadata file is written next to the meshes once all frames are processed. Its
+//! filename is the output filename with the placeholder replaced by `sequence` and the extension `.json`
+//! (e.g. `fluid_surface_sequence.json`). It contains the frames sorted by frame number:
+//! ```json
+//! {
+//!     "format": "obj",
+//!     "frame_padding": 4,
+//!     "frames": [
+//!         { "frame": 1, "file": "fluid_surface_0001.obj", "num_vertices": 1234, "num_triangles": 2460 },
+//!         { "frame": 2, "file": "fluid_surface_0002.obj", "num_vertices": 1250, "num_triangles": 2492 }
+//!     ]
+//! }
+//! ```
+//! The file paths are relative to the directory of the metadata file.
+
+use anyhow::{anyhow, Context};
+use serde_json::json;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+/// Information about a single exported frame of a mesh sequence
+#[derive(Clone, Debug, PartialEq)]
+pub struct SequenceFrame {
+    /// Frame number as used for the placeholder of the sequence
+    pub frame: usize,
+    /// Path of the mesh file of the frame
+    pub file: PathBuf,
+    /// Number of vertices of the mesh
+    pub num_vertices: usize,
+    /// Number of triangles of the mesh
+    pub num_triangles: usize,
+}
+
+/// Replaces the `{}` placeholder in the filename with the frame number zero-padded to the given number of digits
+pub fn frame_filename(filename_pattern: &str, frame: usize, frame_padding: usize) -> String {
+    filename_pattern.replace("{}", &format!("{:0width$}", frame, width = frame_padding))
+}
+
+/// Returns the path of the sidecar metadata file for the sequence with the given output file pattern
+pub fn metadata_path<P: AsRef<Path>>(output_file_pattern: P) -> Result<PathBuf, anyhow::Error> {
+    let output_file_pattern = output_file_pattern.as_ref();
+    let filename = output_file_pattern
+        .file_name()
+        .ok_or_else(|| {
+            anyhow!(
+                "The output file path '{}' does not end with a filename",
+                output_file_pattern.display()
+            )
+        })?
+        .to_string_lossy();
+
+    Ok(output_file_pattern
+        .with_file_name(filename.replace("{}", "sequence"))
+        .with_extension("json"))
+}
+
+/// Writes the sidecar metadata file of a mesh sequence, see the [module level documentation](self) for the format
+pub fn write_sequence_metadata<P: AsRef<Path>>(
+    metadata_file: P,
+    format: &str,
+    frame_padding: usize,
+    frames: &[SequenceFrame],
+) -> Result<(), anyhow::Error> {
+    let metadata_file = metadata_file.as_ref();
+
+    let mut frames = frames.to_vec();
+    frames.sort_by_key(|f| f.frame);
+
+    let frames = frames
+        .iter()
+        .map(|f| {
+            let file = f.file.file_name().unwrap_or(f.file.as_os_str());
+            json!({
+                "frame": f.frame,
+                "file": file.to_string_lossy(),
+                "num_vertices": f.num_vertices,
+                "num_triangles": f.num_triangles,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let metadata = json!({
+        "format": format,
+        "frame_padding": frame_padding,
+        "frames": frames,
+    });
+
+    let file = File::create(metadata_file).with_context(|| {
+        format!(
+            "Failed to create sequence metadata file '{}'",
+            metadata_file.display()
+        )
+    })?;
+    serde_json::to_writer_pretty(BufWriter::new(file), &metadata)
+        .context("Failed to write sequence metadata")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_filename() {
+        assert_eq!(frame_filename("surface_{}.obj", 7, 0), "surface_7.obj");
+        assert_eq!(frame_filename("surface_{}.obj", 7, 4), "surface_0007.obj");
+        assert_eq!(
+            frame_filename("surface_{}.obj", 12345, 4),
+            "surface_12345.obj"
+        );
+        assert_eq!(
+            metadata_path("out/fluid_surface_{}.obj").unwrap(),
+            Path::new("out/fluid_surface_sequence.json")
+        );
+    }
+
+    #[test]
+    fn test_write_sequence_metadata() {
+        let dir = std::env::temp_dir();
+        let metadata_file = dir.join("splashsurf_test_sequence_metadata.json");
+
+        let frames = vec![
+            SequenceFrame {
+                frame: 2,
+                file: dir.join("surface_0002.obj"),
+                num_vertices: 5,
+                num_triangles: 6,
+            },
+            SequenceFrame {
+                frame: 1,
+                file: dir.join("surface_0001.obj"),
+                num_vertices: 3,
+                num_triangles: 1,
+            },
+        ];
+        write_sequence_metadata(&metadata_file, "obj", 4, &frames).unwrap();
+
+        let metadata: serde_json::Value =
+            serde_json::from_reader(File::open(&metadata_file).unwrap()).unwrap();
+        std::fs::remove_file(&metadata_file).unwrap();
+
+        assert_eq!(metadata["format"], "obj");
+        assert_eq!(metadata["frame_padding"], 4);
+        assert_eq!(metadata["frames"][0]["frame"], 1);
+        assert_eq!(metadata["frames"][0]["file"], "surface_0001.obj");
+        assert_eq!(metadata["frames"][0]["num_vertices"], 3);
+        assert_eq!(metadata["frames"][1]["num_triangles"], 6);
+    }
+}
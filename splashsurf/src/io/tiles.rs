@@ -0,0 +1,159 @@
+//! Helpers for exporting the meshes of a tiled surface reconstruction as separate files
+//!
+//! In tiled mode, the mesh of each tile is written to its own file `tile_{x}_{y}_{z}.vtk` (named by the index of
+//! the tile along each axis) so that downstream tools can assemble very large surfaces without loading all of them
+//! at once. Once all tiles are processed, a `manifest.json` file is written next to the tile meshes that describes
+//! the layout of the tiles:
+//! ```json
+//! {
+//!     "domain_min": [0.0, 0.0, 0.0],
+//!     "domain_max": [2.4, 1.2, 1.2],
+//!     "cube_size": 0.01875,
+//!     "cells_per_tile": 64,
+//!     "num_tiles": [2, 1, 1],
+//!     "ghost_margin": 0.1125,
+//!     "tiles": [
+//!         { "index": [0, 0, 0], "file": "tile_0_0_0.vtk", "min": [0.0, 0.0, 0.0], "max": [1.2, 1.2, 1.2], "num_particles": 51234, "num_vertices": 1234, "num_triangles": 2460 },
+//!         { "index": [1, 0, 0], "file": "tile_1_0_0.vtk", "min": [1.2, 0.0, 0.0], "max": [2.4, 1.2, 1.2], "num_particles": 49876, "num_vertices": 1250, "num_triangles": 2492 }
+//!     ]
+//! }
+//! ```
+//! The file paths are relative to the directory of the manifest. The `min` and `max` corners of a tile bound
+//! the marching cubes cells of the tile (in the coordinates of the input particles), the `ghost_margin` is the
+//! distance around each tile from which particles were collected for its reconstruction.
+//!
+//! The tile meshes are not connected: vertices on a face shared by neighboring tiles are contained in the
+//! meshes of all of these tiles. To assemble a closed mesh, concatenate the meshes and merge vertices on the
+//! tile boundaries that coincide with a vertex of a neighboring tile up to a small tolerance (e.g.
+//! `1e-4 * cube_size`), see also the documentation of `splashsurf_lib::tiling`.
+
+use anyhow::Context;
+use serde_json::json;
+use splashsurf_lib::nalgebra::Vector3;
+use splashsurf_lib::tiling::{Tile, TileLayout};
+use splashsurf_lib::Real;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+/// Filename of the manifest describing the tiles of a tiled reconstruction
+pub const MANIFEST_FILENAME: &str = "manifest.json";
+
+/// Information about the exported mesh of a single tile
+#[derive(Clone, Debug)]
+pub struct TileFile<R: Real> {
+    /// The tile of the reconstruction
+    pub tile: Tile<R>,
+    /// Path of the mesh file of the tile
+    pub file: PathBuf,
+    /// Number of vertices of the mesh
+    pub num_vertices: usize,
+    /// Number of triangles of the mesh
+    pub num_triangles: usize,
+}
+
+/// Returns the filename of the mesh of the tile with the given index
+pub fn tile_filename(index: &[usize; 3]) -> String {
+    format!("tile_{}_{}_{}.vtk", index[0], index[1], index[2])
+}
+
+/// Converts a vector to a JSON array of `f64` values
+fn vector_to_json<R: Real>(v: &Vector3<R>) -> serde_json::Value {
+    json!([
+        v.x.to_f64().unwrap_or(f64::NAN),
+        v.y.to_f64().unwrap_or(f64::NAN),
+        v.z.to_f64().unwrap_or(f64::NAN)
+    ])
+}
+
+/// Writes the manifest of a tiled reconstruction, see the [module level documentation](self) for the format
+pub fn write_tile_manifest<R: Real, P: AsRef<Path>>(
+    manifest_file: P,
+    layout: &TileLayout<R>,
+    tiles: &[TileFile<R>],
+) -> Result<(), anyhow::Error> {
+    let manifest_file = manifest_file.as_ref();
+
+    let tiles = tiles
+        .iter()
+        .map(|t| {
+            let file = t.file.file_name().unwrap_or(t.file.as_os_str());
+            json!({
+                "index": t.tile.index,
+                "file": file.to_string_lossy(),
+                "min": vector_to_json(t.tile.aabb.min()),
+                "max": vector_to_json(t.tile.aabb.max()),
+                "num_particles": t.tile.num_particles,
+                "num_vertices": t.num_vertices,
+                "num_triangles": t.num_triangles,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let manifest = json!({
+        "domain_min": vector_to_json(layout.domain_aabb.min()),
+        "domain_max": vector_to_json(layout.domain_aabb.max()),
+        "cube_size": layout.cube_size.to_f64(),
+        "cells_per_tile": layout.cells_per_tile,
+        "num_tiles": layout.num_tiles,
+        "ghost_margin": layout.ghost_margin.to_f64(),
+        "tiles": tiles,
+    });
+
+    let file = File::create(manifest_file).with_context(|| {
+        format!(
+            "Failed to create tile manifest file '{}'",
+            manifest_file.display()
+        )
+    })?;
+    serde_json::to_writer_pretty(BufWriter::new(file), &manifest)
+        .context("Failed to write tile manifest")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use splashsurf_lib::AxisAlignedBoundingBox3d;
+
+    #[test]
+    fn test_write_tile_manifest() {
+        let dir = std::env::temp_dir();
+        let manifest_file = dir.join("splashsurf_test_tile_manifest.json");
+
+        let layout = TileLayout {
+            domain_aabb: AxisAlignedBoundingBox3d::new(
+                Vector3::new(0.0, 0.0, 0.0),
+                Vector3::new(2.0, 1.0, 1.0),
+            ),
+            cube_size: 0.25,
+            cells_per_tile: 4,
+            num_tiles: [2, 1, 1],
+            ghost_margin: 0.5,
+        };
+        let tiles = vec![TileFile {
+            tile: Tile {
+                index: [1, 0, 0],
+                aabb: layout.tile_aabb(&[1, 0, 0]),
+                num_particles: 10,
+            },
+            file: dir.join(tile_filename(&[1, 0, 0])),
+            num_vertices: 3,
+            num_triangles: 1,
+        }];
+        write_tile_manifest(&manifest_file, &layout, &tiles).unwrap();
+
+        let manifest: serde_json::Value =
+            serde_json::from_reader(File::open(&manifest_file).unwrap()).unwrap();
+        std::fs::remove_file(&manifest_file).unwrap();
+
+        assert_eq!(manifest["num_tiles"], json!([2, 1, 1]));
+        assert_eq!(manifest["cube_size"], 0.25);
+        assert_eq!(manifest["tiles"][0]["file"], "tile_1_0_0.vtk");
+        assert_eq!(manifest["tiles"][0]["index"], json!([1, 0, 0]));
+        assert_eq!(manifest["tiles"][0]["min"], json!([1.0, 0.0, 0.0]));
+        assert_eq!(manifest["tiles"][0]["max"], json!([2.0, 1.0, 1.0]));
+        assert_eq!(manifest["tiles"][0]["num_triangles"], 1);
+    }
+}
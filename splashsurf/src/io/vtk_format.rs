@@ -1,34 +1,123 @@
 use anyhow::{anyhow, Context};
-use splashsurf_lib::mesh::{MeshWithData, TriMesh3d};
+use splashsurf_lib::io::{import_legacy_vtk, particles_from_coords, particles_from_point_buffer};
+use splashsurf_lib::mesh::{AttributeData, MeshWithData, TriMesh3d};
 use splashsurf_lib::nalgebra::Vector3;
 use splashsurf_lib::vtkio;
 use splashsurf_lib::vtkio::model::{
-    Attributes, CellType, Cells, UnstructuredGridPiece, VertexNumbers,
+    Attribute, Attributes, CellType, Cells, DataArray, ElementType, UnstructuredGridPiece,
+    VertexNumbers,
 };
 use splashsurf_lib::Real;
-use std::fs::create_dir_all;
-use std::path::Path;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fs::{create_dir_all, remove_file, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
 use vtkio::model::{ByteOrder, DataSet, Version, Vtk};
 use vtkio::IOBuffer;
 
-/// Tries to read a set of particles from the VTK file at the given path
-pub fn particles_from_vtk<R: Real, P: AsRef<Path>>(
-    vtk_file: P,
-) -> Result<Vec<Vector3<R>>, anyhow::Error> {
-    let particle_dataset = read_vtk(vtk_file)?;
-    particles_from_dataset(particle_dataset)
+/// Floating point precision of the coordinate and attribute buffers written to a VTK file
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum VtkPrecision {
+    /// Write single precision (`float`) buffers
+    F32,
+    /// Write double precision (`double`) buffers
+    F64,
+}
+
+impl VtkPrecision {
+    /// Returns the precision that matches the floating point type `R`
+    pub fn of<R: Real>() -> Self {
+        if std::mem::size_of::<R>() > std::mem::size_of::<f32>() {
+            VtkPrecision::F64
+        } else {
+            VtkPrecision::F32
+        }
+    }
+}
+
+/// Encoding of the data of a legacy VTK file
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum VtkEncoding {
+    /// Human readable ASCII file
+    Ascii,
+    /// Big endian binary file (the byte order of the VTK specification)
+    #[default]
+    BinaryBigEndian,
+    /// Little endian binary file
+    BinaryLittleEndian,
 }
 
-/// Tries to write a set of particles to the VTK file at the given path
-pub fn particles_to_vtk<R: Real, P: AsRef<Path>>(
+/// Tries to write a set of particles together with per particle attributes as vertex cells to the VTK file at the given path
+///
+/// The particle coordinates and all real valued attributes are converted to the given precision,
+/// if no precision is given the precision of the input type `R` is used. The attributes are written
+/// in the order of their names, i.e. this is the inverse of [`particles_with_attributes_from_vtk`].
+pub fn particles_with_attributes_to_vtk<R: Real, P: AsRef<Path>>(
     particles: &[Vector3<R>],
+    attributes: &HashMap<String, AttributeData<R>>,
     vtk_file: P,
+    precision: Option<VtkPrecision>,
+    encoding: VtkEncoding,
 ) -> Result<(), anyhow::Error> {
-    write_vtk(
-        UnstructuredGridPiece::from(Particles(particles)),
-        vtk_file,
-        "particles",
-    )
+    let precision = precision.unwrap_or_else(VtkPrecision::of::<R>);
+
+    let mut grid_piece = UnstructuredGridPiece::from(Particles(particles));
+    grid_piece.points = real_buffer(particles.iter().flatten().copied(), precision)?;
+
+    let mut attributes: Vec<_> = attributes.iter().collect();
+    attributes.sort_unstable_by_key(|(name, _)| name.as_str());
+
+    for (name, data) in attributes {
+        let num_values = match data {
+            AttributeData::ScalarU64(values) => values.len(),
+            AttributeData::ScalarReal(values) => values.len(),
+            AttributeData::Vector3Real(values) => values.len(),
+        };
+
+        if num_values != particles.len() {
+            return Err(anyhow!(
+                "Number of values of particle attribute \"{}\" ({}) does not match the number of particles ({})",
+                name,
+                num_values,
+                particles.len()
+            ));
+        }
+
+        let vtk_attribute = match data {
+            AttributeData::ScalarU64(values) => {
+                Attribute::scalars(name, 1).with_data(values.clone())
+            }
+            AttributeData::ScalarReal(values) => Attribute::scalars(name, 1)
+                .with_data(real_buffer(values.iter().copied(), precision)?),
+            AttributeData::Vector3Real(values) => Attribute::scalars(name, 3)
+                .with_data(real_buffer(values.iter().flatten().copied(), precision)?),
+        };
+
+        grid_piece.data.point.push(vtk_attribute);
+    }
+
+    write_vtk_with_encoding(grid_piece, vtk_file, "particles", encoding)
+}
+
+/// Converts the given real values into an `IOBuffer` with the given precision
+fn real_buffer<R: Real>(
+    values: impl Iterator<Item = R>,
+    precision: VtkPrecision,
+) -> Result<IOBuffer, anyhow::Error> {
+    let conversion_error = || anyhow!("Unable to convert value to output precision");
+    Ok(match precision {
+        VtkPrecision::F32 => IOBuffer::F32(
+            values
+                .map(|v| v.to_f32().ok_or_else(conversion_error))
+                .collect::<Result<Vec<_>, _>>()?,
+        ),
+        VtkPrecision::F64 => IOBuffer::F64(
+            values
+                .map(|v| v.to_f64().ok_or_else(conversion_error))
+                .collect::<Result<Vec<_>, _>>()?,
+        ),
+    })
 }
 
 /// Tries to read a surface mesh from the VTK file at the given path
@@ -44,12 +133,25 @@ pub fn write_vtk<P: AsRef<Path>>(
     data: impl Into<DataSet>,
     filename: P,
     title: &str,
+) -> Result<(), anyhow::Error> {
+    write_vtk_with_encoding(data, filename, title, VtkEncoding::BinaryBigEndian)
+}
+
+/// Tries to write `data` that is convertible to a VTK `DataSet` into a VTK file with the given encoding
+pub fn write_vtk_with_encoding<P: AsRef<Path>>(
+    data: impl Into<DataSet>,
+    filename: P,
+    title: &str,
+    encoding: VtkEncoding,
 ) -> Result<(), anyhow::Error> {
     let vtk_file = Vtk {
         version: Version::new((4, 1)),
         title: title.to_string(),
         file_path: None,
-        byte_order: ByteOrder::BigEndian,
+        byte_order: match encoding {
+            VtkEncoding::BinaryLittleEndian => ByteOrder::LittleEndian,
+            VtkEncoding::Ascii | VtkEncoding::BinaryBigEndian => ByteOrder::BigEndian,
+        },
         data: data.into(),
     };
 
@@ -57,54 +159,125 @@ pub fn write_vtk<P: AsRef<Path>>(
     if let Some(dir) = filename.parent() {
         create_dir_all(dir).context("Failed to create parent directory of output file")?;
     }
-    vtk_file
-        .export_be(filename)
-        .context("Error while writing VTK output to file")
+    match encoding {
+        VtkEncoding::Ascii => vtk_file.export_ascii(filename),
+        VtkEncoding::BinaryBigEndian => vtk_file.export_be(filename),
+        VtkEncoding::BinaryLittleEndian => vtk_file.export_le(filename),
+    }
+    .context("Error while writing VTK output to file")
 }
 
-/// Tries to read the given file into a VTK `DataSet`
+/// Tries to read the given file into a VTK `DataSet`, supports ASCII and binary files of both byte orders
 pub fn read_vtk<P: AsRef<Path>>(filename: P) -> Result<DataSet, vtkio::Error> {
-    let filename = filename.as_ref();
-    Vtk::import_legacy_be(filename).map(|vtk| vtk.data)
+    import_legacy_vtk(filename).map(|vtk| vtk.data)
+}
+
+/// Particle positions together with a map of per particle attributes by name
+pub type ParticlesWithAttributes<R> = (Vec<Vector3<R>>, HashMap<String, AttributeData<R>>);
+
+/// Tries to read a set of particles together with their real valued scalar and vector point data attributes from the VTK file at the given path
+pub fn particles_with_attributes_from_vtk<R: Real, P: AsRef<Path>>(
+    vtk_file: P,
+) -> Result<ParticlesWithAttributes<R>, anyhow::Error> {
+    let particle_dataset = read_vtk(vtk_file)?;
+    particles_with_attributes_from_dataset(particle_dataset)
 }
 
-/// Tries to convert a vector of consecutive coordinate triplets into a vector of `Vector3`, also converts between floating point types
-pub fn particles_from_coords<RealOut: Real, RealIn: Real>(
-    coords: &Vec<RealIn>,
-) -> Result<Vec<Vector3<RealOut>>, anyhow::Error> {
-    if coords.len() % 3 != 0 {
-        anyhow!("The number of values in the particle data point buffer is not divisible by 3");
+/// Tries to convert a VTK `DataSet` into a vector of particle positions and a map of their point data attributes
+///
+/// Supported are point data arrays with `f32` or `f64` values and either one component (converted to
+/// [`AttributeData::ScalarReal`]) or three components (converted to [`AttributeData::Vector3Real`]).
+/// Returns an error if the dataset contains point data of any other type.
+pub fn particles_with_attributes_from_dataset<R: Real>(
+    dataset: DataSet,
+) -> Result<ParticlesWithAttributes<R>, anyhow::Error> {
+    let piece = first_unstructured_grid_piece(dataset)?;
+    let particles = particles_from_point_buffer(piece.points)?;
+
+    // Collect the (name, number of components, values) of all point data arrays
+    let mut arrays = Vec::new();
+    for attribute in piece.data.point {
+        match attribute {
+            Attribute::DataArray(DataArray { name, elem, data }) => {
+                let num_comp = match elem {
+                    ElementType::Scalars { num_comp, .. } => num_comp,
+                    ElementType::Vectors | ElementType::Normals => 3,
+                    ElementType::Generic(num_comp) => num_comp,
+                    _ => {
+                        return Err(anyhow!(
+                            "Unsupported element type of point data attribute \"{}\"",
+                            name
+                        ))
+                    }
+                };
+                arrays.push((name, num_comp, data));
+            }
+            Attribute::Field { data_array, .. } => {
+                for array in data_array {
+                    arrays.push((array.name, array.elem, array.data));
+                }
+            }
+        }
     }
 
-    let num_points = coords.len() / 3;
-    let mut positions = Vec::with_capacity(num_points);
-    for i in 0..num_points {
-        positions.push(Vector3::new(
-            RealOut::from_f64(coords[3 * i + 0].to_f64().unwrap()).unwrap(),
-            RealOut::from_f64(coords[3 * i + 1].to_f64().unwrap()).unwrap(),
-            RealOut::from_f64(coords[3 * i + 2].to_f64().unwrap()).unwrap(),
-        ))
+    let mut attributes = HashMap::with_capacity(arrays.len());
+    for (name, num_comp, data) in arrays {
+        let values: Vec<R> = match data {
+            IOBuffer::F64(values) => values
+                .into_iter()
+                .map(|v| R::from_f64(v).unwrap())
+                .collect(),
+            IOBuffer::F32(values) => values
+                .into_iter()
+                .map(|v| R::from_f32(v).unwrap())
+                .collect(),
+            _ => {
+                return Err(anyhow!(
+                    "Point data attribute \"{}\" does not contain f32 or f64 values",
+                    name
+                ))
+            }
+        };
+
+        let data = match num_comp {
+            1 => AttributeData::ScalarReal(values),
+            3 => AttributeData::Vector3Real(particles_from_coords(&values)?),
+            _ => {
+                return Err(anyhow!(
+                    "Point data attribute \"{}\" has {} components, only scalar and 3D vector attributes are supported",
+                    name,
+                    num_comp
+                ))
+            }
+        };
+
+        let num_values = match &data {
+            AttributeData::ScalarU64(values) => values.len(),
+            AttributeData::ScalarReal(values) => values.len(),
+            AttributeData::Vector3Real(values) => values.len(),
+        };
+        if num_values != particles.len() {
+            return Err(anyhow!(
+                "Number of values of point data attribute \"{}\" ({}) does not match the number of particles ({})",
+                name,
+                num_values,
+                particles.len()
+            ));
+        }
+
+        attributes.insert(name, data);
     }
 
-    Ok(positions)
+    Ok((particles, attributes))
 }
 
-/// Tries to convert a VTK `DataSet` into a vector of particle positions
-pub fn particles_from_dataset<R: Real>(dataset: DataSet) -> Result<Vec<Vector3<R>>, anyhow::Error> {
+/// Returns the first unstructured grid piece of a VTK `DataSet` with all of its data loaded
+fn first_unstructured_grid_piece(dataset: DataSet) -> Result<UnstructuredGridPiece, anyhow::Error> {
     if let DataSet::UnstructuredGrid { pieces, .. } = dataset {
         if let Some(piece) = pieces.into_iter().next() {
-            let points = piece
+            Ok(piece
                 .into_loaded_piece_data(None)
-                .context("Failed to load unstructured grid piece")?
-                .points;
-
-            match points {
-                IOBuffer::F64(coords) => particles_from_coords(&coords),
-                IOBuffer::F32(coords) => particles_from_coords(&coords),
-                _ => Err(anyhow!(
-                    "Point coordinate IOBuffer does not contain f32 or f64 values"
-                )),
-            }
+                .context("Failed to load unstructured grid piece")?)
         } else {
             Err(anyhow!(
                 "Loaded dataset does not contain an unstructured grid piece"
@@ -128,12 +301,14 @@ pub fn surface_mesh_from_dataset<R: Real>(
                 .context("Failed to load unstructured grid piece")?;
 
             let vertices = match piece.points {
-                IOBuffer::F64(coords) => particles_from_coords(&coords),
-                IOBuffer::F32(coords) => particles_from_coords(&coords),
-                _ => Err(anyhow!(
-                    "Point coordinate IOBuffer does not contain f32 or f64 values"
-                )),
-            }?;
+                IOBuffer::F64(coords) => particles_from_coords(&coords)?,
+                IOBuffer::F32(coords) => particles_from_coords(&coords)?,
+                _ => {
+                    return Err(anyhow!(
+                        "Point coordinate IOBuffer does not contain f32 or f64 values"
+                    ))
+                }
+            };
 
             let triangles = {
                 let (num_cells, cell_verts) = piece.cells.cell_verts.into_legacy();
@@ -169,6 +344,167 @@ pub fn surface_mesh_from_dataset<R: Real>(
     }
 }
 
+/// Writer that incrementally writes a triangle mesh part by part into a binary (big endian) legacy VTK file
+///
+/// Legacy VTK files store the number of points and cells in front of the respective data. As these
+/// numbers are only known after the last part of the mesh was written, the vertex and triangle data
+/// is spilled to two temporary files next to the output file which are assembled into the final file
+/// by [`StreamingTriMeshVtkWriter::finish`]. This implies that:
+///  - only the current part of the mesh has to be kept in memory,
+///  - up to twice the size of the output file is temporarily required on disk,
+///  - vertices are not shared between parts, i.e. seams between parts are not welded,
+///  - the output file only exists after a successful call to `finish`.
+///
+/// The writer is used by the CLI to stream the meshes of a tiled reconstruction into a single file.
+pub struct StreamingTriMeshVtkWriter {
+    output_file: PathBuf,
+    title: String,
+    precision: VtkPrecision,
+    points_file: PathBuf,
+    cells_file: PathBuf,
+    points: BufWriter<File>,
+    cells: BufWriter<File>,
+    num_vertices: usize,
+    num_triangles: usize,
+}
+
+impl StreamingTriMeshVtkWriter {
+    /// Creates a new writer for the given output file, the vertex coordinates are written with the given precision
+    pub fn new<P: AsRef<Path>>(
+        output_file: P,
+        title: &str,
+        precision: VtkPrecision,
+    ) -> Result<Self, anyhow::Error> {
+        let output_file = output_file.as_ref().to_path_buf();
+        if let Some(dir) = output_file.parent() {
+            create_dir_all(dir).context("Failed to create parent directory of output file")?;
+        }
+
+        let temp_file = |suffix: &str| {
+            let mut file_name = output_file.file_name().unwrap_or_default().to_os_string();
+            file_name.push(suffix);
+            output_file.with_file_name(file_name)
+        };
+        let points_file = temp_file(".points.tmp");
+        let cells_file = temp_file(".cells.tmp");
+
+        let create = |path: &Path| -> Result<BufWriter<File>, anyhow::Error> {
+            Ok(BufWriter::new(File::create(path).with_context(|| {
+                format!("Failed to create temporary file \"{}\"", path.display())
+            })?))
+        };
+
+        Ok(Self {
+            points: create(&points_file)?,
+            cells: create(&cells_file)?,
+            output_file,
+            title: title.to_string(),
+            precision,
+            points_file,
+            cells_file,
+            num_vertices: 0,
+            num_triangles: 0,
+        })
+    }
+
+    /// Returns the number of vertices written so far
+    pub fn num_vertices(&self) -> usize {
+        self.num_vertices
+    }
+
+    /// Returns the number of triangles written so far
+    pub fn num_triangles(&self) -> usize {
+        self.num_triangles
+    }
+
+    /// Appends the vertices and triangles of the given mesh part to the output
+    pub fn write_part<R: Real>(&mut self, mesh: &TriMesh3d<R>) -> Result<(), anyhow::Error> {
+        let conversion_error = || anyhow!("Unable to convert value to output precision");
+        for v in mesh.vertices.iter() {
+            for &c in v.iter() {
+                match self.precision {
+                    VtkPrecision::F32 => self
+                        .points
+                        .write_all(&c.to_f32().ok_or_else(conversion_error)?.to_be_bytes())?,
+                    VtkPrecision::F64 => self
+                        .points
+                        .write_all(&c.to_f64().ok_or_else(conversion_error)?.to_be_bytes())?,
+                }
+            }
+        }
+
+        let vertex_offset = self.num_vertices;
+        for tri in mesh.triangles.iter() {
+            self.cells.write_all(&3i32.to_be_bytes())?;
+            for &v in tri.iter() {
+                if v >= mesh.vertices.len() {
+                    return Err(anyhow!("Triangle refers to non-existent vertex {}", v));
+                }
+                let index = i32::try_from(v + vertex_offset)
+                    .map_err(|_| anyhow!("Too many vertices for a legacy VTK file"))?;
+                self.cells.write_all(&index.to_be_bytes())?;
+            }
+        }
+
+        self.num_vertices += mesh.vertices.len();
+        self.num_triangles += mesh.triangles.len();
+        Ok(())
+    }
+
+    /// Assembles the output file from all written parts and removes the temporary files
+    pub fn finish(mut self) -> Result<(), anyhow::Error> {
+        self.points.flush()?;
+        self.cells.flush()?;
+
+        let num_cell_entries = i32::try_from(4 * self.num_triangles)
+            .map_err(|_| anyhow!("Too many triangles for a legacy VTK file"))?;
+        let precision = match self.precision {
+            VtkPrecision::F32 => "float",
+            VtkPrecision::F64 => "double",
+        };
+
+        let write_output = || -> Result<(), io::Error> {
+            let mut output = BufWriter::new(File::create(&self.output_file)?);
+            writeln!(
+                output,
+                "# vtk DataFile Version 4.1\n{}\nBINARY\n\nDATASET UNSTRUCTURED_GRID",
+                self.title
+            )?;
+
+            writeln!(output, "POINTS {} {}", self.num_vertices, precision)?;
+            io::copy(&mut File::open(&self.points_file)?, &mut output)?;
+            writeln!(output)?;
+
+            writeln!(output, "CELLS {} {}", self.num_triangles, num_cell_entries)?;
+            io::copy(&mut File::open(&self.cells_file)?, &mut output)?;
+            writeln!(output)?;
+
+            writeln!(output, "CELL_TYPES {}", self.num_triangles)?;
+            let triangle_type = (CellType::Triangle as i32).to_be_bytes();
+            for _ in 0..self.num_triangles {
+                output.write_all(&triangle_type)?;
+            }
+            writeln!(output)?;
+            output.flush()
+        };
+
+        write_output().with_context(|| {
+            format!(
+                "Error while writing VTK output to file \"{}\"",
+                self.output_file.display()
+            )
+        })
+    }
+}
+
+impl Drop for StreamingTriMeshVtkWriter {
+    /// Removes the temporary files of the writer
+    fn drop(&mut self) {
+        let _ = remove_file(&self.points_file);
+        let _ = remove_file(&self.cells_file);
+    }
+}
+
 /// Wrapper for a slice of particle positions for converting it into a VTK `UnstructuredGridPiece`
 struct Particles<'a, R: Real>(&'a [Vector3<R>]);
 
@@ -211,3 +547,255 @@ where
         }
     }
 }
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use splashsurf_lib::io::{
+        particles_from_coords_incremental, particles_from_dataset, particles_from_vtk,
+    };
+    use splashsurf_lib::mesh::HexMesh3d;
+
+    #[test]
+    fn test_particles_from_vtk_encodings() -> Result<(), anyhow::Error> {
+        let particles = vec![
+            Vector3::new(0.0, 0.5, 1.0),
+            Vector3::new(-1.5, 2.0, 0.25),
+            Vector3::new(3.0, -0.75, 1.5),
+        ];
+
+        let vtk_file = |byte_order| Vtk {
+            version: Version::new((4, 1)),
+            title: "particles".to_string(),
+            file_path: None,
+            byte_order,
+            data: UnstructuredGridPiece::from(Particles(particles.as_slice())).into(),
+        };
+        let temp_file = |name: &str| std::env::temp_dir().join(name);
+
+        let files = [
+            temp_file("splashsurf_test_particles_ascii.vtk"),
+            temp_file("splashsurf_test_particles_be.vtk"),
+            temp_file("splashsurf_test_particles_le.vtk"),
+        ];
+        vtk_file(ByteOrder::BigEndian).export_ascii(&files[0])?;
+        vtk_file(ByteOrder::BigEndian).export_be(&files[1])?;
+        vtk_file(ByteOrder::LittleEndian).export_le(&files[2])?;
+
+        for file in files.iter() {
+            let read_particles = particles_from_vtk::<f64, _>(file);
+            remove_file(file)?;
+            assert_eq!(read_particles?, particles, "file: {}", file.display());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_particles_from_vtk_incremental() -> Result<(), anyhow::Error> {
+        let particles: Vec<_> = (0..1000)
+            .map(|i| {
+                let i = i as f64;
+                Vector3::new(0.5 * i, (0.1 * i).sin(), -0.25 * i)
+            })
+            .collect();
+
+        for &precision in [VtkPrecision::F32, VtkPrecision::F64].iter() {
+            let vtk_file = std::env::temp_dir().join(
+                format!("splashsurf_test_particles_incremental_{:?}.vtk", precision).to_lowercase(),
+            );
+            particles_with_attributes_to_vtk(
+                &particles,
+                &HashMap::new(),
+                &vtk_file,
+                Some(precision),
+                VtkEncoding::BinaryBigEndian,
+            )?;
+
+            let dataset = read_vtk(&vtk_file);
+            remove_file(&vtk_file)?;
+            let dataset = dataset?;
+
+            // Convert the full coordinate buffer without releasing it
+            let points = if let DataSet::UnstructuredGrid { pieces, .. } = dataset.clone() {
+                pieces
+                    .into_iter()
+                    .next()
+                    .unwrap()
+                    .into_loaded_piece_data(None)?
+                    .points
+            } else {
+                panic!("dataset should be an unstructured grid");
+            };
+            let expected = match points.clone() {
+                IOBuffer::F64(coords) => particles_from_coords::<f64, _>(&coords)?,
+                IOBuffer::F32(coords) => particles_from_coords::<f64, _>(&coords)?,
+                _ => panic!("points should be stored as floating point values"),
+            };
+            assert_eq!(expected.len(), particles.len());
+
+            assert_eq!(particles_from_dataset::<f64>(dataset)?, expected);
+            for &chunk_size in [0, 1, 7, 999, 1000, 5000].iter() {
+                let incremental = match points.clone() {
+                    IOBuffer::F64(coords) => {
+                        particles_from_coords_incremental::<f64, _>(coords, chunk_size)?
+                    }
+                    IOBuffer::F32(coords) => {
+                        particles_from_coords_incremental::<f64, _>(coords, chunk_size)?
+                    }
+                    _ => unreachable!(),
+                };
+                assert_eq!(incremental, expected, "chunk size: {}", chunk_size);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_particles_with_attributes_from_vtk() -> Result<(), anyhow::Error> {
+        let particles = vec![
+            Vector3::new(0.0, 0.5, 1.0),
+            Vector3::new(-1.5, 2.0, 0.25),
+            Vector3::new(3.0, -0.75, 1.5),
+        ];
+        let velocities = vec![
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(0.0, -2.0, 0.0),
+            Vector3::new(0.5, 0.5, 0.5),
+        ];
+        let densities = vec![1000.0, 1010.0, 990.0];
+
+        let vtk_file = std::env::temp_dir().join("splashsurf_test_particles_attributes.vtk");
+        particles_with_attributes_to_vtk(
+            &particles,
+            &vec![
+                (
+                    "velocity".to_string(),
+                    AttributeData::Vector3Real(velocities.clone()),
+                ),
+                (
+                    "density".to_string(),
+                    AttributeData::ScalarReal(densities.clone()),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+            &vtk_file,
+            Some(VtkPrecision::F32),
+            VtkEncoding::BinaryBigEndian,
+        )?;
+        let read = particles_with_attributes_from_vtk::<f64, _>(&vtk_file);
+        remove_file(&vtk_file)?;
+        let (read_particles, attributes) = read?;
+
+        assert_eq!(read_particles, particles);
+        assert_eq!(attributes.len(), 2);
+        match attributes.get("velocity") {
+            Some(AttributeData::Vector3Real(values)) => {
+                assert_eq!(values.len(), particles.len());
+                assert_eq!(values, &velocities);
+            }
+            _ => panic!("velocity should be read as vector attribute"),
+        }
+        match attributes.get("density") {
+            Some(AttributeData::ScalarReal(values)) => assert_eq!(values, &densities),
+            _ => panic!("density should be read as scalar attribute"),
+        }
+
+        // Integer attributes are not supported
+        particles_with_attributes_to_vtk(
+            &particles,
+            &vec![(
+                "id".to_string(),
+                AttributeData::<f64>::ScalarU64(vec![0, 1, 2]),
+            )]
+            .into_iter()
+            .collect(),
+            &vtk_file,
+            None,
+            VtkEncoding::BinaryBigEndian,
+        )?;
+        let read = particles_with_attributes_from_vtk::<f64, _>(&vtk_file);
+        remove_file(&vtk_file)?;
+        assert!(read.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_hexmesh_encodings() -> Result<(), anyhow::Error> {
+        let mut vertices = Vec::new();
+        for &z in [0.0, 1.0].iter() {
+            vertices.push(Vector3::new(0.0, 0.0, z));
+            vertices.push(Vector3::new(1.5, 0.0, z));
+            vertices.push(Vector3::new(1.5, 0.25, z));
+            vertices.push(Vector3::new(0.0, 0.25, z));
+        }
+        let mesh = MeshWithData::new(HexMesh3d {
+            vertices,
+            cells: vec![[0, 1, 2, 3, 4, 5, 6, 7]],
+        });
+
+        for &encoding in [
+            VtkEncoding::Ascii,
+            VtkEncoding::BinaryBigEndian,
+            VtkEncoding::BinaryLittleEndian,
+        ]
+        .iter()
+        {
+            let output_file = std::env::temp_dir()
+                .join(format!("splashsurf_test_hexmesh_{:?}.vtk", encoding).to_lowercase());
+            write_vtk_with_encoding(mesh.to_unstructured_grid(), &output_file, "mesh", encoding)?;
+
+            let read_vertices = read_vtk(&output_file)
+                .map_err(anyhow::Error::from)
+                .and_then(|dataset| Ok(particles_from_dataset::<f64>(dataset)?));
+            remove_file(&output_file)?;
+            assert_eq!(
+                read_vertices?, mesh.mesh.vertices,
+                "encoding: {:?}",
+                encoding
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_streaming_tri_mesh_writer() -> Result<(), anyhow::Error> {
+        let output_file = std::env::temp_dir().join("splashsurf_test_streaming_writer.vtk");
+
+        let part = TriMesh3d {
+            vertices: vec![Vector3::zeros(), Vector3::x(), Vector3::y()],
+            triangles: vec![[0, 1, 2]],
+        };
+        let shifted_part = TriMesh3d {
+            vertices: part.vertices.iter().map(|v| v + Vector3::z()).collect(),
+            triangles: vec![[0, 2, 1]],
+        };
+
+        let mut writer =
+            StreamingTriMeshVtkWriter::new(&output_file, "streaming", VtkPrecision::F32)?;
+        writer.write_part::<f64>(&part)?;
+        writer.write_part::<f64>(&shifted_part)?;
+        assert_eq!(writer.num_vertices(), 6);
+        assert_eq!(writer.num_triangles(), 2);
+        writer.finish()?;
+
+        let mesh = surface_mesh_from_vtk::<f64, _>(&output_file)?.mesh;
+        remove_file(&output_file)?;
+
+        assert_eq!(mesh.triangles, vec![[0, 1, 2], [3, 5, 4]]);
+        assert_eq!(mesh.vertices.len(), 6);
+        for (read, written) in mesh
+            .vertices
+            .iter()
+            .zip(part.vertices.iter().chain(shifted_part.vertices.iter()))
+        {
+            assert!((read - written).norm() < 1e-6);
+        }
+
+        Ok(())
+    }
+}
@@ -1,41 +1,123 @@
 use std::fs::File;
-use std::io::{BufReader, Read};
+use std::io::{BufRead, BufReader};
 use std::path::Path;
 
-use anyhow::Context;
+use anyhow::{anyhow, Context};
 
 use splashsurf_lib::nalgebra::Vector3;
 use splashsurf_lib::Real;
 
-pub fn particles_from_xyz<R: Real, P: AsRef<Path>>(
+/// Loads particle positions from a text file with the coordinates of one particle per line
+///
+/// The coordinates of a line are separated by the given delimiter or by whitespace and/or commas if no delimiter is
+/// specified. Blank lines and comments starting with `#` are skipped.
+pub fn particles_from_text_xyz<R: Real, P: AsRef<Path>>(
     xyz_file: P,
+    delimiter: Option<char>,
 ) -> Result<Vec<Vector3<R>>, anyhow::Error> {
     let file = File::open(xyz_file).context("Unable to open XYZ file for reading")?;
-    let mut reader = BufReader::new(file);
+    let reader = BufReader::new(file);
 
-    let mut buffer = [0u8; 3 * 4];
+    let mut particles = Vec::new();
 
-    let get_four_bytes = |buffer: &[u8], offset: usize| -> [u8; 4] {
-        [
-            buffer[offset + 0],
-            buffer[offset + 1],
-            buffer[offset + 2],
-            buffer[offset + 3],
-        ]
-    };
+    for (i, line) in reader.lines().enumerate() {
+        let line_number = i + 1;
+        let line =
+            line.with_context(|| format!("Error while reading line {} of XYZ file", line_number))?;
 
-    let mut particles = Vec::new();
+        // Strip comments and skip blank lines
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let fields: Vec<_> = match delimiter {
+            Some(delimiter) => line.split(delimiter).map(str::trim).collect(),
+            None => line
+                .split(|c: char| c.is_whitespace() || c == ',')
+                .filter(|field| !field.is_empty())
+                .collect(),
+        };
+
+        if fields.len() != 3 {
+            return Err(anyhow!(
+                "Expected three coordinates in line {} of XYZ file but found {} value(s): \"{}\"",
+                line_number,
+                fields.len(),
+                line
+            ));
+        }
 
-    while let Ok(_) = reader.read_exact(&mut buffer) {
-        let x = f32::from_ne_bytes(get_four_bytes(&buffer, 0));
-        let y = f32::from_ne_bytes(get_four_bytes(&buffer, 4));
-        let z = f32::from_ne_bytes(get_four_bytes(&buffer, 8));
-        particles.push(Vector3::new(
-            R::from_f32(x).unwrap(),
-            R::from_f32(y).unwrap(),
-            R::from_f32(z).unwrap(),
-        ));
+        let mut coords = [R::zero(); 3];
+        for (coord, field) in coords.iter_mut().zip(fields.iter()) {
+            let value = field.parse::<f64>().with_context(|| {
+                format!(
+                    "Unable to parse coordinate \"{}\" in line {} of XYZ file",
+                    field, line_number
+                )
+            })?;
+            *coord = R::from_f64(value).ok_or_else(|| {
+                anyhow!(
+                    "Unable to convert coordinate \"{}\" in line {} of XYZ file",
+                    field,
+                    line_number
+                )
+            })?;
+        }
+
+        particles.push(Vector3::new(coords[0], coords[1], coords[2]));
     }
 
     Ok(particles)
 }
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_text_xyz_read() {
+        let path = std::env::temp_dir().join("splashsurf_test_text_xyz_read.xyz");
+        File::create(&path)
+            .unwrap()
+            .write_all(b"# x y z\n1.0 2.0 3.0\n\n  -0.5\t0.25   1e-1  \n4,5,6 # comment\n")
+            .unwrap();
+
+        let particles = particles_from_text_xyz::<f64, _>(&path, None).unwrap();
+        assert_eq!(
+            particles,
+            vec![
+                Vector3::new(1.0, 2.0, 3.0),
+                Vector3::new(-0.5, 0.25, 0.1),
+                Vector3::new(4.0, 5.0, 6.0),
+            ]
+        );
+
+        File::create(&path)
+            .unwrap()
+            .write_all(b"# x, y, z\n1.0, 2.0, 3.0\n4.0,5.0,6.0\n")
+            .unwrap();
+        let particles = particles_from_text_xyz::<f32, _>(&path, Some(',')).unwrap();
+        assert_eq!(
+            particles,
+            vec![Vector3::new(1.0, 2.0, 3.0), Vector3::new(4.0, 5.0, 6.0)]
+        );
+
+        File::create(&path)
+            .unwrap()
+            .write_all(b"1.0 2.0 3.0\n1.0 2.0\n")
+            .unwrap();
+        let err = particles_from_text_xyz::<f64, _>(&path, None).unwrap_err();
+        assert!(err.to_string().contains("line 2"));
+
+        File::create(&path)
+            .unwrap()
+            .write_all(b"1.0 2.0 3.0\n# comment\n1.0 2.0 abc\n")
+            .unwrap();
+        let err = particles_from_text_xyz::<f64, _>(&path, None).unwrap_err();
+        assert!(err.to_string().contains("line 3"));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -14,7 +14,6 @@ use structopt::StructOpt;
 register_counting_allocator!(GLOBAL_ALLOCATOR, enable = false);
 
 // TODO: Use different logging approach when processing multiple files in parallel
-// TODO: Add start and end index for input file sequences
 // TODO: Does coarse_prof work with multiple threads?
 // TODO: Check if all paths supplied using the cmd args are valid
 // TODO: Clean up the parameter structs and conversions
@@ -26,7 +25,7 @@ register_counting_allocator!(GLOBAL_ALLOCATOR, enable = false);
     about = "Surface reconstruction for particle data from SPH simulations (https://github.com/w1th0utnam3/splashsurf)"
 )]
 struct CommandlineArgs {
-    /// Enable quiet mode (no output except for severe panic messages), overrides verbosity level
+    /// Enable quiet mode (no output except for severe panic messages, no progress bars), overrides verbosity level
     #[structopt(long, short = "-q")]
     quiet: bool,
     /// Print more verbose output, use multiple "v"s for even more verbose output (-v, -vv)
@@ -40,7 +39,7 @@ struct CommandlineArgs {
 #[derive(Clone, Debug, StructOpt)]
 enum Subcommand {
     /// Reconstruct a surface from particle data
-    Reconstruct(reconstruction::ReconstructSubcommandArgs),
+    Reconstruct(Box<reconstruction::ReconstructSubcommandArgs>),
     /// Convert particle or mesh files between different file formats
     Convert(convert::ConvertSubcommandArgs),
 }
@@ -74,7 +73,9 @@ fn run_splashsurf() -> Result<(), anyhow::Error> {
 
     // Delegate to subcommands
     match &cmd_args.subcommand {
-        Subcommand::Reconstruct(cmd_args) => reconstruction::reconstruct_subcommand(cmd_args)?,
+        Subcommand::Reconstruct(cmd_args) => {
+            reconstruction::reconstruct_subcommand(cmd_args, !is_quiet)?
+        }
         Subcommand::Convert(cmd_args) => convert::convert_subcommand(cmd_args)?,
     }
 
@@ -83,7 +84,7 @@ fn run_splashsurf() -> Result<(), anyhow::Error> {
     splashsurf_lib::profiling::write_to_string()
         .unwrap()
         .split("\n")
-        .filter(|l| l.len() > 0)
+        .filter(|l| !l.is_empty())
         .for_each(|l| info!("{}", l));
 
     // Print memory stats if available
@@ -4,34 +4,74 @@ use arguments::{
     ReconstructionRunnerArgs, ReconstructionRunnerPathCollection, ReconstructionRunnerPaths,
 };
 use bytemuck::allocation::cast_vec;
-use log::info;
+use log::{debug, info, warn};
+use progress::ReconstructionProgressBar;
 use rayon::prelude::*;
+use splashsurf_lib::coordinates::CoordinateConvention;
 use splashsurf_lib::mesh::{MeshAttribute, MeshWithData, PointCloud3d};
 use splashsurf_lib::nalgebra::{Unit, Vector3};
 use splashsurf_lib::profile;
 use splashsurf_lib::{density_map, Index, Real};
+use std::borrow::Cow;
 use std::convert::TryFrom;
 use std::path::PathBuf;
 use structopt::clap::arg_enum;
 use structopt::StructOpt;
 
+mod batch;
+mod progress;
+mod schedule;
+
 // TODO: Detect smallest index type (i.e. check if ok to use i32 as index)
 
 /// Command line arguments for the `reconstruct` subcommand
 #[derive(Clone, Debug, StructOpt)]
 pub struct ReconstructSubcommandArgs {
-    /// Path to the input file where the particle positions are stored (supported formats: VTK, binary f32 XYZ, PLY, BGEO)
+    /// Path to the input file where the particle positions are stored (supported formats: VTK, binary f32 XYZ, PLY, BGEO, JSON, CSV)
     #[structopt(short = "-i", long, parse(from_os_str))]
     input_file: Option<PathBuf>,
-    /// Path to a sequence of particle files that should be processed, use `{}` in the filename to indicate a placeholder
+    /// Path to a sequence of particle files that should be processed, use `{}` (or `{frame}`) in the filename to indicate a placeholder for the frame number
     #[structopt(short = "-s", long, parse(from_os_str))]
     input_sequence: Option<PathBuf>,
-    /// Filename for writing the reconstructed surface to disk (default: "{original_filename}_surface.vtk")
+    /// Path to a directory that is searched recursively for particle files, the output files are written to the output directory mirroring the relative paths of the input files (batch mode)
+    #[structopt(long, parse(from_os_str), conflicts_with_all = &["input-file", "input-sequence"])]
+    input_dir: Option<PathBuf>,
+    /// Optional JSON file with per-frame overrides of the surface threshold and cube size when processing an input sequence, format: {"interpolation": "step" or "linear", "frames": {"1": {"surface_threshold": 0.6, "cube_size": 0.5}, ...}}
+    #[structopt(long, parse(from_os_str), requires = "input-sequence")]
+    parameter_schedule: Option<PathBuf>,
+    /// File format of the reconstructed surfaces when processing an input sequence, the output files are named "{original_filename}_surface_{frame}.{format}" (supported formats: vtk, obj, ply)
+    #[structopt(long, default_value = "vtk", possible_values = &["vtk", "obj", "ply"], case_insensitive = true)]
+    sequence_format: String,
+    /// Number of digits to zero-pad the frame numbers in the output filenames to when processing an input sequence (e.g. 4 for "surface_0001.obj")
+    #[structopt(long, requires = "input-sequence")]
+    sequence_frame_padding: Option<usize>,
+    /// Number of the first frame of an input sequence (default: 1)
+    #[structopt(long, requires = "input-sequence")]
+    sequence_start: Option<usize>,
+    /// Number of the last frame of an input sequence, missing frames up to this number are skipped with a warning (default: the sequence ends before the first missing frame)
+    #[structopt(long, requires = "input-sequence")]
+    sequence_end: Option<usize>,
+    /// Increment between the numbers of consecutive frames of an input sequence (default: 1)
+    #[structopt(long, requires = "input-sequence")]
+    sequence_step: Option<usize>,
+    /// Number of digits to which the frame numbers in the filenames of an input sequence are zero-padded (e.g. 4 for "fluid_0001.vtk")
+    #[structopt(long, requires = "input-sequence")]
+    sequence_input_padding: Option<usize>,
+    /// Whether to write a JSON metadata file with the filenames and vertex/triangle counts of all frames when processing an input sequence ("{original_filename}_surface_sequence.json")
+    #[structopt(long, default_value = "off", possible_values = &["on", "off"], case_insensitive = true)]
+    sequence_metadata: Switch,
+    /// Filename for writing the reconstructed surface to disk (default: "{original_filename}_surface.vtk"), when processing an input sequence this is a template that has to contain the `{}` (or `{frame}`) placeholder (e.g. "surface_{}.ply", default: "{original_filename}_surface_{frame}.{sequence_format}")
     #[structopt(short = "-o", parse(from_os_str))]
     output_file: Option<PathBuf>,
     /// Optional base directory for all output files (default: current working directory)
     #[structopt(long, parse(from_os_str))]
     output_dir: Option<PathBuf>,
+    /// Reconstruct the surface in tiles of the given number of marching cubes cells per axis and write each tile to a separate file "tile_{x}_{y}_{z}.vtk" together with a "manifest.json" into the directory "{original_filename}_surface_tiles" (for domains too large to keep the whole mesh in memory, the density map, octree and normal outputs are not supported)
+    #[structopt(long, requires = "input-file")]
+    tile_cells: Option<usize>,
+    /// Whether to write the meshes of all tiles of a tiled reconstruction (requires tile-cells) incrementally into the single VTK output file instead of separate files, only the mesh of the current tile is kept in memory (the seams between the tiles are not welded)
+    #[structopt(long, default_value = "off", possible_values = &["on", "off"], case_insensitive = true)]
+    stream_tiles: Switch,
     /// The particle radius of the input data
     #[structopt(long)]
     particle_radius: f64,
@@ -50,6 +90,9 @@ pub struct ReconstructSubcommandArgs {
     /// Whether to enable the use of double precision for all computations
     #[structopt(short = "-d", long, default_value = "off", possible_values = &["on", "off"], case_insensitive = true)]
     double_precision: Switch,
+    /// Floating point precision used for all computations, f32 halves the memory consumption compared to f64 (overrides the double-precision argument)
+    #[structopt(long, possible_values = &["f32", "f64"], case_insensitive = true)]
+    precision: Option<Precision>,
     /// Lower corner of the domain where surface reconstruction should be performed, format: domain-min=x_min;y_min;z_min (requires domain-max to be specified)
     #[structopt(
         long,
@@ -66,6 +109,18 @@ pub struct ReconstructSubcommandArgs {
         requires = "domain-min"
     )]
     domain_max: Option<Vec<f64>>,
+    /// Number of additional empty marching cubes cells around the particles (beyond the particle radius and kernel support) that pad the reconstruction domain on every side, ignored if the domain is specified with domain-min and domain-max
+    #[structopt(long, default_value = "0")]
+    grid_margin_cells: usize,
+    /// Axes along which the reconstruction domain is periodic, format: periodic-axes=x;z (requires domain-min and domain-max to be specified, the domain extents along these axes have to be integer multiples of the cube size)
+    #[structopt(
+        long,
+        value_delimiter = ";",
+        possible_values = &["x", "y", "z"],
+        case_insensitive = true,
+        requires_all = &["domain-min", "domain-max"]
+    )]
+    periodic_axes: Option<Vec<String>>,
     /// Whether to enable spatial decomposition using an octree (faster) instead of a global approach
     #[structopt(long, default_value = "on", possible_values = &["on", "off"], case_insensitive = true)]
     octree_decomposition: Switch,
@@ -98,6 +153,9 @@ pub struct ReconstructSubcommandArgs {
     /// Flag to enable multi-threading to process multiple input files in parallel
     #[structopt(long = "mt-files", default_value = "off", possible_values = &["on", "off"], case_insensitive = true)]
     parallelize_over_files: Switch,
+    /// Maximum number of input files of a sequence or directory that are reconstructed concurrently, each reconstruction can still use all threads of the worker thread pool (overrides "mt-files")
+    #[structopt(long)]
+    num_jobs: Option<usize>,
     /// Flag to enable multi-threading for a single input file by processing chunks of particles in parallel
     #[structopt(long = "mt-particles", default_value = "on", possible_values = &["on", "off"], case_insensitive = true)]
     parallelize_over_particles: Switch,
@@ -110,6 +168,13 @@ pub struct ReconstructSubcommandArgs {
     /// Whether to write vertex normals to the output file. Note that currently the normals are only computed using an area weighted average of triangle normals.
     #[structopt(long, default_value = "off", possible_values = &["on", "off"], case_insensitive = true)]
     output_normals: Switch,
+    /// Whether to write the magnitude of the density field gradient at every vertex as the attribute "grad_magnitude" to the output file (computed with central differences on the background grid that are trilinearly interpolated to the vertices, not supported for tiled reconstructions)
+    #[structopt(long, default_value = "off", possible_values = &["on", "off"], case_insensitive = true)]
+    output_grad_magnitude: Switch,
+    /// Coordinate convention of the output surface mesh and its normals, the input particles are assumed to be right-handed with the z-axis pointing up.
+    /// All other parameters (e.g. the domain) refer to the coordinates of the input particles. The conventions are right-handed z-up (zup), right-handed y-up (yup) and left-handed y-up (yuplh).
+    #[structopt(long, default_value = "zup", possible_values = &["zup", "yup", "yuplh"], case_insensitive = true)]
+    coordinate_convention: CoordinateConventionArg,
 }
 
 arg_enum! {
@@ -120,6 +185,35 @@ arg_enum! {
     }
 }
 
+arg_enum! {
+    /// Coordinate convention of the output, right-handed z-up, right-handed y-up or left-handed y-up
+    #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+    pub enum CoordinateConventionArg {
+        ZUp,
+        YUp,
+        YUpLh
+    }
+}
+
+impl CoordinateConventionArg {
+    pub(crate) fn into_convention(self) -> CoordinateConvention {
+        match self {
+            CoordinateConventionArg::ZUp => CoordinateConvention::ZUpRightHanded,
+            CoordinateConventionArg::YUp => CoordinateConvention::YUpRightHanded,
+            CoordinateConventionArg::YUpLh => CoordinateConvention::YUpLeftHanded,
+        }
+    }
+}
+
+arg_enum! {
+    /// Floating point type used for the surface reconstruction, selects the instantiation of the reconstruction pipeline at runtime
+    #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+    pub enum Precision {
+        F32,
+        F64
+    }
+}
+
 impl Switch {
     fn into_bool(self) -> bool {
         match self {
@@ -129,50 +223,111 @@ impl Switch {
     }
 }
 
-/// Executes the `reconstruct` subcommand
-pub fn reconstruct_subcommand(cmd_args: &ReconstructSubcommandArgs) -> Result<(), anyhow::Error> {
-    let paths = ReconstructionRunnerPathCollection::try_from(cmd_args)
-        .context("Failed parsing input file path(s) from command line")?
-        .collect();
-    let args = ReconstructionRunnerArgs::try_from(cmd_args)
+/// Executes the `reconstruct` subcommand, optionally showing a progress bar for each reconstructed file
+///
+/// The progress bar is only shown if stderr is a terminal and the files are processed one after another.
+pub fn reconstruct_subcommand(
+    cmd_args: &ReconstructSubcommandArgs,
+    show_progress: bool,
+) -> Result<(), anyhow::Error> {
+    let path_collection = ReconstructionRunnerPathCollection::try_from(cmd_args)
+        .context("Failed parsing input file path(s) from command line")?;
+    let paths = path_collection.collect();
+    let mut args = ReconstructionRunnerArgs::try_from(cmd_args)
         .context("Failed processing parameters from command line")?;
+    args.show_progress = show_progress
+        && !matches!(cmd_args.num_jobs, Some(num_jobs) if num_jobs > 1)
+        && !cmd_args.parallelize_over_files.into_bool();
+
+    let process_in_parallel = |path: &ReconstructionRunnerPaths| {
+        reconstruction_pipeline(path, &args)
+            .with_context(|| {
+                format!(
+                    "Error while processing input file '{}' from a file sequence",
+                    path.input_file.display()
+                )
+            })
+            .inspect_err(|err| {
+                // Already log the error in case there are multiple errors
+                log_error(err);
+            })
+    };
 
-    let result = if cmd_args.parallelize_over_files.into_bool() {
-        paths.par_iter().try_for_each(|path| {
-            reconstruction_pipeline(path, &args)
-                .with_context(|| {
-                    format!(
-                        "Error while processing input file '{}' from a file sequence",
-                        path.input_file.display()
-                    )
-                })
-                .map_err(|err| {
-                    // Already log the error in case there are multiple errors
-                    log_error(&err);
-                    err
-                })
+    let result: Result<Vec<_>, anyhow::Error> = if let Some(num_jobs) = cmd_args.num_jobs {
+        if num_jobs == 0 {
+            return Err(anyhow!("The number of jobs has to be positive"));
+        }
+        info!(
+            "Processing up to {} input file(s) concurrently.",
+            num_jobs.min(paths.len())
+        );
+
+        // The files are distributed over a dedicated pool with one thread per job, a job starts as soon as
+        // another one finished. The reconstructions themselves run in a pool with as many threads as the worker
+        // thread pool, such that each reconstruction can still use all threads.
+        let job_pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(num_jobs)
+            .build()
+            .context("Unable to create thread pool for concurrent jobs")?;
+        let reconstruction_pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(rayon::current_num_threads())
+            .build()
+            .context("Unable to create thread pool for reconstructions")?;
+
+        job_pool.install(|| {
+            paths
+                .par_iter()
+                .map(|path| reconstruction_pool.install(|| process_in_parallel(path)))
+                .collect()
         })
+    } else if cmd_args.parallelize_over_files.into_bool() {
+        paths.par_iter().map(&process_in_parallel).collect()
     } else {
         paths
             .iter()
-            .try_for_each(|path| reconstruction_pipeline(path, &args))
+            .map(|path| reconstruction_pipeline(path, &args))
+            .collect()
     };
-
-    if result.is_ok() {
-        info!("Successfully finished processing all inputs.");
+    let frames: Vec<_> = result?.into_iter().flatten().collect();
+
+    // Write the sidecar metadata file of the exported sequence
+    if cmd_args.sequence_metadata.into_bool() {
+        if let Some(output_pattern) = path_collection.sequence_output_pattern() {
+            let metadata_file = io::sequence::metadata_path(output_pattern)?;
+            info!(
+                "Writing sequence metadata to \"{}\"...",
+                metadata_file.display()
+            );
+            let format = output_pattern
+                .extension()
+                .map(|ext| ext.to_string_lossy().to_lowercase())
+                .unwrap_or_else(|| cmd_args.sequence_format.to_lowercase());
+            io::sequence::write_sequence_metadata(
+                &metadata_file,
+                &format,
+                cmd_args.sequence_frame_padding.unwrap_or(0),
+                &frames,
+            )?;
+        }
     }
 
-    result
+    info!("Successfully finished processing all inputs.");
+    Ok(())
 }
 
 /// Conversion and validation of command line arguments
 mod arguments {
-    use super::ReconstructSubcommandArgs;
+    use super::batch;
+    use super::schedule::ParameterSchedule;
+    use super::{Precision, ReconstructSubcommandArgs};
     use crate::io;
     use anyhow::{anyhow, Context};
-    use log::info;
+    use log::{info, warn};
+    use splashsurf_lib::coordinates::CoordinateConvention;
     use splashsurf_lib::nalgebra::Vector3;
-    use splashsurf_lib::{AxisAlignedBoundingBox3d, ParticleDensityComputationStrategy};
+    use splashsurf_lib::{
+        AxisAlignedBoundingBox3d, ParticleDensityComputationStrategy, PeriodicAxes,
+    };
     use std::convert::TryFrom;
     use std::fs;
     use std::path::{Path, PathBuf};
@@ -180,9 +335,15 @@ mod arguments {
     /// All arguments that can be supplied to the surface reconstruction tool converted to useful types
     pub struct ReconstructionRunnerArgs {
         pub params: splashsurf_lib::Parameters<f64>,
-        pub use_double_precision: bool,
+        pub schedule: Option<ParameterSchedule>,
+        pub precision: Precision,
         pub check_mesh: bool,
+        pub coordinate_convention: CoordinateConvention,
         pub io_params: io::FormatParameters,
+        pub tile_cells: Option<usize>,
+        pub stream_tiles: bool,
+        pub output_grad_magnitude: bool,
+        pub show_progress: bool,
     }
 
     // Convert raw command line arguments to more useful types
@@ -256,6 +417,20 @@ mod arguments {
                 })
             };
 
+            // Collect the periodic axes of the domain
+            let periodic_axes = {
+                let mut periodic_axes = PeriodicAxes::none();
+                for axis in args.periodic_axes.iter().flatten() {
+                    match axis.to_lowercase().as_str() {
+                        "x" => periodic_axes.x = true,
+                        "y" => periodic_axes.y = true,
+                        "z" => periodic_axes.z = true,
+                        _ => return Err(anyhow!("Invalid periodic axis \"{}\"", axis)),
+                    }
+                }
+                periodic_axes
+            };
+
             // Assemble all parameters for the surface reconstruction
             let params = splashsurf_lib::Parameters {
                 particle_radius: args.particle_radius,
@@ -264,10 +439,39 @@ mod arguments {
                 cube_size,
                 iso_surface_threshold: args.surface_threshold,
                 domain_aabb,
+                grid_margin_cells: args.grid_margin_cells,
                 enable_multi_threading: args.parallelize_over_particles.into_bool(),
                 spatial_decomposition,
+                periodic_axes,
+                mask: None,
+                contour_cell_factors: None,
+                normalize_by_rest_density: None,
+                edge_interpolation: None,
+                max_grid_cells: None,
+                kernel: None,
+                anisotropy: None,
             };
 
+            // Optionally load the per-frame parameter schedule
+            let schedule = args
+                .parameter_schedule
+                .as_ref()
+                .map(ParameterSchedule::from_json_file)
+                .transpose()?;
+            if let Some(schedule) = &schedule {
+                info!(
+                    "Using parameter schedule with {:?} interpolation between keyed frames.",
+                    schedule.interpolation()
+                );
+            }
+
+            let stream_tiles = args.stream_tiles.into_bool();
+            if stream_tiles && args.tile_cells.is_none() {
+                return Err(anyhow!(
+                    "Streaming the output of tiles requires a tiled reconstruction (tile-cells argument)"
+                ));
+            }
+
             // Optionally initialize thread pool
             if let Some(num_threads) = args.num_threads {
                 splashsurf_lib::initialize_thread_pool(num_threads)?;
@@ -275,13 +479,80 @@ mod arguments {
 
             Ok(ReconstructionRunnerArgs {
                 params,
-                use_double_precision: args.double_precision.into_bool(),
+                schedule,
+                precision: args.precision.unwrap_or_else(|| {
+                    if args.double_precision.into_bool() {
+                        Precision::F64
+                    } else {
+                        Precision::F32
+                    }
+                }),
                 check_mesh: args.check_mesh.into_bool(),
+                coordinate_convention: args.coordinate_convention.into_convention(),
                 io_params: io::FormatParameters::default(),
+                tile_cells: args.tile_cells,
+                stream_tiles,
+                output_grad_magnitude: args.output_grad_magnitude.into_bool(),
+                show_progress: false,
             })
         }
     }
 
+    /// The frames of an input sequence that should be processed
+    #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+    pub struct SequenceFrames {
+        /// Number of the first frame
+        pub start: usize,
+        /// Number of the last frame, if `None` the sequence ends before the first missing frame
+        pub end: Option<usize>,
+        /// Increment between the numbers of consecutive frames
+        pub step: usize,
+        /// Number of digits to which the frame numbers in the input filenames are zero-padded
+        pub input_padding: usize,
+    }
+
+    impl Default for SequenceFrames {
+        fn default() -> Self {
+            Self {
+                start: 1,
+                end: None,
+                step: 1,
+                input_padding: 0,
+            }
+        }
+    }
+
+    impl TryFrom<&ReconstructSubcommandArgs> for SequenceFrames {
+        type Error = anyhow::Error;
+
+        fn try_from(args: &ReconstructSubcommandArgs) -> Result<Self, Self::Error> {
+            let default = Self::default();
+            let frames = Self {
+                start: args.sequence_start.unwrap_or(default.start),
+                end: args.sequence_end,
+                step: args.sequence_step.unwrap_or(default.step),
+                input_padding: args.sequence_input_padding.unwrap_or(default.input_padding),
+            };
+
+            if frames.step == 0 {
+                return Err(anyhow!(
+                    "The frame step of the input sequence has to be positive"
+                ));
+            }
+            if let Some(end) = frames.end {
+                if end < frames.start {
+                    return Err(anyhow!(
+                        "The last frame ({}) of the input sequence is smaller than the first frame ({})",
+                        end,
+                        frames.start
+                    ));
+                }
+            }
+
+            Ok(frames)
+        }
+    }
+
     #[derive(Clone, Debug)]
     pub struct ReconstructionRunnerPathCollection {
         is_sequence: bool,
@@ -291,11 +562,17 @@ mod arguments {
         output_density_map_grid_file: Option<PathBuf>,
         output_octree_file: Option<PathBuf>,
         output_normals: bool,
+        frame_padding: usize,
+        sequence_frames: SequenceFrames,
+        /// Pairs of input and output files when processing an input directory in batch mode
+        batch_files: Option<Vec<(PathBuf, PathBuf)>>,
     }
 
     impl ReconstructionRunnerPathCollection {
         fn try_new<P: Into<PathBuf>>(
             is_sequence: bool,
+            frame_padding: usize,
+            sequence_frames: SequenceFrames,
             input_file: P,
             output_base_path: Option<P>,
             output_file: P,
@@ -337,6 +614,9 @@ mod arguments {
                         .map(|f| output_base_path.join(f)),
                     output_octree_file: output_octree_file.map(|f| output_base_path.join(f)),
                     output_normals,
+                    frame_padding,
+                    sequence_frames,
+                    batch_files: None,
                 })
             } else {
                 Ok(Self {
@@ -347,13 +627,90 @@ mod arguments {
                     output_density_map_grid_file,
                     output_octree_file,
                     output_normals,
+                    frame_padding,
+                    sequence_frames,
+                    batch_files: None,
                 })
             }
         }
 
+        /// Returns the output file pattern with the `{}` placeholder if the input is a sequence
+        pub(crate) fn sequence_output_pattern(&self) -> Option<&Path> {
+            if self.is_sequence {
+                Some(&self.output_file)
+            } else {
+                None
+            }
+        }
+
+        /// Returns the path collection for all particle files below the input root in batch mode
+        fn try_new_batch(
+            input_root: &Path,
+            output_root: &Path,
+            output_suffix: &str,
+            output_normals: bool,
+        ) -> Result<Self, anyhow::Error> {
+            let input_files = batch::collect_particle_files(input_root, Some(output_root))?;
+            if input_files.is_empty() {
+                return Err(anyhow!(
+                    "No particle files were found in the input directory '{}'",
+                    input_root.display()
+                ));
+            }
+
+            let output_files = batch::mirrored_output_files(
+                input_root,
+                output_root,
+                &input_files,
+                output_suffix,
+                "vtk",
+            )?;
+
+            // Ensure that all output directories exist/create them
+            for output_dir in output_files.iter().filter_map(|f| f.parent()) {
+                if !output_dir.exists() {
+                    fs::create_dir_all(output_dir).with_context(|| {
+                        format!(
+                            "Unable to create output directory '{}'",
+                            output_dir.display()
+                        )
+                    })?;
+                }
+            }
+
+            Ok(Self {
+                is_sequence: false,
+                input_file: input_root.to_path_buf(),
+                output_file: output_root.to_path_buf(),
+                output_density_map_points_file: None,
+                output_density_map_grid_file: None,
+                output_octree_file: None,
+                output_normals,
+                frame_padding: 0,
+                sequence_frames: SequenceFrames::default(),
+                batch_files: Some(input_files.into_iter().zip(output_files).collect()),
+            })
+        }
+
         /// Returns an input/output file path struct for each input file (basically one task per input file)
         pub(crate) fn collect(&self) -> Vec<ReconstructionRunnerPaths> {
-            if self.is_sequence {
+            if let Some(batch_files) = &self.batch_files {
+                batch_files
+                    .iter()
+                    .map(|(input_file, output_file)| {
+                        ReconstructionRunnerPaths::new(
+                            input_file.clone(),
+                            output_file.clone(),
+                            None,
+                            // Don't write density maps etc. when processing a batch of files
+                            None,
+                            None,
+                            None,
+                            self.output_normals,
+                        )
+                    })
+                    .collect()
+            } else if self.is_sequence {
                 let input_file = &self.input_file;
                 let output_file = &self.output_file;
 
@@ -363,30 +720,40 @@ mod arguments {
                 let input_filename = input_file.file_name().unwrap().to_string_lossy();
                 let output_filename = output_file.file_name().unwrap().to_string_lossy();
 
+                let frames = &self.sequence_frames;
                 let mut paths = Vec::new();
-                let mut i: usize = 1;
-                loop {
-                    let input_filename_i = input_filename.replace("{}", &i.to_string());
+                let mut i = frames.start;
+                while frames.end.is_none_or(|end| i <= end) {
+                    let input_filename_i =
+                        io::sequence::frame_filename(&input_filename, i, frames.input_padding);
                     let input_file_i = input_dir.join(input_filename_i);
 
                     if input_file_i.is_file() {
-                        let output_filename_i = output_filename.replace("{}", &i.to_string());
+                        let output_filename_i =
+                            io::sequence::frame_filename(&output_filename, i, self.frame_padding);
                         let output_file_i = output_dir.join(output_filename_i);
 
                         paths.push(ReconstructionRunnerPaths::new(
                             input_file_i,
                             output_file_i,
+                            Some(i),
                             // Don't write density maps etc. when processing a sequence of files
                             None,
                             None,
                             None,
                             self.output_normals,
                         ));
+                    } else if frames.end.is_some() {
+                        warn!(
+                            "The input file '{}' of frame {} does not exist, skipping the frame",
+                            input_file_i.display(),
+                            i
+                        );
                     } else {
                         break;
                     }
 
-                    i += 1;
+                    i += frames.step;
                 }
 
                 paths
@@ -395,6 +762,7 @@ mod arguments {
                     ReconstructionRunnerPaths::new(
                         self.input_file.clone(),
                         self.output_file.clone(),
+                        None,
                         self.output_density_map_points_file.clone(),
                         self.output_density_map_grid_file.clone(),
                         self.output_octree_file.clone(),
@@ -426,6 +794,8 @@ mod arguments {
 
                     Self::try_new(
                         false,
+                        0,
+                        SequenceFrames::default(),
                         input_file.clone(),
                         args.output_dir.clone(),
                         output_file,
@@ -435,12 +805,19 @@ mod arguments {
                         args.output_normals.into_bool(),
                     )
                 } else {
-                    return Err(anyhow!(
+                    Err(anyhow!(
                         "Input file does not exist: \"{}\"",
                         input_file.display()
-                    ));
+                    ))
                 }
             } else if let Some(input_pattern) = &args.input_sequence {
+                // Support the `{frame}` placeholder as an alternative to `{}`
+                let input_pattern = &PathBuf::from(
+                    input_pattern
+                        .to_string_lossy()
+                        .replace(FRAME_PLACEHOLDER, "{}"),
+                );
+
                 // Make sure that the sequence pattern ends with a filename (and not with a path separator)
                 let input_filename = match input_pattern.file_name() {
                     Some(input_filename) => input_filename.to_string_lossy(),
@@ -465,15 +842,35 @@ mod arguments {
 
                 // Make sure that we have a placeholder '{}' in the filename part of the sequence pattern
                 if input_filename.contains("{}") {
-                    let input_stem = input_pattern.file_stem().unwrap().to_string_lossy();
-                    // Currently, only VTK files are supported for output
-                    let output_filename = format!(
-                        "{}.vtk",
-                        input_stem.replace("{}", &format!("{}_{{}}", output_suffix))
-                    );
+                    // Use the user defined output filename template if provided...
+                    let output_filename = if let Some(output_file) = &args.output_file {
+                        let output_file = output_file
+                            .to_string_lossy()
+                            .replace(FRAME_PLACEHOLDER, "{}");
+                        let has_placeholder = Path::new(&output_file)
+                            .file_name()
+                            .is_some_and(|f| f.to_string_lossy().contains("{}"));
+                        if !has_placeholder {
+                            return Err(anyhow!(
+                                "The output filename template \"{}\" of the input sequence does not contain a place holder \"{{}}\"",
+                                output_file
+                            ));
+                        }
+                        output_file
+                    // ...otherwise, generate one based on the input filename
+                    } else {
+                        let input_stem = input_pattern.file_stem().unwrap().to_string_lossy();
+                        format!(
+                            "{}.{}",
+                            input_stem.replace("{}", &format!("{}_{{}}", output_suffix)),
+                            args.sequence_format.to_lowercase()
+                        )
+                    };
 
                     Self::try_new(
                         true,
+                        args.sequence_frame_padding.unwrap_or(0),
+                        SequenceFrames::try_from(args)?,
                         input_pattern.clone(),
                         args.output_dir.clone(),
                         output_filename.into(),
@@ -483,23 +880,47 @@ mod arguments {
                         args.output_normals.into_bool(),
                     )
                 } else {
-                    return Err(anyhow!(
+                    Err(anyhow!(
                         "The input sequence pattern \"{}\" does not contain a place holder \"{{}}\"", input_pattern.display()
-                    ));
+                    ))
+                }
+            } else if let Some(input_dir) = &args.input_dir {
+                if input_dir.is_dir() {
+                    let output_dir = args
+                        .output_dir
+                        .clone()
+                        .unwrap_or_else(|| PathBuf::from("."));
+
+                    Self::try_new_batch(
+                        input_dir,
+                        &output_dir,
+                        output_suffix,
+                        args.output_normals.into_bool(),
+                    )
+                } else {
+                    Err(anyhow!(
+                        "Input directory does not exist: \"{}\"",
+                        input_dir.display()
+                    ))
                 }
             } else {
-                return Err(anyhow!(
-                    "Neither an input file path or input sequence pattern was provided"
-                ));
+                Err(anyhow!(
+                    "Neither an input file path, input sequence pattern or input directory was provided"
+                ))
             }
         }
     }
 
+    /// Alternative placeholder for the frame number in the filenames of sequences
+    const FRAME_PLACEHOLDER: &str = "{frame}";
+
     /// All file paths that are relevant for running a single surface reconstruction task
     #[derive(Clone, Debug)]
     pub(crate) struct ReconstructionRunnerPaths {
         pub input_file: PathBuf,
         pub output_file: PathBuf,
+        /// Index of the frame if the input file is part of a sequence
+        pub frame_index: Option<usize>,
         pub output_density_map_points_file: Option<PathBuf>,
         pub output_density_map_grid_file: Option<PathBuf>,
         pub output_octree_file: Option<PathBuf>,
@@ -510,6 +931,7 @@ mod arguments {
         fn new(
             input_file: PathBuf,
             output_file: PathBuf,
+            frame_index: Option<usize>,
             output_density_map_points_file: Option<PathBuf>,
             output_density_map_grid_file: Option<PathBuf>,
             output_octree_file: Option<PathBuf>,
@@ -518,6 +940,7 @@ mod arguments {
             ReconstructionRunnerPaths {
                 input_file,
                 output_file,
+                frame_index,
                 output_density_map_points_file,
                 output_density_map_grid_file,
                 output_octree_file,
@@ -531,37 +954,76 @@ mod arguments {
 pub(crate) fn reconstruction_pipeline(
     paths: &ReconstructionRunnerPaths,
     args: &ReconstructionRunnerArgs,
-) -> Result<(), anyhow::Error> {
-    if args.use_double_precision {
-        info!("Using double precision (f64) for surface reconstruction.");
-        reconstruction_pipeline_generic::<i64, f64>(
-            paths,
-            &args.params,
-            &args.io_params,
-            args.check_mesh,
-        )?;
-    } else {
-        info!("Using single precision (f32) for surface reconstruction.");
-        reconstruction_pipeline_generic::<i64, f32>(
-            paths,
-            &args.params.try_convert().ok_or(anyhow!(
-                "Unable to convert surface reconstruction parameters from f64 to f32."
-            ))?,
-            &args.io_params,
-            args.check_mesh,
-        )?;
-    }
+) -> Result<Option<io::sequence::SequenceFrame>, anyhow::Error> {
+    // Apply the overrides of the parameter schedule for the current frame
+    let params = match (&args.schedule, paths.frame_index) {
+        (Some(schedule), Some(frame_index)) => {
+            let overrides = schedule.overrides_for_frame(frame_index);
+            let mut params = args.params.clone();
+            if let Some(surface_threshold) = overrides.surface_threshold {
+                params.iso_surface_threshold = surface_threshold;
+            }
+            if let Some(cube_size) = overrides.cube_size {
+                params.cube_size = params.particle_radius * cube_size;
+            }
+            info!(
+                "Using parameters of frame {} from schedule: surface threshold: {}, cube size: {}",
+                frame_index, params.iso_surface_threshold, params.cube_size
+            );
+            Cow::Owned(params)
+        }
+        _ => Cow::Borrowed(&args.params),
+    };
 
-    Ok(())
+    let frame = match args.precision {
+        Precision::F64 => {
+            info!("Using double precision (f64) for surface reconstruction.");
+            reconstruction_pipeline_generic::<i64, f64>(
+                paths,
+                &params,
+                &args.io_params,
+                args.check_mesh,
+                args.coordinate_convention,
+                args.tile_cells,
+                args.stream_tiles,
+                args.output_grad_magnitude,
+                args.show_progress,
+            )?
+        }
+        Precision::F32 => {
+            info!("Using single precision (f32) for surface reconstruction.");
+            reconstruction_pipeline_generic::<i64, f32>(
+                paths,
+                &params.try_convert().ok_or(anyhow!(
+                    "Unable to convert surface reconstruction parameters from f64 to f32."
+                ))?,
+                &args.io_params,
+                args.check_mesh,
+                args.coordinate_convention,
+                args.tile_cells,
+                args.stream_tiles,
+                args.output_grad_magnitude,
+                args.show_progress,
+            )?
+        }
+    };
+
+    Ok(frame)
 }
 
 /// Wrapper for the reconstruction pipeline: loads input file, runs reconstructions, stores output files
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn reconstruction_pipeline_generic<I: Index, R: Real>(
     paths: &ReconstructionRunnerPaths,
     params: &splashsurf_lib::Parameters<R>,
     io_params: &io::FormatParameters,
     check_mesh: bool,
-) -> Result<(), anyhow::Error> {
+    coordinate_convention: CoordinateConvention,
+    tile_cells: Option<usize>,
+    stream_tiles: bool,
+    output_grad_magnitude: bool,
+    show_progress: bool,
+) -> Result<Option<io::sequence::SequenceFrame>, anyhow::Error> {
     profile!("surface reconstruction cli");
 
     // Load particle positions
@@ -573,12 +1035,69 @@ pub(crate) fn reconstruction_pipeline_generic<I: Index, R: Real>(
             )
         })?;
 
+    // Reconstruct the surface in tiles that are written to separate files or streamed into the output file
+    if let Some(cells_per_tile) = tile_cells {
+        if output_grad_magnitude {
+            warn!("The output of the density gradient magnitude is not supported for tiled reconstructions and is skipped");
+        }
+        if stream_tiles {
+            streamed_tiled_reconstruction_pipeline_generic::<I, R>(
+                paths,
+                particle_positions.as_slice(),
+                params,
+                io_params,
+                cells_per_tile,
+                coordinate_convention,
+            )?;
+        } else {
+            tiled_reconstruction_pipeline_generic::<I, R>(
+                paths,
+                particle_positions.as_slice(),
+                params,
+                cells_per_tile,
+                coordinate_convention,
+            )?;
+        }
+        return Ok(None);
+    }
+
     // Perform the surface reconstruction
-    let reconstruction =
-        splashsurf_lib::reconstruct_surface::<I, R>(particle_positions.as_slice(), &params)?;
+    let progress_bar = ReconstructionProgressBar::new(show_progress);
+    let reconstruction = splashsurf_lib::reconstruct_surface_with_progress::<I, R, _>(
+        particle_positions.as_slice(),
+        params,
+        |progress| progress_bar.update(progress),
+    )?;
+
+    // Write marching cubes case distribution using log::debug
+    let case_histogram = reconstruction.marching_cubes_case_histogram();
+    if case_histogram.total() > 0 {
+        debug!(
+            "Marching cubes case distribution ({} triangulated cells):",
+            case_histogram.total()
+        );
+        case_histogram.non_empty_cases().for_each(|(case, count)| {
+            debug!(
+                "  case {:>3}: {} ({:.2}%)",
+                case,
+                count,
+                100.0 * count as f64 / case_histogram.total() as f64
+            )
+        });
+    }
 
     let grid = reconstruction.grid();
-    let mesh = reconstruction.mesh();
+    let mesh = if coordinate_convention == CoordinateConvention::ZUpRightHanded {
+        Cow::Borrowed(reconstruction.mesh())
+    } else {
+        info!(
+            "Converting surface mesh to coordinate convention {:?}",
+            coordinate_convention
+        );
+        let mut mesh = reconstruction.mesh().clone();
+        coordinate_convention.convert_trimesh(&mut mesh);
+        Cow::Owned(mesh)
+    };
 
     // Add normals to mesh if requested
     let mesh = if paths.output_normals {
@@ -593,20 +1112,64 @@ pub(crate) fn reconstruction_pipeline_generic<I: Index, R: Real>(
             cast_vec::<Unit<Vector3<R>>, Vector3<R>>(tri_normals)
         };
 
-        MeshWithData::new(mesh.clone())
+        MeshWithData::new(mesh.into_owned())
             .with_point_data(MeshAttribute::new_real_vector3("normals", tri_normals))
     } else {
-        MeshWithData::new(mesh.clone())
+        MeshWithData::new(mesh.into_owned())
+    };
+
+    // Add the magnitude of the density gradient to the mesh if requested
+    let mesh = if output_grad_magnitude {
+        profile!("compute density gradient magnitude");
+        info!(
+            "Computing density gradient magnitude for {} vertices...",
+            mesh.mesh.vertices.len()
+        );
+
+        let density_map = reconstruction
+            .density_map()
+            .ok_or_else(|| anyhow::anyhow!("No density map was created during reconstruction"))?;
+        let grad_magnitude = density_map::compute_vertex_gradient_magnitudes_from_field(
+            reconstruction.mesh(),
+            density_map,
+            grid,
+        );
+
+        mesh.with_point_data(MeshAttribute::new_real_scalar(
+            "grad_magnitude",
+            grad_magnitude,
+        ))
+    } else {
+        mesh
     };
 
     // Store the surface mesh
     {
         profile!("write surface mesh to file");
+        progress_bar.writing_mesh();
         info!(
             "Writing surface mesh to \"{}\"...",
             paths.output_file.to_string_lossy()
         );
-        io::vtk_format::write_vtk(&mesh, &paths.output_file, "mesh").with_context(|| {
+        let is_obj_or_ply = paths
+            .output_file
+            .extension()
+            .map(|ext| {
+                let ext = ext.to_string_lossy().to_lowercase();
+                ext == "obj" || ext == "ply"
+            })
+            .unwrap_or(false);
+        if is_obj_or_ply {
+            io::write_mesh(&mesh, &paths.output_file, &io_params.output)
+        } else {
+            io::vtk_format::write_vtk_with_encoding(
+                &mesh,
+                &paths.output_file,
+                "mesh",
+                io_params.output.vtk_encoding,
+            )
+        }
+        .with_context(|| {
             format!(
                 "Failed to write reconstructed surface to output file '{}'",
                 paths.output_file.to_string_lossy()
@@ -615,10 +1178,17 @@ pub(crate) fn reconstruction_pipeline_generic<I: Index, R: Real>(
         info!("Done.");
     }
 
+    let frame = paths.frame_index.map(|frame| io::sequence::SequenceFrame {
+        frame,
+        file: paths.output_file.clone(),
+        num_vertices: mesh.mesh.vertices.len(),
+        num_triangles: mesh.mesh.triangles.len(),
+    });
+
     // Store octree leaf nodes as hex cells
     if let Some(output_octree_file) = &paths.output_octree_file {
         info!("Writing octree to \"{}\"...", output_octree_file.display());
-        io::vtk_format::write_vtk(
+        io::vtk_format::write_vtk_with_encoding(
             reconstruction
                 .octree()
                 .unwrap()
@@ -626,6 +1196,7 @@ pub(crate) fn reconstruction_pipeline_generic<I: Index, R: Real>(
                 .to_unstructured_grid(),
             output_octree_file,
             "mesh",
+            io_params.output.vtk_encoding,
         )
         .with_context(|| {
             format!(
@@ -675,7 +1246,7 @@ pub(crate) fn reconstruction_pipeline_generic<I: Index, R: Real>(
             .ok_or_else(|| anyhow::anyhow!("No density map was created during reconstruction"))?;
 
         let density_mesh =
-            density_map::sparse_density_map_to_hex_mesh(&density_map, &grid, R::zero());
+            density_map::sparse_density_map_to_hex_mesh(density_map, grid, R::zero());
 
         info!(
             "Saving density map hex mesh to \"{}\"...",
@@ -692,12 +1263,441 @@ pub(crate) fn reconstruction_pipeline_generic<I: Index, R: Real>(
     }
 
     if check_mesh {
-        if let Err(err) = splashsurf_lib::marching_cubes::check_mesh_consistency(grid, &mesh.mesh) {
+        // The check is performed on the mesh in the coordinates of the background grid
+        if let Err(err) =
+            splashsurf_lib::marching_cubes::check_mesh_consistency(grid, reconstruction.mesh())
+        {
             return Err(anyhow!("{}", err));
         } else {
             info!("Checked mesh for problems (holes, etc.), no problems were found.");
         }
     }
 
+    Ok(frame)
+}
+
+/// Performs a tiled surface reconstruction and writes the mesh of each tile and the tile manifest to disk
+fn tiled_reconstruction_pipeline_generic<I: Index, R: Real>(
+    paths: &ReconstructionRunnerPaths,
+    particle_positions: &[Vector3<R>],
+    params: &splashsurf_lib::Parameters<R>,
+    cells_per_tile: usize,
+    coordinate_convention: CoordinateConvention,
+) -> Result<(), anyhow::Error> {
+    profile!("tiled surface reconstruction cli");
+
+    let tiles_dir = {
+        let output_stem = paths
+            .output_file
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy();
+        paths
+            .output_file
+            .with_file_name(format!("{}_tiles", output_stem))
+    };
+    std::fs::create_dir_all(&tiles_dir).with_context(|| {
+        format!(
+            "Unable to create output directory '{}' for the tiles",
+            tiles_dir.display()
+        )
+    })?;
+    info!(
+        "Writing tiles of the surface mesh to \"{}\"...",
+        tiles_dir.display()
+    );
+
+    let mut tile_files = Vec::new();
+    let layout = splashsurf_lib::tiling::reconstruct_surface_tiled::<I, R, _>(
+        particle_positions,
+        params,
+        cells_per_tile,
+        |tile, mut mesh| {
+            if coordinate_convention != CoordinateConvention::ZUpRightHanded {
+                coordinate_convention.convert_trimesh(&mut mesh);
+            }
+
+            let file = tiles_dir.join(io::tiles::tile_filename(&tile.index));
+            io::vtk_format::write_vtk(&mesh, &file, "mesh").with_context(|| {
+                format!(
+                    "Failed to write surface mesh of tile {:?} to output file '{}'",
+                    tile.index,
+                    file.display()
+                )
+            })?;
+
+            tile_files.push(io::tiles::TileFile {
+                tile: tile.clone(),
+                file,
+                num_vertices: mesh.vertices.len(),
+                num_triangles: mesh.triangles.len(),
+            });
+            Ok(())
+        },
+    )?;
+
+    io::tiles::write_tile_manifest(
+        tiles_dir.join(io::tiles::MANIFEST_FILENAME),
+        &layout,
+        &tile_files,
+    )?;
+    info!("Done. Wrote {} tiles.", tile_files.len());
+
     Ok(())
 }
+
+/// Performs a tiled surface reconstruction and incrementally writes the meshes of all tiles into the single VTK output file
+fn streamed_tiled_reconstruction_pipeline_generic<I: Index, R: Real>(
+    paths: &ReconstructionRunnerPaths,
+    particle_positions: &[Vector3<R>],
+    params: &splashsurf_lib::Parameters<R>,
+    io_params: &io::FormatParameters,
+    cells_per_tile: usize,
+    coordinate_convention: CoordinateConvention,
+) -> Result<(), anyhow::Error> {
+    profile!("streamed tiled surface reconstruction cli");
+
+    let is_vtk = paths
+        .output_file
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.eq_ignore_ascii_case("vtk"))
+        .unwrap_or(false);
+    if !is_vtk {
+        return Err(anyhow!(
+            "Streaming the output of tiles is only supported for VTK output files, got '{}'",
+            paths.output_file.display()
+        ));
+    }
+
+    info!(
+        "Streaming tiles of the surface mesh to \"{}\"...",
+        paths.output_file.display()
+    );
+
+    let precision = io_params
+        .output
+        .vtk_precision
+        .unwrap_or_else(io::vtk_format::VtkPrecision::of::<R>);
+    let mut writer =
+        io::vtk_format::StreamingTriMeshVtkWriter::new(&paths.output_file, "mesh", precision)?;
+    splashsurf_lib::tiling::reconstruct_surface_tiled::<I, R, _>(
+        particle_positions,
+        params,
+        cells_per_tile,
+        |tile, mut mesh| {
+            if coordinate_convention != CoordinateConvention::ZUpRightHanded {
+                coordinate_convention.convert_trimesh(&mut mesh);
+            }
+
+            writer.write_part(&mesh).with_context(|| {
+                format!(
+                    "Failed to write surface mesh of tile {:?} to output file '{}'",
+                    tile.index,
+                    paths.output_file.display()
+                )
+            })
+        },
+    )?;
+
+    let (num_vertices, num_triangles) = (writer.num_vertices(), writer.num_triangles());
+    writer.finish()?;
+    info!(
+        "Done. Wrote surface mesh with {} vertices and {} triangles.",
+        num_vertices, num_triangles
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use std::collections::HashMap;
+    use std::path::Path;
+
+    /// Writes a small block of particles to the given file
+    fn write_particle_block(path: &Path, offset: f64) {
+        let mut particles = Vec::new();
+        for i in 0..4 {
+            for j in 0..4 {
+                for k in 0..4 {
+                    particles.push(Vector3::new(
+                        offset + i as f64 * 0.05,
+                        j as f64 * 0.05,
+                        k as f64 * 0.05,
+                    ));
+                }
+            }
+        }
+        io::write_particles(
+            &particles,
+            &HashMap::new(),
+            path,
+            &io::OutputFormatParameters::default(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_reconstruct_sequence_with_templates() {
+        let dir = std::env::temp_dir().join("splashsurf_test_reconstruct_sequence");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // Frame 3 is missing and should be skipped
+        for &frame in [1, 2, 4].iter() {
+            write_particle_block(
+                &dir.join(format!("fluid_{:04}.vtk", frame)),
+                frame as f64 * 0.1,
+            );
+        }
+
+        let input_pattern = dir.join("fluid_{frame}.vtk").to_string_lossy().to_string();
+        let output_dir = dir.join("out");
+        let output_dir_arg = output_dir.to_string_lossy().to_string();
+        let args = ReconstructSubcommandArgs::from_iter_safe(&[
+            "splashsurf-reconstruct",
+            "-s",
+            input_pattern.as_str(),
+            "--output-dir",
+            output_dir_arg.as_str(),
+            "-o",
+            "surface_{frame}.ply",
+            "--sequence-start=1",
+            "--sequence-end=4",
+            "--sequence-step=1",
+            "--sequence-input-padding=4",
+            "--sequence-frame-padding=4",
+            "--particle-radius=0.025",
+            "--smoothing-length=2.0",
+            "--cube-size=0.5",
+        ])
+        .unwrap();
+
+        let result = reconstruct_subcommand(&args, false);
+        let outputs = ["surface_0001.ply", "surface_0002.ply", "surface_0004.ply"]
+            .iter()
+            .map(|f| output_dir.join(f).is_file())
+            .collect::<Vec<_>>();
+        let missing_output = output_dir.join("surface_0003.ply").exists();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        result.unwrap();
+        assert_eq!(outputs, vec![true, true, true]);
+        assert!(!missing_output);
+    }
+
+    #[test]
+    fn test_reconstruct_sequence_num_jobs() {
+        let dir = std::env::temp_dir().join("splashsurf_test_reconstruct_sequence_num_jobs");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let num_frames = 5;
+        for frame in 1..=num_frames {
+            write_particle_block(
+                &dir.join(format!("fluid_{}.vtk", frame)),
+                frame as f64 * 0.1,
+            );
+        }
+
+        let input_pattern = dir.join("fluid_{}.vtk").to_string_lossy().to_string();
+        let reconstruct = |output_dir: &Path, extra_args: &[&str]| {
+            let output_dir = output_dir.to_string_lossy().to_string();
+            let mut cmd = vec![
+                "splashsurf-reconstruct",
+                "-s",
+                input_pattern.as_str(),
+                "--output-dir",
+                output_dir.as_str(),
+                "--particle-radius=0.025",
+                "--smoothing-length=2.0",
+                "--cube-size=0.5",
+                "--octree-decomposition=off",
+                "--mt-particles=off",
+            ];
+            cmd.extend_from_slice(extra_args);
+            reconstruct_subcommand(
+                &ReconstructSubcommandArgs::from_iter_safe(&cmd).unwrap(),
+                false,
+            )
+        };
+
+        let serial_dir = dir.join("serial");
+        let parallel_dir = dir.join("parallel");
+        let serial_result = reconstruct(&serial_dir, &[]);
+        let parallel_result = reconstruct(&parallel_dir, &["--num-jobs=2"]);
+
+        let read_outputs = |output_dir: &Path| {
+            (1..=num_frames)
+                .map(|frame| {
+                    std::fs::read(output_dir.join(format!("fluid_surface_{}.vtk", frame))).ok()
+                })
+                .collect::<Vec<_>>()
+        };
+        let serial_outputs = read_outputs(&serial_dir);
+        let parallel_outputs = read_outputs(&parallel_dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        serial_result.unwrap();
+        parallel_result.unwrap();
+        assert!(serial_outputs.iter().all(|output| output.is_some()));
+        assert_eq!(serial_outputs, parallel_outputs);
+    }
+
+    #[test]
+    fn test_reconstruct_stream_tiles() {
+        let dir = std::env::temp_dir().join("splashsurf_test_reconstruct_stream_tiles");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let input_file = dir.join("fluid.vtk");
+        write_particle_block(&input_file, 0.0);
+
+        let input_file = input_file.to_string_lossy().to_string();
+        let reconstruct = |output_file: &Path, extra_args: &[&str]| {
+            let output_file = output_file.to_string_lossy().to_string();
+            let mut cmd = vec![
+                "splashsurf-reconstruct",
+                "-i",
+                input_file.as_str(),
+                "-o",
+                output_file.as_str(),
+                "--particle-radius=0.025",
+                "--smoothing-length=2.0",
+                "--cube-size=0.5",
+                "--octree-decomposition=off",
+            ];
+            cmd.extend_from_slice(extra_args);
+            reconstruct_subcommand(
+                &ReconstructSubcommandArgs::from_iter_safe(&cmd).unwrap(),
+                false,
+            )
+        };
+
+        let output_global = dir.join("surface_global.vtk");
+        let output_streamed = dir.join("surface_streamed.vtk");
+        let output_missing_tiles = dir.join("surface_missing_tiles.vtk");
+        let result_global = reconstruct(&output_global, &[]);
+        let result_streamed =
+            reconstruct(&output_streamed, &["--tile-cells=12", "--stream-tiles=on"]);
+        let result_missing_tiles = reconstruct(&output_missing_tiles, &["--stream-tiles=on"]);
+        let read_mesh = |path: &Path| {
+            io::read_surface_mesh::<f64, _>(path, &io::InputFormatParameters::default()).unwrap()
+        };
+        let meshes = result_global
+            .and(result_streamed)
+            .map(|_| (read_mesh(&output_global), read_mesh(&output_streamed)));
+        let streamed_tile_files_exist = dir.join("surface_streamed_tiles").exists();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let (mesh_global, mesh_streamed) = meshes.unwrap();
+        assert!(!mesh_global.mesh.triangles.is_empty());
+        assert_eq!(
+            mesh_streamed.mesh.triangles.len(),
+            mesh_global.mesh.triangles.len()
+        );
+        assert!(!streamed_tile_files_exist);
+        assert!(result_missing_tiles.is_err());
+    }
+
+    #[test]
+    fn test_sequence_frames_validation() {
+        let args = |extra: &[&str]| {
+            let mut cmd = vec![
+                "splashsurf-reconstruct",
+                "-s",
+                "fluid_{}.vtk",
+                "--particle-radius=0.025",
+                "--smoothing-length=2.0",
+                "--cube-size=0.5",
+            ];
+            cmd.extend_from_slice(extra);
+            ReconstructSubcommandArgs::from_iter_safe(&cmd).unwrap()
+        };
+
+        assert_eq!(
+            arguments::SequenceFrames::try_from(&args(&[])).unwrap(),
+            arguments::SequenceFrames::default()
+        );
+        assert_eq!(
+            arguments::SequenceFrames::try_from(&args(&[
+                "--sequence-start=10",
+                "--sequence-end=20",
+                "--sequence-step=5"
+            ]))
+            .unwrap(),
+            arguments::SequenceFrames {
+                start: 10,
+                end: Some(20),
+                step: 5,
+                input_padding: 0,
+            }
+        );
+        assert!(arguments::SequenceFrames::try_from(&args(&["--sequence-step=0"])).is_err());
+        assert!(arguments::SequenceFrames::try_from(&args(&[
+            "--sequence-start=5",
+            "--sequence-end=4"
+        ]))
+        .is_err());
+    }
+
+    #[test]
+    fn test_reconstruct_precision() {
+        let dir = std::env::temp_dir().join("splashsurf_test_reconstruct_precision");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let input_file = dir.join("fluid.vtk");
+        write_particle_block(&input_file, 0.0);
+
+        let input_file = input_file.to_string_lossy().to_string();
+        let args = |output_file: &Path, extra_args: &[&str]| {
+            let output_file = output_file.to_string_lossy().to_string();
+            let mut cmd = vec![
+                "splashsurf-reconstruct",
+                "-i",
+                input_file.as_str(),
+                "-o",
+                output_file.as_str(),
+                "--particle-radius=0.025",
+                "--smoothing-length=2.0",
+                "--cube-size=0.5",
+                "--octree-decomposition=off",
+            ];
+            cmd.extend_from_slice(extra_args);
+            ReconstructSubcommandArgs::from_iter_safe(&cmd).unwrap()
+        };
+
+        // The precision argument overrides the double precision switch
+        let output_f32 = dir.join("surface_f32.vtk");
+        let output_f64 = dir.join("surface_f64.vtk");
+        let runner_args = |extra_args: &[&str]| {
+            ReconstructionRunnerArgs::try_from(&args(&output_f32, extra_args))
+                .unwrap()
+                .precision
+        };
+        assert_eq!(runner_args(&[]), Precision::F32);
+        assert_eq!(runner_args(&["-d=on"]), Precision::F64);
+        assert_eq!(runner_args(&["-d=on", "--precision=f32"]), Precision::F32);
+        assert_eq!(runner_args(&["--precision=F64"]), Precision::F64);
+
+        let result_f32 = reconstruct_subcommand(&args(&output_f32, &["--precision=f32"]), false);
+        let result_f64 = reconstruct_subcommand(&args(&output_f64, &["--precision=f64"]), false);
+        let read_aabb = |path: &Path| {
+            let mesh = io::read_surface_mesh::<f64, _>(path, &io::InputFormatParameters::default())
+                .unwrap();
+            splashsurf_lib::AxisAlignedBoundingBox3d::from_points(&mesh.mesh.vertices)
+        };
+        let aabbs = result_f32
+            .and(result_f64)
+            .map(|_| (read_aabb(&output_f32), read_aabb(&output_f64)));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let (aabb_f32, aabb_f64) = aabbs.unwrap();
+        let tolerance = 1e-4;
+        assert!((aabb_f32.min() - aabb_f64.min()).amax() <= tolerance);
+        assert!((aabb_f32.max() - aabb_f64.max()).amax() <= tolerance);
+    }
+}
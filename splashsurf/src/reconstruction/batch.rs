@@ -0,0 +1,186 @@
+//! Collection of the input and output files for the batch reconstruction of a directory tree
+//!
+//! In batch mode, all particle files below an input root directory are reconstructed. The output
+//! files are written to an output root directory that mirrors the relative subdirectory structure
+//! of the input root, e.g. with the input root `caches` and the output root `surfaces`, the file
+//! `caches/scene_a/fluid_1.bgeo` is reconstructed to `surfaces/scene_a/fluid_1_surface.vtk`.
+//!
+//! If several input files in the same directory would result in the same output filename (e.g.
+//! `fluid.bgeo` and `fluid.vtk`), the extension of the input file is added to the filenames of
+//! all of these outputs (`fluid_bgeo_surface.vtk` and `fluid_vtk_surface.vtk`).
+
+use anyhow::Context;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// File extensions of the particle files that are collected in batch mode
+const PARTICLE_FILE_EXTENSIONS: &[&str] = &["vtk", "xyz", "ply", "bgeo", "json", "csv"];
+
+/// Recursively collects all particle files below the input root sorted by their paths, skipping the excluded directory
+///
+/// The excluded directory is used to skip the output root if it is located inside of the input root, so
+/// that the output files of a previous run are not reconstructed again.
+pub(crate) fn collect_particle_files(
+    input_root: &Path,
+    exclude_dir: Option<&Path>,
+) -> Result<Vec<PathBuf>, anyhow::Error> {
+    let exclude_dir = exclude_dir.and_then(|dir| dir.canonicalize().ok());
+
+    let mut files = Vec::new();
+    let mut dirs = vec![input_root.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let entries = fs::read_dir(&dir)
+            .with_context(|| format!("Unable to read input directory '{}'", dir.display()))?;
+        for entry in entries {
+            let path = entry
+                .with_context(|| format!("Unable to read input directory '{}'", dir.display()))?
+                .path();
+            if path.is_dir() {
+                // The input root itself is never excluded, only subdirectories of it
+                if exclude_dir.is_none() || path.canonicalize().ok() != exclude_dir {
+                    dirs.push(path);
+                }
+            } else if is_particle_file(&path) {
+                files.push(path);
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// Returns whether the path has the extension of a supported particle file format
+fn is_particle_file(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| {
+            let ext = ext.to_string_lossy().to_lowercase();
+            PARTICLE_FILE_EXTENSIONS.contains(&ext.as_str())
+        })
+        .unwrap_or(false)
+}
+
+/// Returns the output file for every input file, mirroring the path relative to the input root in the output root
+///
+/// The output filenames are given by `"{input_stem}_{output_suffix}.{output_extension}"`, collisions of
+/// output files are resolved by adding the input file extension, see the [module level documentation](self).
+pub(crate) fn mirrored_output_files(
+    input_root: &Path,
+    output_root: &Path,
+    input_files: &[PathBuf],
+    output_suffix: &str,
+    output_extension: &str,
+) -> Result<Vec<PathBuf>, anyhow::Error> {
+    let output_file = |input_file: &Path, with_input_extension: bool| {
+        let relative_path = input_file.strip_prefix(input_root).with_context(|| {
+            format!(
+                "The input file '{}' is not located in the input directory '{}'",
+                input_file.display(),
+                input_root.display()
+            )
+        })?;
+        let stem = relative_path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy();
+        let filename = match (with_input_extension, relative_path.extension()) {
+            (true, Some(ext)) => format!(
+                "{}_{}_{}.{}",
+                stem,
+                ext.to_string_lossy(),
+                output_suffix,
+                output_extension
+            ),
+            _ => format!("{}_{}.{}", stem, output_suffix, output_extension),
+        };
+        Ok(output_root.join(relative_path).with_file_name(filename))
+    };
+
+    let output_files = input_files
+        .iter()
+        .map(|input_file| output_file(input_file, false))
+        .collect::<Result<Vec<PathBuf>, anyhow::Error>>()?;
+
+    let mut output_counts: HashMap<&Path, usize> = HashMap::new();
+    for output_file in output_files.iter() {
+        *output_counts.entry(output_file.as_path()).or_insert(0) += 1;
+    }
+
+    input_files
+        .iter()
+        .zip(output_files.iter())
+        .map(|(input_file, output_file_without_extension)| {
+            if output_counts[output_file_without_extension.as_path()] > 1 {
+                output_file(input_file, true)
+            } else {
+                Ok(output_file_without_extension.clone())
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mirrored_output_files() {
+        let input_files = vec![
+            PathBuf::from("caches/fluid.bgeo"),
+            PathBuf::from("caches/scene_a/fluid_1.bgeo"),
+            PathBuf::from("caches/scene_a/fluid_1.vtk"),
+            PathBuf::from("caches/scene_b/deep/fluid_1.xyz"),
+        ];
+        let output_files = mirrored_output_files(
+            Path::new("caches"),
+            Path::new("surfaces"),
+            &input_files,
+            "surface",
+            "vtk",
+        )
+        .unwrap();
+
+        assert_eq!(
+            output_files,
+            vec![
+                PathBuf::from("surfaces/fluid_surface.vtk"),
+                PathBuf::from("surfaces/scene_a/fluid_1_bgeo_surface.vtk"),
+                PathBuf::from("surfaces/scene_a/fluid_1_vtk_surface.vtk"),
+                PathBuf::from("surfaces/scene_b/deep/fluid_1_surface.vtk"),
+            ]
+        );
+
+        assert!(mirrored_output_files(
+            Path::new("caches"),
+            Path::new("surfaces"),
+            &[PathBuf::from("other/fluid.bgeo")],
+            "surface",
+            "vtk",
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_collect_particle_files() {
+        let root = std::env::temp_dir().join("splashsurf_test_collect_particle_files");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("a/b")).unwrap();
+        fs::create_dir_all(root.join("out")).unwrap();
+        for file in &["1.vtk", "a/2.BGEO", "a/b/3.xyz", "a/notes.txt", "out/4.vtk"] {
+            fs::write(root.join(file), b"").unwrap();
+        }
+
+        let files = collect_particle_files(&root, Some(&root.join("out"))).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(
+            files,
+            vec![
+                root.join("1.vtk"),
+                root.join("a/2.BGEO"),
+                root.join("a/b/3.xyz")
+            ]
+        );
+    }
+}
@@ -0,0 +1,64 @@
+//! Progress bar for the reconstruction of a single input file
+
+use indicatif::{ProgressBar, ProgressStyle};
+use splashsurf_lib::{ReconstructionProgress, ReconstructionStage};
+
+/// Number of steps of the progress bar, i.e. the resolution of the reported progress
+const PROGRESS_BAR_LENGTH: u64 = 1000;
+/// Fraction of the progress bar used by the surface reconstruction, the remainder is used for writing the mesh
+const RECONSTRUCTION_FRACTION: f64 = 0.9;
+
+/// Progress bar on stderr that shows the stages of the reconstruction of a single input file
+///
+/// Nothing is drawn if the progress bar is disabled or if stderr is not a terminal. The progress bar is
+/// cleared when it is dropped.
+pub(crate) struct ReconstructionProgressBar {
+    bar: ProgressBar,
+}
+
+impl ReconstructionProgressBar {
+    /// Creates a new progress bar, nothing is drawn if the progress bar is disabled
+    pub fn new(enabled: bool) -> Self {
+        let bar = if enabled {
+            ProgressBar::new(PROGRESS_BAR_LENGTH).with_style(
+                ProgressStyle::default_bar()
+                    .template("[{elapsed_precise}] [{bar:40}] {percent:>3}% {msg}")
+                    .progress_chars("#>-"),
+            )
+        } else {
+            ProgressBar::hidden()
+        };
+
+        Self { bar }
+    }
+
+    /// Updates the progress bar with the progress reported by the surface reconstruction
+    pub fn update(&self, progress: ReconstructionProgress) {
+        let message = match progress.stage {
+            ReconstructionStage::Initialization => "initialization",
+            ReconstructionStage::NeighborhoodSearch => "neighborhood search",
+            ReconstructionStage::DensityMap => "density accumulation",
+            ReconstructionStage::MarchingCubes => "marching cubes",
+            ReconstructionStage::Subdomains => "reconstruction of subdomains",
+            ReconstructionStage::Finished => "post-processing",
+        };
+        self.set(progress.progress * RECONSTRUCTION_FRACTION, message);
+    }
+
+    /// Marks the start of writing the surface mesh to the output file
+    pub fn writing_mesh(&self) {
+        self.set(RECONSTRUCTION_FRACTION, "mesh writing");
+    }
+
+    fn set(&self, progress: f64, message: &'static str) {
+        self.bar.set_message(message);
+        self.bar
+            .set_position((progress.clamp(0.0, 1.0) * PROGRESS_BAR_LENGTH as f64) as u64);
+    }
+}
+
+impl Drop for ReconstructionProgressBar {
+    fn drop(&mut self) {
+        self.bar.finish_and_clear();
+    }
+}
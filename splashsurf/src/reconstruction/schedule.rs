@@ -0,0 +1,282 @@
+//! Per-frame parameter schedules for the reconstruction of input file sequences
+//!
+//! A schedule is read from a JSON file that maps frame indices (as used for the `{}` placeholder
+//! of the input sequence) to overrides of reconstruction parameters, e.g.:
+//! ```json
+//! {
+//!     "interpolation": "linear",
+//!     "frames": {
+//!         "1": { "surface_threshold": 0.6, "cube_size": 0.5 },
+//!         "100": { "surface_threshold": 0.75 }
+//!     }
+//! }
+//! ```
+//! The supported parameters are `surface_threshold` and `cube_size` with the same units as the
+//! corresponding command line arguments. Each parameter is evaluated independently, only considering
+//! the keyed frames that actually specify it:
+//!  - Frames before the first keyed frame of a parameter use the value from the command line.
+//!  - Frames after the last keyed frame of a parameter use the value of the last keyed frame.
+//!  - Frames in between two keyed frames use the value of the preceding keyed frame for `"step"`
+//!    interpolation (the default) and linear interpolation between the two values for `"linear"`
+//!    interpolation.
+
+use anyhow::{anyhow, Context};
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+/// Interpolation mode used to evaluate a schedule between its keyed frames
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ScheduleInterpolation {
+    /// Use the value of the preceding keyed frame
+    Step,
+    /// Linearly interpolate between the values of the preceding and the following keyed frame
+    Linear,
+}
+
+/// Overrides of reconstruction parameters for a single frame, `None` values fall back to the command line values
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FrameOverrides {
+    /// The iso-surface threshold in multiplies of the rest density
+    pub surface_threshold: Option<f64>,
+    /// The marching cubes cube size in multiplies of the particle radius
+    pub cube_size: Option<f64>,
+}
+
+/// A validated per-frame parameter schedule
+#[derive(Clone, Debug)]
+pub struct ParameterSchedule {
+    /// Interpolation mode between keyed frames
+    interpolation: ScheduleInterpolation,
+    /// Keyed frames with their overrides, sorted by frame index
+    keyed_frames: Vec<(usize, FrameOverrides)>,
+}
+
+impl ParameterSchedule {
+    /// Tries to read and validate a parameter schedule from the JSON file at the given path
+    pub fn from_json_file<P: AsRef<Path>>(path: P) -> Result<Self, anyhow::Error> {
+        let path = path.as_ref();
+        let json = fs::read_to_string(path).with_context(|| {
+            format!("Cannot read parameter schedule file \"{}\"", path.display())
+        })?;
+        Self::from_json_str(&json)
+            .with_context(|| format!("Invalid parameter schedule in \"{}\"", path.display()))
+    }
+
+    /// Tries to parse and validate a parameter schedule from the given JSON string
+    pub fn from_json_str(json: &str) -> Result<Self, anyhow::Error> {
+        let json: Value =
+            serde_json::from_str(json).context("Parameter schedule is not valid JSON")?;
+        Self::from_json_value(&json)
+    }
+
+    /// Tries to convert the given JSON value into a parameter schedule
+    fn from_json_value(json: &Value) -> Result<Self, anyhow::Error> {
+        let root = json
+            .as_object()
+            .ok_or_else(|| anyhow!("Expected a JSON object at the top level"))?;
+
+        if let Some(key) = root
+            .keys()
+            .find(|&key| key != "interpolation" && key != "frames")
+        {
+            return Err(anyhow!("Unknown top level key \"{}\"", key));
+        }
+
+        let interpolation = match root.get("interpolation") {
+            None => ScheduleInterpolation::Step,
+            Some(Value::String(mode)) => match mode.to_lowercase().as_str() {
+                "step" => ScheduleInterpolation::Step,
+                "linear" => ScheduleInterpolation::Linear,
+                _ => {
+                    return Err(anyhow!(
+                        "Unknown interpolation mode \"{}\" (expected \"step\" or \"linear\")",
+                        mode
+                    ))
+                }
+            },
+            Some(_) => return Err(anyhow!("The \"interpolation\" value has to be a string")),
+        };
+
+        let frames = root
+            .get("frames")
+            .ok_or_else(|| anyhow!("Missing \"frames\" object"))?
+            .as_object()
+            .ok_or_else(|| anyhow!("The \"frames\" value has to be an object"))?;
+
+        let mut keyed_frames = Vec::with_capacity(frames.len());
+        for (frame, overrides) in frames {
+            let frame_index = frame.parse::<usize>().map_err(|_| {
+                anyhow!(
+                    "Invalid frame index \"{}\" (expected a non-negative integer)",
+                    frame
+                )
+            })?;
+            let overrides = parse_frame_overrides(overrides)
+                .with_context(|| format!("Invalid overrides for frame {}", frame_index))?;
+            keyed_frames.push((frame_index, overrides));
+        }
+
+        keyed_frames.sort_by_key(|(frame_index, _)| *frame_index);
+        if let Some(w) = keyed_frames.windows(2).find(|w| w[0].0 == w[1].0) {
+            return Err(anyhow!("Frame {} is specified more than once", w[0].0));
+        }
+
+        Ok(Self {
+            interpolation,
+            keyed_frames,
+        })
+    }
+
+    /// Returns the interpolation mode of the schedule
+    pub fn interpolation(&self) -> ScheduleInterpolation {
+        self.interpolation
+    }
+
+    /// Evaluates the schedule for the given frame index
+    pub fn overrides_for_frame(&self, frame_index: usize) -> FrameOverrides {
+        FrameOverrides {
+            surface_threshold: self.evaluate(frame_index, |o| o.surface_threshold),
+            cube_size: self.evaluate(frame_index, |o| o.cube_size),
+        }
+    }
+
+    /// Evaluates a single parameter of the schedule for the given frame index
+    fn evaluate<F: Fn(&FrameOverrides) -> Option<f64>>(
+        &self,
+        frame_index: usize,
+        parameter: F,
+    ) -> Option<f64> {
+        let keys = self
+            .keyed_frames
+            .iter()
+            .filter_map(|(key_frame, overrides)| parameter(overrides).map(|v| (*key_frame, v)));
+
+        let mut previous = None;
+        for (key_frame, value) in keys {
+            if key_frame == frame_index {
+                return Some(value);
+            } else if key_frame > frame_index {
+                return match (previous, self.interpolation) {
+                    (None, _) => None,
+                    (Some((_, previous_value)), ScheduleInterpolation::Step) => {
+                        Some(previous_value)
+                    }
+                    (Some((previous_frame, previous_value)), ScheduleInterpolation::Linear) => {
+                        let t = (frame_index - previous_frame) as f64
+                            / (key_frame - previous_frame) as f64;
+                        Some(previous_value + t * (value - previous_value))
+                    }
+                };
+            }
+            previous = Some((key_frame, value));
+        }
+
+        previous.map(|(_, value)| value)
+    }
+}
+
+/// Tries to parse the parameter overrides of a single keyed frame
+fn parse_frame_overrides(json: &Value) -> Result<FrameOverrides, anyhow::Error> {
+    let object = json
+        .as_object()
+        .ok_or_else(|| anyhow!("Expected an object with parameter overrides"))?;
+
+    let mut overrides = FrameOverrides::default();
+    for (key, value) in object {
+        let value = value
+            .as_f64()
+            .ok_or_else(|| anyhow!("The value of \"{}\" has to be a number", key))?;
+        if value <= 0.0 || !value.is_finite() {
+            return Err(anyhow!(
+                "The value of \"{}\" has to be a positive number (got {})",
+                key,
+                value
+            ));
+        }
+
+        match key.as_str() {
+            "surface_threshold" => overrides.surface_threshold = Some(value),
+            "cube_size" => overrides.cube_size = Some(value),
+            _ => {
+                return Err(anyhow!(
+                    "Unknown parameter \"{}\" (supported: \"surface_threshold\", \"cube_size\")",
+                    key
+                ))
+            }
+        }
+    }
+
+    Ok(overrides)
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    const SCHEDULE: &str = r#"{
+        "interpolation": "linear",
+        "frames": {
+            "10": { "surface_threshold": 0.6, "cube_size": 0.5 },
+            "20": { "surface_threshold": 0.8 }
+        }
+    }"#;
+
+    #[test]
+    fn test_schedule_linear() -> Result<(), anyhow::Error> {
+        let schedule = ParameterSchedule::from_json_str(SCHEDULE)?;
+        assert_eq!(schedule.interpolation(), ScheduleInterpolation::Linear);
+
+        assert_eq!(schedule.overrides_for_frame(1), FrameOverrides::default());
+        assert_eq!(
+            schedule.overrides_for_frame(10),
+            FrameOverrides {
+                surface_threshold: Some(0.6),
+                cube_size: Some(0.5)
+            }
+        );
+
+        let overrides = schedule.overrides_for_frame(15);
+        assert!((overrides.surface_threshold.unwrap() - 0.7).abs() < 1e-12);
+        assert_eq!(overrides.cube_size, Some(0.5));
+
+        assert_eq!(
+            schedule.overrides_for_frame(30).surface_threshold,
+            Some(0.8)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_schedule_step() -> Result<(), anyhow::Error> {
+        let schedule =
+            ParameterSchedule::from_json_str(&SCHEDULE.replace("\"linear\"", "\"step\""))?;
+
+        assert_eq!(
+            schedule.overrides_for_frame(19).surface_threshold,
+            Some(0.6)
+        );
+        assert_eq!(
+            schedule.overrides_for_frame(20).surface_threshold,
+            Some(0.8)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_schedule_validation() {
+        assert!(ParameterSchedule::from_json_str(r#"{ "frames": { "a": {} } }"#).is_err());
+        assert!(ParameterSchedule::from_json_str(
+            r#"{ "frames": { "1": { "smoothing_length": 2.0 } } }"#
+        )
+        .is_err());
+        assert!(ParameterSchedule::from_json_str(
+            r#"{ "frames": { "1": { "cube_size": -1.0 } } }"#
+        )
+        .is_err());
+        assert!(
+            ParameterSchedule::from_json_str(r#"{ "interpolation": "cubic", "frames": {} }"#)
+                .is_err()
+        );
+    }
+}
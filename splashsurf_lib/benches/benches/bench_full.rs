@@ -2,8 +2,8 @@ use criterion::{criterion_group, Criterion};
 use nalgebra::Vector3;
 use splashsurf_lib::{
     reconstruct_surface, reconstruct_surface_inplace, Parameters,
-    ParticleDensityComputationStrategy, SpatialDecompositionParameters, SubdivisionCriterion,
-    SurfaceReconstruction,
+    ParticleDensityComputationStrategy, PeriodicAxes, SpatialDecompositionParameters,
+    SubdivisionCriterion, SurfaceReconstruction,
 };
 use std::time::Duration;
 
@@ -28,8 +28,17 @@ pub fn surface_reconstruction_canyon(c: &mut Criterion) {
         cube_size,
         iso_surface_threshold: 0.6,
         domain_aabb: None,
+        grid_margin_cells: 0,
         enable_multi_threading: true,
         spatial_decomposition: None,
+        periodic_axes: PeriodicAxes::none(),
+        mask: None,
+        contour_cell_factors: None,
+        normalize_by_rest_density: None,
+        edge_interpolation: None,
+        max_grid_cells: None,
+        kernel: None,
+        anisotropy: None,
     };
 
     let mut group = c.benchmark_group("full surface reconstruction");
@@ -104,8 +113,17 @@ pub fn surface_reconstruction_dam_break(c: &mut Criterion) {
         cube_size,
         iso_surface_threshold: 0.6,
         domain_aabb: None,
+        grid_margin_cells: 0,
         enable_multi_threading: true,
         spatial_decomposition: None,
+        periodic_axes: PeriodicAxes::none(),
+        mask: None,
+        contour_cell_factors: None,
+        normalize_by_rest_density: None,
+        edge_interpolation: None,
+        max_grid_cells: None,
+        kernel: None,
+        anisotropy: None,
     };
 
     let mut group = c.benchmark_group("full surface reconstruction");
@@ -185,8 +203,17 @@ pub fn surface_reconstruction_double_dam_break(c: &mut Criterion) {
         cube_size,
         iso_surface_threshold: 0.6,
         domain_aabb: None,
+        grid_margin_cells: 0,
         enable_multi_threading: true,
         spatial_decomposition: None,
+        periodic_axes: PeriodicAxes::none(),
+        mask: None,
+        contour_cell_factors: None,
+        normalize_by_rest_density: None,
+        edge_interpolation: None,
+        max_grid_cells: None,
+        kernel: None,
+        anisotropy: None,
     };
 
     let mut group = c.benchmark_group("full surface reconstruction");
@@ -266,8 +293,17 @@ pub fn surface_reconstruction_double_dam_break_inplace(c: &mut Criterion) {
         cube_size,
         iso_surface_threshold: 0.6,
         domain_aabb: None,
+        grid_margin_cells: 0,
         enable_multi_threading: true,
         spatial_decomposition: None,
+        periodic_axes: PeriodicAxes::none(),
+        mask: None,
+        contour_cell_factors: None,
+        normalize_by_rest_density: None,
+        edge_interpolation: None,
+        max_grid_cells: None,
+        kernel: None,
+        anisotropy: None,
     };
 
     let mut group = c.benchmark_group("full surface reconstruction");
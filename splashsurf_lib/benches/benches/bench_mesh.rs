@@ -1,8 +1,9 @@
 use super::io::vtk::particles_from_vtk;
-use criterion::{criterion_group, Criterion};
+use criterion::{criterion_group, BatchSize, Criterion};
+use splashsurf_lib::mesh::{par_concat, TriMesh3d};
 use splashsurf_lib::nalgebra::Vector3;
 use splashsurf_lib::{
-    reconstruct_surface, Parameters, ParticleDensityComputationStrategy,
+    reconstruct_surface, Parameters, ParticleDensityComputationStrategy, PeriodicAxes,
     SpatialDecompositionParameters, SubdivisionCriterion, SurfaceReconstruction,
 };
 use std::path::Path;
@@ -22,6 +23,7 @@ fn reconstruct_particles<P: AsRef<Path>>(particle_file: P) -> SurfaceReconstruct
         cube_size,
         iso_surface_threshold: 0.6,
         domain_aabb: None,
+        grid_margin_cells: 0,
         enable_multi_threading: true,
         spatial_decomposition: Some(SpatialDecompositionParameters {
             subdivision_criterion: SubdivisionCriterion::MaxParticleCountAuto,
@@ -29,6 +31,14 @@ fn reconstruct_particles<P: AsRef<Path>>(particle_file: P) -> SurfaceReconstruct
             enable_stitching: true,
             particle_density_computation: ParticleDensityComputationStrategy::SynchronizeSubdomains,
         }),
+        periodic_axes: PeriodicAxes::none(),
+        mask: None,
+        contour_cell_factors: None,
+        normalize_by_rest_density: None,
+        edge_interpolation: None,
+        max_grid_cells: None,
+        kernel: None,
+        anisotropy: None,
     };
 
     reconstruct_surface::<i64, _>(particle_positions.as_slice(), &parameters).unwrap()
@@ -74,8 +84,54 @@ pub fn mesh_vertex_normals_parallel(c: &mut Criterion) {
     group.finish();
 }
 
+/// Generates many small meshes similar to the per-leaf meshes of a domain decomposed reconstruction
+fn leaf_meshes(num_meshes: usize, triangles_per_mesh: usize) -> Vec<TriMesh3d<f32>> {
+    (0..num_meshes)
+        .map(|i| TriMesh3d {
+            vertices: (0..triangles_per_mesh + 2)
+                .map(|j| Vector3::new(i as f32, j as f32, (i + j) as f32))
+                .collect(),
+            triangles: (0..triangles_per_mesh).map(|j| [j, j + 1, j + 2]).collect(),
+        })
+        .collect()
+}
+
+pub fn mesh_concat(c: &mut Criterion) {
+    let meshes = leaf_meshes(4000, 2000);
+
+    let mut group = c.benchmark_group("mesh");
+    group.sample_size(20);
+    group.warm_up_time(Duration::from_secs(3));
+    group.measurement_time(Duration::from_secs(10));
+
+    group.bench_function("mesh_concat_serial", |b| {
+        b.iter_batched(
+            || meshes.clone(),
+            |meshes| {
+                let mut mesh = TriMesh3d::default();
+                for mut leaf_mesh in meshes {
+                    mesh.append(&mut leaf_mesh);
+                }
+                criterion::black_box(mesh)
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.bench_function("mesh_concat_parallel", |b| {
+        b.iter_batched(
+            || meshes.clone(),
+            |meshes| criterion::black_box(par_concat(meshes)),
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.finish();
+}
+
 criterion_group!(
     bench_mesh,
     mesh_vertex_normals,
-    mesh_vertex_normals_parallel
+    mesh_vertex_normals_parallel,
+    mesh_concat
 );
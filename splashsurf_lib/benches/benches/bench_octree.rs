@@ -21,6 +21,8 @@ pub fn subdivide_recursively_benchmark(c: &mut Criterion) {
         compact_support_radius,
         cube_size,
         None,
+        0,
+        None,
         true,
     )
     .unwrap();
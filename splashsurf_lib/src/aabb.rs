@@ -1,15 +1,23 @@
 //! Axis-aligned bounding boxes
 
-use std::fmt;
-use std::fmt::Debug;
+use core::fmt;
+use core::fmt::Debug;
 
 use nalgebra::SVector;
+#[cfg(feature = "std")]
 use rayon::prelude::*;
 
-use crate::{Real, ThreadSafe};
+use crate::Real;
+#[cfg(feature = "std")]
+use crate::ThreadSafe;
 
 /// Type representing an axis aligned bounding box in arbitrary dimensions
 #[derive(Clone, Eq, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde_crate::Serialize, serde_crate::Deserialize),
+    serde(crate = "serde_crate")
+)]
 pub struct AxisAlignedBoundingBox<R: Real, const D: usize> {
     min: SVector<R, D>,
     max: SVector<R, D>,
@@ -20,6 +28,7 @@ pub type AxisAlignedBoundingBox2d<R> = AxisAlignedBoundingBox<R, 2>;
 /// Convenience type alias for an AABB in three dimensions
 pub type AxisAlignedBoundingBox3d<R> = AxisAlignedBoundingBox<R, 3>;
 
+#[cfg(feature = "std")]
 impl<R, const D: usize> AxisAlignedBoundingBox<R, D>
 where
     R: Real,
@@ -215,6 +224,12 @@ where
         self.max = &self.max + &SVector::repeat(margin);
     }
 
+    /// Grows this AABB in every direction by the margin given per axis (i.e. adding the margins to min/max extents)
+    pub fn grow(&mut self, margin: &SVector<R, D>) {
+        self.min -= margin;
+        self.max += margin;
+    }
+
     /// Returns the smallest cubical AABB with the same center that encloses this AABB
     pub fn enclosing_cube(&self) -> Self {
         let center = self.centroid();
@@ -0,0 +1,251 @@
+//! Anisotropic kernels for surface reconstruction of thin fluid features
+//!
+//! Isotropic kernels smear out thin sheets and filaments of fluid particles over the whole compact support.
+//! Following Yu and Turk ("Reconstructing Surfaces of Particle-Based Fluids Using Anisotropic Kernels", 2013),
+//! the kernel of every particle can be stretched according to the distribution of its neighbors. For this, the
+//! weighted covariance matrix of the neighbor positions is computed per particle:
+//! ```text
+//! x_w = sum_j w_ij x_j / sum_j w_ij
+//! C   = sum_j w_ij (x_j - x_w)(x_j - x_w)^T / sum_j w_ij
+//! ```
+//! with the weights `w_ij = 1 - (|x_i - x_j| / h)^3`. The eigenvalues `σ_1 >= σ_2 >= σ_3` of `C` are clamped
+//! to `σ_k >= σ_1 / k_r` and the kernel is compressed by the factor `σ_1 / σ_k` along the corresponding
+//! eigenvector. The resulting kernel of a particle is given by
+//! ```text
+//! W_i(x) = det(G_i) W(|G_i (x - x̄_i)|, h)
+//! ```
+//! where the transform `G_i` has eigenvalues in `[1, k_r]`. In contrast to the original method, the kernel is
+//! only compressed but never stretched beyond the compact support radius `h`, i.e. the support of every
+//! anisotropic kernel is contained in the support of the isotropic kernel. The factor `det(G_i)` preserves
+//! the integral of the kernel. Particles with too few neighbors keep the isotropic kernel.
+//!
+//! Optionally, the kernel centers `x̄_i` are moved towards the weighted mean of the neighbors to reduce
+//! the bumpiness of flat surfaces: `x̄_i = (1 - λ) x_i + λ x_w`.
+
+use crate::{profile, validate_particle_inputs, ParticleInputLengthError, Real};
+use nalgebra::{Matrix3, Vector3};
+use rayon::prelude::*;
+
+/// Parameters for the anisotropic kernels, see the [module level documentation](self) for details
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AnisotropyParameters<R: Real> {
+    /// Weight `λ` in `[0, 1]` for moving the kernel centers towards the weighted mean of their neighbors, zero disables the smoothing
+    pub smoothing_weight: R,
+    /// Maximum ratio `k_r >= 1` between the largest and smallest eigenvalue of the covariance matrix, limits the compression of the kernels
+    pub max_eigenvalue_ratio: R,
+    /// Minimum number of neighbors of a particle to use an anisotropic kernel, particles with fewer neighbors keep the isotropic kernel
+    pub min_neighbors: usize,
+}
+
+impl<R: Real> Default for AnisotropyParameters<R> {
+    fn default() -> Self {
+        Self {
+            smoothing_weight: R::from_f64(0.9).unwrap(),
+            max_eigenvalue_ratio: R::from_f64(4.0).unwrap(),
+            // Particles along the rim of a thin sheet have considerably fewer neighbors than particles inside
+            // of the sheet, a larger minimum would keep their isotropic kernels and thicken the rim
+            min_neighbors: 4,
+        }
+    }
+}
+
+impl<R: Real> AnisotropyParameters<R> {
+    /// Tries to convert the parameters from one [Real] type to another [Real] type, returns None if conversion fails
+    pub fn try_convert<T: Real>(&self) -> Option<AnisotropyParameters<T>> {
+        Some(AnisotropyParameters {
+            smoothing_weight: self.smoothing_weight.try_convert()?,
+            max_eigenvalue_ratio: self.max_eigenvalue_ratio.try_convert()?,
+            min_neighbors: self.min_neighbors,
+        })
+    }
+
+    /// Returns whether the parameters are in their valid ranges
+    pub fn is_valid(&self) -> bool {
+        self.smoothing_weight >= R::zero()
+            && self.smoothing_weight <= R::one()
+            && self.max_eigenvalue_ratio >= R::one()
+    }
+}
+
+/// The anisotropic kernels of a set of particles
+#[derive(Clone, Debug, Default)]
+pub struct AnisotropicKernels<R: Real> {
+    /// The (optionally smoothed) kernel center of every particle
+    pub centers: Vec<Vector3<R>>,
+    /// The transform `G_i` of every particle, applied to the distance vector before evaluating the isotropic kernel
+    pub transforms: Vec<Matrix3<R>>,
+}
+
+/// Computes the anisotropic kernel centers and transforms of all particles from their neighborhoods
+///
+/// The neighbor lists have to be computed with the given compact support radius, e.g. using the
+/// [`neighborhood_search`](crate::neighborhood_search). Returns an error if the number of neighbor
+/// lists does not match the number of particles.
+pub fn compute_anisotropic_kernels<R: Real>(
+    particle_positions: &[Vector3<R>],
+    particle_neighbor_lists: &[Vec<usize>],
+    compact_support_radius: R,
+    parameters: &AnisotropyParameters<R>,
+    enable_multi_threading: bool,
+) -> Result<AnisotropicKernels<R>, ParticleInputLengthError> {
+    profile!("compute_anisotropic_kernels");
+
+    validate_particle_inputs(
+        particle_positions,
+        &[(
+            "particle_neighbor_lists",
+            Some(particle_neighbor_lists.len()),
+        )],
+    )?;
+
+    let compute_kernel = |i: usize| {
+        compute_particle_kernel(
+            i,
+            particle_positions,
+            &particle_neighbor_lists[i],
+            compact_support_radius,
+            parameters,
+        )
+    };
+
+    let (centers, transforms) = if enable_multi_threading {
+        (0..particle_positions.len())
+            .into_par_iter()
+            .with_min_len(8)
+            .map(compute_kernel)
+            .unzip()
+    } else {
+        (0..particle_positions.len()).map(compute_kernel).unzip()
+    };
+
+    Ok(AnisotropicKernels {
+        centers,
+        transforms,
+    })
+}
+
+/// Computes the kernel center and transform of a single particle
+fn compute_particle_kernel<R: Real>(
+    i: usize,
+    particle_positions: &[Vector3<R>],
+    neighbors: &[usize],
+    compact_support_radius: R,
+    parameters: &AnisotropyParameters<R>,
+) -> (Vector3<R>, Matrix3<R>) {
+    let x_i = particle_positions[i];
+    if neighbors.len() < parameters.min_neighbors.max(1) {
+        return (x_i, Matrix3::identity());
+    }
+
+    let weight = |x_j: &Vector3<R>| {
+        let q = (x_j - x_i).norm() / compact_support_radius;
+        (R::one() - q * q * q).max(R::zero())
+    };
+
+    // The particle itself contributes with weight one
+    let mut weight_sum = R::one();
+    let mut weighted_mean = x_i;
+    for x_j in neighbors.iter().map(|&j| &particle_positions[j]) {
+        let w = weight(x_j);
+        weight_sum += w;
+        weighted_mean += x_j * w;
+    }
+    weighted_mean /= weight_sum;
+
+    let mut covariance = (x_i - weighted_mean) * (x_i - weighted_mean).transpose();
+    for x_j in neighbors.iter().map(|&j| &particle_positions[j]) {
+        let dx = x_j - weighted_mean;
+        covariance += dx * dx.transpose() * weight(x_j);
+    }
+    covariance /= weight_sum;
+
+    let center = x_i * (R::one() - parameters.smoothing_weight)
+        + weighted_mean * parameters.smoothing_weight;
+
+    let eigen = covariance.symmetric_eigen();
+    let max_eigenvalue = eigen.eigenvalues.max();
+    if max_eigenvalue <= R::zero() {
+        return (center, Matrix3::identity());
+    }
+
+    // Compress the kernel along the eigenvectors with small eigenvalues, the largest axis keeps the compact support radius
+    let min_eigenvalue = max_eigenvalue / parameters.max_eigenvalue_ratio;
+    let scaling = eigen
+        .eigenvalues
+        .map(|sigma| max_eigenvalue / sigma.max(min_eigenvalue));
+    let transform =
+        eigen.eigenvectors * Matrix3::from_diagonal(&scaling) * eigen.eigenvectors.transpose();
+
+    (center, transform)
+}
+
+#[test]
+fn test_anisotropic_kernels_sheet() {
+    use crate::neighborhood_search;
+    use crate::AxisAlignedBoundingBox3d;
+
+    let spacing = 0.05;
+    let compact_support_radius = 2.0 * spacing;
+    let mut particle_positions = Vec::new();
+    for i in 0..10 {
+        for j in 0..10 {
+            particle_positions.push(Vector3::new(i as f64, j as f64, 0.0) * spacing);
+        }
+    }
+
+    let mut aabb = AxisAlignedBoundingBox3d::from_points(&particle_positions);
+    aabb.grow_uniformly(compact_support_radius);
+    let mut neighbor_lists = Vec::new();
+    neighborhood_search::search_inplace::<i64, f64>(
+        &aabb,
+        &particle_positions,
+        compact_support_radius,
+        false,
+        &mut neighbor_lists,
+    );
+
+    let parameters = AnisotropyParameters::default();
+    let kernels = compute_anisotropic_kernels(
+        &particle_positions,
+        &neighbor_lists,
+        compact_support_radius,
+        &parameters,
+        false,
+    )
+    .unwrap();
+
+    // An interior particle of the sheet gets a kernel that is compressed along the normal of the sheet
+    let i = 5 * 10 + 5;
+    let transform = kernels.transforms[i];
+    assert!((transform * Vector3::z() - Vector3::z() * 4.0).norm() < 1e-6);
+    assert!((transform * Vector3::x()).norm() < 1.0 + 1e-6);
+    assert!((transform * Vector3::y()).norm() < 1.0 + 1e-6);
+    assert!((kernels.centers[i] - particle_positions[i]).norm() < 1e-6);
+
+    // The smoothed kernel centers stay in the plane of the sheet
+    for center in kernels.centers.iter() {
+        assert!(center.z.abs() < 1e-10);
+    }
+
+    // Multi-threading does not change the result
+    let parallel_kernels = compute_anisotropic_kernels(
+        &particle_positions,
+        &neighbor_lists,
+        compact_support_radius,
+        &parameters,
+        true,
+    )
+    .unwrap();
+    assert_eq!(kernels.transforms, parallel_kernels.transforms);
+
+    // Isolated particles keep the isotropic kernel
+    let kernels = compute_anisotropic_kernels(
+        &particle_positions[..1],
+        &[Vec::new()],
+        compact_support_radius,
+        &parameters,
+        false,
+    )
+    .unwrap();
+    assert_eq!(kernels.transforms[0], Matrix3::identity());
+}
@@ -0,0 +1,531 @@
+//! Bounding volume hierarchy (BVH) over the triangles of a mesh for nearest point and ray queries
+//!
+//! The [`TriangleBvh`] is a binary tree of axis-aligned bounding boxes that is built once from a
+//! [`TriMesh3d`] and can then be queried for the closest point on the surface of the mesh
+//! ([`TriangleBvh::nearest_point`]) and for intersections with rays ([`TriangleBvh::any_hit`],
+//! [`TriangleBvh::all_hits`]). The BVH stores a copy of the triangle coordinates, so it does not
+//! borrow the mesh it was built from.
+
+use crate::mesh::TriMesh3d;
+use crate::{profile, AxisAlignedBoundingBox3d, Real};
+use nalgebra::Vector3;
+
+/// Maximum number of triangles stored in a leaf of the BVH
+const MAX_LEAF_SIZE: usize = 4;
+
+/// A ray with an origin and a direction, points on the ray are given by `origin + t * direction` with `t >= 0`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ray<R: Real> {
+    /// Origin of the ray
+    pub origin: Vector3<R>,
+    /// Direction of the ray, does not have to be normalized
+    pub direction: Vector3<R>,
+}
+
+/// Intersection of a [`Ray`] with a triangle of a [`TriangleBvh`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct RayHit<R: Real> {
+    /// Index of the intersected triangle in the mesh used to build the BVH
+    pub triangle: usize,
+    /// Ray parameter of the intersection, in multiples of the length of the ray direction
+    pub t: R,
+    /// Coordinates of the intersection point
+    pub point: Vector3<R>,
+}
+
+/// Result of a closest point query on a [`TriangleBvh`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct NearestPoint<R: Real> {
+    /// Index of the triangle containing the closest point in the mesh used to build the BVH
+    pub triangle: usize,
+    /// Coordinates of the closest point
+    pub point: Vector3<R>,
+    /// Euclidean distance between the query point and the closest point
+    pub distance: R,
+}
+
+/// Bounding volume hierarchy over the triangles of a [`TriMesh3d`]
+#[derive(Clone, Debug)]
+pub struct TriangleBvh<R: Real> {
+    /// All nodes of the tree, the root is the first node
+    nodes: Vec<BvhNode<R>>,
+    /// Triangle indices ordered such that the triangles of every leaf are stored contiguously
+    triangle_indices: Vec<usize>,
+    /// Vertex coordinates of all triangles, in the order of the mesh
+    triangles: Vec<[Vector3<R>; 3]>,
+}
+
+/// A node of the [`TriangleBvh`]
+#[derive(Clone, Debug)]
+struct BvhNode<R: Real> {
+    /// Bounding box of all triangles in the subtree of the node
+    aabb: AxisAlignedBoundingBox3d<R>,
+    /// Children or triangles of the node
+    content: BvhNodeContent,
+}
+
+#[derive(Clone, Debug)]
+enum BvhNodeContent {
+    /// Inner node with the indices of its two children
+    Inner([usize; 2]),
+    /// Leaf node with a range into the triangle indices of the BVH
+    Leaf { start: usize, end: usize },
+}
+
+impl<R: Real> TriangleBvh<R> {
+    /// Builds the BVH for all triangles of the given mesh
+    ///
+    /// Nodes are split at the median of the triangle centroids along the axis of their largest extent
+    /// until at most four triangles are left in a node.
+    pub fn new(mesh: &TriMesh3d<R>) -> Self {
+        profile!("TriangleBvh::new");
+
+        let triangles: Vec<[Vector3<R>; 3]> = mesh
+            .triangles
+            .iter()
+            .map(|tri| {
+                [
+                    mesh.vertices[tri[0]],
+                    mesh.vertices[tri[1]],
+                    mesh.vertices[tri[2]],
+                ]
+            })
+            .collect();
+        let centroids: Vec<Vector3<R>> = triangles
+            .iter()
+            .map(|tri| (tri[0] + tri[1] + tri[2]) / R::from_f64(3.0).unwrap())
+            .collect();
+
+        let mut bvh = Self {
+            nodes: Vec::new(),
+            triangle_indices: (0..triangles.len()).collect(),
+            triangles,
+        };
+
+        if !bvh.triangles.is_empty() {
+            bvh.build_node(&centroids, 0, bvh.triangles.len());
+        }
+
+        bvh
+    }
+
+    /// Recursively builds the subtree for the given range of triangle indices and returns the index of its root node
+    fn build_node(&mut self, centroids: &[Vector3<R>], start: usize, end: usize) -> usize {
+        let mut aabb =
+            AxisAlignedBoundingBox3d::from_points(&self.triangles[self.triangle_indices[start]]);
+        for &t in self.triangle_indices[start + 1..end].iter() {
+            for vertex in self.triangles[t].iter() {
+                aabb.join_with_point(vertex);
+            }
+        }
+
+        let node_index = self.nodes.len();
+        self.nodes.push(BvhNode {
+            aabb,
+            content: BvhNodeContent::Leaf { start, end },
+        });
+
+        if end - start <= MAX_LEAF_SIZE {
+            return node_index;
+        }
+
+        // Split at the median centroid along the axis of the largest extent of the centroids
+        let centroid_aabb = {
+            let mut centroid_aabb =
+                AxisAlignedBoundingBox3d::from_point(centroids[self.triangle_indices[start]]);
+            for &t in self.triangle_indices[start + 1..end].iter() {
+                centroid_aabb.join_with_point(&centroids[t]);
+            }
+            centroid_aabb
+        };
+        let extents = centroid_aabb.extents();
+        let axis = extents.imax();
+
+        let mid = start + (end - start) / 2;
+        self.triangle_indices[start..end].select_nth_unstable_by(mid - start, |&a, &b| {
+            centroids[a][axis]
+                .partial_cmp(&centroids[b][axis])
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+
+        let left = self.build_node(centroids, start, mid);
+        let right = self.build_node(centroids, mid, end);
+        self.nodes[node_index].content = BvhNodeContent::Inner([left, right]);
+
+        node_index
+    }
+
+    /// Returns the number of triangles stored in the BVH
+    pub fn num_triangles(&self) -> usize {
+        self.triangles.len()
+    }
+
+    /// Returns the bounding box of all triangles or `None` if the BVH is empty
+    pub fn aabb(&self) -> Option<&AxisAlignedBoundingBox3d<R>> {
+        self.nodes.first().map(|root| &root.aabb)
+    }
+
+    /// Returns the closest point on the surface of the mesh to the query point or `None` if the BVH is empty
+    ///
+    /// If several triangles have the same distance to the query point, any of them may be returned.
+    pub fn nearest_point(&self, query: &Vector3<R>) -> Option<NearestPoint<R>> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let mut best: Option<(R, usize, Vector3<R>)> = None;
+        let mut stack = vec![(aabb_distance_squared(&self.nodes[0].aabb, query), 0)];
+        while let Some((node_distance_squared, node_index)) = stack.pop() {
+            if let Some((best_distance_squared, _, _)) = best {
+                if node_distance_squared >= best_distance_squared {
+                    continue;
+                }
+            }
+
+            match &self.nodes[node_index].content {
+                BvhNodeContent::Leaf { start, end } => {
+                    for &t in self.triangle_indices[*start..*end].iter() {
+                        let point = closest_point_on_triangle(&self.triangles[t], query);
+                        let distance_squared = (point - query).norm_squared();
+                        let is_closer = best
+                            .map(|(best_distance_squared, _, _)| {
+                                distance_squared < best_distance_squared
+                            })
+                            .unwrap_or(true);
+                        if is_closer {
+                            best = Some((distance_squared, t, point));
+                        }
+                    }
+                }
+                BvhNodeContent::Inner(children) => {
+                    // Push the farther child first such that the closer child is visited first
+                    let distance_squared =
+                        |child: usize| aabb_distance_squared(&self.nodes[child].aabb, query);
+                    let mut children = [
+                        (distance_squared(children[0]), children[0]),
+                        (distance_squared(children[1]), children[1]),
+                    ];
+                    if children[0].0 < children[1].0 {
+                        children.swap(0, 1);
+                    }
+                    stack.extend(children.iter().copied());
+                }
+            }
+        }
+
+        best.map(|(distance_squared, triangle, point)| NearestPoint {
+            triangle,
+            point,
+            distance: distance_squared.sqrt(),
+        })
+    }
+
+    /// Returns an arbitrary (not necessarily the closest) intersection of the ray with the mesh or `None` if the ray does not hit the mesh
+    ///
+    /// This is cheaper than [`all_hits`](Self::all_hits) as the traversal stops at the first intersection
+    /// found, e.g. for occlusion or inside/outside tests.
+    pub fn any_hit(&self, ray: &Ray<R>) -> Option<RayHit<R>> {
+        let mut hit = None;
+        self.visit_ray_hits(ray, |ray_hit| {
+            hit = Some(ray_hit);
+            false
+        });
+        hit
+    }
+
+    /// Returns all intersections of the ray with the mesh sorted by increasing ray parameter
+    pub fn all_hits(&self, ray: &Ray<R>) -> Vec<RayHit<R>> {
+        let mut hits = Vec::new();
+        self.visit_ray_hits(ray, |ray_hit| {
+            hits.push(ray_hit);
+            true
+        });
+        hits.sort_by(|a, b| {
+            a.t.partial_cmp(&b.t)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then(a.triangle.cmp(&b.triangle))
+        });
+        hits
+    }
+
+    /// Calls the visitor for intersections of the ray with triangles until it returns `false`
+    fn visit_ray_hits<F: FnMut(RayHit<R>) -> bool>(&self, ray: &Ray<R>, mut visitor: F) {
+        if self.nodes.is_empty() {
+            return;
+        }
+
+        let inv_direction = ray.direction.map(|d| R::one() / d);
+        let mut stack = vec![0];
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            if !ray_intersects_aabb(&node.aabb, ray, &inv_direction) {
+                continue;
+            }
+
+            match &node.content {
+                BvhNodeContent::Leaf { start, end } => {
+                    for &t in self.triangle_indices[*start..*end].iter() {
+                        if let Some(ray_t) = ray_triangle_intersection(&self.triangles[t], ray) {
+                            let hit = RayHit {
+                                triangle: t,
+                                t: ray_t,
+                                point: ray.origin + ray.direction * ray_t,
+                            };
+                            if !visitor(hit) {
+                                return;
+                            }
+                        }
+                    }
+                }
+                BvhNodeContent::Inner(children) => stack.extend(children.iter().copied()),
+            }
+        }
+    }
+}
+
+/// Returns the squared distance between the point and the AABB, zero if the point is inside of the AABB
+fn aabb_distance_squared<R: Real>(aabb: &AxisAlignedBoundingBox3d<R>, point: &Vector3<R>) -> R {
+    Vector3::from_fn(|i, _| {
+        (aabb.min()[i] - point[i])
+            .max(point[i] - aabb.max()[i])
+            .max(R::zero())
+    })
+    .norm_squared()
+}
+
+/// Returns whether the ray intersects the AABB using the slab method
+fn ray_intersects_aabb<R: Real>(
+    aabb: &AxisAlignedBoundingBox3d<R>,
+    ray: &Ray<R>,
+    inv_direction: &Vector3<R>,
+) -> bool {
+    let mut t_min = R::zero();
+    let mut t_max = R::max_value();
+    for i in 0..3 {
+        if ray.direction[i] == R::zero() {
+            // The ray is parallel to the slab
+            if ray.origin[i] < aabb.min()[i] || ray.origin[i] > aabb.max()[i] {
+                return false;
+            }
+            continue;
+        }
+
+        let t0 = (aabb.min()[i] - ray.origin[i]) * inv_direction[i];
+        let t1 = (aabb.max()[i] - ray.origin[i]) * inv_direction[i];
+        t_min = t_min.max(t0.min(t1));
+        t_max = t_max.min(t0.max(t1));
+        if t_min > t_max {
+            return false;
+        }
+    }
+    true
+}
+
+/// Returns the ray parameter of the intersection of the ray with the triangle (Möller–Trumbore algorithm)
+fn ray_triangle_intersection<R: Real>(triangle: &[Vector3<R>; 3], ray: &Ray<R>) -> Option<R> {
+    let edge1 = triangle[1] - triangle[0];
+    let edge2 = triangle[2] - triangle[0];
+    let p = ray.direction.cross(&edge2);
+    let det = edge1.dot(&p);
+    if det.abs() <= R::default_epsilon() {
+        // The ray is parallel to the triangle
+        return None;
+    }
+
+    let inv_det = R::one() / det;
+    let s = ray.origin - triangle[0];
+    let u = s.dot(&p) * inv_det;
+    if u < R::zero() || u > R::one() {
+        return None;
+    }
+
+    let q = s.cross(&edge1);
+    let v = ray.direction.dot(&q) * inv_det;
+    if v < R::zero() || u + v > R::one() {
+        return None;
+    }
+
+    let t = edge2.dot(&q) * inv_det;
+    if t >= R::zero() {
+        Some(t)
+    } else {
+        None
+    }
+}
+
+/// Returns the closest point on the triangle to the given point (Ericson, "Real-Time Collision Detection", 5.1.5)
+pub(crate) fn closest_point_on_triangle<R: Real>(
+    triangle: &[Vector3<R>; 3],
+    point: &Vector3<R>,
+) -> Vector3<R> {
+    let [a, b, c] = *triangle;
+    let ab = b - a;
+    let ac = c - a;
+
+    // Vertex region of a
+    let ap = point - a;
+    let d1 = ab.dot(&ap);
+    let d2 = ac.dot(&ap);
+    if d1 <= R::zero() && d2 <= R::zero() {
+        return a;
+    }
+
+    // Vertex region of b
+    let bp = point - b;
+    let d3 = ab.dot(&bp);
+    let d4 = ac.dot(&bp);
+    if d3 >= R::zero() && d4 <= d3 {
+        return b;
+    }
+
+    // Edge region of ab
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= R::zero() && d1 >= R::zero() && d3 <= R::zero() {
+        return a + ab * (d1 / (d1 - d3));
+    }
+
+    // Vertex region of c
+    let cp = point - c;
+    let d5 = ab.dot(&cp);
+    let d6 = ac.dot(&cp);
+    if d6 >= R::zero() && d5 <= d6 {
+        return c;
+    }
+
+    // Edge region of ac
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= R::zero() && d2 >= R::zero() && d6 <= R::zero() {
+        return a + ac * (d2 / (d2 - d6));
+    }
+
+    // Edge region of bc
+    let va = d3 * d6 - d5 * d4;
+    if va <= R::zero() && (d4 - d3) >= R::zero() && (d5 - d6) >= R::zero() {
+        return b + (c - b) * ((d4 - d3) / ((d4 - d3) + (d5 - d6)));
+    }
+
+    // Inside of the face
+    let denom = R::one() / (va + vb + vc);
+    a + ab * (vb * denom) + ac * (vc * denom)
+}
+
+#[cfg(test)]
+fn random_triangle_soup(num_triangles: usize, seed: u64) -> TriMesh3d<f64> {
+    let mut rng = crate::random::rng_from_seed(seed);
+    let mut new_random =
+        || -> Vector3<f64> { Vector3::from_distribution(&rand::distributions::Standard, &mut rng) };
+
+    let mut mesh = TriMesh3d::default();
+    for i in 0..num_triangles {
+        let center = new_random() * 10.0;
+        for _ in 0..3 {
+            mesh.vertices
+                .push(center + new_random() - Vector3::repeat(0.5));
+        }
+        mesh.triangles.push([3 * i, 3 * i + 1, 3 * i + 2]);
+    }
+    mesh
+}
+
+#[test]
+fn test_bvh_nearest_point_brute_force() {
+    let mesh = random_triangle_soup(500, 0);
+    let bvh = TriangleBvh::new(&mesh);
+    assert_eq!(bvh.num_triangles(), 500);
+
+    let mut rng = crate::random::rng_from_seed(1);
+    for _ in 0..200 {
+        let query: Vector3<f64> =
+            Vector3::from_distribution(&rand::distributions::Standard, &mut rng) * 14.0
+                - Vector3::repeat(2.0);
+
+        let brute_force = mesh
+            .triangles
+            .iter()
+            .map(|tri| {
+                let triangle = [
+                    mesh.vertices[tri[0]],
+                    mesh.vertices[tri[1]],
+                    mesh.vertices[tri[2]],
+                ];
+                (closest_point_on_triangle(&triangle, &query) - query).norm()
+            })
+            .fold(f64::INFINITY, f64::min);
+
+        let nearest = bvh.nearest_point(&query).unwrap();
+        assert!((nearest.distance - brute_force).abs() < 1e-12);
+        assert!(((nearest.point - query).norm() - nearest.distance).abs() < 1e-12);
+    }
+
+    assert!(TriangleBvh::<f64>::new(&TriMesh3d::default())
+        .nearest_point(&Vector3::zeros())
+        .is_none());
+}
+
+#[test]
+fn test_closest_point_on_triangle() {
+    let triangle = [
+        Vector3::new(0.0, 0.0, 0.0),
+        Vector3::new(1.0, 0.0, 0.0),
+        Vector3::new(0.0, 1.0, 0.0),
+    ];
+    let closest = |p: Vector3<f64>| closest_point_on_triangle(&triangle, &p);
+
+    assert_eq!(
+        closest(Vector3::new(0.25, 0.25, 1.0)),
+        Vector3::new(0.25, 0.25, 0.0)
+    );
+    assert_eq!(closest(Vector3::new(-1.0, -1.0, 0.0)), triangle[0]);
+    assert_eq!(closest(Vector3::new(2.0, -0.5, 0.0)), triangle[1]);
+    assert_eq!(
+        closest(Vector3::new(0.5, -1.0, 0.0)),
+        Vector3::new(0.5, 0.0, 0.0)
+    );
+    assert_eq!(
+        closest(Vector3::new(1.0, 1.0, 0.0)),
+        Vector3::new(0.5, 0.5, 0.0)
+    );
+}
+
+#[test]
+fn test_bvh_ray_hits_brute_force() {
+    let mesh = random_triangle_soup(500, 2);
+    let bvh = TriangleBvh::new(&mesh);
+
+    let mut rng = crate::random::rng_from_seed(3);
+    let mut new_random =
+        || -> Vector3<f64> { Vector3::from_distribution(&rand::distributions::Standard, &mut rng) };
+    let mut num_hits = 0;
+    for _ in 0..100 {
+        let ray = Ray {
+            origin: new_random() * 10.0,
+            direction: new_random() - Vector3::repeat(0.5),
+        };
+
+        let mut brute_force: Vec<_> = mesh
+            .triangles
+            .iter()
+            .enumerate()
+            .filter_map(|(i, tri)| {
+                let triangle = [
+                    mesh.vertices[tri[0]],
+                    mesh.vertices[tri[1]],
+                    mesh.vertices[tri[2]],
+                ];
+                ray_triangle_intersection(&triangle, &ray).map(|t| (t, i))
+            })
+            .collect();
+        brute_force.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        let hits = bvh.all_hits(&ray);
+        assert_eq!(
+            hits.iter()
+                .map(|hit| (hit.t, hit.triangle))
+                .collect::<Vec<_>>(),
+            brute_force
+        );
+        assert_eq!(bvh.any_hit(&ray).is_some(), !hits.is_empty());
+        num_hits += hits.len();
+    }
+    assert!(num_hits > 0);
+}
@@ -0,0 +1,140 @@
+//! Conversion of particles and meshes between coordinate conventions
+//!
+//! Simulation data is often given in a right-handed coordinate system with the z-axis pointing up,
+//! whereas many game engines and file formats use the y-axis as up direction. The [`CoordinateConvention`]
+//! describes the target convention of a conversion from the right-handed z-up convention of the input data.
+//!
+//! All conversions are rotations or reflections, i.e. lengths and angles are preserved. If a conversion
+//! flips the handedness of the coordinate system, the winding of all triangles is reversed as well, such
+//! that normals computed from the converted mesh are the converted normals of the original mesh and
+//! consistently oriented meshes stay consistently oriented.
+
+use crate::mesh::{AttributeData, MeshWithData, TriMesh3d};
+use crate::Real;
+use nalgebra::{Matrix3, Vector3};
+
+/// Coordinate convention that particles and meshes can be converted to from the right-handed z-up convention
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum CoordinateConvention {
+    /// Right-handed coordinates with the z-axis pointing up, this is the convention of the input data (no conversion)
+    #[default]
+    ZUpRightHanded,
+    /// Right-handed coordinates with the y-axis pointing up, maps `(x, y, z)` to `(x, z, -y)` (e.g. glTF)
+    YUpRightHanded,
+    /// Left-handed coordinates with the y-axis pointing up, maps `(x, y, z)` to `(x, z, y)` (e.g. Unity)
+    YUpLeftHanded,
+}
+
+impl CoordinateConvention {
+    /// Returns the matrix that maps coordinates from the right-handed z-up convention to this convention
+    pub fn matrix<R: Real>(&self) -> Matrix3<R> {
+        let (o, i) = (R::zero(), R::one());
+        match self {
+            CoordinateConvention::ZUpRightHanded => Matrix3::identity(),
+            CoordinateConvention::YUpRightHanded => Matrix3::new(i, o, o, o, o, i, o, -i, o),
+            CoordinateConvention::YUpLeftHanded => Matrix3::new(i, o, o, o, o, i, o, i, o),
+        }
+    }
+
+    /// Returns whether the conversion to this convention changes the handedness of the coordinate system
+    pub fn flips_handedness(&self) -> bool {
+        match self {
+            CoordinateConvention::ZUpRightHanded | CoordinateConvention::YUpRightHanded => false,
+            CoordinateConvention::YUpLeftHanded => true,
+        }
+    }
+
+    /// Converts the given points or direction vectors (e.g. particle positions, velocities or normals) in-place to this convention
+    pub fn convert_points<R: Real>(&self, points: &mut [Vector3<R>]) {
+        if *self == CoordinateConvention::ZUpRightHanded {
+            return;
+        }
+
+        let matrix = self.matrix::<R>();
+        for p in points.iter_mut() {
+            *p = matrix * *p;
+        }
+    }
+
+    /// Converts the vertices of the mesh in-place to this convention, reverses the triangle winding if the handedness changes
+    pub fn convert_trimesh<R: Real>(&self, mesh: &mut TriMesh3d<R>) {
+        self.convert_points(&mut mesh.vertices);
+        if self.flips_handedness() {
+            for tri in mesh.triangles.iter_mut() {
+                tri.swap(1, 2);
+            }
+        }
+    }
+
+    /// Converts the mesh and all of its vector valued point and cell attributes (e.g. normals) in-place to this convention
+    pub fn convert_mesh_with_data<R: Real>(&self, mesh: &mut MeshWithData<R, TriMesh3d<R>>) {
+        self.convert_trimesh(&mut mesh.mesh);
+        for attribute in mesh
+            .point_attributes
+            .iter_mut()
+            .chain(mesh.cell_attributes.iter_mut())
+        {
+            if let AttributeData::Vector3Real(vectors) = &mut attribute.data {
+                self.convert_points(vectors);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_coordinate_convention_normals_and_winding() {
+    use nalgebra::Unit;
+
+    // Closed tetrahedron with outward facing triangles
+    let mesh = TriMesh3d {
+        vertices: vec![
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            Vector3::new(0.0, 0.0, 1.0),
+        ],
+        triangles: vec![[0, 2, 1], [0, 1, 3], [0, 3, 2], [1, 2, 3]],
+    };
+    let normals = mesh.vertex_normals();
+
+    for &convention in &[
+        CoordinateConvention::ZUpRightHanded,
+        CoordinateConvention::YUpRightHanded,
+        CoordinateConvention::YUpLeftHanded,
+    ] {
+        let matrix = convention.matrix::<f64>();
+        assert!((matrix.determinant().abs() - 1.0).abs() < 1e-12);
+        assert_eq!(convention.flips_handedness(), matrix.determinant() < 0.0);
+
+        let mut converted = MeshWithData::new(mesh.clone()).with_point_data(
+            crate::mesh::MeshAttribute::new_real_vector3(
+                "normals",
+                normals.iter().map(|n| n.into_inner()).collect::<Vec<_>>(),
+            ),
+        );
+        convention.convert_mesh_with_data(&mut converted);
+
+        // The up direction of the input is mapped to the up direction of the convention
+        let up = converted.mesh.vertices[3];
+        match convention {
+            CoordinateConvention::ZUpRightHanded => assert_eq!(up, Vector3::z()),
+            _ => assert_eq!(up, Vector3::y()),
+        }
+
+        // Normals computed from the converted mesh agree with the converted normals of the original mesh
+        let recomputed: Vec<Unit<Vector3<f64>>> = converted.mesh.vertex_normals();
+        let converted_normals = match &converted.point_attributes[0].data {
+            AttributeData::Vector3Real(normals) => normals.clone(),
+            _ => unreachable!(),
+        };
+        for (n, m) in recomputed.iter().zip(converted_normals.iter()) {
+            assert!((n.into_inner() - m).norm() < 1e-12);
+        }
+
+        // Normals still point away from the centroid, i.e. the winding is consistent
+        let centroid = converted.mesh.vertices.iter().sum::<Vector3<f64>>() / 4.0;
+        for (v, n) in converted.mesh.vertices.iter().zip(recomputed.iter()) {
+            assert!((v - centroid).dot(n) > 0.0);
+        }
+    }
+}
@@ -19,14 +19,20 @@
 //! indices, even if the density map is only generated for a smaller subdomain.
 
 use crate::aabb::AxisAlignedBoundingBox3d;
-use crate::kernel::DiscreteSquaredDistanceCubicKernel;
-use crate::mesh::{HexMesh3d, MeshAttribute, MeshWithData};
-use crate::uniform_grid::{OwningSubdomainGrid, Subdomain, UniformGrid};
+use crate::anisotropy::AnisotropicKernels;
+use crate::kernel::{DiscreteSquaredDistanceKernel, KernelType};
+use crate::mesh::{HexMesh3d, MeshAttribute, MeshWithData, TriMesh3d};
+use crate::uniform_grid::{
+    CellIndex, GridConstructionError, OwningSubdomainGrid, PointIndex, Subdomain, UniformGrid,
+};
 use crate::utils::{ChunkSize, ParallelPolicy};
-use crate::{new_map, profile, HashState, Index, MapType, ParallelMapType, Real};
+use crate::{
+    new_map, profile, validate_particle_inputs, HashState, Index, MapType, ParallelMapType,
+    ParticleInputLengthError, Real,
+};
 use dashmap::ReadOnlyView as ReadDashMap;
 use log::{info, trace, warn};
-use nalgebra::Vector3;
+use nalgebra::{Matrix3, Vector3};
 use rayon::prelude::*;
 use std::cell::RefCell;
 use thiserror::Error as ThisError;
@@ -50,53 +56,72 @@ pub enum DensityMapError<R: Real> {
         /// The final (invalid) domain after the margin is applied to the user specified domain
         domain: AxisAlignedBoundingBox3d<R>,
     },
+    /// Indicates that the number of particle densities does not match the number of particles
+    #[error("invalid particle input: {0}")]
+    InvalidParticleInput(ParticleInputLengthError),
+}
+
+impl<R: Real> From<ParticleInputLengthError> for DensityMapError<R> {
+    /// Wraps a [`ParticleInputLengthError`] in a [`DensityMapError`] for error propagation
+    fn from(error: ParticleInputLengthError) -> Self {
+        DensityMapError::InvalidParticleInput(error)
+    }
 }
 
 /// Computes the individual densities of particles using a standard SPH sum
+///
+/// Returns an error if the number of neighbor lists does not match the number of particles.
 #[inline(never)]
 pub fn compute_particle_densities<I: Index, R: Real>(
     particle_positions: &[Vector3<R>],
     particle_neighbor_lists: &[Vec<usize>],
     compact_support_radius: R,
+    kernel: KernelType,
     particle_rest_mass: R,
     enable_multi_threading: bool,
-) -> Vec<R> {
+) -> Result<Vec<R>, DensityMapError<R>> {
     let mut densities = Vec::new();
     if enable_multi_threading {
         parallel_compute_particle_densities::<I, R>(
             particle_positions,
             particle_neighbor_lists,
             compact_support_radius,
+            kernel,
             particle_rest_mass,
             &mut densities,
-        )
+        )?
     } else {
         sequential_compute_particle_densities::<I, R>(
             particle_positions,
             particle_neighbor_lists,
             compact_support_radius,
+            kernel,
             particle_rest_mass,
             &mut densities,
-        )
+        )?
     }
-    densities
+    Ok(densities)
 }
 
 /// Computes the individual densities of particles inplace using a standard SPH sum
+///
+/// Returns an error if the number of neighbor lists does not match the number of particles.
 #[inline(never)]
 pub fn compute_particle_densities_inplace<I: Index, R: Real>(
     particle_positions: &[Vector3<R>],
     particle_neighbor_lists: &[Vec<usize>],
     compact_support_radius: R,
+    kernel: KernelType,
     particle_rest_mass: R,
     enable_multi_threading: bool,
     densities: &mut Vec<R>,
-) {
+) -> Result<(), DensityMapError<R>> {
     if enable_multi_threading {
         parallel_compute_particle_densities::<I, R>(
             particle_positions,
             particle_neighbor_lists,
             compact_support_radius,
+            kernel,
             particle_rest_mass,
             densities,
         )
@@ -105,6 +130,7 @@ pub fn compute_particle_densities_inplace<I: Index, R: Real>(
             particle_positions,
             particle_neighbor_lists,
             compact_support_radius,
+            kernel,
             particle_rest_mass,
             densities,
         )
@@ -118,20 +144,32 @@ fn init_density_storage<R: Real>(densities: &mut Vec<R>, new_len: usize) {
 }
 
 /// Computes the individual densities of particles using a standard SPH sum, sequential implementation
+///
+/// Returns an error if the number of neighbor lists does not match the number of particles.
 #[inline(never)]
 pub fn sequential_compute_particle_densities<I: Index, R: Real>(
     particle_positions: &[Vector3<R>],
     particle_neighbor_lists: &[Vec<usize>],
     compact_support_radius: R,
+    kernel: KernelType,
     particle_rest_mass: R,
     particle_densities: &mut Vec<R>,
-) {
+) -> Result<(), DensityMapError<R>> {
     profile!("sequential_compute_particle_densities");
 
+    validate_particle_inputs(
+        particle_positions,
+        &[(
+            "particle_neighbor_lists",
+            Some(particle_neighbor_lists.len()),
+        )],
+    )?;
+
     init_density_storage(particle_densities, particle_positions.len());
 
     // Pre-compute the kernel which can be queried using squared distances
-    let kernel = DiscreteSquaredDistanceCubicKernel::new(1000, compact_support_radius);
+    let kernel =
+        DiscreteSquaredDistanceKernel::new_with_kernel(1000, compact_support_radius, kernel);
 
     for (i, (particle_i_position, particle_i_neighbors)) in particle_positions
         .iter()
@@ -146,23 +184,37 @@ pub fn sequential_compute_particle_densities<I: Index, R: Real>(
         particle_i_density *= particle_rest_mass;
         particle_densities[i] = particle_i_density;
     }
+
+    Ok(())
 }
 
 /// Computes the individual densities of particles using a standard SPH sum, multi-threaded implementation
+///
+/// Returns an error if the number of neighbor lists does not match the number of particles.
 #[inline(never)]
 pub fn parallel_compute_particle_densities<I: Index, R: Real>(
     particle_positions: &[Vector3<R>],
     particle_neighbor_lists: &[Vec<usize>],
     compact_support_radius: R,
+    kernel: KernelType,
     particle_rest_mass: R,
     particle_densities: &mut Vec<R>,
-) {
+) -> Result<(), DensityMapError<R>> {
     profile!("parallel_compute_particle_densities");
 
+    validate_particle_inputs(
+        particle_positions,
+        &[(
+            "particle_neighbor_lists",
+            Some(particle_neighbor_lists.len()),
+        )],
+    )?;
+
     init_density_storage(particle_densities, particle_positions.len());
 
     // Pre-compute the kernel which can be queried using squared distances
-    let kernel = DiscreteSquaredDistanceCubicKernel::new(1000, compact_support_radius);
+    let kernel =
+        DiscreteSquaredDistanceKernel::new_with_kernel(1000, compact_support_radius, kernel);
 
     particle_positions
         .par_iter()
@@ -182,6 +234,8 @@ pub fn parallel_compute_particle_densities<I: Index, R: Real>(
                 *particle_i_density = density;
             },
         );
+
+    Ok(())
 }
 
 /// A sparse density map
@@ -223,6 +277,14 @@ impl<I: Index, R: Real> DensityMap<I, R> {
         }
     }
 
+    /// Returns the number of density entries the map can hold without reallocating
+    pub fn capacity(&self) -> usize {
+        match self {
+            DensityMap::Standard(map) => map.capacity(),
+            DensityMap::DashMap(map) => map.capacity(),
+        }
+    }
+
     /// Returns the density value at the specified flat point index
     pub fn get(&self, flat_point_index: I) -> Option<R> {
         match self {
@@ -242,6 +304,72 @@ impl<I: Index, R: Real> DensityMap<I, R> {
         self.standard_or_insert_mut()
     }
 
+    /// Takes the contained parallel map for reuse (cleared), returns a new map if not of parallel type
+    fn take_parallel_map(&mut self) -> ParallelMapType<I, R> {
+        match std::mem::replace(self, new_map().into()) {
+            DensityMap::DashMap(map) => {
+                let map = map.into_inner();
+                map.clear();
+                map
+            }
+            _ => ParallelMapType::with_hasher(HashState::default()),
+        }
+    }
+
+    /// Returns all active cells of the grid, i.e. all cells with at least one corner point that received a density contribution, sorted by their flat cell index
+    ///
+    /// These are all cells that contain or are close to particles. The grid has to be the (global) grid
+    /// that was used to generate the density map.
+    pub fn active_cells(&self, grid: &UniformGrid<I, R>) -> Vec<CellIndex<I>> {
+        profile!("DensityMap::active_cells");
+
+        let offset = |index: I, d: usize| {
+            if d == 0 {
+                Some(index)
+            } else {
+                index.checked_sub(&I::one())
+            }
+        };
+
+        let mut flat_cell_indices = Vec::with_capacity(self.len());
+        self.for_each(|flat_point_index, _| {
+            if let Some(point) = grid.try_unflatten_point_index(flat_point_index) {
+                let ijk = point.index();
+                // Cells adjacent to a point have a lower corner with an index offset of either 0 or -1 in each dimension
+                for &(di, dj, dk) in [
+                    (0, 0, 0),
+                    (1, 0, 0),
+                    (0, 1, 0),
+                    (1, 1, 0),
+                    (0, 0, 1),
+                    (1, 0, 1),
+                    (0, 1, 1),
+                    (1, 1, 1),
+                ]
+                .iter()
+                {
+                    if let (Some(i), Some(j), Some(k)) =
+                        (offset(ijk[0], di), offset(ijk[1], dj), offset(ijk[2], dk))
+                    {
+                        if grid.cell_exists(&[i, j, k]) {
+                            flat_cell_indices.push(grid.flatten_cell_indices(i, j, k));
+                        }
+                    }
+                }
+            }
+        });
+
+        flat_cell_indices.sort_unstable();
+        flat_cell_indices.dedup();
+        flat_cell_indices
+            .into_iter()
+            .map(|flat_cell_index| {
+                grid.try_unflatten_cell_index(flat_cell_index)
+                    .expect("cell has to be part of the grid")
+            })
+            .collect()
+    }
+
     /// Calls a closure for each `(flat_point_index, density_value)` tuple in the map
     pub fn for_each<F: FnMut(I, R)>(&self, f: F) {
         let mut f = f;
@@ -250,9 +378,284 @@ impl<I: Index, R: Real> DensityMap<I, R> {
             DensityMap::DashMap(map) => map.iter().for_each(|(&i, &r)| f(i, r)),
         }
     }
+
+    /// Calls a closure for each `(flat_point_index, density_value)` tuple in the map that may modify the value
+    pub(crate) fn for_each_mut<F: FnMut(I, &mut R)>(&mut self, f: F) {
+        let mut f = f;
+        match self {
+            DensityMap::Standard(map) => map.iter_mut().for_each(|(&i, r)| f(i, r)),
+            DensityMap::DashMap(map) => {
+                // The read-only view of the dashmap does not allow mutable access
+                let updated: MapType<I, R> = map
+                    .iter()
+                    .map(|(&i, &r)| {
+                        let mut r = r;
+                        f(i, &mut r);
+                        (i, r)
+                    })
+                    .collect();
+                *self = updated.into();
+            }
+        }
+    }
+}
+
+/// Coarse background grid together with the density map on this grid, see [`DensityMap::downsample`]
+pub type DownsampledDensityMap<I, R> = (UniformGrid<I, R>, DensityMap<I, R>);
+
+impl<I: Index, R: Real> DensityMap<I, R> {
+    /// Computes a coarser density map by block-averaging the values of this density map
+    ///
+    /// Returns the coarse background grid together with the coarse density map. The coarse grid has
+    /// the same lower corner as the given (fine) grid of this density map and a cell size that is
+    /// `factor` times larger. Each fine grid point is assigned to the closest coarse grid point and
+    /// the value of a coarse grid point is the average over all fine grid points assigned to it.
+    /// Fine grid points without an entry in the sparse density map contribute a density of zero.
+    ///
+    /// If the number of cells of the fine grid in a dimension is not divisible by `factor`, the
+    /// coarse grid is extended to fully enclose the fine grid, i.e. it may extend beyond the upper
+    /// boundary of the fine grid by up to `factor - 1` fine cells. The blocks of the coarse points
+    /// on this boundary are truncated and their values are averaged only over the fine grid points
+    /// that actually exist, so that the boundary values are not biased towards zero. Coarse grid points
+    /// beyond the fine grid that are not the closest coarse point of any fine grid point get no entry
+    /// in the coarse density map, i.e. they are considered as trivially zero like all other missing points.
+    ///
+    /// Panics if `factor` is zero.
+    pub fn downsample(
+        &self,
+        grid: &UniformGrid<I, R>,
+        factor: usize,
+    ) -> Result<DownsampledDensityMap<I, R>, GridConstructionError<I, R>> {
+        profile!("downsample density map");
+        assert!(factor > 0, "downsampling factor has to be larger than zero");
+
+        let f = I::from_usize(factor).ok_or(GridConstructionError::IndexTypeTooSmallCellsPerDim)?;
+        let half_f = I::from_usize(factor / 2).unwrap();
+        let fine_n_cells = grid.cells_per_dim();
+        let fine_n_points = grid.points_per_dim();
+
+        let coarse_n_cells = [
+            num::Integer::div_ceil(&fine_n_cells[0], &f),
+            num::Integer::div_ceil(&fine_n_cells[1], &f),
+            num::Integer::div_ceil(&fine_n_cells[2], &f),
+        ];
+        let coarse_grid = UniformGrid::new(
+            grid.aabb().min(),
+            &coarse_n_cells,
+            grid.cell_size() * R::from_usize(factor).unwrap(),
+        )?;
+
+        // Maps a fine grid point index to the index of the closest coarse grid point along one axis
+        let to_coarse = |fine: I| (fine + half_f) / f;
+        // Counts the fine grid points along one axis that are mapped to the given coarse grid point
+        let fine_points_per_coarse_point = |coarse: I, dim: usize| -> I {
+            let lower = coarse * f;
+            let lower = if lower > half_f {
+                lower - half_f
+            } else {
+                I::zero()
+            };
+            let upper = (coarse * f + f - half_f).min(fine_n_points[dim]);
+            upper - lower
+        };
+
+        let mut sums: MapType<I, R> = new_map();
+        self.for_each(|flat_point_index, density| {
+            if let Some(point) = grid.try_unflatten_point_index(flat_point_index) {
+                let ijk = point.index();
+                let coarse_ijk = [to_coarse(ijk[0]), to_coarse(ijk[1]), to_coarse(ijk[2])];
+                let flat_coarse_index = coarse_grid.flatten_point_index_array(&coarse_ijk);
+                *sums.entry(flat_coarse_index).or_insert(R::zero()) += density;
+            }
+        });
+
+        let mut coarse_map: MapType<I, R> = new_map();
+        coarse_map.reserve(sums.len());
+        for (flat_coarse_index, sum) in sums {
+            let coarse_point = coarse_grid
+                .try_unflatten_point_index(flat_coarse_index)
+                .expect("coarse point has to be part of the coarse grid");
+            let coarse_ijk = coarse_point.index();
+            let n_fine_points = fine_points_per_coarse_point(coarse_ijk[0], 0)
+                * fine_points_per_coarse_point(coarse_ijk[1], 1)
+                * fine_points_per_coarse_point(coarse_ijk[2], 2);
+            coarse_map.insert(flat_coarse_index, sum / n_fine_points.to_real_unchecked());
+        }
+
+        Ok((coarse_grid, coarse_map.into()))
+    }
+}
+
+/// Histogram of density values with equally sized bins over a fixed value range
+#[derive(Clone, Debug, PartialEq)]
+pub struct DensityHistogram<R: Real> {
+    lower: R,
+    upper: R,
+    counts: Vec<usize>,
+}
+
+impl<R: Real> DensityHistogram<R> {
+    /// Creates an empty histogram with the given number of bins over the range `[lower, upper]`
+    ///
+    /// Values outside of the range are counted in the first or last bin, respectively.
+    /// Panics if `num_bins` is zero or if the range is empty.
+    pub fn new(lower: R, upper: R, num_bins: usize) -> Self {
+        assert!(num_bins > 0, "histogram needs at least one bin");
+        assert!(lower < upper, "histogram range has to be non-empty");
+        Self {
+            lower,
+            upper,
+            counts: vec![0; num_bins],
+        }
+    }
+
+    /// Returns the number of values per bin
+    pub fn counts(&self) -> &[usize] {
+        &self.counts
+    }
+
+    /// Returns the value range `[lower, upper)` covered by the given bin
+    pub fn bin_range(&self, bin: usize) -> (R, R) {
+        let width = self.bin_width();
+        let lower = self.lower + width * R::from_usize(bin).unwrap();
+        (lower, lower + width)
+    }
+
+    /// Returns the total number of values added to the histogram
+    pub fn total(&self) -> usize {
+        self.counts.iter().sum()
+    }
+
+    fn bin_width(&self) -> R {
+        (self.upper - self.lower) / R::from_usize(self.counts.len()).unwrap()
+    }
+
+    /// Returns an empty histogram with the same bins as this histogram
+    fn empty_like(&self) -> Self {
+        Self {
+            lower: self.lower,
+            upper: self.upper,
+            counts: vec![0; self.counts.len()],
+        }
+    }
+
+    /// Adds the counts of another histogram with the same bins to this histogram
+    fn merge(&mut self, other: &Self) {
+        debug_assert_eq!(self.counts.len(), other.counts.len());
+        for (count, other_count) in self.counts.iter_mut().zip(other.counts.iter()) {
+            *count += other_count;
+        }
+    }
+
+    /// Adds a single value to the histogram
+    fn add(&mut self, value: R) {
+        let last_bin = self.counts.len() - 1;
+        let bin = if value <= self.lower {
+            0
+        } else {
+            ((value - self.lower) / self.bin_width())
+                .floor()
+                .to_usize()
+                .map_or(last_bin, |bin| bin.min(last_bin))
+        };
+        self.counts[bin] += 1;
+    }
+}
+
+/// Statistics of the values of a density map that are collected during its construction
+///
+/// Consumers of the density map that need the value range (e.g. to normalize or clamp values) can use these
+/// statistics instead of scanning the density map again.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DensityMapStatistics<R: Real> {
+    /// Number of values stored in the density map
+    pub num_values: usize,
+    /// Smallest value of the density map (zero if the map is empty)
+    pub min: R,
+    /// Largest value of the density map (zero if the map is empty)
+    pub max: R,
+    /// Histogram of the values if one was requested for the construction
+    pub histogram: Option<DensityHistogram<R>>,
+}
+
+impl<R: Real> DensityMapStatistics<R> {
+    /// Returns empty statistics that will be accumulated into the optional (empty) histogram
+    fn new(histogram: Option<DensityHistogram<R>>) -> Self {
+        Self {
+            num_values: 0,
+            min: R::zero(),
+            max: R::zero(),
+            histogram,
+        }
+    }
+
+    /// Adds a single value of the density map to the statistics
+    fn add(&mut self, value: R) {
+        if self.num_values == 0 {
+            self.min = value;
+            self.max = value;
+        } else {
+            self.min = self.min.min(value);
+            self.max = self.max.max(value);
+        }
+        self.num_values += 1;
+        if let Some(histogram) = self.histogram.as_mut() {
+            histogram.add(value);
+        }
+    }
+
+    /// Returns empty statistics with an empty histogram of the same bins (if any)
+    fn empty_like(&self) -> Self {
+        Self::new(self.histogram.as_ref().map(DensityHistogram::empty_like))
+    }
+
+    /// Combines the statistics of a disjoint set of values of the same density map into these statistics
+    fn merge(&mut self, other: &Self) {
+        if other.num_values == 0 {
+            return;
+        }
+
+        if self.num_values == 0 {
+            self.min = other.min;
+            self.max = other.max;
+        } else {
+            self.min = self.min.min(other.min);
+            self.max = self.max.max(other.max);
+        }
+        self.num_values += other.num_values;
+        if let (Some(histogram), Some(other_histogram)) =
+            (self.histogram.as_mut(), other.histogram.as_ref())
+        {
+            histogram.merge(other_histogram);
+        }
+    }
+}
+
+/// Parameters for the construction of a sparse density map that are shared by all particles
+#[derive(Clone, Debug)]
+pub struct DensityMapParameters<R: Real> {
+    /// Rest mass of a single particle
+    pub particle_rest_mass: R,
+    /// Compact support radius of the kernel, i.e. distance from the particle where kernel reaches zero
+    pub compact_support_radius: R,
+    /// SPH kernel that is used to evaluate the density contributions of the particles
+    pub kernel: KernelType,
+    /// Edge length of the cubes of the background grid
+    pub cube_size: R,
+    /// Whether to allow multi threading, ignored by the explicitly sequential and parallel implementations
+    pub allow_threading: bool,
+    /// Histogram that is filled with the values of the density map by [`generate_sparse_density_map`], ignored by the other functions
+    pub histogram: Option<DensityHistogram<R>>,
 }
 
-/// Computes a sparse density map for the fluid based on the specified background grid
+/// Computes a sparse density map for the fluid based on the specified background grid and collects statistics of its values
+///
+/// The statistics (min/max and the optional histogram of the parameters) are collected as part of the construction,
+/// so that consumers of the density map do not have to scan it again. With multi-threading, every thread accumulates
+/// the statistics of the values it finalizes while merging the thread local maps and the partial statistics
+/// are combined afterwards. The sequential implementations collect the statistics from their map directly
+/// after all particle contributions were added. If a histogram is given, all values of the density map are
+/// added to it.
 #[inline(never)]
 pub fn generate_sparse_density_map<I: Index, R: Real>(
     grid: &UniformGrid<I, R>,
@@ -260,12 +663,9 @@ pub fn generate_sparse_density_map<I: Index, R: Real>(
     particle_positions: &[Vector3<R>],
     particle_densities: &[R],
     active_particles: Option<&[usize]>,
-    particle_rest_mass: R,
-    compact_support_radius: R,
-    cube_size: R,
-    allow_threading: bool,
+    parameters: &DensityMapParameters<R>,
     density_map: &mut DensityMap<I, R>,
-) -> Result<(), DensityMapError<R>> {
+) -> Result<DensityMapStatistics<R>, DensityMapError<R>> {
     trace!(
         "Starting construction of sparse density map... (Input: {} particles)",
         if let Some(active_particles) = active_particles {
@@ -275,8 +675,10 @@ pub fn generate_sparse_density_map<I: Index, R: Real>(
         }
     );
 
+    let mut statistics = DensityMapStatistics::new(parameters.histogram.clone());
+
     if let Some(subdomain) = subdomain {
-        if allow_threading {
+        if parameters.allow_threading {
             panic!("Multi threading not implemented for density map with subdomain");
         } else {
             sequential_generate_sparse_density_map_subdomain(
@@ -284,42 +686,46 @@ pub fn generate_sparse_density_map<I: Index, R: Real>(
                 particle_positions,
                 particle_densities,
                 active_particles,
-                particle_rest_mass,
-                compact_support_radius,
-                cube_size,
+                parameters,
                 density_map,
             )?;
+
+            profile!("collect density map statistics");
+            density_map.for_each(|_, density| statistics.add(density));
         }
     } else {
-        if allow_threading {
-            *density_map = parallel_generate_sparse_density_map(
+        if parameters.allow_threading {
+            let global_density_map = density_map.take_parallel_map();
+            *density_map = parallel_generate_sparse_density_map_into(
                 grid,
                 particle_positions,
                 particle_densities,
                 active_particles,
-                particle_rest_mass,
-                compact_support_radius,
-                cube_size,
+                parameters,
+                global_density_map,
+                Some(&mut statistics),
             )?
         } else {
-            *density_map = sequential_generate_sparse_density_map(
+            sequential_generate_sparse_density_map_inplace(
                 grid,
                 particle_positions,
                 particle_densities,
                 active_particles,
-                particle_rest_mass,
-                compact_support_radius,
-                cube_size,
+                parameters,
+                density_map,
+                Some(&mut statistics),
             )?
         }
     };
 
     trace!(
-        "Sparse density map was constructed. (Output: density map with {} grid point data entries)",
-        density_map.len()
+        "Sparse density map was constructed. (Output: density map with {} grid point data entries, values in [{:?}, {:?}])",
+        density_map.len(),
+        statistics.min,
+        statistics.max
     );
 
-    Ok(())
+    Ok(statistics)
 }
 
 /// Computes a sparse density map for the fluid based on the specified background grid, sequential implementation
@@ -329,19 +735,49 @@ pub fn sequential_generate_sparse_density_map<I: Index, R: Real>(
     particle_positions: &[Vector3<R>],
     particle_densities: &[R],
     active_particles: Option<&[usize]>,
-    particle_rest_mass: R,
-    compact_support_radius: R,
-    cube_size: R,
+    parameters: &DensityMapParameters<R>,
 ) -> Result<DensityMap<I, R>, DensityMapError<R>> {
+    let mut density_map = new_map().into();
+    sequential_generate_sparse_density_map_inplace(
+        grid,
+        particle_positions,
+        particle_densities,
+        active_particles,
+        parameters,
+        &mut density_map,
+        None,
+    )?;
+    Ok(density_map)
+}
+
+/// Computes a sparse density map for the fluid based on the specified background grid, sequential implementation, reuses the memory of the given density map
+///
+/// If statistics are given, all values of the resulting density map are added to them.
+fn sequential_generate_sparse_density_map_inplace<I: Index, R: Real>(
+    grid: &UniformGrid<I, R>,
+    particle_positions: &[Vector3<R>],
+    particle_densities: &[R],
+    active_particles: Option<&[usize]>,
+    parameters: &DensityMapParameters<R>,
+    density_map: &mut DensityMap<I, R>,
+    statistics: Option<&mut DensityMapStatistics<R>>,
+) -> Result<(), DensityMapError<R>> {
     profile!("sequential_generate_sparse_density_map");
 
-    let mut sparse_densities = new_map();
+    validate_particle_inputs(
+        particle_positions,
+        &[("particle_densities", Some(particle_densities.len()))],
+    )?;
+
+    let mut sparse_densities = density_map.standard_or_insert_mut();
+    sparse_densities.clear();
 
     let density_map_generator = SparseDensityMapGenerator::try_new(
         grid,
-        compact_support_radius,
-        cube_size,
-        particle_rest_mass,
+        parameters.compact_support_radius,
+        parameters.kernel,
+        parameters.cube_size,
+        parameters.particle_rest_mass,
     )?;
 
     let process_particle = |particle_data: (&Vector3<R>, R)| {
@@ -366,6 +802,78 @@ pub fn sequential_generate_sparse_density_map<I: Index, R: Real>(
             .for_each(process_particle),
     }
 
+    if let Some(statistics) = statistics {
+        profile!("collect density map statistics");
+        sparse_densities
+            .values()
+            .for_each(|&density| statistics.add(density));
+    }
+
+    Ok(())
+}
+
+/// Computes a sparse density map for the fluid that only contains values for the given points of the background grid
+///
+/// In contrast to [`generate_sparse_density_map`], the density is only evaluated at the given points and all
+/// other points of the grid are skipped. The resulting map contains an entry for every given point that is part
+/// of the grid (zero if no particle contributes to it), points outside of the grid are ignored.
+/// This is the basic building block for narrow band or multi-pass refinement schemes, e.g. a coarse pass
+/// identifies the grid points close to the surface and a second pass only evaluates these points.
+///
+/// Note that grid points without an entry in the map are treated like points with background (zero)
+/// density during contouring, i.e. they are considered to be outside of the fluid. Furthermore, all (active)
+/// particles are still visited, only the kernel evaluations and map updates are restricted to the given points.
+#[inline(never)]
+pub fn generate_sparse_density_map_at_points<I: Index, R: Real>(
+    grid: &UniformGrid<I, R>,
+    particle_positions: &[Vector3<R>],
+    particle_densities: &[R],
+    active_particles: Option<&[usize]>,
+    parameters: &DensityMapParameters<R>,
+    points: &[PointIndex<I>],
+) -> Result<DensityMap<I, R>, DensityMapError<R>> {
+    profile!("generate_sparse_density_map_at_points");
+
+    validate_particle_inputs(
+        particle_positions,
+        &[("particle_densities", Some(particle_densities.len()))],
+    )?;
+
+    let mut sparse_densities = new_map();
+    for point in points.iter().filter(|p| grid.point_exists(p.index())) {
+        sparse_densities.insert(grid.flatten_point_index(point), R::zero());
+    }
+
+    let density_map_generator = SparseDensityMapGenerator::try_new(
+        grid,
+        parameters.compact_support_radius,
+        parameters.kernel,
+        parameters.cube_size,
+        parameters.particle_rest_mass,
+    )?;
+
+    let process_particle = |particle_data: (&Vector3<R>, R)| {
+        let (particle, particle_density) = particle_data;
+        density_map_generator.compute_particle_density_contribution_existing_points(
+            grid,
+            &mut sparse_densities,
+            particle,
+            particle_density,
+        );
+    };
+
+    match active_particles {
+        None => particle_positions
+            .iter()
+            .zip(particle_densities.iter().copied())
+            .for_each(process_particle),
+        Some(indices) => indices
+            .iter()
+            .map(|&i| &particle_positions[i])
+            .zip(indices.iter().map(|&i| particle_densities[i]))
+            .for_each(process_particle),
+    }
+
     Ok(sparse_densities.into())
 }
 
@@ -376,21 +884,25 @@ pub fn sequential_generate_sparse_density_map_subdomain<I: Index, R: Real>(
     particle_positions: &[Vector3<R>],
     particle_densities: &[R],
     active_particles: Option<&[usize]>,
-    particle_rest_mass: R,
-    compact_support_radius: R,
-    cube_size: R,
+    parameters: &DensityMapParameters<R>,
     density_map: &mut DensityMap<I, R>,
 ) -> Result<(), DensityMapError<R>> {
     profile!("sequential_generate_sparse_density_map_subdomain");
 
+    validate_particle_inputs(
+        particle_positions,
+        &[("particle_densities", Some(particle_densities.len()))],
+    )?;
+
     let mut sparse_densities = density_map.standard_or_insert_mut();
     sparse_densities.clear();
 
     let density_map_generator = SparseDensityMapGenerator::try_new(
         &subdomain.global_grid(),
-        compact_support_radius,
-        cube_size,
-        particle_rest_mass,
+        parameters.compact_support_radius,
+        parameters.kernel,
+        parameters.cube_size,
+        parameters.particle_rest_mass,
     )?;
 
     let process_particle = |particle_data: (&Vector3<R>, R)| {
@@ -425,12 +937,38 @@ pub fn parallel_generate_sparse_density_map<I: Index, R: Real>(
     particle_positions: &[Vector3<R>],
     particle_densities: &[R],
     active_particles: Option<&[usize]>,
-    particle_rest_mass: R,
-    compact_support_radius: R,
-    cube_size: R,
+    parameters: &DensityMapParameters<R>,
+) -> Result<DensityMap<I, R>, DensityMapError<R>> {
+    parallel_generate_sparse_density_map_into(
+        grid,
+        particle_positions,
+        particle_densities,
+        active_particles,
+        parameters,
+        ParallelMapType::with_hasher(HashState::default()),
+        None,
+    )
+}
+
+/// Computes a sparse density map for the fluid based on the specified background grid, multi-threaded implementation, the thread local maps are merged into the given (empty) global map
+///
+/// If statistics are given, all values of the resulting density map are added to them while the thread local maps are merged.
+fn parallel_generate_sparse_density_map_into<I: Index, R: Real>(
+    grid: &UniformGrid<I, R>,
+    particle_positions: &[Vector3<R>],
+    particle_densities: &[R],
+    active_particles: Option<&[usize]>,
+    parameters: &DensityMapParameters<R>,
+    global_density_map: ParallelMapType<I, R>,
+    statistics: Option<&mut DensityMapStatistics<R>>,
 ) -> Result<DensityMap<I, R>, DensityMapError<R>> {
     profile!("parallel_generate_sparse_density_map");
 
+    validate_particle_inputs(
+        particle_positions,
+        &[("particle_densities", Some(particle_densities.len()))],
+    )?;
+
     // Each thread will write to its own local density map
     let sparse_densities: ThreadLocal<RefCell<MapType<I, R>>> = ThreadLocal::new();
 
@@ -438,9 +976,10 @@ pub fn parallel_generate_sparse_density_map<I: Index, R: Real>(
     {
         let density_map_generator = SparseDensityMapGenerator::try_new(
             grid,
-            compact_support_radius,
-            cube_size,
-            particle_rest_mass,
+            parameters.compact_support_radius,
+            parameters.kernel,
+            parameters.cube_size,
+            parameters.particle_rest_mass,
         )?;
 
         profile!("generate thread local maps");
@@ -515,7 +1054,7 @@ pub fn parallel_generate_sparse_density_map<I: Index, R: Real>(
         profile!("merge thread local maps to global map");
 
         // Collect all thread local maps into a single vec
-        let mut local_density_maps = sparse_densities
+        let local_density_maps = sparse_densities
             .into_iter()
             .map(|m| m.into_inner())
             .collect::<Vec<_>>();
@@ -525,25 +1064,144 @@ pub fn parallel_generate_sparse_density_map<I: Index, R: Real>(
             local_density_maps.len()
         );
 
-        // Merge local density maps in parallel by summing the density contributions
-        let global_density_map = ParallelMapType::with_hasher(HashState::default());
-        local_density_maps.par_iter_mut().for_each(|local_map| {
-            for (idx, density) in local_map.drain() {
-                *global_density_map.entry(idx).or_insert(R::zero()) += density;
+        // Partition the entries of the local maps by their point index, such that all contributions to a
+        // point end up in the same partition and its final value is known after merging a single partition
+        let num_partitions = rayon::current_num_threads();
+        let partitioned_local_maps = local_density_maps
+            .into_par_iter()
+            .map(|local_map| {
+                let mut partitions = vec![Vec::new(); num_partitions];
+                for (idx, density) in local_map {
+                    let partition = idx.to_usize().unwrap() % num_partitions;
+                    partitions[partition].push((idx, density));
+                }
+                partitions
+            })
+            .collect::<Vec<_>>();
+
+        // Merge the partitions in parallel by summing the density contributions, statistics are accumulated per partition
+        let empty_statistics = statistics.as_deref().map(DensityMapStatistics::empty_like);
+        let partition_statistics = (0..num_partitions)
+            .into_par_iter()
+            .map(|partition| {
+                let mut merged_partition = new_map();
+                for (idx, density) in partitioned_local_maps
+                    .iter()
+                    .flat_map(|partitions| partitions[partition].iter().copied())
+                {
+                    *merged_partition.entry(idx).or_insert(R::zero()) += density;
+                }
+
+                let mut partition_statistics = empty_statistics.clone();
+                for (idx, density) in merged_partition {
+                    if let Some(partition_statistics) = partition_statistics.as_mut() {
+                        partition_statistics.add(density);
+                    }
+                    global_density_map.insert(idx, density);
+                }
+                partition_statistics
+            })
+            .collect::<Vec<_>>();
+
+        if let Some(statistics) = statistics {
+            for partition_statistics in partition_statistics.iter().flatten() {
+                statistics.merge(partition_statistics);
             }
-        });
+        }
 
         Ok(global_density_map.into())
     }
 }
 
+/// Computes a sparse density map for the fluid using an anisotropic kernel per particle
+///
+/// The density contributions of the particles are evaluated at the kernel centers with the kernel transforms
+/// of the given [`AnisotropicKernels`], see the [`anisotropy`](crate::anisotropy) module for details.
+/// The particle densities and the optional list of active particles refer to the same particles as the kernels.
+#[inline(never)]
+pub fn generate_sparse_density_map_anisotropic<I: Index, R: Real>(
+    grid: &UniformGrid<I, R>,
+    anisotropic_kernels: &AnisotropicKernels<R>,
+    particle_densities: &[R],
+    active_particles: Option<&[usize]>,
+    parameters: &DensityMapParameters<R>,
+) -> Result<DensityMap<I, R>, DensityMapError<R>> {
+    profile!("generate_sparse_density_map_anisotropic");
+
+    validate_particle_inputs(
+        &anisotropic_kernels.centers,
+        &[
+            ("particle_densities", Some(particle_densities.len())),
+            (
+                "kernel_transforms",
+                Some(anisotropic_kernels.transforms.len()),
+            ),
+        ],
+    )?;
+
+    let density_map_generator = SparseDensityMapGenerator::try_new(
+        grid,
+        parameters.compact_support_radius,
+        parameters.kernel,
+        parameters.cube_size,
+        parameters.particle_rest_mass,
+    )?;
+
+    let process_particle = |sparse_densities: &mut MapType<I, R>, i: usize| {
+        density_map_generator.compute_anisotropic_particle_density_contribution(
+            grid,
+            sparse_densities,
+            &anisotropic_kernels.centers[i],
+            &anisotropic_kernels.transforms[i],
+            particle_densities[i],
+        );
+    };
+
+    let all_particles;
+    let indices = match active_particles {
+        Some(indices) => indices,
+        None => {
+            all_particles = (0..particle_densities.len()).collect::<Vec<_>>();
+            all_particles.as_slice()
+        }
+    };
+
+    let sparse_densities = if parameters.allow_threading {
+        indices
+            .par_iter()
+            .with_min_len(64)
+            .fold(new_map, |mut sparse_densities, &i| {
+                process_particle(&mut sparse_densities, i);
+                sparse_densities
+            })
+            .reduce(new_map, |mut a, mut b| {
+                // Merge the smaller map into the larger one
+                if a.len() < b.len() {
+                    std::mem::swap(&mut a, &mut b);
+                }
+                for (idx, density) in b.drain() {
+                    *a.entry(idx).or_insert(R::zero()) += density;
+                }
+                a
+            })
+    } else {
+        let mut sparse_densities = new_map();
+        for &i in indices {
+            process_particle(&mut sparse_densities, i);
+        }
+        sparse_densities
+    };
+
+    Ok(sparse_densities.into())
+}
+
 /// Internal helper type used to evaluate the density contribution for a particle
 struct SparseDensityMapGenerator<I: Index, R: Real> {
     particle_rest_mass: R,
     half_supported_cells: I,
     supported_points: I,
     kernel_evaluation_radius_sq: R,
-    kernel: DiscreteSquaredDistanceCubicKernel<R>,
+    kernel: DiscreteSquaredDistanceKernel<R>,
     allowed_domain: AxisAlignedBoundingBox3d<R>,
 }
 
@@ -586,6 +1244,7 @@ impl<I: Index, R: Real> SparseDensityMapGenerator<I, R> {
     fn try_new(
         grid: &UniformGrid<I, R>,
         compact_support_radius: R,
+        kernel: KernelType,
         cube_size: R,
         particle_rest_mass: R,
     ) -> Result<Self, DensityMapError<R>> {
@@ -597,7 +1256,8 @@ impl<I: Index, R: Real> SparseDensityMapGenerator<I, R> {
 
         // Pre-compute the kernel which can be queried using squared distances
         let kernel_evaluation_radius_sq = kernel_evaluation_radius * kernel_evaluation_radius;
-        let kernel = DiscreteSquaredDistanceCubicKernel::new(1000, compact_support_radius);
+        let kernel =
+            DiscreteSquaredDistanceKernel::new_with_kernel(1000, compact_support_radius, kernel);
 
         // Shrink the allowed domain for particles by the kernel evaluation radius. This ensures that all cells/points
         // that are affected by a particle are actually part of the domain/grid, so it does not have to be checked in the loops below.
@@ -672,6 +1332,114 @@ impl<I: Index, R: Real> SparseDensityMapGenerator<I, R> {
         );
     }
 
+    /// Computes all density contributions of a particle with an anisotropic kernel to the background grid into the given map
+    ///
+    /// The eigenvalues of the kernel transform have to be at least one, such that the support of the anisotropic
+    /// kernel is contained in the support of the isotropic kernel.
+    fn compute_anisotropic_particle_density_contribution(
+        &self,
+        grid: &UniformGrid<I, R>,
+        sparse_densities: &mut MapType<I, R>,
+        kernel_center: &Vector3<R>,
+        kernel_transform: &Matrix3<R>,
+        particle_density: R,
+    ) {
+        // Skip particles outside of allowed domain
+        if !self.allowed_domain.contains_point(kernel_center) {
+            return;
+        }
+
+        // The determinant of the transform preserves the integral of the kernel
+        let particle_volume =
+            self.particle_rest_mass / particle_density * kernel_transform.determinant();
+
+        let min_supported_point_ijk = {
+            let cell_ijk = grid.enclosing_cell(kernel_center);
+            [
+                cell_ijk[0] - self.half_supported_cells,
+                cell_ijk[1] - self.half_supported_cells,
+                cell_ijk[2] - self.half_supported_cells,
+            ]
+        };
+        let min_supported_point = grid.point_coordinates_array(&min_supported_point_ijk);
+
+        // Loop over all points that might receive a density contribution from this particle
+        let mut i = I::zero();
+        while i != self.supported_points {
+            let mut j = I::zero();
+            while j != self.supported_points {
+                let mut k = I::zero();
+                while k != self.supported_points {
+                    let dx = Vector3::new(
+                        min_supported_point[0] + i.to_real_unchecked::<R>() * grid.cell_size(),
+                        min_supported_point[1] + j.to_real_unchecked::<R>() * grid.cell_size(),
+                        min_supported_point[2] + k.to_real_unchecked::<R>() * grid.cell_size(),
+                    ) - kernel_center;
+
+                    let r_squared = (kernel_transform * dx).norm_squared();
+                    if r_squared < self.kernel_evaluation_radius_sq {
+                        let density_contribution =
+                            particle_volume * self.kernel.evaluate(r_squared);
+
+                        let flat_point_index = grid.flatten_point_indices(
+                            min_supported_point_ijk[0] + i,
+                            min_supported_point_ijk[1] + j,
+                            min_supported_point_ijk[2] + k,
+                        );
+                        *sparse_densities
+                            .entry(flat_point_index)
+                            .or_insert(R::zero()) += density_contribution;
+                    }
+                    k += I::one();
+                }
+                j += I::one();
+            }
+            i += I::one();
+        }
+    }
+
+    /// Adds all density contributions of a particle to the points of the background grid that already have an entry in the given map
+    fn compute_particle_density_contribution_existing_points(
+        &self,
+        grid: &UniformGrid<I, R>,
+        sparse_densities: &mut MapType<I, R>,
+        particle: &Vector3<R>,
+        particle_density: R,
+    ) {
+        // Skip particles outside of allowed domain
+        if !self.allowed_domain.contains_point(particle) {
+            return;
+        }
+
+        let min_supported_point_ijk = {
+            let cell_ijk = grid.enclosing_cell(particle);
+            [
+                cell_ijk[0] - self.half_supported_cells,
+                cell_ijk[1] - self.half_supported_cells,
+                cell_ijk[2] - self.half_supported_cells,
+            ]
+        };
+
+        let max_supported_point_ijk = [
+            min_supported_point_ijk[0] + self.supported_points,
+            min_supported_point_ijk[1] + self.supported_points,
+            min_supported_point_ijk[2] + self.supported_points,
+        ];
+
+        self.particle_support_loop_with(
+            grid,
+            &min_supported_point_ijk,
+            &max_supported_point_ijk,
+            particle,
+            particle_density,
+            |flat_point_index, density_contribution| {
+                if let Some(density) = sparse_densities.get_mut(&flat_point_index) {
+                    *density += density_contribution;
+                }
+            },
+        );
+    }
+
     /// Computes all density contributions of a particle to a subdomain of the background grid into the given map
     fn compute_particle_density_contribution_subdomain(
         &self,
@@ -760,6 +1528,31 @@ impl<I: Index, R: Real> SparseDensityMapGenerator<I, R> {
         max_supported_point_ijk: &[I; 3],
         particle: &Vector3<R>,
         particle_density: R,
+    ) {
+        self.particle_support_loop_with(
+            grid,
+            min_supported_point_ijk,
+            max_supported_point_ijk,
+            particle,
+            particle_density,
+            |flat_point_index, density_contribution| {
+                *sparse_densities
+                    .entry(flat_point_index)
+                    .or_insert(R::zero()) += density_contribution;
+            },
+        );
+    }
+
+    /// Loops over a cube of background grid points that are potentially in the support radius of the particle and passes all density contributions to the given closure
+    #[inline(always)]
+    fn particle_support_loop_with<F: FnMut(I, R)>(
+        &self,
+        grid: &UniformGrid<I, R>,
+        min_supported_point_ijk: &[I; 3],
+        max_supported_point_ijk: &[I; 3],
+        particle: &Vector3<R>,
+        particle_density: R,
+        mut accumulate: F,
     ) {
         // Compute the volume of this particle
         let particle_volume = self.particle_rest_mass / particle_density;
@@ -801,9 +1594,7 @@ impl<I: Index, R: Real> SparseDensityMapGenerator<I, R> {
                             particle_volume * self.kernel.evaluate(r_squared);
 
                         let flat_point_index = grid.flatten_point_indices(i, j, k);
-                        *sparse_densities
-                            .entry(flat_point_index)
-                            .or_insert(R::zero()) += density_contribution;
+                        accumulate(flat_point_index, density_contribution);
                     }
                     k = k + I::one();
                 }
@@ -814,6 +1605,136 @@ impl<I: Index, R: Real> SparseDensityMapGenerator<I, R> {
     }
 }
 
+/// Computes vertex normals of a mesh from the gradient of the density field discretized by the density map
+///
+/// The gradient is evaluated with central differences at the points of the background grid (one-sided
+/// differences at the grid boundary) and trilinearly interpolated to the vertex positions. As the
+/// density decreases towards the outside of the fluid, the normals point in the direction of the
+/// negative gradient. Grid points without an entry in the density map are considered to have zero density.
+/// Vertices where the interpolated gradient vanishes get a zero normal.
+#[inline(never)]
+pub fn compute_vertex_normals_from_field<I: Index, R: Real>(
+    mesh: &TriMesh3d<R>,
+    density_map: &DensityMap<I, R>,
+    grid: &UniformGrid<I, R>,
+) -> Vec<Vector3<R>> {
+    profile!("compute_vertex_normals_from_field");
+
+    interpolate_field_gradients(mesh, density_map, grid)
+        .into_iter()
+        .map(|gradient| {
+            let norm = gradient.norm();
+            if norm > R::default_epsilon() {
+                -gradient / norm
+            } else {
+                Vector3::zeros()
+            }
+        })
+        .collect()
+}
+
+/// Computes the magnitude of the gradient of the density field discretized by the density map at every vertex of a mesh
+///
+/// The gradient is computed with the same finite difference scheme as in [`compute_vertex_normals_from_field`]:
+/// central differences at the points of the background grid (one-sided differences at the grid boundary) that
+/// are trilinearly interpolated to the vertex positions. Grid points without an entry in the density map are
+/// considered to have zero density. The magnitude is a measure for how sharply the iso-surface is defined at a
+/// vertex: large values correspond to a steep transition between the inside and outside of the fluid, small
+/// values to a flat density field where the position of the surface is sensitive to the iso-surface threshold.
+#[inline(never)]
+pub fn compute_vertex_gradient_magnitudes_from_field<I: Index, R: Real>(
+    mesh: &TriMesh3d<R>,
+    density_map: &DensityMap<I, R>,
+    grid: &UniformGrid<I, R>,
+) -> Vec<R> {
+    profile!("compute_vertex_gradient_magnitudes_from_field");
+
+    interpolate_field_gradients(mesh, density_map, grid)
+        .into_iter()
+        .map(|gradient| gradient.norm())
+        .collect()
+}
+
+/// Evaluates the gradient of the density field with central differences at the grid points and interpolates it trilinearly to the vertices of the mesh
+fn interpolate_field_gradients<I: Index, R: Real>(
+    mesh: &TriMesh3d<R>,
+    density_map: &DensityMap<I, R>,
+    grid: &UniformGrid<I, R>,
+) -> Vec<Vector3<R>> {
+    let n_points = grid.points_per_dim();
+    let n_cells = grid.cells_per_dim();
+    let cell_size = grid.cell_size();
+
+    let density_at = |ijk: &[I; 3]| {
+        density_map
+            .get(grid.flatten_point_index_array(ijk))
+            .unwrap_or_else(R::zero)
+    };
+
+    let gradient_at = |ijk: &[I; 3]| -> Vector3<R> {
+        Vector3::from_fn(|dim, _| {
+            let mut lower = *ijk;
+            let mut upper = *ijk;
+            if ijk[dim] > I::zero() {
+                lower[dim] -= I::one();
+            }
+            if ijk[dim] + I::one() < n_points[dim] {
+                upper[dim] += I::one();
+            }
+
+            let steps = upper[dim] - lower[dim];
+            if steps > I::zero() {
+                (density_at(&upper) - density_at(&lower))
+                    / (steps.to_real_unchecked::<R>() * cell_size)
+            } else {
+                R::zero()
+            }
+        })
+    };
+
+    let vertex_gradient = |vertex: &Vector3<R>| -> Vector3<R> {
+        // Clamp the enclosing cell to the grid to also handle vertices on the upper boundary
+        let mut cell_ijk = grid.enclosing_cell(vertex);
+        for (c, &n) in cell_ijk.iter_mut().zip(n_cells.iter()) {
+            *c = (*c).max(I::zero()).min(n - I::one());
+        }
+
+        let cell_min = grid.point_coordinates_array(&cell_ijk);
+        let t = (vertex - cell_min) / cell_size;
+        let t = Vector3::new(
+            t.x.max(R::zero()).min(R::one()),
+            t.y.max(R::zero()).min(R::one()),
+            t.z.max(R::zero()).min(R::one()),
+        );
+
+        let weight = |d: usize, t: R| if d == 1 { t } else { R::one() - t };
+        let mut gradient = Vector3::zeros();
+        for &(di, dj, dk) in [
+            (0, 0, 0),
+            (1, 0, 0),
+            (0, 1, 0),
+            (1, 1, 0),
+            (0, 0, 1),
+            (1, 0, 1),
+            (0, 1, 1),
+            (1, 1, 1),
+        ]
+        .iter()
+        {
+            let w = weight(di, t.x) * weight(dj, t.y) * weight(dk, t.z);
+            let point_ijk = [
+                cell_ijk[0] + I::from_usize(di).unwrap(),
+                cell_ijk[1] + I::from_usize(dj).unwrap(),
+                cell_ijk[2] + I::from_usize(dk).unwrap(),
+            ];
+            gradient += gradient_at(&point_ijk) * w;
+        }
+        gradient
+    };
+
+    mesh.vertices.par_iter().map(vertex_gradient).collect()
+}
+
 /// Converts a sparse density map (based on the implicit background grid) to a sparse hexahedral mesh with explicit coordinates for the cells' vertices.
 #[inline(never)]
 pub fn sparse_density_map_to_hex_mesh<I: Index, R: Real>(
@@ -900,3 +1821,445 @@ pub fn sparse_density_map_to_hex_mesh<I: Index, R: Real>(
 
     MeshWithData::new(mesh).with_point_data(MeshAttribute::new_real_scalar("density", values))
 }
+
+#[test]
+fn test_downsample_density_map() {
+    let grid = UniformGrid::<i64, f64>::new(&Vector3::zeros(), &[4, 4, 5], 0.5).unwrap();
+
+    // A constant density field should stay constant, also at the truncated boundary blocks
+    let mut map = new_map();
+    for i in 0..5 {
+        for j in 0..5 {
+            for k in 0..6 {
+                map.insert(grid.flatten_point_indices(i, j, k), 1.0);
+            }
+        }
+    }
+    let density_map = DensityMap::from(map);
+
+    for &factor in [1, 2, 3].iter() {
+        let (coarse_grid, coarse_map) = density_map.downsample(&grid, factor).unwrap();
+        assert_eq!(coarse_grid.cell_size(), 0.5 * factor as f64);
+        assert_eq!(coarse_grid.aabb().min(), grid.aabb().min());
+        assert!(coarse_grid.aabb().max() >= grid.aabb().max());
+
+        let n_points = coarse_grid.points_per_dim();
+        if factor < 3 {
+            assert_eq!(
+                coarse_map.len() as i64,
+                n_points[0] * n_points[1] * n_points[2]
+            );
+        } else {
+            // The last layer of coarse points in x and y is not the closest coarse point of any fine point
+            assert_eq!(
+                coarse_map.len() as i64,
+                (n_points[0] - 1) * (n_points[1] - 1) * n_points[2]
+            );
+        }
+        coarse_map.for_each(|_, density| assert!((density - 1.0).abs() < 1e-12));
+    }
+}
+
+#[test]
+fn test_density_map_particle_input_validation() {
+    let grid = UniformGrid::<i64, f64>::new(&Vector3::zeros(), &[20, 20, 20], 0.1).unwrap();
+    let particle_positions = vec![Vector3::new(1.0, 1.0, 1.0); 2];
+    let particle_densities = vec![1000.0];
+    let parameters = DensityMapParameters {
+        particle_rest_mass: 1.0,
+        compact_support_radius: 0.2,
+        kernel: KernelType::CubicSpline,
+        cube_size: 0.1,
+        allow_threading: false,
+        histogram: None,
+    };
+
+    let result = sequential_generate_sparse_density_map(
+        &grid,
+        &particle_positions,
+        &particle_densities,
+        None,
+        &parameters,
+    );
+    assert!(matches!(
+        result,
+        Err(DensityMapError::InvalidParticleInput(
+            ParticleInputLengthError {
+                name: "particle_densities",
+                len: 1,
+                num_particles: 2,
+            }
+        ))
+    ));
+
+    let result = parallel_generate_sparse_density_map(
+        &grid,
+        &particle_positions,
+        &particle_densities,
+        None,
+        &parameters,
+    );
+    assert!(matches!(
+        result,
+        Err(DensityMapError::InvalidParticleInput(_))
+    ));
+}
+
+#[test]
+fn test_particle_densities_input_validation() {
+    let particle_positions = vec![Vector3::<f64>::zeros(); 2];
+    let particle_neighbor_lists = vec![Vec::new()];
+    for &enable_multi_threading in [false, true].iter() {
+        let result = compute_particle_densities::<i64, f64>(
+            &particle_positions,
+            &particle_neighbor_lists,
+            0.2,
+            KernelType::CubicSpline,
+            1.0,
+            enable_multi_threading,
+        );
+        assert!(matches!(
+            result,
+            Err(DensityMapError::InvalidParticleInput(ref e)) if e.name == "particle_neighbor_lists"
+        ));
+    }
+}
+
+#[test]
+fn test_generate_sparse_density_map_at_points() {
+    let grid = UniformGrid::<i64, f64>::new(&Vector3::zeros(), &[20, 20, 20], 0.1).unwrap();
+    let particle_positions = vec![
+        Vector3::new(1.0, 1.0, 1.0),
+        Vector3::new(1.05, 1.0, 1.0),
+        Vector3::new(1.0, 1.1, 0.95),
+    ];
+    let particle_densities = vec![1000.0; 3];
+    let parameters = DensityMapParameters {
+        particle_rest_mass: 1.0,
+        compact_support_radius: 0.2,
+        kernel: KernelType::CubicSpline,
+        cube_size: 0.1,
+        allow_threading: false,
+        histogram: None,
+    };
+
+    let full_map = sequential_generate_sparse_density_map(
+        &grid,
+        &particle_positions,
+        &particle_densities,
+        None,
+        &parameters,
+    )
+    .unwrap();
+
+    // Evaluate some points with density contributions and one point far away from all particles
+    let mut points: Vec<_> = full_map
+        .to_vec()
+        .into_iter()
+        .step_by(3)
+        .map(|(flat_point_index, _)| grid.try_unflatten_point_index(flat_point_index).unwrap())
+        .collect();
+    let far_point = grid.get_point([1, 1, 1]).unwrap();
+    points.push(far_point);
+
+    let partial_map = generate_sparse_density_map_at_points(
+        &grid,
+        &particle_positions,
+        &particle_densities,
+        None,
+        &parameters,
+        &points,
+    )
+    .unwrap();
+
+    assert_eq!(partial_map.len(), points.len());
+    for point in points.iter() {
+        let flat_point_index = grid.flatten_point_index(point);
+        let expected = full_map.get(flat_point_index).unwrap_or(0.0);
+        assert!((partial_map.get(flat_point_index).unwrap() - expected).abs() < 1e-12);
+    }
+    assert_eq!(
+        partial_map.get(grid.flatten_point_index(&far_point)),
+        Some(0.0)
+    );
+}
+
+#[test]
+fn test_sparse_density_map_matches_dense_evaluation() {
+    use crate::kernel::cubic_kernel_r;
+
+    let grid = UniformGrid::<i64, f64>::new(&Vector3::zeros(), &[20, 20, 20], 0.1).unwrap();
+    let particle_positions = vec![
+        Vector3::new(1.0, 1.0, 1.0),
+        Vector3::new(1.05, 1.0, 1.0),
+        Vector3::new(1.0, 1.1, 0.95),
+        Vector3::new(0.62, 1.33, 1.41),
+    ];
+    let particle_densities = vec![1000.0; particle_positions.len()];
+    let particle_rest_mass = 1.0;
+    let compact_support_radius = 0.2;
+    let parameters = DensityMapParameters {
+        particle_rest_mass,
+        compact_support_radius,
+        kernel: KernelType::CubicSpline,
+        cube_size: 0.1,
+        allow_threading: false,
+        histogram: None,
+    };
+
+    let sequential_map = sequential_generate_sparse_density_map(
+        &grid,
+        &particle_positions,
+        &particle_densities,
+        None,
+        &parameters,
+    )
+    .unwrap();
+    let parallel_map = parallel_generate_sparse_density_map(
+        &grid,
+        &particle_positions,
+        &particle_densities,
+        None,
+        &parameters,
+    )
+    .unwrap();
+
+    // Evaluate the density field with the exact kernel at every point of the grid
+    let n = grid.points_per_dim()[0];
+    let mut dense_values = Vec::new();
+    for i in 0..n {
+        for j in 0..n {
+            for k in 0..n {
+                let point = grid.get_point([i, j, k]).unwrap();
+                let x = grid.point_coordinates(&point);
+                let value: f64 = particle_positions
+                    .iter()
+                    .zip(particle_densities.iter())
+                    .map(|(x_p, &density)| {
+                        particle_rest_mass / density
+                            * cubic_kernel_r((x - x_p).norm(), compact_support_radius)
+                    })
+                    .sum();
+                dense_values.push((grid.flatten_point_index(&point), value));
+            }
+        }
+    }
+
+    let max_value = dense_values
+        .iter()
+        .map(|&(_, value)| value)
+        .fold(0.0, f64::max);
+    assert!(max_value > 0.0);
+
+    for map in [&sequential_map, &parallel_map].iter() {
+        // Only points in the support of a particle have entries in the sparse map
+        assert!(map.len() < dense_values.len() / 10);
+        for &(flat_point_index, dense_value) in dense_values.iter() {
+            match map.get(flat_point_index) {
+                Some(sparse_value) => {
+                    assert!((sparse_value - dense_value).abs() <= 1e-2 * max_value)
+                }
+                None => assert_eq!(dense_value, 0.0),
+            }
+        }
+    }
+}
+
+#[test]
+fn test_vertex_normals_from_field_sphere() {
+    // Quadratic density field decreasing away from the center of the grid
+    let grid = UniformGrid::<i64, f64>::new(&Vector3::zeros(), &[20, 20, 20], 0.1).unwrap();
+    let center = Vector3::new(1.0, 1.0, 1.0);
+    let mut map = new_map();
+    for i in 0..21 {
+        for j in 0..21 {
+            for k in 0..21 {
+                let x = grid.point_coordinates_indices(i, j, k);
+                map.insert(
+                    grid.flatten_point_indices(i, j, k),
+                    1.0 - (x - center).norm_squared(),
+                );
+            }
+        }
+    }
+    let density_map = DensityMap::from(map);
+
+    // Sphere from a subdivided octahedron
+    let mut vertices = vec![
+        Vector3::x(),
+        -Vector3::x(),
+        Vector3::y(),
+        -Vector3::y(),
+        Vector3::z(),
+        -Vector3::z(),
+    ];
+    let mut triangles = vec![
+        [4, 0, 2],
+        [4, 2, 1],
+        [4, 1, 3],
+        [4, 3, 0],
+        [5, 2, 0],
+        [5, 1, 2],
+        [5, 3, 1],
+        [5, 0, 3],
+    ];
+    for _ in 0..3 {
+        let mut midpoints: MapType<[usize; 2], usize> = new_map();
+        let mut midpoint = |a: usize, b: usize, vertices: &mut Vec<Vector3<f64>>| {
+            *midpoints.entry([a.min(b), a.max(b)]).or_insert_with(|| {
+                vertices.push((vertices[a] + vertices[b]).normalize());
+                vertices.len() - 1
+            })
+        };
+
+        let mut subdivided = Vec::with_capacity(4 * triangles.len());
+        for &[a, b, c] in triangles.iter() {
+            let ab = midpoint(a, b, &mut vertices);
+            let bc = midpoint(b, c, &mut vertices);
+            let ca = midpoint(c, a, &mut vertices);
+            subdivided.extend_from_slice(&[[a, ab, ca], [ab, b, bc], [ca, bc, c], [ab, bc, ca]]);
+        }
+        triangles = subdivided;
+    }
+
+    let sphere = TriMesh3d {
+        vertices: vertices.into_iter().map(|v| center + v * 0.55).collect(),
+        triangles,
+    };
+
+    let field_normals = compute_vertex_normals_from_field(&sphere, &density_map, &grid);
+    let geometric_normals = sphere.vertex_normals();
+    assert_eq!(field_normals.len(), sphere.vertices.len());
+
+    for ((v, field_normal), geometric_normal) in sphere
+        .vertices
+        .iter()
+        .zip(field_normals.iter())
+        .zip(geometric_normals.iter())
+    {
+        // The gradient of the quadratic field is reproduced exactly and points radially inwards
+        let radial = (v - center).normalize();
+        assert!((field_normal - radial).norm() < 1e-10);
+        assert!(field_normal.dot(geometric_normal) > 0.99);
+    }
+}
+
+#[test]
+fn test_vertex_gradient_magnitudes_from_field() {
+    // Linear density field, its gradient is reproduced exactly by central and one-sided differences
+    let grid = UniformGrid::<i64, f64>::new(&Vector3::zeros(), &[10, 10, 10], 0.1).unwrap();
+    let slope = Vector3::new(-2.0, 1.0, 2.0);
+    let mut map = new_map();
+    for i in 0..11 {
+        for j in 0..11 {
+            for k in 0..11 {
+                let x = grid.point_coordinates_indices(i, j, k);
+                map.insert(grid.flatten_point_indices(i, j, k), 5.0 + slope.dot(&x));
+            }
+        }
+    }
+    let density_map = DensityMap::from(map);
+
+    // Vertices in the interior, on grid points and on the upper boundary of the grid
+    let mesh = TriMesh3d {
+        vertices: vec![
+            Vector3::new(0.33, 0.47, 0.51),
+            Vector3::new(0.2, 0.3, 0.4),
+            Vector3::new(1.0, 1.0, 1.0),
+        ],
+        triangles: vec![[0, 1, 2]],
+    };
+
+    let magnitudes = compute_vertex_gradient_magnitudes_from_field(&mesh, &density_map, &grid);
+    assert_eq!(magnitudes.len(), mesh.vertices.len());
+    for magnitude in magnitudes {
+        assert!((magnitude - slope.norm()).abs() < 1e-10);
+    }
+
+    // Vertices outside of the support of the density map have a vanishing gradient
+    let empty_map = DensityMap::from(new_map());
+    let magnitudes = compute_vertex_gradient_magnitudes_from_field(&mesh, &empty_map, &grid);
+    assert!(magnitudes.iter().all(|&m| m.abs() < 1e-12));
+}
+
+#[test]
+fn test_density_map_active_cells() {
+    let grid = UniformGrid::<i64, f64>::new(&Vector3::zeros(), &[5, 5, 5], 0.1).unwrap();
+
+    // An interior point touches eight cells, a corner point of the grid only one
+    let mut map = new_map();
+    map.insert(grid.flatten_point_indices(2, 2, 2), 1.0);
+    map.insert(grid.flatten_point_indices(5, 5, 5), 1.0);
+    let active_cells = DensityMap::from(map).active_cells(&grid);
+
+    assert_eq!(active_cells.len(), 9);
+    assert!(active_cells.iter().any(|cell| cell.index() == &[4, 4, 4]));
+    for i in 1..=2 {
+        for j in 1..=2 {
+            for k in 1..=2 {
+                assert!(active_cells.iter().any(|cell| cell.index() == &[i, j, k]));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_density_map_statistics() {
+    let grid = UniformGrid::<i64, f64>::new(&Vector3::zeros(), &[20, 20, 20], 0.1).unwrap();
+    let mut particle_positions = vec![
+        Vector3::new(1.0, 1.0, 1.0),
+        Vector3::new(1.05, 1.0, 1.0),
+        Vector3::new(1.0, 1.1, 0.95),
+        Vector3::new(0.5, 0.6, 0.7),
+    ];
+    // Block of particles such that the contributions to a point are spread over several thread local maps
+    for i in 0..20 {
+        for j in 0..20 {
+            for k in 0..20 {
+                particle_positions.push(Vector3::new(i as f64, j as f64, k as f64) * 0.05);
+            }
+        }
+    }
+    let particle_densities = vec![1000.0; particle_positions.len()];
+
+    for &allow_threading in &[false, true] {
+        let mut density_map = new_map().into();
+        let parameters = DensityMapParameters {
+            particle_rest_mass: 1.0,
+            compact_support_radius: 0.2,
+            kernel: KernelType::CubicSpline,
+            cube_size: 0.1,
+            allow_threading,
+            histogram: Some(DensityHistogram::new(0.0, 0.5, 10)),
+        };
+        let statistics = generate_sparse_density_map(
+            &grid,
+            None,
+            &particle_positions,
+            &particle_densities,
+            None,
+            &parameters,
+            &mut density_map,
+        )
+        .unwrap();
+
+        // Compare with a separate scan over the density map
+        let values: Vec<f64> = density_map.to_vec().into_iter().map(|(_, v)| v).collect();
+        let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        assert_eq!(statistics.num_values, values.len());
+        assert_eq!(statistics.min, min);
+        assert_eq!(statistics.max, max);
+
+        let histogram = statistics.histogram.unwrap();
+        assert_eq!(histogram.total(), values.len());
+        for (bin, &count) in histogram.counts().iter().enumerate() {
+            let (lower, upper) = histogram.bin_range(bin);
+            let expected = values
+                .iter()
+                .filter(|&&v| (bin == 0 || v >= lower) && (bin == 9 || v < upper))
+                .count();
+            assert_eq!(count, expected);
+        }
+    }
+}
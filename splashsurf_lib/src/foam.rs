@@ -0,0 +1,185 @@
+//! Foam and spray intensities on reconstructed surfaces computed from particle velocities
+//!
+//! Regions of a fluid with strong relative motion of the particles (e.g. breaking waves or splashes)
+//! are typically rendered with foam or spray. This module computes a per-vertex foam intensity from the
+//! local variance of the particle velocities that can be attached to a reconstructed mesh as an attribute
+//! (e.g. using [`MeshWithData::with_point_data`](crate::mesh::MeshWithData::with_point_data)).
+//!
+//! The intensity is computed in two steps using the cubic spline kernel `W` with the given compact support radius:
+//!  1. For every particle `i`, the kernel weighted mean and variance of the velocities `v_j` of all
+//!     particles `j` in its neighborhood (including the particle itself) are computed:
+//!     ```text
+//!     mean_i     = sum_j W(x_i - x_j) v_j / sum_j W(x_i - x_j)
+//!     variance_i = sum_j W(x_i - x_j) |v_j - mean_i|^2 / sum_j W(x_i - x_j)
+//!     ```
+//!  2. The variances are interpolated to every mesh vertex `x` with a normalized (Shepard) SPH interpolation
+//!     (see [`interpolate_particle_attribute`]):
+//!     ```text
+//!     foam(x) = sum_j W(x - x_j) variance_j / sum_j W(x - x_j)
+//!     ```
+//!
+//! The resulting intensity has the unit of a squared velocity. It is zero for fluid moving as a rigid
+//! translation and for vertices without any particle in their support radius.
+
+use crate::interpolation::interpolate_particle_attribute;
+use crate::kernel::cubic_kernel_r;
+use crate::mesh::TriMesh3d;
+use crate::{
+    neighborhood_search, profile, validate_particle_inputs, AxisAlignedBoundingBox3d, Index,
+    ParticleInputLengthError, Real,
+};
+use nalgebra::Vector3;
+use rayon::prelude::*;
+
+/// Computes the foam intensity for every vertex of the mesh from the local variance of the particle velocities
+///
+/// See the [module level documentation](self) for the formula. The returned values are in the same
+/// order as the vertices of the mesh. Returns an error if the number of velocities does not match
+/// the number of particles.
+pub fn compute_foam_intensity<I: Index, R: Real>(
+    mesh: &TriMesh3d<R>,
+    particle_positions: &[Vector3<R>],
+    particle_velocities: &[Vector3<R>],
+    compact_support_radius: R,
+    enable_multi_threading: bool,
+) -> Result<Vec<R>, ParticleInputLengthError> {
+    profile!("compute_foam_intensity");
+
+    validate_particle_inputs(
+        particle_positions,
+        &[("particle_velocities", Some(particle_velocities.len()))],
+    )?;
+
+    if particle_positions.is_empty() {
+        return Ok(vec![R::zero(); mesh.vertices.len()]);
+    }
+
+    let variances = compute_velocity_variances::<I, R>(
+        particle_positions,
+        particle_velocities,
+        compact_support_radius,
+        enable_multi_threading,
+    );
+
+    interpolate_particle_attribute(
+        mesh,
+        particle_positions,
+        &variances,
+        compact_support_radius,
+        enable_multi_threading,
+    )
+}
+
+/// Computes the kernel weighted variance of the velocities in the neighborhood of every particle
+fn compute_velocity_variances<I: Index, R: Real>(
+    particle_positions: &[Vector3<R>],
+    particle_velocities: &[Vector3<R>],
+    compact_support_radius: R,
+    enable_multi_threading: bool,
+) -> Vec<R> {
+    let domain = {
+        let mut aabb = AxisAlignedBoundingBox3d::par_from_points(particle_positions);
+        aabb.grow_uniformly(compact_support_radius);
+        aabb
+    };
+    let neighbor_lists = neighborhood_search::search::<I, R>(
+        &domain,
+        particle_positions,
+        compact_support_radius,
+        enable_multi_threading,
+    );
+
+    let variance = |(i, neighbors): (usize, &Vec<usize>)| -> R {
+        let x_i = &particle_positions[i];
+        let weights: Vec<(R, &Vector3<R>)> = std::iter::once(i)
+            .chain(neighbors.iter().copied().filter(|&j| j != i))
+            .map(|j| {
+                (
+                    cubic_kernel_r((x_i - particle_positions[j]).norm(), compact_support_radius),
+                    &particle_velocities[j],
+                )
+            })
+            .collect();
+
+        let weight_sum = weights.iter().fold(R::zero(), |sum, (w, _)| sum + *w);
+        let mean = weights
+            .iter()
+            .fold(Vector3::zeros(), |sum, (w, v)| sum + *v * *w)
+            / weight_sum;
+        weights.iter().fold(R::zero(), |sum, (w, v)| {
+            sum + (*v - mean).norm_squared() * *w
+        }) / weight_sum
+    };
+
+    if enable_multi_threading {
+        neighbor_lists
+            .par_iter()
+            .enumerate()
+            .map(variance)
+            .collect()
+    } else {
+        neighbor_lists.iter().enumerate().map(variance).collect()
+    }
+}
+
+#[test]
+fn test_foam_intensity_shear_layer() {
+    let spacing = 0.1;
+    let compact_support_radius = 2.0 * spacing;
+
+    // Two layers of particles moving in opposite directions, separated by the plane z = 0
+    let mut particle_positions = Vec::new();
+    for i in 0..10 {
+        for j in 0..10 {
+            for k in -5..5 {
+                particle_positions.push(Vector3::new(i as f64, j as f64, k as f64 + 0.5) * spacing);
+            }
+        }
+    }
+    let sheared_velocities: Vec<_> = particle_positions
+        .iter()
+        .map(|x| Vector3::new(x.z.signum(), 0.0, 0.0))
+        .collect();
+    let uniform_velocities = vec![Vector3::new(1.0, 2.0, 3.0); particle_positions.len()];
+
+    let mesh = TriMesh3d {
+        vertices: vec![
+            Vector3::new(0.45, 0.45, 0.0),
+            Vector3::new(0.45, 0.45, 0.45),
+            Vector3::new(10.0, 10.0, 10.0),
+        ],
+        triangles: vec![[0, 1, 2]],
+    };
+
+    let uniform = compute_foam_intensity::<i64, f64>(
+        &mesh,
+        &particle_positions,
+        &uniform_velocities,
+        compact_support_radius,
+        false,
+    )
+    .unwrap();
+    assert!(uniform.iter().all(|&foam| foam.abs() < 1e-12));
+
+    let sheared = compute_foam_intensity::<i64, f64>(
+        &mesh,
+        &particle_positions,
+        &sheared_velocities,
+        compact_support_radius,
+        true,
+    )
+    .unwrap();
+    assert_eq!(sheared.len(), mesh.vertices.len());
+    assert!(sheared[0] > 0.2);
+    assert!(sheared[1] < 1e-12);
+    assert_eq!(sheared[2], 0.0);
+
+    assert!(compute_foam_intensity::<i64, f64>(
+        &mesh,
+        &particle_positions,
+        &sheared_velocities[1..],
+        compact_support_radius,
+        false,
+    )
+    .is_err());
+}
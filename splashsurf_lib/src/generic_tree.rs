@@ -43,6 +43,9 @@ pub trait VisitableTree: TreeNode {
     }
 
     /// An iterator over all nodes and its children in breadth-first order.
+    ///
+    /// The nodes are yielded level by level, starting with this node. The children of each node are
+    /// yielded in the order of [`TreeNode::children`], e.g. in octant order for the nodes of an octree.
     fn bfs_iter<'a>(&'a self) -> BfsIter<'a, Self> {
         BfsIter::new(self)
     }
@@ -130,7 +133,7 @@ impl<'a, T: TreeNode + ?Sized> Iterator for BfsIter<'a, T> {
     fn next(&mut self) -> Option<Self::Item> {
         if let Some(current_node) = self.queue.pop_front() {
             self.queue
-                .extend(current_node.children().iter().rev().map(Deref::deref));
+                .extend(current_node.children().iter().map(Deref::deref));
             Some(current_node)
         } else {
             None
@@ -0,0 +1,145 @@
+//! Interpolation of per-particle quantities onto the vertices of a reconstructed surface
+//!
+//! Quantities carried by the particles (e.g. temperature or concentration) can be sampled at the vertices
+//! of a reconstructed mesh, e.g. for coloring the surface. The values are interpolated with a normalized
+//! (Shepard) SPH interpolation using the cubic spline kernel `W` that is also used for the density field:
+//! ```text
+//! A(x) = sum_j W(x - x_j) A_j / sum_j W(x - x_j)
+//! ```
+//! The normalization compensates for the missing neighbors close to the free surface, in particular a
+//! constant attribute is reproduced exactly. Vertices without any particle in their support radius get
+//! the value zero.
+
+use crate::kernel::cubic_kernel_r;
+use crate::mesh::TriMesh3d;
+use crate::{new_map, profile, validate_particle_inputs, MapType, ParticleInputLengthError, Real};
+use nalgebra::Vector3;
+use rayon::prelude::*;
+
+/// Interpolates the given per-particle attribute to every vertex of the mesh
+///
+/// See the [module level documentation](self) for the formula. The compact support radius should be
+/// the one used for the reconstruction of the mesh. The returned values are in the same order as the
+/// vertices of the mesh. Returns an error if the number of attribute values does not match the number
+/// of particles.
+pub fn interpolate_particle_attribute<R: Real>(
+    mesh: &TriMesh3d<R>,
+    particle_positions: &[Vector3<R>],
+    particle_attribute: &[R],
+    compact_support_radius: R,
+    enable_multi_threading: bool,
+) -> Result<Vec<R>, ParticleInputLengthError> {
+    profile!("interpolate_particle_attribute");
+
+    validate_particle_inputs(
+        particle_positions,
+        &[("particle_attribute", Some(particle_attribute.len()))],
+    )?;
+
+    // Spatial hashing of the particles with cells of the size of the support radius
+    let cell_of = |x: &Vector3<R>| -> [i64; 3] {
+        let cell = x.map(|c| {
+            (c / compact_support_radius)
+                .floor()
+                .to_i64()
+                .unwrap_or(i64::MAX)
+        });
+        [cell.x, cell.y, cell.z]
+    };
+    let mut particles_per_cell: MapType<[i64; 3], Vec<usize>> = new_map();
+    for (i, x_i) in particle_positions.iter().enumerate() {
+        particles_per_cell.entry(cell_of(x_i)).or_default().push(i);
+    }
+
+    let interpolate = |x: &Vector3<R>| -> R {
+        let [ci, cj, ck] = cell_of(x);
+        let mut weighted_sum = R::zero();
+        let mut weight_sum = R::zero();
+        for di in -1..=1 {
+            for dj in -1..=1 {
+                for dk in -1..=1 {
+                    let cell = [
+                        ci.saturating_add(di),
+                        cj.saturating_add(dj),
+                        ck.saturating_add(dk),
+                    ];
+                    for &j in particles_per_cell.get(&cell).into_iter().flatten() {
+                        let w = cubic_kernel_r(
+                            (x - particle_positions[j]).norm(),
+                            compact_support_radius,
+                        );
+                        weighted_sum += w * particle_attribute[j];
+                        weight_sum += w;
+                    }
+                }
+            }
+        }
+
+        if weight_sum > R::zero() {
+            weighted_sum / weight_sum
+        } else {
+            R::zero()
+        }
+    };
+
+    let values = if enable_multi_threading {
+        mesh.vertices.par_iter().map(interpolate).collect()
+    } else {
+        mesh.vertices.iter().map(interpolate).collect()
+    };
+
+    Ok(values)
+}
+
+#[test]
+fn test_interpolate_constant_attribute() {
+    let spacing = 0.1;
+    let compact_support_radius = 2.0 * spacing;
+
+    let mut particle_positions = Vec::new();
+    for i in 0..10 {
+        for j in 0..10 {
+            for k in 0..10 {
+                particle_positions.push(Vector3::new(i as f64, j as f64, k as f64) * spacing);
+            }
+        }
+    }
+    let temperature = vec![293.15; particle_positions.len()];
+
+    // Vertices in the interior, close to the boundary and outside of the support of all particles
+    let mesh = TriMesh3d {
+        vertices: vec![
+            Vector3::new(0.45, 0.45, 0.45),
+            Vector3::new(0.45, 0.45, 0.95),
+            Vector3::new(-0.05, 0.0, 0.0),
+            Vector3::new(10.0, 10.0, 10.0),
+        ],
+        triangles: vec![[0, 1, 2], [1, 2, 3]],
+    };
+
+    for &enable_multi_threading in [false, true].iter() {
+        let interpolated = interpolate_particle_attribute(
+            &mesh,
+            &particle_positions,
+            &temperature,
+            compact_support_radius,
+            enable_multi_threading,
+        )
+        .unwrap();
+
+        assert_eq!(interpolated.len(), mesh.vertices.len());
+        for &value in &interpolated[0..3] {
+            assert!((value - 293.15).abs() < 1e-9);
+        }
+        assert_eq!(interpolated[3], 0.0);
+    }
+
+    assert!(interpolate_particle_attribute(
+        &mesh,
+        &particle_positions,
+        &temperature[1..],
+        compact_support_radius,
+        false,
+    )
+    .is_err());
+}
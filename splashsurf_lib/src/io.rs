@@ -0,0 +1,330 @@
+//! Readers for particle files and convenience functions to run the whole reconstruction pipeline from an input file to an output file
+//!
+//! Supported input formats are legacy VTK files (`.vtk`) and raw binary `f32` triplets (`.xyz`).
+//! The reconstructed mesh is written as a legacy binary VTK file (`.vtk`). Further formats are
+//! only supported by the `splashsurf` CLI.
+
+use std::fs::{create_dir_all, File};
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+
+use nalgebra::Vector3;
+use thiserror::Error as ThisError;
+use vtkio::model::{ByteOrder, DataSet, Version, Vtk};
+use vtkio::IOBuffer;
+
+use crate::{profile, reconstruct_surface, Index, Parameters, Real, ReconstructionError};
+
+/// Summary of a surface reconstruction performed by [`reconstruct_file`]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct ReconstructFileStats {
+    /// Number of particles read from the input file
+    pub num_particles: usize,
+    /// Number of vertices of the reconstructed mesh
+    pub num_vertices: usize,
+    /// Number of triangles of the reconstructed mesh
+    pub num_triangles: usize,
+}
+
+/// Error type returned when reading particle positions from a file fails
+#[derive(Debug, ThisError)]
+pub enum ParticleFileError {
+    /// The extension of the given file is not supported by the library
+    #[error("unsupported file format of file \"{0}\"")]
+    UnsupportedFormat(PathBuf),
+    /// Error while reading a plain file
+    #[error("io: {0}")]
+    Io(std::io::Error),
+    /// Error while reading a VTK file
+    #[error("vtk: {0}")]
+    Vtk(vtkio::Error),
+    /// The input file could be read but does not contain valid particle data
+    #[error("invalid input data: {0}")]
+    InvalidInput(String),
+}
+
+impl From<std::io::Error> for ParticleFileError {
+    /// Wraps a [`std::io::Error`] in a [`ParticleFileError`] for error propagation
+    fn from(error: std::io::Error) -> Self {
+        ParticleFileError::Io(error)
+    }
+}
+
+impl From<vtkio::Error> for ParticleFileError {
+    /// Wraps a [`vtkio::Error`] in a [`ParticleFileError`] for error propagation
+    fn from(error: vtkio::Error) -> Self {
+        ParticleFileError::Vtk(error)
+    }
+}
+
+impl From<vtkio::model::Error> for ParticleFileError {
+    /// Wraps a [`vtkio::model::Error`] (e.g. while loading the data of a piece) in a [`ParticleFileError`] for error propagation
+    fn from(error: vtkio::model::Error) -> Self {
+        ParticleFileError::Vtk(vtkio::Error::Load(error))
+    }
+}
+
+/// Error type returned when reading the input, the reconstruction or writing the output of [`reconstruct_file`] fails
+#[derive(Debug, ThisError)]
+pub enum ReconstructFileError<I: Index, R: Real> {
+    /// The extension of the output file is not supported by the library
+    #[error("unsupported file format of file \"{0}\"")]
+    UnsupportedFormat(PathBuf),
+    /// Error while reading the particles from the input file
+    #[error("input: {0}")]
+    Input(ParticleFileError),
+    /// Error while writing a plain file
+    #[error("io: {0}")]
+    Io(std::io::Error),
+    /// Error while writing a VTK file
+    #[error("vtk: {0}")]
+    Vtk(vtkio::Error),
+    /// Error during the surface reconstruction
+    #[error("reconstruction: {0}")]
+    Reconstruction(ReconstructionError<I, R>),
+}
+
+impl<I: Index, R: Real> From<ParticleFileError> for ReconstructFileError<I, R> {
+    /// Wraps a [`ParticleFileError`] in a [`ReconstructFileError`] for error propagation
+    fn from(error: ParticleFileError) -> Self {
+        ReconstructFileError::Input(error)
+    }
+}
+
+impl<I: Index, R: Real> From<std::io::Error> for ReconstructFileError<I, R> {
+    /// Wraps a [`std::io::Error`] in a [`ReconstructFileError`] for error propagation
+    fn from(error: std::io::Error) -> Self {
+        ReconstructFileError::Io(error)
+    }
+}
+
+impl<I: Index, R: Real> From<vtkio::Error> for ReconstructFileError<I, R> {
+    /// Wraps a [`vtkio::Error`] in a [`ReconstructFileError`] for error propagation
+    fn from(error: vtkio::Error) -> Self {
+        ReconstructFileError::Vtk(error)
+    }
+}
+
+impl<I: Index, R: Real> From<vtkio::model::Error> for ReconstructFileError<I, R> {
+    /// Wraps a [`vtkio::model::Error`] (e.g. while loading the data of a piece) in a [`ReconstructFileError`] for error propagation
+    fn from(error: vtkio::model::Error) -> Self {
+        ReconstructFileError::Vtk(vtkio::Error::Load(error))
+    }
+}
+
+impl<I: Index, R: Real> From<ReconstructionError<I, R>> for ReconstructFileError<I, R> {
+    /// Wraps a [`ReconstructionError`] in a [`ReconstructFileError`] for error propagation
+    fn from(error: ReconstructionError<I, R>) -> Self {
+        ReconstructFileError::Reconstruction(error)
+    }
+}
+
+/// Reads the particles from the input file, reconstructs the surface and writes the mesh to the output file
+///
+/// The file formats are selected by the file extensions, see the [module level documentation](self) for
+/// supported formats. This is a thin wrapper around [`reconstruct_surface`].
+pub fn reconstruct_file<I: Index, R: Real>(
+    input: &Path,
+    output: &Path,
+    parameters: &Parameters<R>,
+) -> Result<ReconstructFileStats, ReconstructFileError<I, R>> {
+    profile!("reconstruct_file");
+
+    let output_is_vtk = has_extension(output, "vtk");
+    if !output_is_vtk {
+        return Err(ReconstructFileError::UnsupportedFormat(
+            output.to_path_buf(),
+        ));
+    }
+
+    let particle_positions = particles_from_file::<R, _>(input)?;
+    let reconstruction = reconstruct_surface::<I, R>(&particle_positions, parameters)?;
+    let mesh = reconstruction.mesh();
+
+    if let Some(dir) = output.parent() {
+        create_dir_all(dir)?;
+    }
+
+    let vtk_file = Vtk {
+        version: Version::new((4, 1)),
+        title: "surface".to_string(),
+        file_path: None,
+        byte_order: ByteOrder::BigEndian,
+        data: mesh.into(),
+    };
+    vtk_file.export_be(output)?;
+
+    Ok(ReconstructFileStats {
+        num_particles: particle_positions.len(),
+        num_vertices: mesh.vertices.len(),
+        num_triangles: mesh.triangles.len(),
+    })
+}
+
+/// Imports a legacy VTK file with ASCII or binary encoding in either byte order
+///
+/// The header of a legacy VTK file does not specify the byte order of binary data. The file is first
+/// parsed as big endian (the byte order of the VTK specification) and, if this fails, as little endian.
+/// A little endian file is reliably rejected by the big endian parser as its cell types cannot be
+/// parsed. If both attempts fail, the error of the big endian parser is returned.
+pub fn import_legacy_vtk<P: AsRef<Path>>(path: P) -> Result<Vtk, vtkio::Error> {
+    let path = path.as_ref();
+    Vtk::import_legacy_be(path)
+        .or_else(|be_error| Vtk::import_legacy_le(path).map_err(|_| be_error))
+}
+
+/// Reads particle positions from a file with a supported extension, see the [module level documentation](self)
+pub fn particles_from_file<R: Real, P: AsRef<Path>>(
+    input: P,
+) -> Result<Vec<Vector3<R>>, ParticleFileError> {
+    let input = input.as_ref();
+    if has_extension(input, "vtk") {
+        particles_from_vtk(input)
+    } else if has_extension(input, "xyz") {
+        particles_from_xyz(input)
+    } else {
+        Err(ParticleFileError::UnsupportedFormat(input.to_path_buf()))
+    }
+}
+
+/// Returns whether the path has the given extension (case insensitive)
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.eq_ignore_ascii_case(extension))
+        .unwrap_or(false)
+}
+
+/// Reads the particle positions from the points of the first unstructured grid piece of the legacy VTK file at the given path
+pub fn particles_from_vtk<R: Real, P: AsRef<Path>>(
+    vtk_file: P,
+) -> Result<Vec<Vector3<R>>, ParticleFileError> {
+    let dataset = import_legacy_vtk(vtk_file)?.data;
+    particles_from_dataset(dataset)
+}
+
+/// Extracts the particle positions from the points of the first unstructured grid piece of the dataset
+///
+/// The point coordinate buffer of the dataset is released incrementally during the conversion, see [`particles_from_coords_incremental`].
+pub fn particles_from_dataset<R: Real>(
+    dataset: DataSet,
+) -> Result<Vec<Vector3<R>>, ParticleFileError> {
+    let invalid = |msg: &str| ParticleFileError::InvalidInput(msg.to_string());
+
+    let piece = match dataset {
+        DataSet::UnstructuredGrid { pieces, .. } => pieces
+            .into_iter()
+            .next()
+            .ok_or_else(|| invalid("dataset does not contain an unstructured grid piece"))?,
+        _ => return Err(invalid("dataset does not contain an unstructured grid")),
+    };
+
+    particles_from_point_buffer(piece.into_loaded_piece_data(None)?.points)
+}
+
+/// Converts the point coordinate buffer of a VTK piece into particle positions, the buffer is released incrementally during the conversion
+pub fn particles_from_point_buffer<R: Real>(
+    points: IOBuffer,
+) -> Result<Vec<Vector3<R>>, ParticleFileError> {
+    match points {
+        IOBuffer::F64(coords) => {
+            particles_from_coords_incremental(coords, COORDS_CONVERSION_CHUNK_SIZE)
+        }
+        IOBuffer::F32(coords) => {
+            particles_from_coords_incremental(coords, COORDS_CONVERSION_CHUNK_SIZE)
+        }
+        _ => Err(ParticleFileError::InvalidInput(
+            "point coordinates are not f32 or f64 values".to_string(),
+        )),
+    }
+}
+
+/// Converts a flat buffer of consecutive coordinate triplets into particle positions, also converts between floating point types
+pub fn particles_from_coords<RealOut: Real, RealIn: Real>(
+    coords: &[RealIn],
+) -> Result<Vec<Vector3<RealOut>>, ParticleFileError> {
+    check_coords_len(coords.len())?;
+
+    let mut positions = Vec::with_capacity(coords.len() / 3);
+    for c in coords.chunks_exact(3) {
+        positions.push(convert_coords(c)?);
+    }
+    Ok(positions)
+}
+
+/// Number of particles converted at once when incrementally converting coordinate buffers of VTK files
+const COORDS_CONVERSION_CHUNK_SIZE: usize = 1 << 20;
+
+/// Converts a flat buffer of consecutive coordinate triplets into particle positions while incrementally releasing the input buffer
+///
+/// In contrast to [`particles_from_coords`], the input buffer is consumed from its end in chunks of the given number
+/// of particles and shrunk after every chunk. Therefore, the memory of the input buffer is released while the output
+/// is filled and the full input and output buffers never have to be kept in memory at the same time (assuming that
+/// the allocator returns the memory of shrunk allocations to the operating system).
+pub fn particles_from_coords_incremental<RealOut: Real, RealIn: Real>(
+    mut coords: Vec<RealIn>,
+    chunk_size: usize,
+) -> Result<Vec<Vector3<RealOut>>, ParticleFileError> {
+    check_coords_len(coords.len())?;
+    let chunk_size = chunk_size.max(1);
+
+    let mut positions = Vec::with_capacity(coords.len() / 3);
+    while !coords.is_empty() {
+        let chunk_start = coords.len() - 3 * chunk_size.min(coords.len() / 3);
+        // The chunk is converted in reverse order, the order is restored after converting all chunks
+        for c in coords[chunk_start..].chunks_exact(3).rev() {
+            positions.push(convert_coords(c)?);
+        }
+
+        coords.truncate(chunk_start);
+        coords.shrink_to_fit();
+    }
+    positions.reverse();
+
+    Ok(positions)
+}
+
+/// Returns an error if the number of coordinates is not divisible by three
+fn check_coords_len(len: usize) -> Result<(), ParticleFileError> {
+    if !len.is_multiple_of(3) {
+        return Err(ParticleFileError::InvalidInput(
+            "number of point coordinates is not divisible by 3".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Converts a coordinate triplet to a position of the target type
+fn convert_coords<RealOut: Real, RealIn: Real>(
+    c: &[RealIn],
+) -> Result<Vector3<RealOut>, ParticleFileError> {
+    let convert = |v: RealIn| {
+        v.try_convert().ok_or_else(|| {
+            ParticleFileError::InvalidInput(
+                "point coordinates cannot be converted to the target type".to_string(),
+            )
+        })
+    };
+    Ok(Vector3::new(convert(c[0])?, convert(c[1])?, convert(c[2])?))
+}
+
+/// Reads particle positions stored as consecutive native endian `f32` triplets, trailing incomplete triplets are ignored
+pub fn particles_from_xyz<R: Real, P: AsRef<Path>>(
+    xyz_file: P,
+) -> Result<Vec<Vector3<R>>, ParticleFileError> {
+    let mut bytes = Vec::new();
+    BufReader::new(File::open(xyz_file)?).read_to_end(&mut bytes)?;
+
+    let coords = bytes
+        .chunks_exact(4)
+        .map(|b| f32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+        .collect::<Vec<_>>();
+    particles_from_coords(&coords[..coords.len() - coords.len() % 3])
+}
+
+#[test]
+fn test_particles_from_coords_invalid_length() {
+    let coords = vec![0.0f64, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
+    assert!(particles_from_coords::<f64, _>(&coords).is_err());
+    assert!(particles_from_coords_incremental::<f64, _>(coords, 1).is_err());
+}
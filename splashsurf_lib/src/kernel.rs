@@ -5,6 +5,7 @@ use crate::Real;
 const ALPHA: f64 = 3.0 / (2.0 * std::f64::consts::PI);
 const TWO_THIRDS: f64 = 2.0 / 3.0;
 const ONE_SIXTH: f64 = 1.0 / 6.0;
+const WENDLAND_C2_ALPHA: f64 = 21.0 / (2.0 * std::f64::consts::PI);
 
 // TODO: Add reference for the kernel function, document formula
 
@@ -37,6 +38,135 @@ pub fn cubic_kernel_r<R: Real>(r: R, h: R) -> R {
     R::from_f64(cubic_kernel_r_f64(r, h)).unwrap()
 }
 
+/// The derivative of the cubic kernel function with respect to the parameter `q`
+#[inline(always)]
+fn cubic_function_derivative_f64(q: f64) -> f64 {
+    if q < 1.0 {
+        ALPHA * (-2.0 * q + 1.5 * q * q)
+    } else if q < 2.0 {
+        let x = 2.0 - q;
+        -ALPHA * 0.5 * x * x
+    } else {
+        0.0
+    }
+}
+
+/// Evaluates the radial derivative of the cubic kernel with compact support radius `h` at the radius `r`, `f64` version
+#[inline(always)]
+pub fn cubic_kernel_gradient_r_f64(r: f64, h: f64) -> f64 {
+    let q = (2.0 * r) / h;
+    16.0 * cubic_function_derivative_f64(q) / (h * h * h * h)
+}
+
+/// Evaluates the Wendland C2 kernel with compact support radius `h` at the radius `r`, `f64` version
+///
+/// The kernel is given by `W(r, h) = 21/(2πh³) (1 - q)⁴ (1 + 4q)` with `q = r/h` for `q < 1` and zero otherwise.
+#[inline(always)]
+pub fn wendland_c2_kernel_r_f64(r: f64, h: f64) -> f64 {
+    let q = r / h;
+    if q < 1.0 {
+        let x = 1.0 - q;
+        WENDLAND_C2_ALPHA * x * x * x * x * (1.0 + 4.0 * q) / (h * h * h)
+    } else {
+        0.0
+    }
+}
+
+/// Evaluates the radial derivative of the Wendland C2 kernel with compact support radius `h` at the radius `r`, `f64` version
+#[inline(always)]
+pub fn wendland_c2_kernel_gradient_r_f64(r: f64, h: f64) -> f64 {
+    let q = r / h;
+    if q < 1.0 {
+        let x = 1.0 - q;
+        WENDLAND_C2_ALPHA * -20.0 * q * x * x * x / (h * h * h * h)
+    } else {
+        0.0
+    }
+}
+
+/// Evaluates the Wendland C2 kernel with compact support radius `h` at the radius `r`, generic version
+#[inline(always)]
+pub fn wendland_c2_kernel_r<R: Real>(r: R, h: R) -> R {
+    let r = r.to_f64().unwrap();
+    let h = h.to_f64().unwrap();
+
+    R::from_f64(wendland_c2_kernel_r_f64(r, h)).unwrap()
+}
+
+/// Common interface of radially symmetric SPH kernels with compact support
+pub trait SphKernel<R: Real> {
+    /// Evaluates the kernel with compact support radius `h` at the radius `r`
+    fn evaluate(&self, r: R, h: R) -> R;
+    /// Evaluates the radial derivative `dW/dr` of the kernel with compact support radius `h` at the radius `r`
+    ///
+    /// The gradient with respect to a position `x` is obtained by multiplying this value with `x / |x|`.
+    fn evaluate_gradient(&self, r: R, h: R) -> R;
+}
+
+/// The cubic spline kernel, vanishes for radii larger than the compact support radius `h`
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct CubicSplineKernel;
+
+impl<R: Real> SphKernel<R> for CubicSplineKernel {
+    #[inline(always)]
+    fn evaluate(&self, r: R, h: R) -> R {
+        cubic_kernel_r(r, h)
+    }
+
+    #[inline(always)]
+    fn evaluate_gradient(&self, r: R, h: R) -> R {
+        let r = r.to_f64().unwrap();
+        let h = h.to_f64().unwrap();
+        R::from_f64(cubic_kernel_gradient_r_f64(r, h)).unwrap()
+    }
+}
+
+/// The Wendland C2 kernel, vanishes for radii larger than the compact support radius `h`
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct WendlandC2Kernel;
+
+impl<R: Real> SphKernel<R> for WendlandC2Kernel {
+    #[inline(always)]
+    fn evaluate(&self, r: R, h: R) -> R {
+        wendland_c2_kernel_r(r, h)
+    }
+
+    #[inline(always)]
+    fn evaluate_gradient(&self, r: R, h: R) -> R {
+        let r = r.to_f64().unwrap();
+        let h = h.to_f64().unwrap();
+        R::from_f64(wendland_c2_kernel_gradient_r_f64(r, h)).unwrap()
+    }
+}
+
+/// Selection of the SPH kernel that is used to compute particle densities and the density map
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum KernelType {
+    /// The cubic spline kernel, see [`CubicSplineKernel`]
+    #[default]
+    CubicSpline,
+    /// The Wendland C2 kernel, see [`WendlandC2Kernel`]
+    WendlandC2,
+}
+
+impl<R: Real> SphKernel<R> for KernelType {
+    #[inline(always)]
+    fn evaluate(&self, r: R, h: R) -> R {
+        match self {
+            KernelType::CubicSpline => CubicSplineKernel.evaluate(r, h),
+            KernelType::WendlandC2 => WendlandC2Kernel.evaluate(r, h),
+        }
+    }
+
+    #[inline(always)]
+    fn evaluate_gradient(&self, r: R, h: R) -> R {
+        match self {
+            KernelType::CubicSpline => CubicSplineKernel.evaluate_gradient(r, h),
+            KernelType::WendlandC2 => WendlandC2Kernel.evaluate_gradient(r, h),
+        }
+    }
+}
+
 #[test]
 fn test_cubic_kernel_r_compact_support() {
     let hs = [0.025, 0.1, 2.0];
@@ -75,9 +205,87 @@ fn test_cubic_kernel_r_integral() {
     }
 }
 
-/// Accelerator for efficient evaluation of a precomputed cubic kernel
+#[cfg(test)]
+fn integrate_kernel<K: SphKernel<f64>>(kernel: &K, h: f64, n: i32) -> f64 {
+    use nalgebra::Vector3;
+
+    let dr = h / (n as f64);
+    let dvol = dr * dr * dr;
+
+    let mut integral = 0.0;
+    for i in -n..n {
+        for j in -n..n {
+            for k in -n..n {
+                let r_in = Vector3::new(i as f64, j as f64, k as f64) * dr;
+                let r_out = Vector3::new((i + 1) as f64, (j + 1) as f64, (k + 1) as f64) * dr;
+                let r = ((r_in + r_out) * 0.5).norm();
+
+                integral += dvol * kernel.evaluate(r, h);
+            }
+        }
+    }
+
+    integral
+}
+
+#[test]
+fn test_kernel_types_integral() {
+    let hs = [0.025, 0.1, 2.0];
+    for &kernel in [KernelType::CubicSpline, KernelType::WendlandC2].iter() {
+        for &h in hs.iter() {
+            let integral = integrate_kernel(&kernel, h, 20);
+            assert!(
+                (integral - 1.0).abs() <= 1e-3,
+                "integral of {:?} kernel with h={} is {}",
+                kernel,
+                h,
+                integral
+            );
+        }
+    }
+}
+
+#[test]
+fn test_kernel_types_compact_support() {
+    let hs = [0.025, 0.1, 2.0];
+    for &kernel in [KernelType::CubicSpline, KernelType::WendlandC2].iter() {
+        for &h in hs.iter() {
+            assert!(kernel.evaluate(0.0, h) > 0.0);
+            assert!(kernel.evaluate(0.99 * h, h) >= 0.0);
+            for &r in [h, 1.5 * h, 2.0 * h, 10.0 * h].iter() {
+                assert_eq!(kernel.evaluate(r, h), 0.0);
+                assert_eq!(kernel.evaluate_gradient(r, h), 0.0);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_kernel_types_gradient() {
+    let h = 0.1;
+    let eps = 1e-7;
+    for &kernel in [KernelType::CubicSpline, KernelType::WendlandC2].iter() {
+        for i in 1..20 {
+            let r = (i as f64) * h / 20.0;
+            let finite_difference =
+                (kernel.evaluate(r + eps, h) - kernel.evaluate(r - eps, h)) / (2.0 * eps);
+            let gradient = kernel.evaluate_gradient(r, h);
+            assert!(gradient <= 0.0);
+            assert!(
+                (finite_difference - gradient).abs() <= 1e-4 * gradient.abs().max(1.0),
+                "{:?} kernel at r={}: finite difference {} vs. gradient {}",
+                kernel,
+                r,
+                finite_difference,
+                gradient
+            );
+        }
+    }
+}
+
+/// Accelerator for efficient evaluation of a precomputed kernel
 ///
-/// This structure is used to pre-compute a discrete representation of the cubic kernel function.
+/// This structure is used to pre-compute a discrete representation of a kernel function (by default the cubic kernel).
 /// In some computations that require many evaluations of the kernel, it is more efficient to evaluate
 /// the kernel using a squared distance to avoid taking the square root.
 /// To produce an appropriate quantization of the kernel for this use case, the compact support
@@ -89,16 +297,21 @@ fn test_cubic_kernel_r_integral() {
 /// `s` by just mapping this radius back to the corresponding segment index `i` followed by a lookup
 /// in the value array, i.e. `k(sqrt(s)) ≈ K[s/dr]` (while taking care of rounding and clamping to the
 /// allowed index range).
-pub struct DiscreteSquaredDistanceCubicKernel<R: Real> {
-    /// Precomputed values of the kernel functionclamp
+pub struct DiscreteSquaredDistanceKernel<R: Real> {
+    /// Precomputed values of the kernel function
     values: Vec<R>,
     /// The radial resolution of the discretization on a quadratic scale
     dr: R,
 }
 
-impl<R: Real> DiscreteSquaredDistanceCubicKernel<R> {
+impl<R: Real> DiscreteSquaredDistanceKernel<R> {
     /// Precomputes the discrete cubic kernel with compact support radius `h`, the squared radius `h * h` is divided into `n` segments for the quantization
     pub fn new(n: usize, h: R) -> Self {
+        Self::new_with_kernel(n, h, KernelType::CubicSpline)
+    }
+
+    /// Precomputes the discrete representation of the given kernel with compact support radius `h`, see [`DiscreteSquaredDistanceKernel::new`]
+    pub fn new_with_kernel(n: usize, h: R, kernel: KernelType) -> Self {
         let mut values = Vec::with_capacity(n);
 
         let compact_support = h;
@@ -109,13 +322,13 @@ impl<R: Real> DiscreteSquaredDistanceCubicKernel<R> {
             let i_and_half = R::from_usize(i).unwrap() + R::from_f64(0.5).unwrap();
             let r_squared = dr * i_and_half;
             let r = r_squared.sqrt();
-            values.push(cubic_kernel_r(r, h));
+            values.push(kernel.evaluate(r, h));
         }
 
         Self { values, dr }
     }
 
-    /// Evaluates the precomputed kernel function at the specified squared radius, i.e. returns an approximate cubic kernel value at the radius `sqrt(r_squared)`
+    /// Evaluates the precomputed kernel function at the specified squared radius, i.e. returns an approximate kernel value at the radius `sqrt(r_squared)`
     #[inline(always)]
     pub fn evaluate(&self, r_squared: R) -> R {
         let normalized = (r_squared / self.dr).round();
@@ -128,7 +341,7 @@ impl<R: Real> DiscreteSquaredDistanceCubicKernel<R> {
 fn test_discrete_kernel() {
     let n = 10000;
     let h = 0.025;
-    let kernel = DiscreteSquaredDistanceCubicKernel::new(n, h);
+    let kernel = DiscreteSquaredDistanceKernel::new(n, h);
 
     // Test the pre-computed values using a linear stepping
     let dr = h / (n as f64);
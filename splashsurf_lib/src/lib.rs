@@ -1,4 +1,5 @@
 #![cfg_attr(doc_cfg, feature(doc_cfg))]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
 //!
 //! Library for surface reconstruction of SPH particle data using marching cubes.
@@ -6,63 +7,167 @@
 //! Entry points are the [`reconstruct_surface`] or [`reconstruct_surface_inplace`] functions.
 //!
 //! ## Feature flags
-//! The following features are all non-default features to reduce the amount of additional dependencies.
+//! Only the `std` feature is enabled by default, all other features are non-default features to reduce the amount of additional dependencies.
 //!
+//! - **`std`** (default): Enables the surface reconstruction and all modules that depend on the standard library. Without it,
+//!   the crate is `no_std` and only contains its geometric core, i.e. the [`AxisAlignedBoundingBox`], the [`Index`] and [`Real`]
+//!   traits, the [`topology`] module and the [`UniformGrid`]. All other features imply `std`.
 //! - **`vtk_extras`**: Enables helper functions and trait implementations to export meshes using [`vtkio`](https://github.com/elrnv/vtkio).
-//!  In particular it adds `From` impls for the [mesh](crate::mesh) types used by this crate to convert them to
-//!  [`vtkio::model::UnstructuredGridPiece`](https://docs.rs/vtkio/0.6.*/vtkio/model/struct.UnstructuredGridPiece.html) and [`vtkio::model::DataSet`](https://docs.rs/vtkio/0.6.*/vtkio/model/enum.DataSet.html)
-//!  types. If the feature is enabled, The crate exposes its `vtkio` dependency as `splashsurflib::vtkio`.
+//!   In particular it adds `From` impls for the [mesh](crate::mesh) types used by this crate to convert them to
+//!   [`vtkio::model::UnstructuredGridPiece`](https://docs.rs/vtkio/0.6.*/vtkio/model/struct.UnstructuredGridPiece.html) and [`vtkio::model::DataSet`](https://docs.rs/vtkio/0.6.*/vtkio/model/enum.DataSet.html)
+//!   types. If the feature is enabled, The crate exposes its `vtkio` dependency as `splashsurflib::vtkio`.
+//!   Additionally, the [`reconstruct_file`] function can be used to reconstruct a surface directly from a particle file.
 //! - **`profiling`**: Enables profiling of internal functions. The resulting data can be displayed using the functions
-//!  from the [`profiling`] module. Furthermore, it exposes the [`profile`] macro that can be used e.g.
-//!  by binary crates calling into this library to add their own profiling scopes to the measurements.
-//!  If this features is not enabled, the macro will just expend to a no-op and remove the (small)
-//!  performance overhead of the profiling.
+//!   from the [`profiling`] module. Furthermore, it exposes the [`profile`] macro that can be used e.g.
+//!   by binary crates calling into this library to add their own profiling scopes to the measurements.
+//!   If this features is not enabled, the macro will just expend to a no-op and remove the (small)
+//!   performance overhead of the profiling.
+//! - **`stats`**: Enables collection of statistics during the reconstruction, e.g. a histogram of the marching cubes
+//!   cases that occurred during triangulation. The statistics are collected per reconstruction and can be queried from
+//!   its result, e.g. using [`SurfaceReconstruction::marching_cubes_case_histogram`].
+//! - **`random`**: Enables the [`random::DefaultRng`] and [`random::rng_from_seed`] helpers that provide a portable,
+//!   seedable RNG based on [`rand_chacha`](https://docs.rs/rand_chacha), e.g. to obtain reproducible inputs for tests.
+//!   The crate exposes its `rand` dependency as `splashsurf_lib::rand`.
+//! - **`serde`**: Implements `Serialize` and `Deserialize` for the [`Octree`](crate::octree::Octree) and its nodes
+//!   (including the [`AxisAlignedBoundingBox`] and [`PointIndex`](crate::uniform_grid::PointIndex) types they contain),
+//!   e.g. to cache a spatial decomposition across runs. The crate exposes its `serde` dependency as `splashsurf_lib::serde`.
 //!
 
+#[cfg(feature = "std")]
 use log::info;
 /// Re-export the version of `nalgebra` used by this crate
 pub use nalgebra;
+#[cfg(feature = "std")]
 use nalgebra::Vector3;
+/// Re-export the version of `rand` used by this crate, see the [`random`] module for the handling of randomness
+#[cfg(feature = "random")]
+pub use rand;
+/// Re-export the version of `serde` used by this crate, if serde support is enabled
+#[cfg(feature = "serde")]
+pub use serde_crate as serde;
+#[cfg(feature = "std")]
 use thiserror::Error as ThisError;
 /// Re-export the version of `vtkio` used by this crate, if vtk support is enabled
 #[cfg(feature = "vtk_extras")]
 pub use vtkio;
 
 pub use crate::aabb::{AxisAlignedBoundingBox, AxisAlignedBoundingBox2d, AxisAlignedBoundingBox3d};
+#[cfg(feature = "std")]
 pub use crate::density_map::DensityMap;
+#[cfg(feature = "vtk_extras")]
+pub use crate::io::reconstruct_file;
+#[cfg(feature = "std")]
+pub use crate::kernel::KernelType;
+#[cfg(feature = "std")]
+pub use crate::mask::{MaskAlignmentError, ReconstructionMask};
+#[cfg(feature = "std")]
 pub use crate::octree::SubdivisionCriterion;
+#[cfg(feature = "std")]
+pub use crate::parameters_builder::{ParametersBuilderError, ReconstructionParametersBuilder};
+#[cfg(feature = "std")]
+pub use crate::periodic::{PeriodicAxes, PeriodicBoundaryError};
+#[cfg(feature = "std")]
+pub use crate::progress::{ReconstructionProgress, ReconstructionStage};
 pub use crate::traits::{Index, Real, ThreadSafe};
 pub use crate::uniform_grid::UniformGrid;
+#[cfg(feature = "std")]
+pub use crate::validation::{validate_particle_inputs, ParticleInputLengthError};
+#[cfg(feature = "std")]
+use crate::workspace::CaseHistogram;
+#[cfg(feature = "std")]
+pub use crate::workspace::ReconstructionWorkspace;
 
+#[cfg(feature = "std")]
+use crate::anisotropy::AnisotropyParameters;
+#[cfg(feature = "std")]
 use crate::density_map::DensityMapError;
-use crate::marching_cubes::MarchingCubesError;
+#[cfg(feature = "std")]
+use crate::marching_cubes::{EdgeInterpolation, MarchingCubesError};
+#[cfg(feature = "std")]
 use crate::mesh::TriMesh3d;
+#[cfg(feature = "std")]
 use crate::octree::Octree;
-use crate::uniform_grid::GridConstructionError;
-use crate::workspace::ReconstructionWorkspace;
+#[cfg(feature = "std")]
+use crate::progress::ProgressReporter;
+#[cfg(feature = "std")]
+use crate::uniform_grid::{CellIndex, GridConstructionError};
 
 #[cfg(feature = "profiling")]
 #[cfg_attr(doc_cfg, doc(cfg(feature = "profiling")))]
 pub mod profiling;
 #[doc(hidden)]
 pub mod profiling_macro;
+#[cfg(feature = "stats")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "stats")))]
+pub mod stats;
 
 mod aabb;
+#[cfg(feature = "std")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+pub mod anisotropy;
+#[cfg(feature = "std")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+pub mod bvh;
+#[cfg(feature = "std")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+pub mod coordinates;
+#[cfg(feature = "std")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
 pub mod density_map;
+#[cfg(feature = "std")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+pub mod foam;
+#[cfg(feature = "std")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
 pub mod generic_tree;
+#[cfg(feature = "std")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+pub mod interpolation;
+#[cfg(feature = "vtk_extras")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "vtk_extras")))]
+pub mod io;
+#[cfg(feature = "std")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
 pub mod kernel;
+#[cfg(feature = "std")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
 pub mod marching_cubes;
+#[cfg(feature = "std")]
+mod mask;
+#[cfg(feature = "std")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
 pub mod mesh;
+#[cfg(feature = "std")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
 pub mod neighborhood_search;
+#[cfg(feature = "std")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
 pub mod octree;
+#[cfg(feature = "std")]
+mod parameters_builder;
+#[cfg(feature = "std")]
+mod periodic;
+#[cfg(feature = "std")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+pub mod progress;
+#[cfg(feature = "std")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+pub mod random;
+#[cfg(feature = "std")]
 mod reconstruction;
+#[cfg(feature = "std")]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "std")))]
+pub mod tiling;
 pub mod topology;
 mod traits;
 pub mod uniform_grid;
+#[cfg(feature = "std")]
 mod utils;
+#[cfg(feature = "std")]
+mod validation;
+#[cfg(feature = "std")]
 pub(crate) mod workspace;
 
-// TODO: Add documentation of feature flags
 // TODO: Feature flag for multi threading
 // TODO: Feature flag to disable (debug level) logging?
 
@@ -75,8 +180,11 @@ pub(crate) mod workspace;
 // TODO: Make flat indices strongly typed
 // TODO: Function that detects smallest usable index type
 
+#[cfg(feature = "std")]
 pub(crate) type HashState = fxhash::FxBuildHasher;
+#[cfg(feature = "std")]
 pub(crate) type MapType<K, V> = std::collections::HashMap<K, V, HashState>;
+#[cfg(feature = "std")]
 pub(crate) fn new_map<K, V>() -> MapType<K, V> {
     MapType::with_hasher(HashState::default())
 }
@@ -99,9 +207,11 @@ pub(crate) fn new_map<K, V>() -> MapType<K, V> {
 }
 */
 
+#[cfg(feature = "std")]
 pub(crate) type ParallelMapType<K, V> = dashmap::DashMap<K, V, HashState>;
 
 /// Macro version of Option::map that allows using e.g. using the ?-operator in the map expression
+#[cfg(feature = "std")]
 macro_rules! map_option {
     ($some_optional:expr, $value_identifier:ident => $value_transformation:expr) => {
         match $some_optional {
@@ -112,6 +222,7 @@ macro_rules! map_option {
 }
 
 /// Parameters for the spatial decomposition
+#[cfg(feature = "std")]
 #[derive(Clone, Debug)]
 pub struct SpatialDecompositionParameters<R: Real> {
     /// Criterion used for subdivision of the octree cells
@@ -125,6 +236,7 @@ pub struct SpatialDecompositionParameters<R: Real> {
 }
 
 /// Available strategies for the computation of the particle densities
+#[cfg(feature = "std")]
 #[derive(Copy, Clone, Debug)]
 pub enum ParticleDensityComputationStrategy {
     /// Compute the particle densities globally before performing domain decomposition.
@@ -166,6 +278,7 @@ pub enum ParticleDensityComputationStrategy {
     IndependentSubdomains,
 }
 
+#[cfg(feature = "std")]
 impl<R: Real> SpatialDecompositionParameters<R> {
     /// Tries to convert the parameters from one [Real] type to another [Real] type, returns None if conversion fails
     pub fn try_convert<T: Real>(&self) -> Option<SpatialDecompositionParameters<T>> {
@@ -181,7 +294,40 @@ impl<R: Real> SpatialDecompositionParameters<R> {
     }
 }
 
+/// Rest density used to normalize the density field before contouring, see [`Parameters::normalize_by_rest_density`]
+#[cfg(feature = "std")]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum RestDensityNormalization<R: Real> {
+    /// Normalize the density field by the given rest density
+    Supplied(R),
+    /// Estimate the rest density from the particles as the median of all positive per-particle densities.
+    ///
+    /// As the per-particle density is proportional to the kernel weighted number of neighbors, this is
+    /// the density of a particle with a median neighbor count. This is robust against the low densities
+    /// of particles at the free surface and of isolated particles as long as the majority of the particles
+    /// has a full neighborhood. Particles with a density of zero (e.g. particles that were skipped by
+    /// the density computation) are ignored for the median. If no particle has a positive density, the
+    /// `rest_density` of the parameters is used instead. With domain decomposition and
+    /// [`ParticleDensityComputationStrategy::IndependentSubdomains`], the rest density is estimated
+    /// per subdomain.
+    Estimated,
+}
+
+#[cfg(feature = "std")]
+impl<R: Real> RestDensityNormalization<R> {
+    /// Tries to convert the normalization from one [Real] type to another [Real] type, returns None if conversion fails
+    pub fn try_convert<T: Real>(&self) -> Option<RestDensityNormalization<T>> {
+        Some(match self {
+            RestDensityNormalization::Supplied(rest_density) => {
+                RestDensityNormalization::Supplied(rest_density.try_convert()?)
+            }
+            RestDensityNormalization::Estimated => RestDensityNormalization::Estimated,
+        })
+    }
+}
+
 /// Parameters for the surface reconstruction
+#[cfg(feature = "std")]
 #[derive(Clone, Debug)]
 pub struct Parameters<R: Real> {
     /// Radius per particle (used to calculate the particle volume)
@@ -197,13 +343,53 @@ pub struct Parameters<R: Real> {
     /// Manually restrict the domain to the surface reconstruction.
     /// If not provided, the smallest AABB enclosing all particles is computed instead.
     pub domain_aabb: Option<AxisAlignedBoundingBox3d<R>>,
+    /// Number of additional empty marching cubes cells that pad the background grid on every side.
+    /// The padding is added beyond the AABB of the particles grown by the particle radius and the kernel support
+    /// radius, so that surfaces of fluid close to the domain boundary are reliably closed. Only applies if the
+    /// domain is not given by `domain_aabb`.
+    pub grid_margin_cells: usize,
     /// Whether to allow multi threading within the surface reconstruction procedure
     pub enable_multi_threading: bool,
     /// Parameters for the spatial decomposition (octree subdivision) of the particles.
     /// If not provided, no octree is generated and a global approach is used instead.
     pub spatial_decomposition: Option<SpatialDecompositionParameters<R>>,
+    /// Axes along which the domain is periodic.
+    /// Periodic boundaries require a `domain_aabb` whose extents along the periodic axes are integer multiples of the `cube_size`.
+    /// Reconstructions with periodic boundaries are always performed globally, i.e. the `spatial_decomposition` is ignored.
+    pub periodic_axes: PeriodicAxes,
+    /// Optional voxel mask that restricts the reconstruction to a region of interest.
+    /// The mask has to be aligned to the background grid, see [`ReconstructionMask`] for details.
+    pub mask: Option<ReconstructionMask<R>>,
+    /// Optional per-axis integer factors for the edge lengths of the marching cubes cells relative to the `cube_size`.
+    /// The density map is still evaluated on a grid with the `cube_size` but contoured on a grid with cells of
+    /// `cube_size * factor` along each axis, e.g. `[1, 1, 4]` for data that is well resolved along the x- and y-axis
+    /// but sparse along the z-axis. Currently only supported for global reconstructions without spatial decomposition
+    /// or periodic boundaries.
+    pub contour_cell_factors: Option<[usize; 3]>,
+    /// Optionally normalize the density field by a supplied or estimated rest density before contouring.
+    /// Instead of the particle volumes computed from the individual particle densities, the field is
+    /// evaluated with the particle mass divided by the rest density, such that it is one in fluid at rest
+    /// and the `iso_surface_threshold` becomes a fraction of the rest density (e.g. `0.5`) that transfers
+    /// across simulations with different particle spacings.
+    pub normalize_by_rest_density: Option<RestDensityNormalization<R>>,
+    /// Method used to place the iso-surface vertices on the edges of the marching cubes cells.
+    /// If not provided, linear interpolation is used. See [`EdgeInterpolation`] for the accuracy/cost tradeoff.
+    pub edge_interpolation: Option<EdgeInterpolation>,
+    /// Optional upper limit for the number of cells of the background grid.
+    /// If the grid required for the domain and `cube_size` has more cells, the reconstruction returns an error
+    /// before any storage for the grid is allocated. This guards against accidental huge allocations caused by a
+    /// cube size that is too small for a large domain, see [`uniform_grid::grid_cell_count_for`].
+    pub max_grid_cells: Option<u128>,
+    /// SPH kernel used to compute the particle densities and to evaluate the density map.
+    /// If not provided, the cubic spline kernel is used. See [`KernelType`] for the available kernels.
+    pub kernel: Option<KernelType>,
+    /// Optionally use anisotropic kernels that are compressed per particle according to the distribution of its neighbors.
+    /// This reduces the over-smoothing of thin sheets and filaments, see the [`anisotropy`] module for details.
+    /// Currently only supported for global reconstructions without spatial decomposition or periodic boundaries.
+    pub anisotropy: Option<AnisotropyParameters<R>>,
 }
 
+#[cfg(feature = "std")]
 impl<R: Real> Parameters<R> {
     /// Tries to convert the parameters from one [Real] type to another [Real] type, returns None if conversion fails
     pub fn try_convert<T: Real>(&self) -> Option<Parameters<T>> {
@@ -214,13 +400,47 @@ impl<R: Real> Parameters<R> {
             cube_size: self.cube_size.try_convert()?,
             iso_surface_threshold: self.iso_surface_threshold.try_convert()?,
             domain_aabb: map_option!(&self.domain_aabb, aabb => aabb.try_convert()?),
+            grid_margin_cells: self.grid_margin_cells,
             enable_multi_threading: self.enable_multi_threading,
             spatial_decomposition: map_option!(&self.spatial_decomposition, sd => sd.try_convert()?),
+            periodic_axes: self.periodic_axes,
+            mask: map_option!(&self.mask, mask => mask.try_convert()?),
+            contour_cell_factors: self.contour_cell_factors,
+            normalize_by_rest_density: map_option!(&self.normalize_by_rest_density, n => n.try_convert()?),
+            edge_interpolation: self.edge_interpolation,
+            max_grid_cells: self.max_grid_cells,
+            kernel: self.kernel,
+            anisotropy: map_option!(&self.anisotropy, a => a.try_convert()?),
         })
     }
 }
 
+/// A set of particles with its own kernel, used to reconstruct the union of several particle sets with [`reconstruct_multi_group`]
+#[cfg(feature = "std")]
+#[derive(Copy, Clone, Debug)]
+pub struct ParticleGroup<'a, R: Real> {
+    /// Positions of the particles of this group
+    pub positions: &'a [Vector3<R>],
+    /// Compact support radius of the kernel used for the particles of this group
+    pub compact_support_radius: R,
+    /// Mass of each particle of this group
+    pub particle_mass: R,
+}
+
+#[cfg(feature = "std")]
+impl<'a, R: Real> ParticleGroup<'a, R> {
+    /// Creates a particle group from its positions, the compact support radius of its kernel and its particle mass
+    pub fn new(positions: &'a [Vector3<R>], compact_support_radius: R, particle_mass: R) -> Self {
+        Self {
+            positions,
+            compact_support_radius,
+            particle_mass,
+        }
+    }
+}
+
 /// Result data returned when the surface reconstruction was successful
+#[cfg(feature = "std")]
 #[derive(Clone, Debug)]
 pub struct SurfaceReconstruction<I: Index, R: Real> {
     /// Background grid that was used as a basis for generating the density map for marching cubes
@@ -233,8 +453,11 @@ pub struct SurfaceReconstruction<I: Index, R: Real> {
     mesh: TriMesh3d<R>,
     /// Workspace with allocated memory for subsequent surface reconstructions
     workspace: ReconstructionWorkspace<I, R>,
+    /// Histogram of the marching cubes cases triangulated during the reconstruction (only collected with the `stats` feature)
+    case_histogram: CaseHistogram,
 }
 
+#[cfg(feature = "std")]
 impl<I: Index, R: Real> Default for SurfaceReconstruction<I, R> {
     /// Returns an empty [SurfaceReconstruction] to pass into the inplace surface reconstruction
     fn default() -> Self {
@@ -244,10 +467,12 @@ impl<I: Index, R: Real> Default for SurfaceReconstruction<I, R> {
             density_map: None,
             mesh: TriMesh3d::default(),
             workspace: ReconstructionWorkspace::default(),
+            case_histogram: Default::default(),
         }
     }
 }
 
+#[cfg(feature = "std")]
 impl<I: Index, R: Real> SurfaceReconstruction<I, R> {
     /// Returns a reference to the actual triangulated surface mesh that is the result of the reconstruction
     pub fn mesh(&self) -> &TriMesh3d<R> {
@@ -264,12 +489,34 @@ impl<I: Index, R: Real> SurfaceReconstruction<I, R> {
         self.density_map.as_ref()
     }
 
+    /// Returns the histogram of the marching cubes cases that were triangulated during this reconstruction
+    #[cfg(feature = "stats")]
+    #[cfg_attr(doc_cfg, doc(cfg(feature = "stats")))]
+    pub fn marching_cubes_case_histogram(&self) -> &stats::MarchingCubesCaseHistogram {
+        &self.case_histogram
+    }
+
+    /// Returns all active cells of the background grid, i.e. cells that received a density contribution from any particle
+    ///
+    /// The cells are computed from the density map and are therefore only available if the density map was retained,
+    /// see [`Self::density_map`].
+    pub fn active_cells(&self) -> Option<Vec<CellIndex<I>>> {
+        self.density_map
+            .as_ref()
+            .map(|density_map| density_map.active_cells(&self.grid))
+    }
+
     /// Returns a reference to the virtual background grid that was used as a basis for discretization of the density map for marching cubes, can be used to convert the density map to a hex mesh (using [sparse_density_map_to_hex_mesh](density_map::sparse_density_map_to_hex_mesh))
+    ///
+    /// The grid describes the origin ([`UniformGrid::origin`]), spacing ([`UniformGrid::cell_size`])
+    /// and dimensions ([`UniformGrid::points_per_dim`], [`UniformGrid::cells_per_dim`]) of the
+    /// discretization and can be cloned cheaply to align other data with the reconstruction.
     pub fn grid(&self) -> &UniformGrid<I, R> {
         &self.grid
     }
 }
 
+#[cfg(feature = "std")]
 impl<I: Index, R: Real> From<SurfaceReconstruction<I, R>> for TriMesh3d<R> {
     /// Extracts the reconstructed mesh
     fn from(result: SurfaceReconstruction<I, R>) -> Self {
@@ -278,6 +525,7 @@ impl<I: Index, R: Real> From<SurfaceReconstruction<I, R>> for TriMesh3d<R> {
 }
 
 /// Error type returned when the surface reconstruction fails
+#[cfg(feature = "std")]
 #[non_exhaustive]
 #[derive(Debug, ThisError)]
 pub enum ReconstructionError<I: Index, R: Real> {
@@ -290,11 +538,24 @@ pub enum ReconstructionError<I: Index, R: Real> {
     /// Error that occurred during the marching cubes stage of the reconstruction
     #[error("marching cubes: {0}")]
     MarchingCubesError(MarchingCubesError),
+    /// Error caused by an invalid configuration of periodic boundaries
+    #[error("periodic boundary: {0}")]
+    PeriodicBoundaryError(PeriodicBoundaryError<R>),
+    /// Error caused by a reconstruction mask that is not aligned to the background grid
+    #[error("reconstruction mask: {0}")]
+    MaskAlignmentError(MaskAlignmentError<R>),
+    /// Error caused by a background grid with more cells than allowed by [`Parameters::max_grid_cells`]
+    #[error("the background grid would consist of {0} cells which exceeds the limit of {1} cells, consider increasing the cube size or restricting the domain")]
+    GridCellLimitExceeded(u128, u128),
+    /// Error caused by an invalid combination of parameters
+    #[error("invalid parameters: {0}")]
+    InvalidParameters(String),
     /// Any error that is not represented by some other explicit variant
     #[error("unknown error")]
     Unknown(anyhow::Error),
 }
 
+#[cfg(feature = "std")]
 impl<I: Index, R: Real> From<GridConstructionError<I, R>> for ReconstructionError<I, R> {
     /// Wraps a [`GridConstructionError`] in a [`ReconstructionError`] for error propagation
     fn from(error: GridConstructionError<I, R>) -> Self {
@@ -302,6 +563,7 @@ impl<I: Index, R: Real> From<GridConstructionError<I, R>> for ReconstructionErro
     }
 }
 
+#[cfg(feature = "std")]
 impl<I: Index, R: Real> From<DensityMapError<R>> for ReconstructionError<I, R> {
     /// Wraps a [`DensityMapError`] in a [`ReconstructionError`] for error propagation
     fn from(error: DensityMapError<R>) -> Self {
@@ -309,6 +571,7 @@ impl<I: Index, R: Real> From<DensityMapError<R>> for ReconstructionError<I, R> {
     }
 }
 
+#[cfg(feature = "std")]
 impl<I: Index, R: Real> From<MarchingCubesError> for ReconstructionError<I, R> {
     /// Wraps a [`MarchingCubesError`] in a [`ReconstructionError`] for error propagation
     fn from(error: MarchingCubesError) -> Self {
@@ -316,6 +579,23 @@ impl<I: Index, R: Real> From<MarchingCubesError> for ReconstructionError<I, R> {
     }
 }
 
+#[cfg(feature = "std")]
+impl<I: Index, R: Real> From<PeriodicBoundaryError<R>> for ReconstructionError<I, R> {
+    /// Wraps a [`PeriodicBoundaryError`] in a [`ReconstructionError`] for error propagation
+    fn from(error: PeriodicBoundaryError<R>) -> Self {
+        ReconstructionError::PeriodicBoundaryError(error)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<I: Index, R: Real> From<MaskAlignmentError<R>> for ReconstructionError<I, R> {
+    /// Wraps a [`MaskAlignmentError`] in a [`ReconstructionError`] for error propagation
+    fn from(error: MaskAlignmentError<R>) -> Self {
+        ReconstructionError::MaskAlignmentError(error)
+    }
+}
+
+#[cfg(feature = "std")]
 impl<I: Index, R: Real> From<anyhow::Error> for ReconstructionError<I, R> {
     /// Wraps an `anyhow::Error` in a [`ReconstructionError`] for error propagation
     fn from(error: anyhow::Error) -> Self {
@@ -328,6 +608,7 @@ impl<I: Index, R: Real> From<anyhow::Error> for ReconstructionError<I, R> {
 /// Initialization of the global thread pool happens exactly once.
 /// Therefore, if you call `initialize_thread_pool` a second time, it will return an error.
 /// An `Ok` result indicates that this is the first initialization of the thread pool.
+#[cfg(feature = "std")]
 pub fn initialize_thread_pool(num_threads: usize) -> Result<(), anyhow::Error> {
     rayon::ThreadPoolBuilder::new()
         .num_threads(num_threads)
@@ -336,6 +617,7 @@ pub fn initialize_thread_pool(num_threads: usize) -> Result<(), anyhow::Error> {
 }
 
 /// Performs a marching cubes surface construction of the fluid represented by the given particle positions
+#[cfg(feature = "std")]
 #[inline(never)]
 pub fn reconstruct_surface<I: Index, R: Real>(
     particle_positions: &[Vector3<R>],
@@ -346,14 +628,82 @@ pub fn reconstruct_surface<I: Index, R: Real>(
     Ok(surface)
 }
 
+/// Performs a marching cubes surface construction of the fluid represented by the given particle positions and reports its progress
+///
+/// The callback is called with the current stage and the overall progress of the reconstruction in `[0, 1]`,
+/// which never decreases between consecutive calls, see the [`progress`] module for details. The callback may
+/// be called from different threads but never concurrently.
+#[cfg(feature = "std")]
+pub fn reconstruct_surface_with_progress<I: Index, R: Real, F>(
+    particle_positions: &[Vector3<R>],
+    parameters: &Parameters<R>,
+    mut on_progress: F,
+) -> Result<SurfaceReconstruction<I, R>, ReconstructionError<I, R>>
+where
+    F: FnMut(ReconstructionProgress) + Send,
+{
+    let reporter = ProgressReporter::new(&mut on_progress);
+    let mut surface = SurfaceReconstruction::default();
+    reconstruct_surface_inplace_with_progress(
+        particle_positions,
+        parameters,
+        &mut surface,
+        Some(&reporter),
+    )?;
+    reporter.report(ReconstructionStage::Finished, 1.0);
+    Ok(surface)
+}
+
 /// Performs a marching cubes surface construction of the fluid represented by the given particle positions, inplace
+#[cfg(feature = "std")]
 pub fn reconstruct_surface_inplace<'a, I: Index, R: Real>(
     particle_positions: &[Vector3<R>],
     parameters: &Parameters<R>,
     output_surface: &'a mut SurfaceReconstruction<I, R>,
 ) -> Result<(), ReconstructionError<I, R>> {
-    // Clear the existing mesh
+    reconstruct_surface_inplace_with_progress(particle_positions, parameters, output_surface, None)
+}
+
+/// Performs an inplace surface reconstruction, optionally reporting the progress of the individual stages
+#[cfg(feature = "std")]
+fn reconstruct_surface_inplace_with_progress<I: Index, R: Real>(
+    particle_positions: &[Vector3<R>],
+    parameters: &Parameters<R>,
+    output_surface: &mut SurfaceReconstruction<I, R>,
+    progress: Option<&ProgressReporter>,
+) -> Result<(), ReconstructionError<I, R>> {
+    // Clear the existing mesh and the statistics of previous reconstructions (including failed ones)
     output_surface.mesh.clear();
+    output_surface.workspace.take_case_histogram();
+    output_surface.case_histogram = Default::default();
+
+    validate_parameters(parameters)?;
+    progress::report(progress, ReconstructionStage::Initialization, 0.0);
+
+    // Periodic boundaries are handled by a reconstruction on an extended domain with ghost particles
+    let periodic_setup = if parameters.periodic_axes.any() {
+        let domain = periodic::validate_periodic_domain(
+            parameters.domain_aabb.as_ref(),
+            &parameters.periodic_axes,
+            parameters.cube_size,
+        )?;
+        Some(periodic::setup_periodic_reconstruction::<I, R>(
+            particle_positions,
+            &domain,
+            &parameters.periodic_axes,
+            parameters.compact_support_radius,
+            parameters.cube_size,
+        ))
+    } else {
+        None
+    };
+    let (particle_positions, domain_aabb) = match &periodic_setup {
+        Some(setup) => (
+            setup.particle_positions.as_slice(),
+            Some(&setup.extended_domain),
+        ),
+        None => (particle_positions, parameters.domain_aabb.as_ref()),
+    };
 
     // Initialize grid for the reconstruction
     output_surface.grid = grid_for_reconstruction(
@@ -361,37 +711,460 @@ pub fn reconstruct_surface_inplace<'a, I: Index, R: Real>(
         parameters.particle_radius,
         parameters.compact_support_radius,
         parameters.cube_size,
-        parameters.domain_aabb.as_ref(),
+        domain_aabb,
+        grid_margin_cells(parameters)?,
+        parameters.max_grid_cells,
         parameters.enable_multi_threading,
     )?;
 
     output_surface.grid.log_grid_info();
 
-    if parameters.spatial_decomposition.is_some() {
+    // Fail early if the mask does not fit to the grid
+    if let Some(mask) = &parameters.mask {
+        mask.aligned_to(&output_surface.grid)?;
+    }
+
+    if parameters.spatial_decomposition.is_some() && periodic_setup.is_none() {
         reconstruction::reconstruct_surface_domain_decomposition(
             particle_positions,
             parameters,
             output_surface,
+            progress,
         )?;
     } else {
-        reconstruction::reconstruct_surface_global(particle_positions, parameters, output_surface)?;
+        reconstruction::reconstruct_surface_global(
+            particle_positions,
+            parameters,
+            output_surface,
+            progress,
+        )?;
+    }
+
+    if let Some(setup) = &periodic_setup {
+        periodic::finalize_periodic_mesh(
+            &mut output_surface.mesh,
+            &setup.domain,
+            &parameters.periodic_axes,
+            parameters.cube_size,
+        )?;
+    }
+
+    output_surface.case_histogram = output_surface.workspace.take_case_histogram();
+
+    Ok(())
+}
+
+/// Checks the parameters for invalid values and combinations that are not caught by the individual reconstruction steps
+#[cfg(feature = "std")]
+fn validate_parameters<I: Index, R: Real>(
+    parameters: &Parameters<R>,
+) -> Result<(), ReconstructionError<I, R>> {
+    if let Some(contour_cell_factors) = &parameters.contour_cell_factors {
+        if contour_cell_factors.contains(&0) {
+            return Err(ReconstructionError::InvalidParameters(
+                "the marching cubes cell factors have to be larger than zero".to_string(),
+            ));
+        }
+        let anisotropic = contour_cell_factors != &[1, 1, 1];
+        if anisotropic
+            && (parameters.spatial_decomposition.is_some() || parameters.periodic_axes.any())
+        {
+            return Err(ReconstructionError::InvalidParameters(
+                "per-axis marching cubes cell factors are not supported with spatial decomposition or periodic boundaries"
+                    .to_string(),
+            ));
+        }
+    }
+
+    if let Some(anisotropy) = &parameters.anisotropy {
+        if !anisotropy.is_valid() {
+            return Err(ReconstructionError::InvalidParameters(
+                "the anisotropy smoothing weight has to be in [0, 1] and the maximum eigenvalue ratio at least one"
+                    .to_string(),
+            ));
+        }
+        if parameters.spatial_decomposition.is_some() || parameters.periodic_axes.any() {
+            return Err(ReconstructionError::InvalidParameters(
+                "anisotropic kernels are not supported with spatial decomposition or periodic boundaries"
+                    .to_string(),
+            ));
+        }
+    }
+
+    if let Some(RestDensityNormalization::Supplied(rest_density)) =
+        parameters.normalize_by_rest_density
+    {
+        if rest_density <= R::zero() {
+            return Err(ReconstructionError::InvalidParameters(
+                "the rest density used for normalization has to be positive".to_string(),
+            ));
+        }
     }
 
     Ok(())
 }
 
+/// Performs a marching cubes surface reconstruction with a quick coarse preview of the given particles
+///
+/// The preview is reconstructed first on a background grid with a cube size that is `preview_factor` times
+/// larger than the `cube_size` of the parameters and passed to the `on_preview` callback before the full
+/// resolution reconstruction starts, e.g. to show it in a user interface. The full resolution reconstruction
+/// is returned afterwards.
+///
+/// Both passes share the neighborhood search and the per-particle densities, which are computed only once.
+/// Compared to two separate reconstructions, this saves the most expensive step for the preview pass.
+/// Only the density map of the preview is evaluated separately, which requires about `preview_factor^3`
+/// times fewer kernel evaluations than the density map of the full resolution pass.
+///
+/// Note that the preview is only a rough approximation of the final surface: thin features and small
+/// droplets may be missing or merged, the surface may be offset by up to a coarse cell and its topology
+/// may differ from the final surface. A reconstruction mask and per-axis cell factors are ignored for
+/// the preview. Currently, only global reconstructions without spatial decomposition or periodic
+/// boundaries are supported.
+#[cfg(feature = "std")]
+pub fn reconstruct_surface_with_preview<I: Index, R: Real, F>(
+    particle_positions: &[Vector3<R>],
+    parameters: &Parameters<R>,
+    preview_factor: usize,
+    on_preview: F,
+) -> Result<SurfaceReconstruction<I, R>, ReconstructionError<I, R>>
+where
+    F: FnOnce(&SurfaceReconstruction<I, R>),
+{
+    profile!("reconstruct_surface_with_preview");
+
+    if preview_factor == 0 {
+        return Err(ReconstructionError::InvalidParameters(
+            "the preview factor has to be larger than zero".to_string(),
+        ));
+    }
+    if parameters.spatial_decomposition.is_some() || parameters.periodic_axes.any() {
+        return Err(ReconstructionError::InvalidParameters(
+            "the reconstruction with preview does not support spatial decomposition or periodic boundaries"
+                .to_string(),
+        ));
+    }
+    validate_parameters(parameters)?;
+
+    let preview_parameters = Parameters {
+        cube_size: parameters.cube_size * R::from_usize(preview_factor).unwrap(),
+        mask: None,
+        contour_cell_factors: None,
+        ..parameters.clone()
+    };
+
+    let mut surface = SurfaceReconstruction {
+        grid: grid_for_reconstruction(
+            particle_positions,
+            parameters.particle_radius,
+            parameters.compact_support_radius,
+            parameters.cube_size,
+            parameters.domain_aabb.as_ref(),
+            grid_margin_cells(parameters)?,
+            parameters.max_grid_cells,
+            parameters.enable_multi_threading,
+        )?,
+        ..SurfaceReconstruction::default()
+    };
+    let mut preview = SurfaceReconstruction {
+        grid: grid_for_reconstruction(
+            particle_positions,
+            preview_parameters.particle_radius,
+            preview_parameters.compact_support_radius,
+            preview_parameters.cube_size,
+            preview_parameters.domain_aabb.as_ref(),
+            grid_margin_cells(&preview_parameters)?,
+            preview_parameters.max_grid_cells,
+            preview_parameters.enable_multi_threading,
+        )?,
+        ..SurfaceReconstruction::default()
+    };
+
+    surface.grid.log_grid_info();
+
+    // Fail early if the mask does not fit to the grid
+    if let Some(mask) = &parameters.mask {
+        mask.aligned_to(&surface.grid)?;
+    }
+
+    reconstruction::reconstruct_surface_global_with_preview(
+        particle_positions,
+        parameters,
+        &preview_parameters,
+        &mut preview,
+        &mut surface,
+        on_preview,
+    )?;
+
+    Ok(surface)
+}
+
+/// Performs a marching cubes surface reconstruction of the union of several particle groups with individual kernels
+///
+/// In contrast to concatenating the particles of all groups (which forces a single kernel for all particles),
+/// the density map of each group is evaluated with the compact support radius and particle mass of the group.
+/// The per-particle densities are computed only from the particles of the same group. All density maps are
+/// accumulated on the same background grid before the combined field is contoured once, which results in a
+/// smooth surface where the groups overlap, e.g. for fluids with different particle resolutions.
+///
+/// The per-group values replace the `particle_radius`, `rest_density` and `compact_support_radius` of the
+/// parameters. If no `domain_aabb` is given, the domain encloses all particles with the kernel margin of
+/// their group. Currently, only global reconstructions without spatial decomposition, periodic boundaries,
+/// per-axis marching cubes cell factors or anisotropic kernels are supported.
+#[cfg(feature = "std")]
+pub fn reconstruct_multi_group<I: Index, R: Real>(
+    groups: &[ParticleGroup<R>],
+    parameters: &Parameters<R>,
+) -> Result<SurfaceReconstruction<I, R>, ReconstructionError<I, R>> {
+    profile!("reconstruct_multi_group");
+
+    if parameters.spatial_decomposition.is_some()
+        || parameters.periodic_axes.any()
+        || parameters
+            .contour_cell_factors
+            .is_some_and(|factors| factors != [1, 1, 1])
+        || parameters.anisotropy.is_some()
+    {
+        return Err(ReconstructionError::InvalidParameters(
+            "the reconstruction of multiple particle groups does not support spatial decomposition, periodic boundaries, per-axis marching cubes cell factors or anisotropic kernels"
+                .to_string(),
+        ));
+    }
+
+    if groups
+        .iter()
+        .any(|group| group.compact_support_radius <= R::zero() || group.particle_mass <= R::zero())
+    {
+        return Err(ReconstructionError::InvalidParameters(
+            "the compact support radius and particle mass of all particle groups have to be positive"
+                .to_string(),
+        ));
+    }
+
+    if let Some(RestDensityNormalization::Supplied(rest_density)) =
+        parameters.normalize_by_rest_density
+    {
+        if rest_density <= R::zero() {
+            return Err(ReconstructionError::InvalidParameters(
+                "the rest density used for normalization has to be positive".to_string(),
+            ));
+        }
+    }
+
+    let domain_aabb = if let Some(domain_aabb) = &parameters.domain_aabb {
+        domain_aabb.clone()
+    } else {
+        let mut domain_aabb: Option<AxisAlignedBoundingBox3d<R>> = None;
+        for group in groups.iter().filter(|group| !group.positions.is_empty()) {
+            let mut aabb = if parameters.enable_multi_threading {
+                AxisAlignedBoundingBox3d::par_from_points(group.positions)
+            } else {
+                AxisAlignedBoundingBox3d::from_points(group.positions)
+            };
+
+            // Ensure that the kernel support of every particle of the group is completely in the domain
+            let kernel_margin = density_map::compute_kernel_evaluation_radius::<I, R>(
+                group.compact_support_radius,
+                parameters.cube_size,
+            )
+            .kernel_evaluation_radius;
+            aabb.grow_uniformly(kernel_margin);
+
+            match domain_aabb.as_mut() {
+                Some(domain_aabb) => domain_aabb.join(&aabb),
+                None => domain_aabb = Some(aabb),
+            }
+        }
+
+        let mut domain_aabb = domain_aabb.ok_or_else(|| {
+            ReconstructionError::InvalidParameters(
+                "at least one particle is required to determine the domain of the reconstruction"
+                    .to_string(),
+            )
+        })?;
+        domain_aabb.grow_uniformly(
+            parameters.cube_size * R::from_usize(parameters.grid_margin_cells).unwrap(),
+        );
+        domain_aabb
+    };
+
+    let mut surface = SurfaceReconstruction {
+        grid: grid_for_reconstruction(
+            &[],
+            parameters.particle_radius,
+            parameters.compact_support_radius,
+            parameters.cube_size,
+            Some(&domain_aabb),
+            I::zero(),
+            parameters.max_grid_cells,
+            parameters.enable_multi_threading,
+        )?,
+        ..SurfaceReconstruction::default()
+    };
+
+    surface.grid.log_grid_info();
+
+    reconstruction::reconstruct_multi_group_global(groups, parameters, &mut surface)?;
+
+    Ok(surface)
+}
+
+/// Performs a marching cubes surface reconstruction of particles with individual smoothing lengths
+///
+/// The smoothing length of a particle is the compact support radius of its kernel, it replaces the
+/// `compact_support_radius` of the parameters for this particle. The mass of a particle is scaled with the cube of
+/// the ratio of its smoothing length to the `compact_support_radius` of the parameters, i.e. the `particle_radius`
+/// of the parameters refers to a particle with the `compact_support_radius` of the parameters. The per-particle
+/// densities are computed with the mean smoothing length of each pair of particles and every particle contributes
+/// to the density map with its own smoothing length. This results in surfaces that closely follow simulations with
+/// adaptive particle sizes without over-smoothing the fine regions or producing holes in the coarse regions.
+///
+/// If no smoothing lengths are given, this is equivalent to [`reconstruct_surface`]. Currently, only global
+/// reconstructions without spatial decomposition, periodic boundaries, per-axis marching cubes cell factors or
+/// anisotropic kernels are supported.
+#[cfg(feature = "std")]
+pub fn reconstruct_surface_with_smoothing_lengths<I: Index, R: Real>(
+    particle_positions: &[Vector3<R>],
+    particle_smoothing_lengths: Option<&[R]>,
+    parameters: &Parameters<R>,
+) -> Result<SurfaceReconstruction<I, R>, ReconstructionError<I, R>> {
+    profile!("reconstruct_surface_with_smoothing_lengths");
+
+    let particle_smoothing_lengths = match particle_smoothing_lengths {
+        Some(particle_smoothing_lengths) => particle_smoothing_lengths,
+        None => return reconstruct_surface(particle_positions, parameters),
+    };
+
+    if parameters.spatial_decomposition.is_some()
+        || parameters.periodic_axes.any()
+        || parameters
+            .contour_cell_factors
+            .is_some_and(|factors| factors != [1, 1, 1])
+        || parameters.anisotropy.is_some()
+    {
+        return Err(ReconstructionError::InvalidParameters(
+            "the reconstruction with per-particle smoothing lengths does not support spatial decomposition, periodic boundaries, per-axis marching cubes cell factors or anisotropic kernels"
+                .to_string(),
+        ));
+    }
+    validate_parameters(parameters)?;
+
+    validate_particle_inputs(
+        particle_positions,
+        &[(
+            "particle_smoothing_lengths",
+            Some(particle_smoothing_lengths.len()),
+        )],
+    )
+    .map_err(DensityMapError::<R>::from)?;
+
+    if particle_smoothing_lengths.iter().any(|&h| h <= R::zero()) {
+        return Err(ReconstructionError::InvalidParameters(
+            "the smoothing lengths of all particles have to be positive".to_string(),
+        ));
+    }
+
+    // The background grid has to contain the kernel support of the largest particles
+    let max_smoothing_length = particle_smoothing_lengths
+        .iter()
+        .copied()
+        .fold(parameters.compact_support_radius, |max, h| max.max(h));
+    let max_particle_radius =
+        parameters.particle_radius * max_smoothing_length / parameters.compact_support_radius;
+
+    let mut surface = SurfaceReconstruction {
+        grid: grid_for_reconstruction(
+            particle_positions,
+            max_particle_radius,
+            max_smoothing_length,
+            parameters.cube_size,
+            parameters.domain_aabb.as_ref(),
+            grid_margin_cells(parameters)?,
+            parameters.max_grid_cells,
+            parameters.enable_multi_threading,
+        )?,
+        ..SurfaceReconstruction::default()
+    };
+
+    surface.grid.log_grid_info();
+
+    // Fail early if the mask does not fit to the grid
+    if let Some(mask) = &parameters.mask {
+        mask.aligned_to(&surface.grid)?;
+    }
+
+    reconstruction::reconstruct_surface_variable_support_global(
+        particle_positions,
+        particle_smoothing_lengths,
+        parameters,
+        &mut surface,
+    )?;
+
+    Ok(surface)
+}
+
+/// Returns the [`Parameters::grid_margin_cells`] converted to the index type
+#[cfg(feature = "std")]
+fn grid_margin_cells<I: Index, R: Real>(
+    parameters: &Parameters<R>,
+) -> Result<I, ReconstructionError<I, R>> {
+    I::from_usize(parameters.grid_margin_cells).ok_or_else(|| {
+        ReconstructionError::InvalidParameters(
+            "the grid margin in cells cannot be represented by the index type".to_string(),
+        )
+    })
+}
+
 /// Constructs the background grid for marching cubes based on the parameters supplied to the surface reconstruction
+///
+/// If no domain is given, the domain is the AABB of the particles grown by the particle radius and the kernel
+/// evaluation radius (both in world units, i.e. the support of every particle is contained in the domain) and
+/// additionally padded by `margin_cells` empty marching cubes cells of edge length `cube_size` on every side.
+/// A given domain is used as is and `margin_cells` is ignored.
+///
+/// If a maximum number of grid cells is given, an error is returned if the grid would have more cells.
+#[cfg(feature = "std")]
 pub fn grid_for_reconstruction<I: Index, R: Real>(
     particle_positions: &[Vector3<R>],
     particle_radius: R,
     compact_support_radius: R,
     cube_size: R,
     domain_aabb: Option<&AxisAlignedBoundingBox3d<R>>,
+    margin_cells: I,
+    max_grid_cells: Option<u128>,
     enable_multi_threading: bool,
 ) -> Result<UniformGrid<I, R>, ReconstructionError<I, R>> {
-    let domain_aabb = if let Some(domain_aabb) = domain_aabb {
-        domain_aabb.clone()
+    if let Some(domain_aabb) = domain_aabb {
+        grid_for_domain(domain_aabb, cube_size, max_grid_cells)
     } else {
+        grid_for_reconstruction_anisotropic(
+            particle_positions,
+            particle_radius,
+            compact_support_radius,
+            cube_size,
+            [margin_cells; 3],
+            max_grid_cells,
+            enable_multi_threading,
+        )
+    }
+}
+
+/// Constructs the background grid for marching cubes like [`grid_for_reconstruction`] but with a separate number of margin cells per axis
+///
+/// This allows to avoid wasting cells in thin dimensions of the fluid (e.g. sheets of fluid). The margin required for
+/// the kernel evaluation is still added uniformly on every side. As the margin only applies to the domain computed
+/// from the particles, there is no option to specify the domain, use [`grid_for_reconstruction`] instead.
+#[cfg(feature = "std")]
+pub fn grid_for_reconstruction_anisotropic<I: Index, R: Real>(
+    particle_positions: &[Vector3<R>],
+    particle_radius: R,
+    compact_support_radius: R,
+    cube_size: R,
+    margin_cells: [I; 3],
+    max_grid_cells: Option<u128>,
+    enable_multi_threading: bool,
+) -> Result<UniformGrid<I, R>, ReconstructionError<I, R>> {
+    let domain_aabb = {
         profile!("compute minimum enclosing aabb");
 
         let mut domain_aabb = {
@@ -417,8 +1190,41 @@ pub fn grid_for_reconstruction<I: Index, R: Real>(
         .kernel_evaluation_radius;
         domain_aabb.grow_uniformly(kernel_margin);
 
+        // Pad the domain by the requested number of empty cells
+        let mut margin = Vector3::<R>::zeros();
+        for (margin, margin_cells) in margin.iter_mut().zip(margin_cells.iter()) {
+            *margin = cube_size
+                * margin_cells.to_real::<R>().ok_or_else(|| {
+                    ReconstructionError::InvalidParameters(
+                        "the grid margin in cells cannot be represented by the real type"
+                            .to_string(),
+                    )
+                })?;
+        }
+        domain_aabb.grow(&margin);
+
         domain_aabb
     };
 
-    Ok(UniformGrid::from_aabb(&domain_aabb, cube_size)?)
+    grid_for_domain(&domain_aabb, cube_size, max_grid_cells)
+}
+
+/// Constructs the background grid for the given domain, returns an error if the grid would have more than `max_grid_cells` cells
+#[cfg(feature = "std")]
+fn grid_for_domain<I: Index, R: Real>(
+    domain_aabb: &AxisAlignedBoundingBox3d<R>,
+    cube_size: R,
+    max_grid_cells: Option<u128>,
+) -> Result<UniformGrid<I, R>, ReconstructionError<I, R>> {
+    if let Some(max_grid_cells) = max_grid_cells {
+        let grid_cells = uniform_grid::grid_cell_count_for(domain_aabb, cube_size);
+        if grid_cells > max_grid_cells {
+            return Err(ReconstructionError::GridCellLimitExceeded(
+                grid_cells,
+                max_grid_cells,
+            ));
+        }
+    }
+
+    Ok(UniformGrid::from_aabb(domain_aabb, cube_size)?)
 }
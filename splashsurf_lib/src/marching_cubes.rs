@@ -1,5 +1,6 @@
 //! Triangulation of [`DensityMap`](crate::density_map::DensityMap)s using marching cubes
 
+use crate::marching_cubes::marching_cubes_lut::flags_to_index;
 use crate::marching_cubes::narrow_band_extraction::{
     construct_mc_input, construct_mc_input_with_stitching_data,
 };
@@ -10,6 +11,7 @@ use crate::mesh::TriMesh3d;
 use crate::uniform_grid::{DummySubdomain, OwningSubdomainGrid, Subdomain};
 use crate::{new_map, profile, DensityMap, Index, MapType, Real, UniformGrid};
 use nalgebra::Vector3;
+use std::fmt;
 use thiserror::Error as ThisError;
 
 pub mod marching_cubes_lut;
@@ -34,6 +36,47 @@ impl From<TriangulationError> for MarchingCubesError {
     }
 }
 
+/// Method used to place the iso-surface vertex on a grid edge crossing the iso-surface
+///
+/// The methods differ in accuracy and cost per vertex:
+///  - [`Midpoint`](EdgeInterpolation::Midpoint) is the cheapest method but places vertices with an error of up to
+///    half a cell size, resulting in the typical "blocky" look of the surface.
+///  - [`Linear`](EdgeInterpolation::Linear) requires one division per vertex and is exact for density fields that
+///    vary linearly along the edge. Its error is second order in the cell size.
+///  - [`Cubic`](EdgeInterpolation::Cubic) fits a Catmull-Rom spline through the density values of the two edge points
+///    and the two grid points preceding and following the edge, and places the vertex on the root of this spline.
+///    This requires two additional density map lookups and a few Newton iterations per vertex, but reduces the error
+///    for curved density profiles, which is especially noticeable on coarse grids. The spline reproduces linear
+///    fields exactly. If one of the outer values is not available (e.g. on the boundary of the grid or subdomain),
+///    linear interpolation is used for this edge instead.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum EdgeInterpolation {
+    /// Place the vertex in the middle of the edge (no interpolation)
+    Midpoint,
+    /// Linear interpolation of the density values of the two edge points
+    #[default]
+    Linear,
+    /// Root of a cubic (Catmull-Rom) interpolation of the density values of the edge and the two neighboring points along the edge
+    Cubic,
+}
+
+/// Returns the point where the iso-surface crosses the edge from `p0` to `p1` using linear interpolation of the density values `d0` and `d1`
+///
+/// If both density values are equal, there is no unique crossing on the edge and the midpoint of the edge is returned.
+pub fn iso_crossing<R: Real>(p0: Vector3<R>, d0: R, p1: Vector3<R>, d1: R, iso: R) -> Vector3<R> {
+    let alpha = linear_iso_crossing_parameter(d0, d1, iso);
+    p0 * (R::one() - alpha) + p1 * alpha
+}
+
+/// Returns the parameter `t` of the linearly interpolated iso-surface crossing `(1 - t) * p0 + t * p1` on an edge, see [`iso_crossing`]
+pub(crate) fn linear_iso_crossing_parameter<R: Real>(d0: R, d1: R, iso: R) -> R {
+    if d0 == d1 {
+        R::one() / (R::one() + R::one())
+    } else {
+        (iso - d0) / (d1 - d0)
+    }
+}
+
 /// Input data required by the marching cubes triangulation
 #[derive(Clone, Debug)]
 pub(crate) struct MarchingCubesInput<I: Index> {
@@ -134,6 +177,25 @@ pub fn triangulate_density_map_append<I: Index, R: Real>(
     density_map: &DensityMap<I, R>,
     iso_surface_threshold: R,
     mesh: &mut TriMesh3d<R>,
+) -> Result<(), MarchingCubesError> {
+    triangulate_density_map_with_interpolation_append(
+        grid,
+        subdomain,
+        density_map,
+        iso_surface_threshold,
+        EdgeInterpolation::Linear,
+        mesh,
+    )
+}
+
+/// Performs a marching cubes triangulation of a density map using the given edge interpolation, appends triangles to the given mesh
+pub fn triangulate_density_map_with_interpolation_append<I: Index, R: Real>(
+    grid: &UniformGrid<I, R>,
+    subdomain: Option<&OwningSubdomainGrid<I, R>>,
+    density_map: &DensityMap<I, R>,
+    iso_surface_threshold: R,
+    edge_interpolation: EdgeInterpolation,
+    mesh: &mut TriMesh3d<R>,
 ) -> Result<(), MarchingCubesError> {
     profile!("triangulate_density_map_append");
 
@@ -142,6 +204,7 @@ pub fn triangulate_density_map_append<I: Index, R: Real>(
             subdomain,
             &density_map,
             iso_surface_threshold,
+            edge_interpolation,
             &mut mesh.vertices,
         )
     } else {
@@ -150,6 +213,7 @@ pub fn triangulate_density_map_append<I: Index, R: Real>(
             &subdomain,
             &density_map,
             iso_surface_threshold,
+            edge_interpolation,
             &mut mesh.vertices,
         )
     };
@@ -158,11 +222,113 @@ pub fn triangulate_density_map_append<I: Index, R: Real>(
     Ok(())
 }
 
+/// Performs a marching cubes triangulation of a density map on a coarser grid with independent cell sizes per axis, appends triangles to the given mesh
+///
+/// The marching cubes cells have an edge length of `cell_factors[d]` times the cell size of the density
+/// map's grid along axis `d`, i.e. the contouring grid is the sub-lattice of every `cell_factors[d]`-th
+/// point of the density grid along each axis. Only density values at points of this sub-lattice are used.
+/// If the number of cells of the density grid along an axis is not divisible by the factor, the cells beyond
+/// the last full contouring cell are not triangulated. The iso-surface vertices are interpolated along the
+/// edges of the contouring cells, taking the per-axis edge lengths into account.
+///
+/// Panics if any of the factors is zero.
+pub fn triangulate_density_map_anisotropic_append<I: Index, R: Real>(
+    grid: &UniformGrid<I, R>,
+    density_map: &DensityMap<I, R>,
+    iso_surface_threshold: R,
+    edge_interpolation: EdgeInterpolation,
+    cell_factors: [usize; 3],
+    mesh: &mut TriMesh3d<R>,
+) -> Result<(), MarchingCubesError> {
+    profile!("triangulate_density_map_anisotropic_append");
+    assert!(
+        cell_factors.iter().all(|&f| f > 0),
+        "marching cubes cell factors have to be larger than zero"
+    );
+
+    if cell_factors == [1, 1, 1] {
+        return triangulate_density_map_with_interpolation_append(
+            grid,
+            None,
+            density_map,
+            iso_surface_threshold,
+            edge_interpolation,
+            mesh,
+        );
+    }
+
+    let factors = [
+        I::from_usize(cell_factors[0]).unwrap_or_else(I::max_value),
+        I::from_usize(cell_factors[1]).unwrap_or_else(I::max_value),
+        I::from_usize(cell_factors[2]).unwrap_or_else(I::max_value),
+    ];
+    let n_cells = grid.cells_per_dim();
+    let contour_n_cells = [
+        n_cells[0] / factors[0],
+        n_cells[1] / factors[1],
+        n_cells[2] / factors[2],
+    ];
+    if contour_n_cells.contains(&I::zero()) {
+        return Ok(());
+    }
+
+    // The contouring is performed on a unit grid in index space of the sub-lattice, afterwards the vertices
+    // are mapped to world space. As this mapping is an axis-aligned scaling, the interpolation along
+    // the cell edges is preserved.
+    let contour_grid = UniformGrid::new(&Vector3::zeros(), &contour_n_cells, R::one())
+        .expect("contouring grid has to be representable if the density grid is");
+
+    let mut contour_density_map: MapType<I, R> = new_map();
+    density_map.for_each(|flat_point_index, density| {
+        if let Some(point) = grid.try_unflatten_point_index(flat_point_index) {
+            let ijk = point.index();
+            let on_sub_lattice = (0..3).all(|dim| ijk[dim] % factors[dim] == I::zero());
+            if on_sub_lattice {
+                let contour_ijk = [
+                    ijk[0] / factors[0],
+                    ijk[1] / factors[1],
+                    ijk[2] / factors[2],
+                ];
+                if contour_grid.point_exists(&contour_ijk) {
+                    contour_density_map.insert(
+                        contour_grid.flatten_point_index_array(&contour_ijk),
+                        density,
+                    );
+                }
+            }
+        }
+    });
+
+    let mut contour_mesh = TriMesh3d::default();
+    triangulate_density_map_with_interpolation_append(
+        &contour_grid,
+        None,
+        &contour_density_map.into(),
+        iso_surface_threshold,
+        edge_interpolation,
+        &mut contour_mesh,
+    )?;
+
+    let origin = grid.aabb().min();
+    let cell_sizes = Vector3::new(
+        grid.cell_size() * R::from_usize(cell_factors[0]).unwrap(),
+        grid.cell_size() * R::from_usize(cell_factors[1]).unwrap(),
+        grid.cell_size() * R::from_usize(cell_factors[2]).unwrap(),
+    );
+    for vertex in contour_mesh.vertices.iter_mut() {
+        *vertex = origin + vertex.component_mul(&cell_sizes);
+    }
+
+    mesh.append(&mut contour_mesh);
+    Ok(())
+}
+
 /// Performs triangulation of the given density map to a surface patch
 pub(crate) fn triangulate_density_map_to_surface_patch<I: Index, R: Real>(
     subdomain: &OwningSubdomainGrid<I, R>,
     density_map: &DensityMap<I, R>,
     iso_surface_threshold: R,
+    edge_interpolation: EdgeInterpolation,
 ) -> Result<SurfacePatch<I, R>, MarchingCubesError> {
     profile!("triangulate_density_map_append");
 
@@ -178,6 +344,7 @@ pub(crate) fn triangulate_density_map_to_surface_patch<I: Index, R: Real>(
         &subdomain,
         &density_map,
         iso_surface_threshold,
+        edge_interpolation,
         &mut mesh.vertices,
     );
 
@@ -197,6 +364,115 @@ pub(crate) fn triangulate_density_map_to_surface_patch<I: Index, R: Real>(
     })
 }
 
+/// Number of distinct marching cubes cases (one per vertex configuration of a cell)
+pub const MARCHING_CUBES_CASE_COUNT: usize = 256;
+
+/// Histogram of marching cubes cases (lookup table indices), i.e. the number of cells per case
+#[derive(Clone, Eq, PartialEq)]
+pub struct MarchingCubesCaseHistogram {
+    counts: [usize; MARCHING_CUBES_CASE_COUNT],
+}
+
+impl Default for MarchingCubesCaseHistogram {
+    fn default() -> Self {
+        Self {
+            counts: [0; MARCHING_CUBES_CASE_COUNT],
+        }
+    }
+}
+
+impl MarchingCubesCaseHistogram {
+    /// Returns the number of cells per marching cubes case, indexed by the case
+    pub fn counts(&self) -> &[usize; MARCHING_CUBES_CASE_COUNT] {
+        &self.counts
+    }
+
+    /// Returns the number of cells with the given case
+    pub fn count(&self, case: u8) -> usize {
+        self.counts[case as usize]
+    }
+
+    /// Returns the total number of cells in the histogram
+    pub fn total(&self) -> usize {
+        self.counts.iter().sum()
+    }
+
+    /// Returns an iterator over all `(case, count)` pairs with a non-zero count
+    pub fn non_empty_cases(&self) -> impl Iterator<Item = (u8, usize)> + '_ {
+        self.counts
+            .iter()
+            .enumerate()
+            .filter(|(_, &count)| count > 0)
+            .map(|(case, &count)| (case as u8, count))
+    }
+
+    /// Increments the count of the given case by one
+    pub(crate) fn increment(&mut self, case: u8) {
+        self.counts[case as usize] += 1;
+    }
+
+    /// Adds all counts of the other histogram to this histogram
+    pub(crate) fn merge(&mut self, other: &Self) {
+        for (count, &other_count) in self.counts.iter_mut().zip(other.counts.iter()) {
+            *count += other_count;
+        }
+    }
+}
+
+impl fmt::Debug for MarchingCubesCaseHistogram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.non_empty_cases()).finish()
+    }
+}
+
+/// Counts the cells of the background grid per marching cubes case, e.g. for debugging of reconstructions
+///
+/// The case of a cell is the index into the marching cubes lookup table, i.e. bit `i` is set if the local corner `i`
+/// of the cell is above the iso-surface threshold. Case `0` are cells completely outside and case `255` cells
+/// completely inside of the fluid. Points without a value in the density map are below the threshold. In contrast
+/// to the histogram of the [`stats`](crate::stats) feature, which only counts the cells triangulated during a
+/// reconstruction, every cell of the grid is classified, including the cells without triangles.
+pub fn marching_cubes_case_counts<I: Index, R: Real>(
+    grid: &UniformGrid<I, R>,
+    density_map: &DensityMap<I, R>,
+    iso_surface_threshold: R,
+) -> MarchingCubesCaseHistogram {
+    profile!("marching_cubes_case_counts");
+
+    // Only cells with at least one corner above the threshold have a case other than zero
+    let mut cell_corners_above: MapType<I, [bool; 8]> = new_map();
+    density_map.for_each(|flat_point_index, point_value| {
+        if point_value <= iso_surface_threshold {
+            return;
+        }
+
+        let point = match grid.try_unflatten_point_index(flat_point_index) {
+            Some(point) => point,
+            None => return,
+        };
+        let neighborhood = grid.get_point_neighborhood(&point);
+        for cell in grid.cells_adjacent_to_point(&neighborhood).iter().flatten() {
+            let local_point_index = cell.local_point_index_of(point.index()).unwrap();
+            cell_corners_above
+                .entry(grid.flatten_cell_index(cell))
+                .or_insert([false; 8])[local_point_index] = true;
+        }
+    });
+
+    let mut case_counts = MarchingCubesCaseHistogram::default();
+    for corners_above in cell_corners_above.values() {
+        case_counts.increment(flags_to_index(corners_above) as u8);
+    }
+
+    // All remaining cells are completely below the threshold
+    let num_cells = grid.cells_per_dim().iter().fold(1usize, |num_cells, &n| {
+        num_cells.saturating_mul(n.to_usize().unwrap_or(usize::MAX))
+    });
+    case_counts.counts[0] = num_cells.saturating_sub(cell_corners_above.len());
+
+    case_counts
+}
+
 /// Checks the consistency of the mesh (currently only checks for holes) and returns a string with debug information in case of problems
 pub fn check_mesh_consistency<I: Index, R: Real>(
     grid: &UniformGrid<I, R>,
@@ -327,6 +603,28 @@ fn assert_cell_data_point_data_consistency<I: Index, R: Real>(
     }
 }
 
+#[test]
+fn test_iso_crossing() {
+    let p0 = Vector3::new(1.0, 2.0, 3.0);
+    let p1 = Vector3::new(3.0, 2.0, 3.0);
+
+    assert_eq!(
+        iso_crossing(p0, 0.0, p1, 1.0, 0.25),
+        Vector3::new(1.5, 2.0, 3.0)
+    );
+    assert_eq!(
+        iso_crossing(p0, 1.0, p1, 0.0, 0.25),
+        Vector3::new(2.5, 2.0, 3.0)
+    );
+    assert_eq!(iso_crossing(p0, 0.2, p1, 0.6, 0.2), p0);
+    assert_eq!(iso_crossing(p0, 0.2, p1, 0.6, 0.6), p1);
+
+    // Equal density values have no unique crossing, the midpoint is used instead of dividing by zero
+    let midpoint = iso_crossing(p0, 0.5, p1, 0.5, 0.5);
+    assert!(midpoint.iter().all(|c: &f64| c.is_finite()));
+    assert_eq!(midpoint, Vector3::new(2.0, 2.0, 3.0));
+}
+
 #[test]
 fn test_interpolate_cell_data() {
     use nalgebra::Vector3;
@@ -350,6 +648,7 @@ fn test_interpolate_cell_data() {
             &subdomain,
             &sparse_data.clone().into(),
             iso_surface_threshold,
+            EdgeInterpolation::Linear,
             &mut trimesh.vertices,
         )
     };
@@ -378,6 +677,7 @@ fn test_interpolate_cell_data() {
             &subdomain,
             &sparse_data.clone().into(),
             iso_surface_threshold,
+            EdgeInterpolation::Linear,
             &mut trimesh.vertices,
         )
     };
@@ -409,3 +709,119 @@ fn test_interpolate_cell_data() {
     let _mesh = triangulate(marching_cubes_data, &mut trimesh);
     //println!("{:?}", mesh)
 }
+
+#[cfg(test)]
+fn contour_sampled_field<F: Fn(&Vector3<f64>) -> f64>(
+    n_cubes_per_dim: i32,
+    field: F,
+    iso_surface_threshold: f64,
+    edge_interpolation: EdgeInterpolation,
+) -> Vec<Vector3<f64>> {
+    let grid = UniformGrid::<i32, f64>::new(
+        &Vector3::zeros(),
+        &[n_cubes_per_dim, n_cubes_per_dim, n_cubes_per_dim],
+        1.0,
+    )
+    .unwrap();
+
+    let mut density_map = new_map();
+    for i in 0..=n_cubes_per_dim {
+        for j in 0..=n_cubes_per_dim {
+            for k in 0..=n_cubes_per_dim {
+                let ijk = [i, j, k];
+                density_map.insert(
+                    grid.flatten_point_index_array(&ijk),
+                    field(&grid.point_coordinates_array(&ijk)),
+                );
+            }
+        }
+    }
+
+    let mut vertices = Vec::new();
+    let subdomain = DummySubdomain::new(&grid);
+    let _ = construct_mc_input(
+        &subdomain,
+        &density_map.into(),
+        iso_surface_threshold,
+        edge_interpolation,
+        &mut vertices,
+    );
+    vertices
+}
+
+#[test]
+fn test_linear_interpolation_reproduces_linear_field() {
+    let field = |x: &Vector3<f64>| x.x + 0.5 * x.y + 0.25 * x.z;
+    let iso_surface_threshold = 2.3;
+
+    for &edge_interpolation in &[EdgeInterpolation::Linear, EdgeInterpolation::Cubic] {
+        let vertices = contour_sampled_field(4, field, iso_surface_threshold, edge_interpolation);
+        assert!(!vertices.is_empty());
+        for v in vertices.iter() {
+            assert!((field(v) - iso_surface_threshold).abs() < 1e-10);
+        }
+    }
+
+    // Midpoint vertices are placed in the middle of the crossing edges
+    let vertices =
+        contour_sampled_field(4, field, iso_surface_threshold, EdgeInterpolation::Midpoint);
+    assert!(!vertices.is_empty());
+    for v in vertices.iter() {
+        let num_half = v.iter().filter(|c| (c.fract() - 0.5).abs() < 1e-12).count();
+        let num_integer = v.iter().filter(|c| c.fract().abs() < 1e-12).count();
+        assert_eq!((num_half, num_integer), (1, 2));
+    }
+}
+
+#[test]
+fn test_cubic_interpolation_reproduces_quadratic_field() {
+    let center = Vector3::repeat(4.0);
+    let field = |x: &Vector3<f64>| (x - center).norm_squared();
+    let iso_surface_threshold = 2.6 * 2.6;
+
+    let cubic = contour_sampled_field(8, field, iso_surface_threshold, EdgeInterpolation::Cubic);
+    assert!(!cubic.is_empty());
+    for v in cubic.iter() {
+        assert!((field(v) - iso_surface_threshold).abs() < 1e-9);
+    }
+
+    let linear = contour_sampled_field(8, field, iso_surface_threshold, EdgeInterpolation::Linear);
+    assert_eq!(linear.len(), cubic.len());
+    let max_linear_error = linear
+        .iter()
+        .map(|v| (field(v) - iso_surface_threshold).abs())
+        .fold(0.0, f64::max);
+    assert!(max_linear_error > 0.01);
+}
+
+#[test]
+fn test_marching_cubes_case_counts() {
+    let grid = UniformGrid::<i64, f64>::new(&Vector3::zeros(), &[4, 4, 4], 1.0).unwrap();
+    let num_points = 5 * 5 * 5;
+
+    // All points inside of the fluid: only cells completely inside and no surface
+    let mut inside_map = new_map();
+    for flat_point_index in 0..num_points {
+        inside_map.insert(flat_point_index, 1.0);
+    }
+    let inside_map = DensityMap::from(inside_map);
+
+    let case_counts = marching_cubes_case_counts(&grid, &inside_map, 0.5);
+    assert_eq!(case_counts.count(255), 64);
+    assert_eq!(case_counts.total(), 64);
+    let mesh = triangulate_density_map(&grid, &inside_map, 0.5).unwrap();
+    assert!(mesh.triangles.is_empty());
+
+    // A single interior point above the threshold: each adjacent cell has exactly one corner inside
+    let mut point_map = new_map();
+    let point = grid.get_point([2, 2, 2]).unwrap();
+    point_map.insert(grid.flatten_point_index(&point), 1.0);
+    let point_map = DensityMap::from(point_map);
+
+    let case_counts = marching_cubes_case_counts(&grid, &point_map, 0.5);
+    assert_eq!(case_counts.count(0), 64 - 8);
+    for local_point in 0..8 {
+        assert_eq!(case_counts.count(1 << local_point), 1);
+    }
+    assert_eq!(case_counts.total(), 64);
+}
@@ -1,5 +1,8 @@
 use crate::marching_cubes::stitching::{collect_boundary_cell_data, BoundaryData};
-use crate::marching_cubes::{CellData, MarchingCubesInput, RelativeToThreshold};
+use crate::marching_cubes::{
+    linear_iso_crossing_parameter, CellData, EdgeInterpolation, MarchingCubesInput,
+    RelativeToThreshold,
+};
 use crate::topology::{Axis, DirectedAxisArray};
 use crate::uniform_grid::{CellIndex, GridBoundaryFaceFlags, PointIndex, Subdomain};
 use crate::{profile, DensityMap, Index, MapType, Real};
@@ -48,6 +51,7 @@ pub(crate) fn construct_mc_input<I: Index, R: Real, S: Subdomain<I, R>>(
     subdomain: &S,
     density_map: &DensityMap<I, R>,
     iso_surface_threshold: R,
+    edge_interpolation: EdgeInterpolation,
     vertices: &mut Vec<Vector3<R>>,
 ) -> MarchingCubesInput<I> {
     let mut marching_cubes_data = MarchingCubesInput::default();
@@ -55,6 +59,7 @@ pub(crate) fn construct_mc_input<I: Index, R: Real, S: Subdomain<I, R>>(
         subdomain,
         density_map,
         iso_surface_threshold,
+        edge_interpolation,
         vertices,
         &mut marching_cubes_data,
         IdentityDensityMapFilter,
@@ -76,6 +81,7 @@ pub(crate) fn construct_mc_input_with_stitching_data<I: Index, R: Real, S: Subdo
     subdomain: &S,
     density_map: &DensityMap<I, R>,
     iso_surface_threshold: R,
+    edge_interpolation: EdgeInterpolation,
     vertices: &mut Vec<Vector3<R>>,
 ) -> (MarchingCubesInput<I>, DirectedAxisArray<BoundaryData<I, R>>) {
     let mut marching_cubes_data = MarchingCubesInput::default();
@@ -83,6 +89,7 @@ pub(crate) fn construct_mc_input_with_stitching_data<I: Index, R: Real, S: Subdo
         subdomain,
         density_map,
         iso_surface_threshold,
+        edge_interpolation,
         vertices,
         &mut marching_cubes_data,
         SkipBoundaryLayerFilter::new(),
@@ -112,6 +119,7 @@ pub(crate) fn update_mc_input_for_stitching_domain<I: Index, R: Real, S: Subdoma
     subdomain: &S,
     density_map: &DensityMap<I, R>,
     iso_surface_threshold: R,
+    edge_interpolation: EdgeInterpolation,
     stitching_axis: Axis,
     vertices: &mut Vec<Vector3<R>>,
     marching_cubes_input: &mut MarchingCubesInput<I>,
@@ -120,6 +128,7 @@ pub(crate) fn update_mc_input_for_stitching_domain<I: Index, R: Real, S: Subdoma
         subdomain,
         density_map,
         iso_surface_threshold,
+        edge_interpolation,
         vertices,
         marching_cubes_input,
         StitchingDomainNarrowBandFilter::new(stitching_axis),
@@ -159,6 +168,7 @@ fn interpolate_points_to_cell_data_generic<
     subdomain: &S,
     density_map: &DensityMap<I, R>,
     iso_surface_threshold: R,
+    edge_interpolation: EdgeInterpolation,
     vertices: &mut Vec<Vector3<R>>,
     marching_cubes_data: &mut MarchingCubesInput<I>,
     mut filter: F,
@@ -243,7 +253,39 @@ fn interpolate_points_to_cell_data_generic<
                 }
 
                 // Interpolate iso-surface vertex on the edge
-                let alpha = (iso_surface_threshold - point_value) / (neighbor_value - point_value);
+                let alpha = match edge_interpolation {
+                    EdgeInterpolation::Midpoint => R::one() / (R::one() + R::one()),
+                    EdgeInterpolation::Linear => linear_iso_crossing_parameter(
+                        point_value,
+                        neighbor_value,
+                        iso_surface_threshold,
+                    ),
+                    EdgeInterpolation::Cubic => {
+                        // Values of the grid points preceding and following the edge
+                        let connectivity = neighbor_edge.connectivity();
+                        let outer_value = |p: &PointIndex<I>, direction| {
+                            let outer = subdomain_grid.get_point_neighbor(p, direction)?;
+                            let global_outer = subdomain.inv_map_point(&outer)?;
+                            density_map.get(grid.flatten_point_index(&global_outer))
+                        };
+                        let linear_alpha = linear_iso_crossing_parameter(
+                            point_value,
+                            neighbor_value,
+                            iso_surface_threshold,
+                        );
+                        match (
+                            outer_value(&point, connectivity.opposite()),
+                            outer_value(neighbor, connectivity),
+                        ) {
+                            (Some(before_value), Some(after_value)) => cubic_edge_crossing(
+                                [before_value, point_value, neighbor_value, after_value],
+                                iso_surface_threshold,
+                                linear_alpha,
+                            ),
+                            _ => linear_alpha,
+                        }
+                    }
+                };
                 let point_coords = subdomain_grid.point_coordinates(&point);
                 let neighbor_coords = subdomain_grid.point_coordinates(neighbor);
                 let interpolated_coords =
@@ -296,6 +338,56 @@ fn interpolate_points_to_cell_data_generic<
     filter
 }
 
+/// Returns the parameter `t` in `[0, 1]` of the iso-surface crossing of a Catmull-Rom spline through the given values
+///
+/// The values are sampled at `t = -1, 0, 1, 2`, the crossing is bracketed by `values[1] <= iso_surface_threshold < values[2]`.
+/// The root is found with Newton iterations starting from the given initial guess that fall back to bisection steps
+/// if they leave the bracket.
+fn cubic_edge_crossing<R: Real>(values: [R; 4], iso_surface_threshold: R, initial_guess: R) -> R {
+    let two = R::one() + R::one();
+    let three = two + R::one();
+    let half = R::one() / two;
+    let [p_m1, p_0, p_1, p_2] = values;
+
+    // Coefficients of the spline minus the threshold in monomial form: c0 + c1*t + c2*t^2 + c3*t^3
+    let c0 = p_0 - iso_surface_threshold;
+    let c1 = half * (p_1 - p_m1);
+    let c2 = half * (two * p_m1 - (two + three) * p_0 + (two + two) * p_1 - p_2);
+    let c3 = half * (three * (p_0 - p_1) + p_2 - p_m1);
+
+    let f = |t: R| c0 + t * (c1 + t * (c2 + t * c3));
+    let df = |t: R| c1 + t * (two * c2 + t * three * c3);
+
+    let mut lower = R::zero();
+    let mut upper = R::one();
+    let mut t = initial_guess;
+    for _ in 0..16 {
+        let value = f(t);
+        if value == R::zero() {
+            return t;
+        } else if value < R::zero() {
+            lower = t;
+        } else {
+            upper = t;
+        }
+
+        let derivative = df(t);
+        let newton = t - value / derivative;
+        let next = if derivative != R::zero() && newton > lower && newton < upper {
+            newton
+        } else {
+            half * (lower + upper)
+        };
+
+        if (next - t).abs() <= R::default_epsilon() {
+            return next;
+        }
+        t = next;
+    }
+
+    t
+}
+
 /// Loops through all corner vertices in the given marching cubes input and updates the above/below threshold flags
 fn update_cell_data_threshold_flags<I: Index, R: Real, S: Subdomain<I, R>>(
     subdomain: &S,
@@ -2,7 +2,7 @@ use crate::marching_cubes::narrow_band_extraction::update_mc_input_for_stitching
 use crate::marching_cubes::triangulation::{
     triangulate_with_criterion, DebugTriangleGenerator, TriangulationStitchingInterior,
 };
-use crate::marching_cubes::{CellData, MarchingCubesError, MarchingCubesInput};
+use crate::marching_cubes::{CellData, EdgeInterpolation, MarchingCubesError, MarchingCubesInput};
 use crate::mesh::TriMesh3d;
 use crate::topology::{Axis, DirectedAxis, DirectedAxisArray, Direction};
 use crate::uniform_grid::{GridBoundaryFaceFlags, OwningSubdomainGrid, Subdomain, UniformGrid};
@@ -12,6 +12,7 @@ use log::{debug, trace};
 /// Stitches the two given surface patches by triangulating the domain between them
 pub(crate) fn stitch_surface_patches<I: Index, R: Real>(
     iso_surface_threshold: R,
+    edge_interpolation: EdgeInterpolation,
     stitching_axis: Axis,
     mut negative_side: SurfacePatch<I, R>,
     mut positive_side: SurfacePatch<I, R>,
@@ -102,6 +103,7 @@ pub(crate) fn stitch_surface_patches<I: Index, R: Real>(
             &stitching_subdomain,
             &boundary_density_map.into(),
             iso_surface_threshold,
+            edge_interpolation,
             stitching_axis,
             &mut output_mesh.vertices,
             &mut marching_cubes_input,
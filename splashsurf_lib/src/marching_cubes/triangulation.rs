@@ -1,3 +1,5 @@
+#[cfg(feature = "stats")]
+use crate::marching_cubes::marching_cubes_lut::flags_to_index;
 use crate::marching_cubes::marching_cubes_lut::marching_cubes_triangulation_iter;
 use crate::marching_cubes::{CellData, MarchingCubesInput};
 use crate::mesh::TriMesh3d;
@@ -90,6 +92,9 @@ pub(crate) fn triangulate_with_criterion<
         mesh.vertices.len(),
     );
 
+    #[cfg(feature = "stats")]
+    let mut case_histogram = super::MarchingCubesCaseHistogram::default();
+
     // Triangulate affected cells
     for (&flat_cell_index, cell_data) in &cell_data {
         // Skip cells that don't fulfill triangulation criterion
@@ -98,8 +103,12 @@ pub(crate) fn triangulate_with_criterion<
         }
 
         // TODO: Replace `are_vertices_above_unchecked` with something that can return an error
-        for triangle in marching_cubes_triangulation_iter(&cell_data.are_vertices_above_unchecked())
-        {
+        let vertices_inside = cell_data.are_vertices_above_unchecked();
+
+        #[cfg(feature = "stats")]
+        case_histogram.increment(flags_to_index(&vertices_inside) as u8);
+
+        for triangle in marching_cubes_triangulation_iter(&vertices_inside) {
             // TODO: Allow user to set option to skip invalid triangles?
             let global_triangle = triangle_generator
                 .triangle_connectivity(subdomain, flat_cell_index, cell_data, triangle)
@@ -108,6 +117,9 @@ pub(crate) fn triangulate_with_criterion<
         }
     }
 
+    #[cfg(feature = "stats")]
+    crate::stats::add_to_current_scope(&case_histogram);
+
     trace!(
         "Triangulation done. (Output: surface mesh with {} triangles and {} vertices)",
         mesh.triangles.len(),
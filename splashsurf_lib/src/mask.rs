@@ -0,0 +1,296 @@
+//! Restriction of the surface reconstruction to a region of interest given by a voxel mask
+//!
+//! A [`ReconstructionMask`] is a coarse boolean voxel grid. Points of the marching cubes background
+//! grid that are not inside of an active voxel are treated as background (zero density), so the
+//! reconstructed surface is closed at the boundary of the masked region. Particles whose kernel
+//! support does not overlap any active voxel are skipped during the generation of the density map.
+//!
+//! The mask has to be aligned to the background grid of the reconstruction: the voxel size has to be
+//! an integer multiple of the cube size and the origin of the mask has to coincide with a point of the
+//! background grid. As the background grid starts at the lower corner of the domain, this usually
+//! requires to set the [`domain_aabb`](crate::Parameters::domain_aabb) of the reconstruction such that its
+//! lower corner is on a point of the lattice spanned by the mask origin and the cube size.
+
+use crate::density_map::DensityMap;
+use crate::uniform_grid::UniformGrid;
+use crate::{Index, Real};
+use nalgebra::Vector3;
+use thiserror::Error as ThisError;
+
+/// Boolean voxel grid that selects the region of interest of a surface reconstruction
+#[derive(Clone, Debug)]
+pub struct ReconstructionMask<R: Real> {
+    /// Lower corner of the first voxel
+    origin: Vector3<R>,
+    /// Edge length of the cubic voxels
+    voxel_size: R,
+    /// Number of voxels along each axis
+    voxels_per_dim: [usize; 3],
+    /// Flags for all voxels, stored in the same order as flat point indices of a [`UniformGrid`] (i.e. z-index is the fastest)
+    values: Vec<bool>,
+}
+
+/// Error type returned if a [`ReconstructionMask`] is not aligned to the background grid of the reconstruction
+#[derive(Debug, ThisError)]
+pub enum MaskAlignmentError<R: Real> {
+    /// The voxel size of the mask is not an integer multiple of the cube size
+    #[error("the voxel size of the mask ({voxel_size}) is not an integer multiple of the cube size ({cube_size})")]
+    IncompatibleVoxelSize {
+        /// Voxel size of the mask
+        voxel_size: R,
+        /// The cube size of the reconstruction
+        cube_size: R,
+    },
+    /// The origin of the mask is not a point of the background grid
+    #[error("the origin of the mask along axis {dim} ({mask_origin}) is not on a point of the background grid (grid origin: {grid_origin}, cube size: {cube_size})")]
+    UnalignedOrigin {
+        /// Dimension of the misaligned axis
+        dim: usize,
+        /// Coordinate of the mask origin along the axis
+        mask_origin: R,
+        /// Coordinate of the background grid origin along the axis
+        grid_origin: R,
+        /// The cube size of the reconstruction
+        cube_size: R,
+    },
+}
+
+impl<R: Real> ReconstructionMask<R> {
+    /// Constructs a mask from flags for all voxels in the same order as flat point indices of a [`UniformGrid`] (i.e. z-index is the fastest)
+    ///
+    /// Panics if the number of flags does not match the number of voxels or if the voxel size is not positive.
+    pub fn new(
+        origin: Vector3<R>,
+        voxel_size: R,
+        voxels_per_dim: [usize; 3],
+        values: Vec<bool>,
+    ) -> Self {
+        assert!(voxel_size > R::zero(), "voxel size has to be positive");
+        assert_eq!(
+            values.len(),
+            voxels_per_dim[0] * voxels_per_dim[1] * voxels_per_dim[2],
+            "number of mask values has to match the number of voxels"
+        );
+
+        Self {
+            origin,
+            voxel_size,
+            voxels_per_dim,
+            values,
+        }
+    }
+
+    /// Constructs a mask by evaluating a predicate for the index triplet of every voxel
+    ///
+    /// Panics if the voxel size is not positive.
+    pub fn from_fn<F: FnMut([usize; 3]) -> bool>(
+        origin: Vector3<R>,
+        voxel_size: R,
+        voxels_per_dim: [usize; 3],
+        mut f: F,
+    ) -> Self {
+        let mut values =
+            Vec::with_capacity(voxels_per_dim[0] * voxels_per_dim[1] * voxels_per_dim[2]);
+        for i in 0..voxels_per_dim[0] {
+            for j in 0..voxels_per_dim[1] {
+                for k in 0..voxels_per_dim[2] {
+                    values.push(f([i, j, k]));
+                }
+            }
+        }
+
+        Self::new(origin, voxel_size, voxels_per_dim, values)
+    }
+
+    /// Returns the lower corner of the first voxel
+    pub fn origin(&self) -> &Vector3<R> {
+        &self.origin
+    }
+
+    /// Returns the edge length of the voxels
+    pub fn voxel_size(&self) -> R {
+        self.voxel_size
+    }
+
+    /// Returns the number of voxels along each axis
+    pub fn voxels_per_dim(&self) -> &[usize; 3] {
+        &self.voxels_per_dim
+    }
+
+    /// Returns whether the voxel with the given index triplet is active, voxels outside of the mask are inactive
+    pub fn get(&self, ijk: [i64; 3]) -> bool {
+        let n = &self.voxels_per_dim;
+        if (0..3).any(|dim| ijk[dim] < 0 || ijk[dim] as usize >= n[dim]) {
+            return false;
+        }
+
+        let [i, j, k] = [ijk[0] as usize, ijk[1] as usize, ijk[2] as usize];
+        self.values[(i * n[1] + j) * n[2] + k]
+    }
+
+    /// Returns whether any voxel overlapping with the ball of the given radius around a point is active
+    pub fn overlaps_ball(&self, center: &Vector3<R>, radius: R) -> bool {
+        let to_voxel = |x: R, dim: usize| -> i64 {
+            ((x - self.origin[dim]) / self.voxel_size)
+                .floor()
+                .to_i64()
+                .unwrap_or(i64::MIN)
+        };
+        let clamp = |v: i64, dim: usize| v.max(-1).min(self.voxels_per_dim[dim] as i64);
+        let lower = |dim: usize| clamp(to_voxel(center[dim] - radius, dim), dim);
+        let upper = |dim: usize| clamp(to_voxel(center[dim] + radius, dim), dim);
+
+        let (lower, upper) = (
+            [lower(0), lower(1), lower(2)],
+            [upper(0), upper(1), upper(2)],
+        );
+
+        for i in lower[0]..=upper[0] {
+            for j in lower[1]..=upper[1] {
+                for k in lower[2]..=upper[2] {
+                    if self.get([i, j, k]) {
+                        return true;
+                    }
+                }
+            }
+        }
+
+        false
+    }
+
+    /// Tries to convert the mask from one [Real] type to another [Real] type, returns None if conversion fails
+    pub fn try_convert<T: Real>(&self) -> Option<ReconstructionMask<T>> {
+        Some(ReconstructionMask {
+            origin: T::try_convert_vec_from(&self.origin)?,
+            voxel_size: self.voxel_size.try_convert()?,
+            voxels_per_dim: self.voxels_per_dim,
+            values: self.values.clone(),
+        })
+    }
+
+    /// Checks that the mask is aligned to the given background grid and returns a view of the mask for lookups of grid points
+    pub(crate) fn aligned_to<'a, I: Index>(
+        &'a self,
+        grid: &UniformGrid<I, R>,
+    ) -> Result<AlignedMask<'a, R>, MaskAlignmentError<R>> {
+        let tolerance = R::from_f64(1e-6).unwrap();
+        let is_integer = |x: R| (x - x.round()).abs() <= tolerance * x.abs().max(R::one());
+
+        let cube_size = grid.cell_size();
+        let ratio = self.voxel_size / cube_size;
+        if !is_integer(ratio) || ratio.round() < R::one() {
+            return Err(MaskAlignmentError::IncompatibleVoxelSize {
+                voxel_size: self.voxel_size,
+                cube_size,
+            });
+        }
+
+        let grid_origin = grid.aabb().min();
+        let mut offset = [0; 3];
+        for (dim, dim_offset) in offset.iter_mut().enumerate() {
+            let cells = (self.origin[dim] - grid_origin[dim]) / cube_size;
+            if !is_integer(cells) {
+                return Err(MaskAlignmentError::UnalignedOrigin {
+                    dim,
+                    mask_origin: self.origin[dim],
+                    grid_origin: grid_origin[dim],
+                    cube_size,
+                });
+            }
+            *dim_offset = cells.round().to_i64().unwrap();
+        }
+
+        Ok(AlignedMask {
+            mask: self,
+            offset,
+            cells_per_voxel: ratio.round().to_i64().unwrap(),
+        })
+    }
+}
+
+/// View of a [`ReconstructionMask`] that was checked to be aligned to a background grid
+pub(crate) struct AlignedMask<'a, R: Real> {
+    mask: &'a ReconstructionMask<R>,
+    /// Index triplet of the mask origin in the background grid
+    offset: [i64; 3],
+    /// Number of grid cells along the edge of a voxel
+    cells_per_voxel: i64,
+}
+
+impl<'a, R: Real> AlignedMask<'a, R> {
+    /// Returns whether the grid point with the given index triplet is inside of an active voxel
+    ///
+    /// Points on the faces between voxels belong to the voxel with the larger index.
+    pub(crate) fn contains_point<I: Index>(&self, ijk: &[I; 3]) -> bool {
+        let voxel = |dim: usize| {
+            (ijk[dim].to_i64().unwrap() - self.offset[dim]).div_euclid(self.cells_per_voxel)
+        };
+        self.mask.get([voxel(0), voxel(1), voxel(2)])
+    }
+
+    /// Returns the indices of all particles whose ball of the given radius overlaps with an active voxel
+    pub(crate) fn active_particles(
+        &self,
+        particle_positions: &[Vector3<R>],
+        radius: R,
+    ) -> Vec<usize> {
+        particle_positions
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| self.mask.overlaps_ball(p, radius))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Sets the values of all grid points outside of active voxels in the density map to zero
+    ///
+    /// The entries are kept in the map such that the marching cubes edges crossing the boundary of the
+    /// mask are still processed, i.e. the surface is closed along the boundary of the mask instead of
+    /// leaving cells with missing corner values.
+    pub(crate) fn apply_to_density_map<I: Index>(
+        &self,
+        grid: &UniformGrid<I, R>,
+        density_map: &mut DensityMap<I, R>,
+    ) {
+        density_map.for_each_mut(|flat_point_index, density| {
+            let inside = grid
+                .try_unflatten_point_index(flat_point_index)
+                .map(|point| self.contains_point(point.index()))
+                .unwrap_or(false);
+            if !inside {
+                *density = R::zero();
+            }
+        });
+    }
+}
+
+#[test]
+fn test_mask_alignment_and_lookup() {
+    let grid = UniformGrid::<i64, f64>::new(&Vector3::zeros(), &[10, 10, 10], 0.1).unwrap();
+
+    // Voxels of two cells starting at grid point (2, 0, 0), only the first voxel is active
+    let mask = ReconstructionMask::from_fn(Vector3::new(0.2, 0.0, 0.0), 0.2, [2, 2, 2], |ijk| {
+        ijk == [0, 0, 0]
+    });
+    let aligned = mask.aligned_to(&grid).unwrap();
+    assert!(aligned.contains_point(&[2i64, 0, 0]));
+    assert!(aligned.contains_point(&[3i64, 1, 1]));
+    assert!(!aligned.contains_point(&[4i64, 0, 0]));
+    assert!(!aligned.contains_point(&[1i64, 0, 0]));
+
+    assert!(mask.overlaps_ball(&Vector3::new(0.15, 0.1, 0.1), 0.1));
+    assert!(!mask.overlaps_ball(&Vector3::new(0.9, 0.9, 0.9), 0.1));
+
+    let unaligned =
+        ReconstructionMask::from_fn(Vector3::new(0.25, 0.0, 0.0), 0.2, [2, 2, 2], |_| true);
+    assert!(matches!(
+        unaligned.aligned_to(&grid),
+        Err(MaskAlignmentError::UnalignedOrigin { dim: 0, .. })
+    ));
+
+    let incompatible = ReconstructionMask::from_fn(Vector3::zeros(), 0.15, [2, 2, 2], |_| true);
+    assert!(matches!(
+        incompatible.aligned_to(&grid),
+        Err(MaskAlignmentError::IncompatibleVoxelSize { .. })
+    ));
+}
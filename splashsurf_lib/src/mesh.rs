@@ -6,6 +6,9 @@
 //!  - [`HexMesh3d`]
 //!  - [`PointCloud3d`]
 //!
+//! Additionally, the [`QuadMesh3d`] type represents surface meshes with mixed triangle and quad faces, e.g.
+//! obtained from a triangle mesh using [`TriMesh3d::to_quad_dominant`].
+//!
 //! Furthermore, it provides the [`MeshWithData`] type that is used when additional attributes are
 //! attached to the vertices (e.g. normals) or cells (e.g. some identifiers) of the mesh.
 //!
@@ -16,13 +19,15 @@
 //!  - `From<T> for UnstructuredGridPiece` implementations for the basic mesh types
 //!  - `Into<DataSet>` implementations for the basic mesh types
 
-use crate::{new_map, Real};
+use crate::bvh::TriangleBvh;
+use crate::{new_map, profile, MapType, Real};
 use bytemuck::{cast_slice, cast_slice_mut};
 use bytemuck_derive::{Pod, Zeroable};
 use nalgebra::{Unit, Vector3};
 use rayon::prelude::*;
 use std::cell::RefCell;
 use std::fmt::Debug;
+use thiserror::Error as ThisError;
 use thread_local::ThreadLocal;
 #[cfg(feature = "vtk_extras")]
 use vtkio::model::{Attribute, DataSet, UnstructuredGridPiece};
@@ -48,6 +53,36 @@ pub enum AttributeData<R: Real> {
     Vector3Real(Vec<Vector3<R>>),
 }
 
+/// Error type returned if the length of the data of a [`MeshAttribute`] does not match the number of points or cells of the mesh
+#[derive(Clone, Debug, Eq, PartialEq, ThisError)]
+#[error("the attribute \"{name}\" has {len} entries but the mesh has {expected} {kind}")]
+pub struct MeshAttributeLengthError {
+    /// Name of the attribute
+    pub name: &'static str,
+    /// Number of entries in the data of the attribute
+    pub len: usize,
+    /// Number of points or cells of the mesh
+    pub expected: usize,
+    /// Whether the attribute was attached to the `"points"` or `"cells"` of the mesh
+    pub kind: &'static str,
+}
+
+/// Error type returned by [`TriMesh3d::weld_vertices`] for an invalid welding distance or vertex coordinates
+#[derive(Clone, Debug, PartialEq, ThisError)]
+pub enum WeldVerticesError<R: Real> {
+    /// The welding distance is not positive or not finite
+    #[error("the welding distance ({0}) has to be positive and finite")]
+    InvalidDistance(R),
+    /// The coordinates of a vertex cannot be bucketed with the welding distance, e.g. because they are not finite
+    #[error("the coordinates of vertex {index} ({vertex:?}) cannot be bucketed with the welding distance")]
+    InvalidVertex {
+        /// Index of the vertex
+        index: usize,
+        /// Coordinates of the vertex
+        vertex: Vector3<R>,
+    },
+}
+
 /// A triangle (surface) mesh in 3D
 #[derive(Clone, Debug, Default)]
 pub struct TriMesh3d<R: Real> {
@@ -66,6 +101,75 @@ pub struct HexMesh3d<R: Real> {
     pub cells: Vec<[usize; 8]>,
 }
 
+/// A face of a [`QuadMesh3d`], either a triangle or a quad identified by its vertex indices
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum QuadMeshCell {
+    /// A triangle face
+    Triangle([usize; 3]),
+    /// A quad face, the vertices are ordered along its boundary
+    Quad([usize; 4]),
+}
+
+/// A mixed triangle and quad (surface) mesh in 3D
+#[derive(Clone, Debug, Default)]
+pub struct QuadMesh3d<R: Real> {
+    /// Coordinates of all vertices of the mesh
+    pub vertices: Vec<Vector3<R>>,
+    /// The triangle and quad faces of the mesh
+    pub cells: Vec<QuadMeshCell>,
+}
+
+impl<R: Real> QuadMesh3d<R> {
+    /// Returns the number of quad faces of the mesh
+    pub fn num_quads(&self) -> usize {
+        self.cells
+            .iter()
+            .filter(|cell| matches!(cell, QuadMeshCell::Quad(_)))
+            .count()
+    }
+
+    /// Returns the number of triangle faces of the mesh
+    pub fn num_triangles(&self) -> usize {
+        self.cells.len() - self.num_quads()
+    }
+}
+
+impl<R: Real> HexMesh3d<R> {
+    /// Local vertex indices of the six quad faces of a hexahedron, ordered counter-clockwise when viewed from outside
+    ///
+    /// Assumes the VTK vertex ordering of the cells, i.e. vertices `0..4` form the bottom face (in `-z` direction)
+    /// counter-clockwise when viewed from above and vertices `4..8` the top face in the same order.
+    const HEX_FACES: [[usize; 4]; 6] = [
+        [0, 3, 2, 1],
+        [4, 5, 6, 7],
+        [0, 1, 5, 4],
+        [3, 7, 6, 2],
+        [0, 4, 7, 3],
+        [1, 2, 6, 5],
+    ];
+
+    /// Converts the boundary faces of all cells into a triangle mesh, e.g. for viewers that only support triangles
+    ///
+    /// Every quad face of a cell is split into two triangles with outward facing normals, i.e. each cell results
+    /// in twelve triangles. The triangles reference the vertices of this mesh, so vertices shared by several cells
+    /// are shared by their triangles as well. Faces between adjacent cells are not removed.
+    pub fn to_trimesh(&self) -> TriMesh3d<R> {
+        let mut triangles = Vec::with_capacity(self.cells.len() * 12);
+        for cell in self.cells.iter() {
+            for face in Self::HEX_FACES.iter() {
+                let (a, b, c, d) = (cell[face[0]], cell[face[1]], cell[face[2]], cell[face[3]]);
+                triangles.push([a, b, c]);
+                triangles.push([a, c, d]);
+            }
+        }
+
+        TriMesh3d {
+            vertices: self.vertices.clone(),
+            triangles,
+        }
+    }
+}
+
 /// A point cloud in 3D
 #[derive(Clone, Debug, Default)]
 pub struct PointCloud3d<R: Real> {
@@ -83,6 +187,133 @@ impl<R: Real> PointCloud3d<R> {
     }
 }
 
+/// Measure used to rank the connected components of a mesh by size
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ComponentSize {
+    /// Number of triangles of the component
+    Triangles,
+    /// Absolute enclosed volume of the component, only meaningful for closed components
+    Volume,
+}
+
+/// Summary of the edge topology of a triangle mesh returned by [`TriMesh3d::manifold_info`]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct ManifoldInfo {
+    /// Total number of distinct (undirected) edges of the mesh
+    pub num_edges: usize,
+    /// Number of edges that are used by exactly one triangle
+    pub num_boundary_edges: usize,
+    /// Number of edges that are used by more than two triangles
+    pub num_non_manifold_edges: usize,
+}
+
+impl ManifoldInfo {
+    /// Returns whether the mesh has no boundary edges
+    pub fn is_closed(&self) -> bool {
+        self.num_boundary_edges == 0
+    }
+
+    /// Returns whether every edge of the mesh is used by at most two triangles
+    pub fn is_manifold(&self) -> bool {
+        self.num_non_manifold_edges == 0
+    }
+
+    /// Returns whether the mesh is closed and every edge is used by exactly two triangles
+    pub fn is_watertight(&self) -> bool {
+        self.is_closed() && self.is_manifold()
+    }
+}
+
+/// Vertex-vertex adjacency (one-ring neighborhoods) of a mesh stored in a compressed sparse row (CSR) layout
+///
+/// The neighbors of every vertex are sorted by increasing vertex index and do not contain duplicates
+/// or the vertex itself. Vertices that are not referenced by any cell have no neighbors.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct VertexAdjacency {
+    /// Offsets into the neighbor storage, the neighbors of vertex `i` are stored in the range `offsets[i]..offsets[i + 1]`
+    offsets: Vec<usize>,
+    /// Concatenated neighbor lists of all vertices
+    neighbors: Vec<usize>,
+}
+
+impl VertexAdjacency {
+    /// Constructs the adjacency from a list of undirected edges between the given number of vertices
+    fn from_edges(num_vertices: usize, edges: impl Iterator<Item = [usize; 2]> + Clone) -> Self {
+        let mut offsets = vec![0; num_vertices + 1];
+        for [v0, v1] in edges.clone() {
+            offsets[v0 + 1] += 1;
+            offsets[v1 + 1] += 1;
+        }
+        for i in 0..num_vertices {
+            offsets[i + 1] += offsets[i];
+        }
+
+        let mut next = offsets.clone();
+        let mut neighbors = vec![0; offsets[num_vertices]];
+        for [v0, v1] in edges {
+            neighbors[next[v0]] = v1;
+            next[v0] += 1;
+            neighbors[next[v1]] = v0;
+            next[v1] += 1;
+        }
+
+        // Sort and deduplicate the neighbor lists, compacting the storage in place
+        let mut new_offsets = Vec::with_capacity(num_vertices + 1);
+        new_offsets.push(0);
+        let mut len = 0;
+        for i in 0..num_vertices {
+            let range = offsets[i]..offsets[i + 1];
+            neighbors[range.clone()].sort_unstable();
+            for j in range {
+                let v = neighbors[j];
+                if len == *new_offsets.last().unwrap() || neighbors[len - 1] != v {
+                    neighbors[len] = v;
+                    len += 1;
+                }
+            }
+            new_offsets.push(len);
+        }
+        neighbors.truncate(len);
+
+        Self {
+            offsets: new_offsets,
+            neighbors,
+        }
+    }
+
+    /// Returns the number of vertices of the adjacency structure
+    pub fn num_vertices(&self) -> usize {
+        self.offsets.len().saturating_sub(1)
+    }
+
+    /// Returns the sorted indices of all vertices that share an edge with the given vertex
+    pub fn neighbors(&self, vertex: usize) -> &[usize] {
+        &self.neighbors[self.offsets[vertex]..self.offsets[vertex + 1]]
+    }
+
+    /// Returns the number of neighbors of the given vertex
+    pub fn degree(&self, vertex: usize) -> usize {
+        self.offsets[vertex + 1] - self.offsets[vertex]
+    }
+
+    /// Returns an iterator over the neighbor lists of all vertices in the order of the vertex indices
+    pub fn iter(&self) -> impl Iterator<Item = &[usize]> {
+        self.offsets
+            .windows(2)
+            .map(move |range| &self.neighbors[range[0]..range[1]])
+    }
+
+    /// Returns the offsets into [`Self::neighbor_storage`] of all vertices, with one additional entry for the end of the last list
+    pub fn offsets(&self) -> &[usize] {
+        &self.offsets
+    }
+
+    /// Returns the concatenated neighbor lists of all vertices
+    pub fn neighbor_storage(&self) -> &[usize] {
+        &self.neighbors
+    }
+}
+
 /// Basic functionality that is provided by all meshes of the library
 ///
 /// Meshes consist of vertices and cells. Cells identify their associated vertices using indices
@@ -99,8 +330,12 @@ pub trait Mesh3d<R: Real> {
 
 /// Basic interface for mesh cells consisting of a collection of vertex indices
 pub trait CellConnectivity {
-    /// Returns the number of vertices per cell
+    /// Returns the number of vertices per cell (the maximum number for cells with a varying number of vertices)
     fn num_vertices() -> usize;
+    /// Returns the number of vertices of this cell
+    fn cell_num_vertices(&self) -> usize {
+        Self::num_vertices()
+    }
     /// Calls the given closure with each vertex index that is part of this cell, stopping at the first error and returning that error
     fn try_for_each_vertex<E, F: FnMut(usize) -> Result<(), E>>(&self, f: F) -> Result<(), E>;
     /// Calls the given closure with each vertex index that is part of this cell
@@ -146,6 +381,26 @@ impl CellConnectivity for HexCell {
     }
 }
 
+impl CellConnectivity for QuadMeshCell {
+    fn num_vertices() -> usize {
+        4
+    }
+
+    fn cell_num_vertices(&self) -> usize {
+        match self {
+            QuadMeshCell::Triangle(_) => 3,
+            QuadMeshCell::Quad(_) => 4,
+        }
+    }
+
+    fn try_for_each_vertex<E, F: FnMut(usize) -> Result<(), E>>(&self, f: F) -> Result<(), E> {
+        match self {
+            QuadMeshCell::Triangle(tri) => tri.iter().copied().try_for_each(f),
+            QuadMeshCell::Quad(quad) => quad.iter().copied().try_for_each(f),
+        }
+    }
+}
+
 impl CellConnectivity for PointCell {
     fn num_vertices() -> usize {
         1
@@ -156,6 +411,65 @@ impl CellConnectivity for PointCell {
     }
 }
 
+/// Concatenates all given meshes into a single mesh, in parallel
+///
+/// The vertex and triangle offsets of the meshes in the output are computed with a prefix sum. Afterwards,
+/// the vertices and triangles of all meshes are copied in parallel into the preallocated output storage
+/// and the vertex indices of the triangles are shifted by the vertex offset of their mesh.
+/// The order of the meshes is preserved, i.e. the result is identical to successively calling [`TriMesh3d::append`].
+pub fn par_concat<R: Real>(meshes: Vec<TriMesh3d<R>>) -> TriMesh3d<R> {
+    profile!("mesh::par_concat");
+
+    // Prefix sums of the vertex and triangle counts
+    let mut vertex_offsets = Vec::with_capacity(meshes.len());
+    let mut num_vertices = 0;
+    let mut num_triangles = 0;
+    for mesh in meshes.iter() {
+        vertex_offsets.push(num_vertices);
+        num_vertices += mesh.vertices.len();
+        num_triangles += mesh.triangles.len();
+    }
+
+    let mut vertices = vec![Vector3::zeros(); num_vertices];
+    let mut triangles = vec![[0; 3]; num_triangles];
+
+    // Split the output storage into disjoint chunks for every mesh
+    let mut vertex_chunks = Vec::with_capacity(meshes.len());
+    let mut triangle_chunks = Vec::with_capacity(meshes.len());
+    {
+        let mut remaining_vertices = vertices.as_mut_slice();
+        let mut remaining_triangles = triangles.as_mut_slice();
+        for mesh in meshes.iter() {
+            let (chunk, rest) =
+                std::mem::take(&mut remaining_vertices).split_at_mut(mesh.vertices.len());
+            vertex_chunks.push(chunk);
+            remaining_vertices = rest;
+
+            let (chunk, rest) =
+                std::mem::take(&mut remaining_triangles).split_at_mut(mesh.triangles.len());
+            triangle_chunks.push(chunk);
+            remaining_triangles = rest;
+        }
+    }
+
+    meshes
+        .par_iter()
+        .zip(vertex_offsets.par_iter())
+        .zip(vertex_chunks.into_par_iter())
+        .zip(triangle_chunks.into_par_iter())
+        .for_each(|(((mesh, &offset), vertex_chunk), triangle_chunk)| {
+            vertex_chunk.copy_from_slice(mesh.vertices.as_slice());
+            for (out_tri, tri) in triangle_chunk.iter_mut().zip(mesh.triangles.iter()) {
+                *out_tri = [tri[0] + offset, tri[1] + offset, tri[2] + offset];
+            }
+        });
+
+    TriMesh3d {
+        vertices,
+        triangles,
+    }
+}
+
 impl<R: Real> Mesh3d<R> for TriMesh3d<R> {
     type Cell = TriangleCell;
 
@@ -180,6 +494,18 @@ impl<R: Real> Mesh3d<R> for HexMesh3d<R> {
     }
 }
 
+impl<R: Real> Mesh3d<R> for QuadMesh3d<R> {
+    type Cell = QuadMeshCell;
+
+    fn vertices(&self) -> &[Vector3<R>] {
+        self.vertices.as_slice()
+    }
+
+    fn cells(&self) -> &[QuadMeshCell] {
+        self.cells.as_slice()
+    }
+}
+
 impl<R: Real> Mesh3d<R> for PointCloud3d<R> {
     type Cell = PointCell;
 
@@ -323,8 +649,10 @@ impl<R: Real> TriMesh3d<R> {
         // ...then actually normalize them.
         for normal in normals.iter_mut() {
             let norm = (normal.x * normal.x + normal.y * normal.y + normal.z * normal.z).sqrt();
-            *normal.as_mut_unchecked() /= norm;
-            //normal.renormalize();
+            // Skip vertices without adjacent non-degenerate triangles instead of producing NaNs
+            if norm > R::zero() {
+                *normal.as_mut_unchecked() /= norm;
+            }
         }
     }
 
@@ -342,8 +670,9 @@ impl<R: Real> TriMesh3d<R> {
         normals.par_chunks_mut(256).for_each(|normal_chunk| {
             for normal in normal_chunk.iter_mut() {
                 let norm = (normal.x * normal.x + normal.y * normal.y + normal.z * normal.z).sqrt();
-                *normal.as_mut_unchecked() /= norm;
-                //normal.renormalize();
+                if norm > R::zero() {
+                    *normal.as_mut_unchecked() /= norm;
+                }
             }
         });
     }
@@ -364,6 +693,9 @@ impl<R: Real> TriMesh3d<R> {
     }
 
     /// Computes the mesh's vertex normals using an area weighted average of the adjacent triangle faces
+    ///
+    /// Degenerate triangles do not contribute to the normals. Vertices that are not part of any non-degenerate
+    /// triangle get a zero vector as normal.
     pub fn vertex_normals(&self) -> Vec<Unit<Vector3<R>>> {
         let mut normals = vec![Unit::new_unchecked(Vector3::zeros()); self.vertices.len()];
         self.vertex_normals_inplace_assume_zeroed(normals.as_mut_slice());
@@ -394,146 +726,1477 @@ impl<R: Real> TriMesh3d<R> {
         normals
     }
 
-    /// Returns all boundary edges of the mesh
+    /// Computes the total area of all triangles of the mesh
+    pub fn surface_area(&self) -> R {
+        let half = R::from_f64(0.5).unwrap();
+        self.triangles
+            .iter()
+            .map(|tri| {
+                let v0 = &self.vertices[tri[0]];
+                let v1 = &self.vertices[tri[1]];
+                let v2 = &self.vertices[tri[2]];
+                (v1 - v0).cross(&(v2 - v0)).norm() * half
+            })
+            .fold(R::zero(), |acc, area| acc + area)
+    }
+
+    /// Computes the signed volume enclosed by the mesh using the divergence theorem
     ///
-    /// Returns edges which are only connected to exactly one triangle, along with the connected triangle
-    /// index and the local index of the edge within that triangle.
+    /// The volume is the sum of the signed volumes of the tetrahedra spanned by each triangle and the origin.
+    /// The result is only meaningful for closed meshes with a consistent winding of the triangles. It is
+    /// positive if the triangles are oriented counter-clockwise when viewed from the outside (i.e. the
+    /// normals point outwards, as for meshes produced by the reconstruction) and negative otherwise.
+    pub fn signed_volume(&self) -> R {
+        let sixth = R::from_f64(1.0 / 6.0).unwrap();
+        self.triangles
+            .iter()
+            .map(|tri| {
+                let v0 = &self.vertices[tri[0]];
+                let v1 = &self.vertices[tri[1]];
+                let v2 = &self.vertices[tri[2]];
+                v0.dot(&v1.cross(v2)) * sixth
+            })
+            .fold(R::zero(), |acc, volume| acc + volume)
+    }
+
+    /// Computes the Euler characteristic `V - E + F` of the mesh
     ///
-    /// Note that the output order is not necessarily deterministic due to the internal use of hashmaps.
-    pub fn find_boundary_edges(&self) -> Vec<([usize; 2], usize, usize)> {
-        let mut sorted_edges = Vec::new();
-        let mut edge_info = Vec::new();
+    /// All vertices of the mesh are counted, including vertices that are not referenced by any triangle.
+    /// Edges are counted as unique (undirected) pairs of vertex indices, so duplicate vertices that were
+    /// not merged will result in a different value than for the corresponding welded mesh.
+    pub fn euler_characteristic(&self) -> i64 {
+        let num_edges = self.edge_triangle_counts().len();
+        self.vertices.len() as i64 - num_edges as i64 + self.triangles.len() as i64
+    }
 
-        // Local indices into the triangle connectivity to obtain all edges
-        let tri_edges: [(usize, usize); 3] = [(0, 1), (1, 2), (2, 0)];
+    /// Computes the genus of the mesh based on its Euler characteristic, returns `None` if the mesh is not closed
+    ///
+    /// The genus `g` is computed from the Euler characteristic `χ` using the relation `χ = 2 - 2g`.
+    /// This relation only holds for closed, orientable, manifold meshes consisting of a single
+    /// connected component. This function checks that every edge of the mesh is shared by exactly
+    /// two triangles and returns `None` otherwise (i.e. if there are boundary or non-manifold edges)
+    /// or if the Euler characteristic is inconsistent with a genus. Connectivity and orientability are
+    /// not checked: for a mesh with several closed components the relation does not hold and the
+    /// returned value is meaningless.
+    pub fn genus(&self) -> Option<i64> {
+        if self.triangles.is_empty() {
+            return None;
+        }
 
-        // For each triangle collect
-        //  - each edge (with sorted vertices to use as unique key)
-        //  - each edge with the index of the triangle and local index in the triangle
-        for (tri_idx, tri_conn) in self.triangles.iter().enumerate() {
-            for (local_idx, (v0, v1)) in tri_edges
-                .iter()
-                .copied()
-                .map(|(i0, i1)| (tri_conn[i0], tri_conn[i1]))
-                .enumerate()
-            {
-                // Sort the edge
-                if v0 < v1 {
-                    sorted_edges.push([v0, v1])
-                } else {
-                    sorted_edges.push([v1, v0])
-                };
+        let edge_counts = self.edge_triangle_counts();
+        if edge_counts.values().any(|&count| count != 2) {
+            return None;
+        }
 
-                edge_info.push(([v0, v1], tri_idx, local_idx));
+        let euler_characteristic =
+            self.vertices.len() as i64 - edge_counts.len() as i64 + self.triangles.len() as i64;
+        if euler_characteristic > 2 || euler_characteristic % 2 != 0 {
+            return None;
+        }
+
+        Some((2 - euler_characteristic) / 2)
+    }
+
+    /// Counts the boundary and non-manifold edges of the mesh
+    ///
+    /// Edges are identified by their sorted pair of vertex indices, i.e. coincident but unwelded vertices
+    /// result in boundary edges. Note that non-manifold vertices (e.g. two closed components touching at a
+    /// single vertex) are not detected.
+    pub fn manifold_info(&self) -> ManifoldInfo {
+        let edge_counts = self.edge_triangle_counts();
+        let mut info = ManifoldInfo {
+            num_edges: edge_counts.len(),
+            ..Default::default()
+        };
+        for &count in edge_counts.values() {
+            if count == 1 {
+                info.num_boundary_edges += 1;
+            } else if count > 2 {
+                info.num_non_manifold_edges += 1;
             }
         }
+        info
+    }
 
-        // Count the number of occurrences of "equivalent" edges (in the sense that they refer
-        // to the same vertex indices).
-        let mut edge_counts = new_map();
-        for (edge_idx, edge) in sorted_edges.iter().copied().enumerate() {
-            edge_counts
-                .entry(edge)
-                .and_modify(|(_, count)| *count += 1)
-                .or_insert((edge_idx, 1));
+    /// Constructs a closed shell of the given thickness by offsetting a copy of the mesh inwards along the vertex normals
+    ///
+    /// The returned mesh contains the original (outer) surface, an inner surface with inverted orientation
+    /// where every vertex is moved by `thickness` against its area weighted vertex normal and, if the mesh
+    /// has a boundary, triangles connecting the boundary edges of the outer surface to the corresponding
+    /// edges of the inner surface. The first half of the vertices belongs to the outer surface, the second
+    /// half to the inner surface. Vertices without adjacent triangles are not moved.
+    ///
+    /// Note that the inner surface is not checked for self-intersections. These occur if the thickness
+    /// exceeds the local radius of curvature of the surface (e.g. at sharp features or small droplets) or
+    /// if the vertex normals are not representative for the surface (e.g. for very coarse meshes).
+    pub fn offset_shell(&self, thickness: R) -> TriMesh3d<R> {
+        let normal_directions = self.vertex_normal_directions();
+        let num_vertices = self.vertices.len();
+
+        let mut vertices = Vec::with_capacity(2 * num_vertices);
+        vertices.extend(self.vertices.iter().copied());
+        vertices.extend(self.vertices.iter().zip(normal_directions.iter()).map(
+            |(v, normal_direction)| match normal_direction.try_normalize(R::default_epsilon()) {
+                Some(normal) => v - normal * thickness,
+                None => *v,
+            },
+        ));
+
+        let boundary_edges = self.find_boundary_edges();
+        let mut triangles = Vec::with_capacity(2 * self.triangles.len() + 2 * boundary_edges.len());
+        triangles.extend(self.triangles.iter().copied());
+        triangles.extend(self.triangles.iter().map(|tri| {
+            [
+                tri[0] + num_vertices,
+                tri[2] + num_vertices,
+                tri[1] + num_vertices,
+            ]
+        }));
+
+        // Close the shell along the boundary with two triangles per boundary edge
+        for ([v0, v1], _, _) in boundary_edges {
+            triangles.push([v1, v0, v0 + num_vertices]);
+            triangles.push([v1, v0 + num_vertices, v1 + num_vertices]);
         }
 
-        // Take only the faces which have a count of 1, which correspond to boundary faces
-        edge_counts
-            .into_iter()
-            .map(|(_edge, value)| value)
-            .filter(|&(_, count)| count == 1)
-            .map(move |(edge_idx, _)| edge_info[edge_idx].clone())
-            .collect()
+        TriMesh3d {
+            vertices,
+            triangles,
+        }
     }
-}
 
-#[test]
-fn test_find_boundary() {
-    // TODO: Needs a test with a real mesh
-    let mesh = TriMesh3d::<f64> {
-        vertices: vec![
-            Vector3::new_random(),
-            Vector3::new_random(),
-            Vector3::new_random(),
-        ],
-        triangles: vec![[0, 1, 2]],
-    };
+    /// Computes the discrete mean curvature at every vertex of the mesh using the cotangent Laplace-Beltrami operator
+    ///
+    /// The mean curvature normal of a vertex is evaluated with the cotangent weights of its one-ring
+    /// and normalized by the mixed Voronoi area of the vertex (Meyer et al. 2003, "Discrete
+    /// Differential-Geometry Operators for Triangulated 2-Manifolds"). For obtuse triangles, which have
+    /// no well-defined Voronoi region, half of the triangle area is assigned to the obtuse vertex and a
+    /// quarter to each of the other two vertices. The sign of the curvature is given with respect to the
+    /// area weighted vertex normals, i.e. it is positive for convex regions of surfaces with outward
+    /// facing normals (a sphere of radius `r` has a mean curvature of `1/r`).
+    ///
+    /// The operator is not defined for vertices on the boundary of the mesh or on non-manifold edges,
+    /// as their one-ring is incomplete. For these vertices, as well as for vertices without
+    /// non-degenerate adjacent triangles, a curvature of zero is returned.
+    pub fn mean_curvature(&self) -> Vec<R> {
+        let num_vertices = self.vertices.len();
+        let mut laplacians = vec![Vector3::<R>::zeros(); num_vertices];
+        let mut areas = vec![R::zero(); num_vertices];
+
+        let half = R::from_f64(0.5).unwrap();
+        let quarter = R::from_f64(0.25).unwrap();
+        let eighth = R::from_f64(0.125).unwrap();
+
+        for tri in self.triangles.iter() {
+            let p = [
+                self.vertices[tri[0]],
+                self.vertices[tri[1]],
+                self.vertices[tri[2]],
+            ];
+
+            let double_area = (p[1] - p[0]).cross(&(p[2] - p[0])).norm();
+            if double_area <= R::default_epsilon() {
+                continue;
+            }
+            let area = double_area * half;
 
-    let mut boundary = mesh.find_boundary_edges();
-    boundary.sort_unstable();
+            // Cotangent of the interior angle at every corner
+            let cot = [0, 1, 2].map(|c| {
+                let a = p[(c + 1) % 3] - p[c];
+                let b = p[(c + 2) % 3] - p[c];
+                a.dot(&b) / double_area
+            });
 
-    assert_eq!(
-        boundary,
-        vec![
-            ([0usize, 1usize], 0, 0),
-            ([1usize, 2usize], 0, 1),
-            ([2usize, 0usize], 0, 2),
-        ]
-    );
-}
+            // Contribution to the cotangent Laplacian of the edge opposite of every corner
+            for (c, &cot_c) in cot.iter().enumerate() {
+                let i = (c + 1) % 3;
+                let j = (c + 2) % 3;
+                let edge = (p[i] - p[j]) * cot_c;
+                laplacians[tri[i]] += edge;
+                laplacians[tri[j]] -= edge;
+            }
 
-/// Wrapper type for meshes with attached point or cell data
-#[derive(Clone, Debug)]
-pub struct MeshWithData<R: Real, MeshT: Mesh3d<R>> {
-    /// The mesh geometry itself
-    pub mesh: MeshT,
-    /// Data attached to each vertex or point of the mesh
-    pub point_attributes: Vec<MeshAttribute<R>>,
-    /// Data attached to each cell of the mesh
-    pub cell_attributes: Vec<MeshAttribute<R>>,
-}
+            // Mixed Voronoi area
+            if let Some(obtuse) = (0..3).find(|&c| cot[c] < R::zero()) {
+                for c in 0..3 {
+                    areas[tri[c]] += if c == obtuse {
+                        area * half
+                    } else {
+                        area * quarter
+                    };
+                }
+            } else {
+                for c in 0..3 {
+                    let j = (c + 1) % 3;
+                    let k = (c + 2) % 3;
+                    areas[tri[c]] += ((p[c] - p[j]).norm_squared() * cot[k]
+                        + (p[c] - p[k]).norm_squared() * cot[j])
+                        * eighth;
+                }
+            }
+        }
 
-/// Returns an mesh data wrapper with a default mesh and without attached attributes
-impl<R: Real, MeshT: Mesh3d<R> + Default> Default for MeshWithData<R, MeshT> {
-    fn default() -> Self {
-        Self::new(MeshT::default())
+        let mut is_boundary = vec![false; num_vertices];
+        for (edge, &count) in self.edge_triangle_counts().iter() {
+            if count != 2 {
+                is_boundary[edge[0]] = true;
+                is_boundary[edge[1]] = true;
+            }
+        }
+
+        let normal_directions = self.vertex_normal_directions();
+        (0..num_vertices)
+            .map(|i| {
+                if is_boundary[i] || areas[i] <= R::zero() {
+                    return R::zero();
+                }
+
+                // Mean curvature normal is half of the area normalized Laplacian
+                let curvature_normal = laplacians[i] * (quarter / areas[i]);
+                match normal_directions[i].try_normalize(R::default_epsilon()) {
+                    Some(normal) => curvature_normal.dot(&normal),
+                    None => curvature_normal.norm(),
+                }
+            })
+            .collect()
     }
-}
 
-impl<R: Real, MeshT: Mesh3d<R>> MeshWithData<R, MeshT> {
-    /// Wraps the given mesh such that point and cell data can be attached
-    pub fn new(mesh: MeshT) -> Self {
-        Self {
-            mesh,
-            point_attributes: Vec::new(),
-            cell_attributes: Vec::new(),
+    /// Returns the index of the connected component of every triangle and the total number of components
+    ///
+    /// Triangles are connected if they share a vertex. Components are numbered in the order of their first triangle.
+    fn triangle_components(&self) -> (Vec<usize>, usize) {
+        // Union-find over the vertices of the mesh
+        let mut parents: Vec<usize> = (0..self.vertices.len()).collect();
+        fn find(parents: &mut [usize], mut v: usize) -> usize {
+            while parents[v] != v {
+                parents[v] = parents[parents[v]];
+                v = parents[v];
+            }
+            v
         }
-    }
 
-    /// Attaches an attribute to the points of the mesh, panics if the length of the data does not match the mesh's number of points
-    pub fn with_point_data(mut self, point_attribute: impl Into<MeshAttribute<R>>) -> Self {
-        let point_attribute = point_attribute.into();
-        assert_eq!(point_attribute.data.len(), self.mesh.vertices().len());
-        self.point_attributes.push(point_attribute);
-        self
-    }
+        for tri in self.triangles.iter() {
+            let root = find(&mut parents, tri[0]);
+            for &v in tri[1..].iter() {
+                let other = find(&mut parents, v);
+                parents[other] = root;
+            }
+        }
 
-    /// Attaches an attribute to the cells of the mesh, panics if the length of the data does not match the mesh's number of cells
-    pub fn with_cell_data(mut self, cell_attribute: impl Into<MeshAttribute<R>>) -> Self {
-        let cell_attribute = cell_attribute.into();
-        assert_eq!(cell_attribute.data.len(), self.mesh.cells().len());
-        self.cell_attributes.push(cell_attribute);
-        self
+        let mut component_ids = vec![None; self.vertices.len()];
+        let mut num_components = 0;
+        let triangle_components = self
+            .triangles
+            .iter()
+            .map(|tri| {
+                let root = find(&mut parents, tri[0]);
+                *component_ids[root].get_or_insert_with(|| {
+                    num_components += 1;
+                    num_components - 1
+                })
+            })
+            .collect();
+
+        (triangle_components, num_components)
     }
-}
 
-impl<R: Real> MeshAttribute<R> {
-    /// Creates a new named mesh attribute with the given data
-    pub fn new(name: &'static str, data: impl Into<AttributeData<R>>) -> Self {
-        Self {
-            name,
-            data: data.into(),
+    /// Splits the mesh into its connected components, each with its own re-indexed vertices
+    ///
+    /// Triangles are considered connected if they share a vertex. The components are returned in the order
+    /// of their first triangle and the order of the triangles within a component is preserved. Vertices that
+    /// are not referenced by any triangle are dropped.
+    pub fn connected_components(&self) -> Vec<TriMesh3d<R>> {
+        let (triangle_components, num_components) = self.triangle_components();
+
+        // Every vertex belongs to exactly one component, so a single index map is sufficient
+        let mut new_indices = vec![None; self.vertices.len()];
+        let mut components = vec![TriMesh3d::default(); num_components];
+        for (tri, &component) in self.triangles.iter().zip(triangle_components.iter()) {
+            let component: &mut TriMesh3d<R> = &mut components[component];
+            let mut new_tri = [0; 3];
+            for (new_v, &v) in new_tri.iter_mut().zip(tri.iter()) {
+                *new_v = *new_indices[v].get_or_insert_with(|| {
+                    component.vertices.push(self.vertices[v]);
+                    component.vertices.len() - 1
+                });
+            }
+            component.triangles.push(new_tri);
         }
+
+        components
     }
 
-    /// Creates a new named mesh attribute with scalar values implementing the [`Real`](crate::Real) trait
-    pub fn new_real_scalar(name: &'static str, data: impl Into<Vec<R>>) -> Self {
-        Self {
-            name,
-            data: AttributeData::ScalarReal(data.into()),
+    /// Removes all connected components with less than the given number of triangles, returns the number of removed components
+    ///
+    /// Triangles are considered connected if they share a vertex. The order of the remaining triangles is
+    /// preserved. Afterwards, vertices that are no longer referenced are removed from the mesh, so vertex
+    /// indices are not preserved.
+    pub fn remove_small_components(&mut self, min_triangles: usize) -> usize {
+        let (triangle_components, num_components) = self.triangle_components();
+
+        let mut num_triangles = vec![0; num_components];
+        for &component in triangle_components.iter() {
+            num_triangles[component] += 1;
         }
-    }
+
+        let num_removed = num_triangles.iter().filter(|&&n| n < min_triangles).count();
+        if num_removed > 0 {
+            let mut tri_idx = 0;
+            self.triangles.retain(|_| {
+                tri_idx += 1;
+                num_triangles[triangle_components[tri_idx - 1]] >= min_triangles
+            });
+            self.remove_unreferenced_vertices();
+        }
+
+        num_removed
+    }
+
+    /// Keeps only the `n` largest connected components of the mesh and removes all other triangles
+    ///
+    /// Triangles are considered connected if they share a vertex. With [`ComponentSize::Volume`], the
+    /// absolute enclosed volume of each component is computed with the divergence theorem, which is only
+    /// meaningful for closed (watertight) components. Components of equal size are ranked by the order of
+    /// their first triangle in the triangle list, i.e. the component that appears first is kept.
+    ///
+    /// The order of the remaining triangles is preserved. Afterwards, vertices that are no longer
+    /// referenced are removed from the mesh, so vertex indices are not preserved.
+    pub fn keep_largest_n_components(&mut self, n: usize, by: ComponentSize) {
+        let (triangle_components, num_components) = self.triangle_components();
+        if num_components <= n {
+            return;
+        }
+
+        let mut sizes = vec![R::zero(); num_components];
+        for (tri, &component) in self.triangles.iter().zip(triangle_components.iter()) {
+            sizes[component] += match by {
+                ComponentSize::Triangles => R::one(),
+                ComponentSize::Volume => {
+                    let [v0, v1, v2] = [
+                        self.vertices[tri[0]],
+                        self.vertices[tri[1]],
+                        self.vertices[tri[2]],
+                    ];
+                    v0.dot(&v1.cross(&v2)) / R::from_f64(6.0).unwrap()
+                }
+            };
+        }
+
+        // Stable sort by decreasing size, ties keep the order of the first triangle of the components
+        let mut ranking: Vec<usize> = (0..num_components).collect();
+        ranking.sort_by(|&a, &b| {
+            sizes[b]
+                .abs()
+                .partial_cmp(&sizes[a].abs())
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+
+        let mut keep = vec![false; num_components];
+        for &component in ranking.iter().take(n) {
+            keep[component] = true;
+        }
+
+        let mut tri_idx = 0;
+        self.triangles.retain(|_| {
+            tri_idx += 1;
+            keep[triangle_components[tri_idx - 1]]
+        });
+        self.remove_unreferenced_vertices();
+    }
+
+    /// Removes all vertices that are not referenced by any triangle and updates the triangle connectivity accordingly
+    ///
+    /// The remaining vertices are reordered in the order of their first occurrence in the triangle list.
+    pub fn remove_unreferenced_vertices(&mut self) {
+        let TriMesh3d {
+            vertices,
+            triangles,
+        } = self;
+
+        let mut new_indices = vec![None; vertices.len()];
+        let mut new_vertices = Vec::with_capacity(vertices.len());
+        for tri in triangles.iter_mut() {
+            for v in tri.iter_mut() {
+                let new_index = *new_indices[*v].get_or_insert_with(|| {
+                    new_vertices.push(vertices[*v]);
+                    new_vertices.len() - 1
+                });
+                *v = new_index;
+            }
+        }
+        *vertices = new_vertices;
+    }
+
+    /// Merges all vertices that are closer than the given distance, returns the number of removed vertices
+    ///
+    /// The vertices are bucketed in a spatial hash grid with cells of size `epsilon`. Each vertex is merged into
+    /// the first previously visited vertex within a distance of `epsilon`, if there is any. Triangles that become
+    /// degenerate (i.e. refer to the same vertex more than once) are removed. Afterwards, vertices that are no
+    /// longer referenced are removed from the mesh, so vertex indices are not preserved.
+    ///
+    /// Returns an error without modifying the mesh if `epsilon` is not positive and finite or if the coordinates
+    /// of a vertex cannot be bucketed (e.g. non-finite coordinates).
+    pub fn weld_vertices(&mut self, epsilon: R) -> Result<usize, WeldVerticesError<R>> {
+        if !(epsilon > R::zero() && epsilon.is_finite()) {
+            return Err(WeldVerticesError::InvalidDistance(epsilon));
+        }
+        let epsilon_squared = epsilon * epsilon;
+        let num_vertices = self.vertices.len();
+
+        let bucket = |index: usize, v: &Vector3<R>| -> Result<[i64; 3], WeldVerticesError<R>> {
+            let mut bucket = [0; 3];
+            for (b, &c) in bucket.iter_mut().zip(v.iter()) {
+                *b = (c / epsilon)
+                    .floor()
+                    .to_i64()
+                    .ok_or(WeldVerticesError::InvalidVertex { index, vertex: *v })?;
+            }
+            Ok(bucket)
+        };
+
+        // Buckets of the vertices that are kept
+        let mut buckets: MapType<[i64; 3], Vec<usize>> = new_map();
+        let mut merged_into = (0..num_vertices).collect::<Vec<_>>();
+        for (i, v) in self.vertices.iter().enumerate() {
+            let b = bucket(i, v)?;
+
+            let mut existing = None;
+            'search: for dx in -1..=1 {
+                for dy in -1..=1 {
+                    for dz in -1..=1 {
+                        let neighbor_bucket = [
+                            b[0].saturating_add(dx),
+                            b[1].saturating_add(dy),
+                            b[2].saturating_add(dz),
+                        ];
+                        if let Some(candidates) = buckets.get(&neighbor_bucket) {
+                            if let Some(&j) = candidates.iter().find(|&&j| {
+                                (self.vertices[j] - v).norm_squared() <= epsilon_squared
+                            }) {
+                                existing = Some(j);
+                                break 'search;
+                            }
+                        }
+                    }
+                }
+            }
+
+            match existing {
+                Some(j) => merged_into[i] = j,
+                None => buckets.entry(b).or_default().push(i),
+            }
+        }
+
+        for tri in self.triangles.iter_mut() {
+            for v in tri.iter_mut() {
+                *v = merged_into[*v];
+            }
+        }
+        self.triangles
+            .retain(|tri| tri[0] != tri[1] && tri[1] != tri[2] && tri[2] != tri[0]);
+        self.remove_unreferenced_vertices();
+
+        Ok(num_vertices - self.vertices.len())
+    }
+
+    /// Collapses all edges shorter than the given length to remove sliver triangles, returns the number of collapsed edges
+    ///
+    /// Edges are collapsed in the order of increasing length by moving both vertices of the edge to its
+    /// midpoint and removing the two triangles adjacent to the edge. An edge is only collapsed if
+    ///  - both of its vertices are interior vertices of a manifold region (boundary and non-manifold edges are preserved),
+    ///  - the one-rings of the vertices share exactly two vertices (i.e. the collapse does not change the topology) and
+    ///  - none of the remaining adjacent triangles is flipped or becomes degenerate by the collapse.
+    ///
+    /// Collapses are performed in several passes until no further edge can be collapsed. Afterwards,
+    /// vertices that are no longer referenced are removed from the mesh, so vertex indices are not preserved.
+    pub fn collapse_short_edges(&mut self, min_length: R) -> usize {
+        let min_length_squared = min_length * min_length;
+
+        let mut num_collapsed = 0;
+        loop {
+            let num_collapsed_pass = self.collapse_short_edges_pass(min_length_squared);
+            if num_collapsed_pass == 0 {
+                break;
+            }
+            num_collapsed += num_collapsed_pass;
+        }
+
+        if num_collapsed > 0 {
+            self.remove_unreferenced_vertices();
+        }
+
+        num_collapsed
+    }
+
+    /// Performs a single pass of [`Self::collapse_short_edges`] where each vertex is affected by at most one collapse
+    fn collapse_short_edges_pass(&mut self, min_length_squared: R) -> usize {
+        let edge_counts = self.edge_triangle_counts();
+
+        // Vertices of boundary or non-manifold edges must not be moved
+        let mut locked = vec![false; self.vertices.len()];
+        for (edge, &count) in edge_counts.iter() {
+            if count != 2 {
+                locked[edge[0]] = true;
+                locked[edge[1]] = true;
+            }
+        }
+
+        let mut short_edges: Vec<_> = edge_counts
+            .keys()
+            .map(|&[v0, v1]| {
+                (
+                    (self.vertices[v1] - self.vertices[v0]).norm_squared(),
+                    [v0, v1],
+                )
+            })
+            .filter(|&(length_squared, _)| length_squared < min_length_squared)
+            .collect();
+        if short_edges.is_empty() {
+            return 0;
+        }
+        short_edges.sort_unstable_by(|a, b| {
+            a.0.partial_cmp(&b.0)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then(a.1.cmp(&b.1))
+        });
+
+        let mut vertex_triangles = vec![Vec::new(); self.vertices.len()];
+        for (tri_idx, tri) in self.triangles.iter().enumerate() {
+            for &v in tri.iter() {
+                vertex_triangles[v].push(tri_idx);
+            }
+        }
+
+        // The one-rings of all vertices that are not locked remain valid during the pass
+        let adjacency = self.vertex_adjacency();
+
+        let mut removed_triangles = vec![false; self.triangles.len()];
+        let mut num_collapsed = 0;
+        for (_, [v0, v1]) in short_edges {
+            if locked[v0] || locked[v1] {
+                continue;
+            }
+
+            // Link condition: the collapse must not create non-manifold edges
+            let ring0 = adjacency.neighbors(v0);
+            let ring1 = adjacency.neighbors(v1);
+            let num_common = ring0
+                .iter()
+                .filter(|v| ring1.binary_search(v).is_ok())
+                .count();
+            if num_common != 2 {
+                continue;
+            }
+
+            // The collapse must not flip or degenerate any of the remaining triangles
+            let new_position = (self.vertices[v0] + self.vertices[v1]) * R::from_f64(0.5).unwrap();
+            let vertices = &self.vertices;
+            let triangles = &self.triangles;
+            let is_valid = vertex_triangles[v0]
+                .iter()
+                .chain(vertex_triangles[v1].iter())
+                .map(|&t| &triangles[t])
+                .filter(|tri| !(tri.contains(&v0) && tri.contains(&v1)))
+                .all(|tri| {
+                    let old = [vertices[tri[0]], vertices[tri[1]], vertices[tri[2]]];
+                    let mut new = old;
+                    for (i, &v) in tri.iter().enumerate() {
+                        if v == v0 || v == v1 {
+                            new[i] = new_position;
+                        }
+                    }
+
+                    let old_normal = (old[1] - old[0]).cross(&(old[2] - old[0]));
+                    let new_normal = (new[1] - new[0]).cross(&(new[2] - new[0]));
+                    new_normal.dot(&old_normal) > R::zero()
+                });
+            if !is_valid {
+                continue;
+            }
+
+            // Collapse the edge into the first vertex
+            self.vertices[v0] = new_position;
+            for &t in vertex_triangles[v1].iter() {
+                let tri = &mut self.triangles[t];
+                if tri.contains(&v0) {
+                    removed_triangles[t] = true;
+                } else {
+                    for v in tri.iter_mut().filter(|v| **v == v1) {
+                        *v = v0;
+                    }
+                }
+            }
+
+            // Lock the neighborhood as the adjacency information is not updated
+            locked[v0] = true;
+            locked[v1] = true;
+            for &v in ring0.iter().chain(ring1.iter()) {
+                locked[v] = true;
+            }
+
+            num_collapsed += 1;
+        }
+
+        let mut tri_idx = 0;
+        self.triangles.retain(|_| {
+            tri_idx += 1;
+            !removed_triangles[tri_idx - 1]
+        });
+
+        num_collapsed
+    }
+
+    /// Projects the points onto the surface of the mesh, returns the closest surface point and the index of its triangle for each point
+    ///
+    /// A [`TriangleBvh`](crate::bvh::TriangleBvh) of the mesh is built once for all queries, which are then
+    /// evaluated in parallel. If several triangles are equally close to a point (e.g. if the closest point is a
+    /// vertex or on an edge), any of them may be returned. Panics if the mesh has no triangles.
+    pub fn project_points(&self, points: &[Vector3<R>]) -> Vec<(Vector3<R>, usize)> {
+        profile!("project_points");
+        assert!(
+            !self.triangles.is_empty(),
+            "cannot project points onto a mesh without triangles"
+        );
+
+        let bvh = TriangleBvh::new(self);
+        points
+            .par_iter()
+            .map(|point| {
+                let nearest = bvh
+                    .nearest_point(point)
+                    .expect("BVH of a non-empty mesh has to return a nearest point");
+                (nearest.point, nearest.triangle)
+            })
+            .collect()
+    }
+
+    /// Computes the one-ring vertex adjacency of the mesh
+    ///
+    /// The returned structure can be computed once and reused by all operations that require the
+    /// neighborhood of the vertices, see [`VertexAdjacency`] for the ordering of the neighbors.
+    pub fn vertex_adjacency(&self) -> VertexAdjacency {
+        VertexAdjacency::from_edges(
+            self.vertices.len(),
+            self.triangles
+                .iter()
+                .flat_map(|tri| (0..3).map(move |i| [tri[i], tri[(i + 1) % 3]])),
+        )
+    }
+
+    /// Returns a map from each unique (undirected) edge of the mesh to the number of triangles adjacent to it
+    fn edge_triangle_counts(&self) -> MapType<[usize; 2], usize> {
+        let mut edge_counts = new_map();
+        for tri in self.triangles.iter() {
+            for &(v0, v1) in [(tri[0], tri[1]), (tri[1], tri[2]), (tri[2], tri[0])].iter() {
+                let edge = if v0 < v1 { [v0, v1] } else { [v1, v0] };
+                *edge_counts.entry(edge).or_insert(0) += 1;
+            }
+        }
+        edge_counts
+    }
+
+    /// Returns all boundary edges of the mesh
+    ///
+    /// Returns edges which are only connected to exactly one triangle, along with the connected triangle
+    /// index and the local index of the edge within that triangle.
+    ///
+    /// Note that the output order is not necessarily deterministic due to the internal use of hashmaps.
+    pub fn find_boundary_edges(&self) -> Vec<([usize; 2], usize, usize)> {
+        let mut sorted_edges = Vec::new();
+        let mut edge_info = Vec::new();
+
+        // Local indices into the triangle connectivity to obtain all edges
+        let tri_edges: [(usize, usize); 3] = [(0, 1), (1, 2), (2, 0)];
+
+        // For each triangle collect
+        //  - each edge (with sorted vertices to use as unique key)
+        //  - each edge with the index of the triangle and local index in the triangle
+        for (tri_idx, tri_conn) in self.triangles.iter().enumerate() {
+            for (local_idx, (v0, v1)) in tri_edges
+                .iter()
+                .copied()
+                .map(|(i0, i1)| (tri_conn[i0], tri_conn[i1]))
+                .enumerate()
+            {
+                // Sort the edge
+                if v0 < v1 {
+                    sorted_edges.push([v0, v1])
+                } else {
+                    sorted_edges.push([v1, v0])
+                };
+
+                edge_info.push(([v0, v1], tri_idx, local_idx));
+            }
+        }
+
+        // Count the number of occurrences of "equivalent" edges (in the sense that they refer
+        // to the same vertex indices).
+        let mut edge_counts = new_map();
+        for (edge_idx, edge) in sorted_edges.iter().copied().enumerate() {
+            edge_counts
+                .entry(edge)
+                .and_modify(|(_, count)| *count += 1)
+                .or_insert((edge_idx, 1));
+        }
+
+        // Take only the faces which have a count of 1, which correspond to boundary faces
+        edge_counts
+            .into_iter()
+            .map(|(_edge, value)| value)
+            .filter(|&(_, count)| count == 1)
+            .map(move |(edge_idx, _)| edge_info[edge_idx].clone())
+            .collect()
+    }
+
+    /// Converts the mesh into a quad-dominant mesh by merging pairs of adjacent coplanar triangles into quads
+    ///
+    /// Two triangles are merged if they share a manifold edge with consistent orientation, their normals deviate
+    /// by less than `1e-3` radians and the resulting quad is strictly convex. Triangle pairs with longer shared
+    /// edges are merged first, such that the diagonals of e.g. a triangulated regular grid are removed. All
+    /// triangles that cannot be merged are kept. The vertices of the mesh are not modified.
+    pub fn to_quad_dominant(&self) -> QuadMesh3d<R> {
+        profile!("to_quad_dominant");
+
+        let vertices = &self.vertices;
+        let normals = self
+            .triangles
+            .iter()
+            .map(|tri| {
+                (vertices[tri[1]] - vertices[tri[0]]).cross(&(vertices[tri[2]] - vertices[tri[0]]))
+            })
+            .collect::<Vec<_>>();
+
+        // Map from directed edges to the triangle and local index of the edge, `None` for non-manifold edges
+        let mut directed_edges: MapType<[usize; 2], Option<(usize, usize)>> = new_map();
+        for (tri_idx, tri) in self.triangles.iter().enumerate() {
+            for (i, &v0) in tri.iter().enumerate() {
+                directed_edges
+                    .entry([v0, tri[(i + 1) % 3]])
+                    .and_modify(|e| *e = None)
+                    .or_insert(Some((tri_idx, i)));
+            }
+        }
+
+        let max_angle_cos = R::from_f64(1e-3).unwrap().cos();
+        let is_convex = |quad: &[usize; 4], normal: &Vector3<R>| {
+            (0..4).all(|k| {
+                let e0 = vertices[quad[(k + 1) % 4]] - vertices[quad[k]];
+                let e1 = vertices[quad[(k + 2) % 4]] - vertices[quad[(k + 1) % 4]];
+                e0.cross(&e1).dot(normal) > R::zero()
+            })
+        };
+
+        // Collect all pairs of triangles that can be merged together with the squared length of their shared edge
+        let mut candidates = Vec::new();
+        for (&[a, b], &first) in directed_edges.iter() {
+            let (t1, i1, t2, i2) = match (first, directed_edges.get(&[b, a])) {
+                (Some((t1, i1)), Some(&Some((t2, i2)))) if t1 < t2 => (t1, i1, t2, i2),
+                _ => continue,
+            };
+
+            let (n1, n2) = (normals[t1], normals[t2]);
+            let norms = n1.norm() * n2.norm();
+            if norms <= R::zero() || n1.dot(&n2) < max_angle_cos * norms {
+                continue;
+            }
+
+            // The first triangle is (a, b, c) and the second triangle is (b, a, d)
+            let c = self.triangles[t1][(i1 + 2) % 3];
+            let d = self.triangles[t2][(i2 + 2) % 3];
+            let quad = [b, c, a, d];
+            if c == d || !is_convex(&quad, &(n1 + n2)) {
+                continue;
+            }
+
+            let edge_length_squared = (vertices[b] - vertices[a]).norm_squared();
+            candidates.push((edge_length_squared, t1, t2, quad));
+        }
+
+        // Merge greedily starting with the longest shared edges (ties are broken by triangle indices for determinism)
+        candidates.sort_unstable_by(|x, y| {
+            y.0.partial_cmp(&x.0)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| (x.1, x.2).cmp(&(y.1, y.2)))
+        });
+
+        let mut merged = vec![false; self.triangles.len()];
+        let mut quads = vec![None; self.triangles.len()];
+        for (_, t1, t2, quad) in candidates {
+            if !merged[t1] && !merged[t2] {
+                merged[t1] = true;
+                merged[t2] = true;
+                quads[t1] = Some(quad);
+            }
+        }
+
+        let cells = self
+            .triangles
+            .iter()
+            .zip(merged.iter().zip(quads.iter()))
+            .filter_map(|(tri, (&is_merged, quad))| match quad {
+                Some(quad) => Some(QuadMeshCell::Quad(*quad)),
+                None if is_merged => None,
+                None => Some(QuadMeshCell::Triangle(*tri)),
+            })
+            .collect();
+
+        QuadMesh3d {
+            vertices: self.vertices.clone(),
+            cells,
+        }
+    }
+}
+
+#[test]
+fn test_volume_and_surface_area() {
+    let unit_cube = |offset: Vector3<f64>| {
+        let mut vertices = Vec::new();
+        for &z in [0.0, 1.0].iter() {
+            for &(x, y) in [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)].iter() {
+                vertices.push(Vector3::new(x, y, z) + offset);
+            }
+        }
+        HexMesh3d {
+            vertices,
+            cells: vec![[0, 1, 2, 3, 4, 5, 6, 7]],
+        }
+        .to_trimesh()
+    };
+
+    // The volume does not depend on the position of the mesh relative to the origin
+    for offset in [Vector3::zeros(), Vector3::new(-3.0, 2.5, 10.0)].iter() {
+        let mut cube = unit_cube(*offset);
+        assert!((cube.signed_volume() - 1.0).abs() < 1e-12);
+        assert!((cube.surface_area() - 6.0).abs() < 1e-12);
+
+        // Flipping the orientation flips the sign of the volume
+        for tri in cube.triangles.iter_mut() {
+            tri.swap(1, 2);
+        }
+        assert!((cube.signed_volume() + 1.0).abs() < 1e-12);
+        assert!((cube.surface_area() - 6.0).abs() < 1e-12);
+    }
+}
+
+#[test]
+fn test_hexmesh_to_trimesh() {
+    let vertices = vec![
+        Vector3::new(0.0, 0.0, 0.0),
+        Vector3::new(1.0, 0.0, 0.0),
+        Vector3::new(1.0, 1.0, 0.0),
+        Vector3::new(0.0, 1.0, 0.0),
+        Vector3::new(0.0, 0.0, 1.0),
+        Vector3::new(1.0, 0.0, 1.0),
+        Vector3::new(1.0, 1.0, 1.0),
+        Vector3::new(0.0, 1.0, 1.0),
+    ];
+    let hexmesh = HexMesh3d::<f64> {
+        vertices,
+        cells: vec![[0, 1, 2, 3, 4, 5, 6, 7]],
+    };
+
+    let trimesh = hexmesh.to_trimesh();
+    assert_eq!(trimesh.vertices.len(), 8);
+    assert_eq!(trimesh.triangles.len(), 12);
+
+    let center = Vector3::new(0.5, 0.5, 0.5);
+    for triangle in trimesh.triangles.iter() {
+        assert!(triangle.iter().all(|&v| v < 8));
+
+        // All normals point away from the center of the cell
+        let (a, b, c) = (
+            trimesh.vertices[triangle[0]],
+            trimesh.vertices[triangle[1]],
+            trimesh.vertices[triangle[2]],
+        );
+        let normal = (b - a).cross(&(c - a));
+        let triangle_center = (a + b + c) / 3.0;
+        assert!(normal.dot(&(triangle_center - center)) > 0.0);
+    }
+
+    // The surface of the cell is closed
+    let mut edge_counts = std::collections::HashMap::new();
+    for triangle in trimesh.triangles.iter() {
+        for i in 0..3 {
+            let edge = (triangle[i], triangle[(i + 1) % 3]);
+            *edge_counts.entry(edge).or_insert(0) += 1;
+        }
+    }
+    for (&(a, b), &count) in edge_counts.iter() {
+        assert_eq!(count, 1);
+        assert_eq!(edge_counts.get(&(b, a)), Some(&1));
+    }
+}
+
+#[test]
+fn test_to_quad_dominant_flat_grid() {
+    // Regular grid of n x n squares, each split into two triangles along its diagonal
+    let n = 4;
+    let vertex_index = |i: usize, j: usize| i * (n + 1) + j;
+    let mut mesh = TriMesh3d::<f64>::default();
+    for i in 0..=n {
+        for j in 0..=n {
+            mesh.vertices.push(Vector3::new(i as f64, j as f64, 0.0));
+        }
+    }
+    for i in 0..n {
+        for j in 0..n {
+            let (v00, v10, v11, v01) = (
+                vertex_index(i, j),
+                vertex_index(i + 1, j),
+                vertex_index(i + 1, j + 1),
+                vertex_index(i, j + 1),
+            );
+            mesh.triangles.push([v00, v10, v11]);
+            mesh.triangles.push([v00, v11, v01]);
+        }
+    }
+
+    let quad_mesh = mesh.to_quad_dominant();
+    assert_eq!(quad_mesh.vertices.len(), mesh.vertices.len());
+    assert_eq!(quad_mesh.num_quads(), n * n);
+    assert_eq!(quad_mesh.num_triangles(), 0);
+
+    // Every quad has to be one of the grid squares, i.e. must not contain a diagonal
+    for cell in quad_mesh.cells.iter() {
+        if let QuadMeshCell::Quad(quad) = cell {
+            for (k, &v0) in quad.iter().enumerate() {
+                let edge = quad_mesh.vertices[quad[(k + 1) % 4]] - quad_mesh.vertices[v0];
+                assert!((edge.norm() - 1.0).abs() < 1e-12);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_to_quad_dominant_keeps_triangles() {
+    // Two triangles that are not coplanar
+    let folded = TriMesh3d::<f64> {
+        vertices: vec![
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(0.5, 1.0, 0.0),
+            Vector3::new(0.5, -1.0, 1.0),
+        ],
+        triangles: vec![[0, 1, 2], [1, 0, 3]],
+    };
+    let quad_mesh = folded.to_quad_dominant();
+    assert_eq!(quad_mesh.num_quads(), 0);
+    assert_eq!(
+        quad_mesh.cells,
+        vec![
+            QuadMeshCell::Triangle([0, 1, 2]),
+            QuadMeshCell::Triangle([1, 0, 3])
+        ]
+    );
+
+    // Two coplanar triangles forming a non-convex quad
+    let dart = TriMesh3d::<f64> {
+        vertices: vec![
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(3.0, 1.0, 0.0),
+            Vector3::new(3.0, -1.0, 0.0),
+        ],
+        triangles: vec![[0, 1, 2], [1, 0, 3]],
+    };
+    let quad_mesh = dart.to_quad_dominant();
+    assert_eq!(quad_mesh.num_quads(), 0);
+    assert_eq!(quad_mesh.num_triangles(), 2);
+
+    // The same triangles merged into a convex quad
+    let mut kite = dart.clone();
+    kite.vertices[1] = Vector3::new(4.0, 0.0, 0.0);
+    let quad_mesh = kite.to_quad_dominant();
+    assert_eq!(quad_mesh.cells, vec![QuadMeshCell::Quad([1, 2, 0, 3])]);
+}
+
+#[test]
+fn test_vertex_normals_planar() {
+    let mut mesh = TriMesh3d::<f64> {
+        vertices: vec![
+            Vector3::new(0.0, 0.0, 1.0),
+            Vector3::new(1.0, 0.0, 1.0),
+            Vector3::new(1.0, 1.0, 1.0),
+            Vector3::new(0.0, 1.0, 1.0),
+            Vector3::new(0.5, 0.0, 1.0),
+        ],
+        triangles: vec![[0, 1, 2], [0, 2, 3]],
+    };
+    // A degenerate triangle with collinear vertices
+    mesh.triangles.push([0, 4, 1]);
+
+    let normals = mesh.vertex_normals();
+    for normal in &normals[0..4] {
+        assert!((normal.into_inner() - Vector3::z()).norm() < 1e-12);
+    }
+    assert_eq!(normals[4].into_inner(), Vector3::zeros());
+    assert_eq!(mesh.par_vertex_normals(), normals);
+}
+
+#[test]
+fn test_weld_vertices() {
+    // Two triangles of a quad where the second triangle uses a duplicate of a shared vertex
+    let mut mesh = TriMesh3d::<f64> {
+        vertices: vec![
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(1.0, 1.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            Vector3::new(1.0 + 1e-8, 1.0, 0.0),
+        ],
+        triangles: vec![[0, 1, 2], [0, 4, 3]],
+    };
+    let triangle_positions = |mesh: &TriMesh3d<f64>| {
+        mesh.triangles
+            .iter()
+            .map(|tri| tri.iter().map(|&v| mesh.vertices[v]).collect())
+            .collect::<Vec<Vec<_>>>()
+    };
+
+    assert_eq!(mesh.weld_vertices(1e-6), Ok(1));
+    assert_eq!(mesh.vertices.len(), 4);
+    assert_eq!(mesh.triangles, vec![[0, 1, 2], [0, 2, 3]]);
+    assert_eq!(
+        triangle_positions(&mesh),
+        vec![
+            vec![Vector3::zeros(), Vector3::x(), Vector3::new(1.0, 1.0, 0.0)],
+            vec![Vector3::zeros(), Vector3::new(1.0, 1.0, 0.0), Vector3::y()],
+        ]
+    );
+
+    // Triangles collapsing to degenerate triangles are removed
+    let mut sliver = TriMesh3d::<f64> {
+        vertices: vec![
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(1.0, 1e-8, 0.0),
+        ],
+        triangles: vec![[0, 1, 2]],
+    };
+    assert_eq!(sliver.weld_vertices(1e-6), Ok(3));
+    assert!(sliver.triangles.is_empty());
+    assert!(sliver.vertices.is_empty());
+
+    // Invalid distances and coordinates are reported without modifying the mesh
+    let mut invalid = TriMesh3d::<f64> {
+        vertices: vec![
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(f64::NAN, 1.0, 0.0),
+        ],
+        triangles: vec![[0, 1, 2]],
+    };
+    assert_eq!(
+        invalid.weld_vertices(0.0),
+        Err(WeldVerticesError::InvalidDistance(0.0))
+    );
+    assert!(matches!(
+        invalid.weld_vertices(1e-6),
+        Err(WeldVerticesError::InvalidVertex { index: 2, .. })
+    ));
+    assert_eq!(invalid.vertices.len(), 3);
+    assert_eq!(invalid.triangles, vec![[0, 1, 2]]);
+}
+
+#[test]
+fn test_manifold_info() {
+    let mut mesh = TriMesh3d::<f64> {
+        vertices: vec![
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            Vector3::new(0.0, 0.0, 1.0),
+            Vector3::new(0.0, 0.0, -1.0),
+        ],
+        triangles: vec![[0, 1, 2]],
+    };
+
+    // A single triangle is open
+    let info = mesh.manifold_info();
+    assert_eq!(info.num_edges, 3);
+    assert_eq!(info.num_boundary_edges, 3);
+    assert_eq!(info.num_non_manifold_edges, 0);
+    assert!(!info.is_closed());
+    assert!(info.is_manifold());
+
+    // A tetrahedron is closed and manifold
+    mesh.triangles = vec![[0, 2, 1], [0, 1, 3], [1, 2, 3], [0, 3, 2]];
+    let info = mesh.manifold_info();
+    assert_eq!(info.num_edges, 6);
+    assert!(info.is_watertight());
+
+    // A third triangle at the edge (0, 1) makes it non-manifold
+    mesh.triangles.push([0, 1, 4]);
+    let info = mesh.manifold_info();
+    assert_eq!(info.num_boundary_edges, 2);
+    assert_eq!(info.num_non_manifold_edges, 1);
+    assert!(!info.is_closed());
+    assert!(!info.is_manifold());
+}
+
+#[test]
+fn test_find_boundary() {
+    // TODO: Needs a test with a real mesh
+    let mut rng = crate::random::rng_from_seed(0);
+    let mut new_random = || Vector3::from_distribution(&rand::distributions::Standard, &mut rng);
+    let mesh = TriMesh3d::<f64> {
+        vertices: vec![new_random(), new_random(), new_random()],
+        triangles: vec![[0, 1, 2]],
+    };
+
+    let mut boundary = mesh.find_boundary_edges();
+    boundary.sort_unstable();
+
+    assert_eq!(
+        boundary,
+        vec![
+            ([0usize, 1usize], 0, 0),
+            ([1usize, 2usize], 0, 1),
+            ([2usize, 0usize], 0, 2),
+        ]
+    );
+}
+
+#[test]
+fn test_euler_characteristic_and_genus() {
+    let triangle = TriMesh3d::<f64> {
+        vertices: vec![Vector3::zeros(), Vector3::x(), Vector3::y()],
+        triangles: vec![[0, 1, 2]],
+    };
+
+    assert_eq!(triangle.euler_characteristic(), 1);
+    assert_eq!(triangle.genus(), None);
+
+    let tetrahedron = TriMesh3d::<f64> {
+        vertices: vec![Vector3::zeros(), Vector3::x(), Vector3::y(), Vector3::z()],
+        triangles: vec![[0, 2, 1], [0, 1, 3], [1, 2, 3], [0, 3, 2]],
+    };
+
+    assert_eq!(tetrahedron.euler_characteristic(), 2);
+    assert_eq!(tetrahedron.genus(), Some(0));
+}
+
+#[test]
+fn test_offset_shell() {
+    // Open surface: a single triangle becomes a closed triangular prism
+    let triangle = TriMesh3d::<f64> {
+        vertices: vec![Vector3::zeros(), Vector3::x(), Vector3::y()],
+        triangles: vec![[0, 1, 2]],
+    };
+
+    let shell = triangle.offset_shell(0.1);
+    assert_eq!(shell.vertices.len(), 6);
+    assert_eq!(shell.triangles.len(), 2 + 2 * 3);
+    for (outer, inner) in shell.vertices[0..3].iter().zip(shell.vertices[3..6].iter()) {
+        assert!((outer - inner - Vector3::new(0.0, 0.0, 0.1)).norm() < 1e-12);
+    }
+    assert_eq!(shell.genus(), Some(0));
+
+    // Closed surface: the shell consists of two nested surfaces without connecting triangles
+    let tetrahedron = TriMesh3d::<f64> {
+        vertices: vec![Vector3::zeros(), Vector3::x(), Vector3::y(), Vector3::z()],
+        triangles: vec![[0, 2, 1], [0, 1, 3], [1, 2, 3], [0, 3, 2]],
+    };
+
+    let shell = tetrahedron.offset_shell(0.01);
+    assert_eq!(shell.vertices.len(), 8);
+    assert_eq!(shell.triangles.len(), 8);
+    assert_eq!(shell.euler_characteristic(), 4);
+}
+
+#[test]
+fn test_collapse_short_edges() {
+    // Unit square where the center vertex was split into two close vertices, resulting in two slivers
+    let mut mesh = TriMesh3d::<f64> {
+        vertices: vec![
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(1.0, 1.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            Vector3::new(0.5, 0.5, 0.0),
+            Vector3::new(0.5, 0.51, 0.0),
+        ],
+        triangles: vec![
+            [0, 1, 4],
+            [1, 5, 4],
+            [1, 2, 5],
+            [2, 3, 5],
+            [3, 4, 5],
+            [3, 0, 4],
+        ],
+    };
+
+    // Ratio of the triangle area to the area of an equilateral triangle with the same sum of squared edge lengths
+    let min_quality = |mesh: &TriMesh3d<f64>| {
+        mesh.triangles
+            .iter()
+            .map(|tri| {
+                let [a, b, c] = [
+                    mesh.vertices[tri[0]],
+                    mesh.vertices[tri[1]],
+                    mesh.vertices[tri[2]],
+                ];
+                let area = 0.5 * (b - a).cross(&(c - a)).norm();
+                let sum_squared =
+                    (b - a).norm_squared() + (c - b).norm_squared() + (a - c).norm_squared();
+                4.0 * 3.0f64.sqrt() * area / sum_squared
+            })
+            .fold(f64::INFINITY, f64::min)
+    };
+    let total_area = |mesh: &TriMesh3d<f64>| {
+        mesh.triangles
+            .iter()
+            .map(|tri| {
+                let [a, b, c] = [
+                    mesh.vertices[tri[0]],
+                    mesh.vertices[tri[1]],
+                    mesh.vertices[tri[2]],
+                ];
+                0.5 * (b - a).cross(&(c - a)).norm()
+            })
+            .sum::<f64>()
+    };
+
+    let quality_before = min_quality(&mesh);
+    assert!(quality_before < 0.05);
+
+    // No edge is shorter than the threshold
+    assert_eq!(mesh.collapse_short_edges(0.001), 0);
+    assert_eq!(mesh.triangles.len(), 6);
+
+    assert_eq!(mesh.collapse_short_edges(0.1), 1);
+    assert_eq!(mesh.vertices.len(), 5);
+    assert_eq!(mesh.triangles.len(), 4);
+    assert!(min_quality(&mesh) > 0.5);
+    assert!((total_area(&mesh) - 1.0).abs() < 1e-12);
+    assert_eq!(mesh.euler_characteristic(), 1);
+}
+
+/// Returns a sphere mesh with the given radius obtained by repeated subdivision of an octahedron
+#[cfg(test)]
+fn subdivided_octahedron_sphere(radius: f64, subdivisions: usize) -> TriMesh3d<f64> {
+    let mut vertices = vec![
+        Vector3::x(),
+        -Vector3::x(),
+        Vector3::y(),
+        -Vector3::y(),
+        Vector3::z(),
+        -Vector3::z(),
+    ];
+    let mut triangles = vec![
+        [4, 0, 2],
+        [4, 2, 1],
+        [4, 1, 3],
+        [4, 3, 0],
+        [5, 2, 0],
+        [5, 1, 2],
+        [5, 3, 1],
+        [5, 0, 3],
+    ];
+    for _ in 0..subdivisions {
+        let mut midpoints: MapType<[usize; 2], usize> = new_map();
+        let mut midpoint = |a: usize, b: usize, vertices: &mut Vec<Vector3<f64>>| {
+            *midpoints.entry([a.min(b), a.max(b)]).or_insert_with(|| {
+                vertices.push((vertices[a] + vertices[b]).normalize());
+                vertices.len() - 1
+            })
+        };
+
+        let mut subdivided = Vec::with_capacity(4 * triangles.len());
+        for &[a, b, c] in triangles.iter() {
+            let ab = midpoint(a, b, &mut vertices);
+            let bc = midpoint(b, c, &mut vertices);
+            let ca = midpoint(c, a, &mut vertices);
+            subdivided.extend_from_slice(&[[a, ab, ca], [ab, b, bc], [ca, bc, c], [ab, bc, ca]]);
+        }
+        triangles = subdivided;
+    }
+
+    TriMesh3d {
+        vertices: vertices.into_iter().map(|v| v * radius).collect(),
+        triangles,
+    }
+}
+
+#[test]
+fn test_mean_curvature() {
+    // Sphere of radius 2 from a subdivided octahedron
+    let radius = 2.0;
+    let sphere = subdivided_octahedron_sphere(radius, 3);
+    for h in sphere.mean_curvature() {
+        assert!((h - 1.0 / radius).abs() < 0.01 / radius);
+    }
+
+    // Flat mesh with boundary
+    let square = TriMesh3d::<f64> {
+        vertices: vec![
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(1.0, 1.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            Vector3::new(0.4, 0.6, 0.0),
+        ],
+        triangles: vec![[0, 1, 4], [1, 2, 4], [2, 3, 4], [3, 0, 4]],
+    };
+    for h in square.mean_curvature() {
+        assert!(h.abs() < 1e-12);
+    }
+}
+
+#[test]
+fn test_project_points_onto_sphere() {
+    let radius = 2.0;
+    let sphere = subdivided_octahedron_sphere(radius, 4);
+
+    // Points inside and outside of the sphere
+    let mut rng = crate::random::rng_from_seed(42);
+    let points: Vec<Vector3<f64>> = (0..200)
+        .map(|_| {
+            Vector3::from_distribution(&rand::distributions::Standard, &mut rng) * 8.0
+                - Vector3::repeat(4.0)
+        })
+        .filter(|p: &Vector3<f64>| p.norm() > 0.5)
+        .collect();
+
+    let projected = sphere.project_points(&points);
+    assert_eq!(projected.len(), points.len());
+
+    // Maximum distance between the sphere and the planes of its triangles
+    let max_deviation = 0.01 * radius;
+    for (point, (surface_point, triangle)) in points.iter().zip(projected.iter()) {
+        // The projected point lands on the sphere...
+        assert!((surface_point.norm() - radius).abs() < max_deviation);
+        // ...in the direction of the query point...
+        assert!((surface_point.normalize() - point.normalize()).norm() < 0.15);
+        // ...and on the returned triangle
+        let tri = sphere.triangles[*triangle];
+        let corners = [
+            sphere.vertices[tri[0]],
+            sphere.vertices[tri[1]],
+            sphere.vertices[tri[2]],
+        ];
+        let on_triangle = crate::bvh::closest_point_on_triangle(&corners, surface_point);
+        assert!((on_triangle - surface_point).norm() < 1e-10);
+
+        // No vertex of the mesh is closer than the projected point
+        let distance = (surface_point - point).norm();
+        assert!(sphere
+            .vertices
+            .iter()
+            .all(|v| (v - point).norm() >= distance - 1e-10));
+    }
+}
+
+#[test]
+fn test_mesh_with_data_attribute_length() {
+    let mesh = HexMesh3d::<f64> {
+        vertices: vec![Vector3::zeros(); 8],
+        cells: vec![[0, 1, 2, 3, 4, 5, 6, 7]],
+    };
+
+    let mesh = MeshWithData::new(mesh);
+    assert_eq!(
+        mesh.clone()
+            .try_with_cell_data(MeshAttribute::new_real_scalar("value", vec![1.0, 2.0]))
+            .unwrap_err(),
+        MeshAttributeLengthError {
+            name: "value",
+            len: 2,
+            expected: 1,
+            kind: "cells"
+        }
+    );
+    assert!(mesh
+        .clone()
+        .try_with_point_data(MeshAttribute::new_real_scalar("value", vec![1.0]))
+        .is_err());
+
+    let mesh = mesh
+        .try_with_cell_data(MeshAttribute::new_real_scalar("value", vec![1.0]))
+        .unwrap();
+    assert_eq!(mesh.cell_attributes.len(), 1);
+}
+
+/// Wrapper type for meshes with attached point or cell data
+#[derive(Clone, Debug)]
+pub struct MeshWithData<R: Real, MeshT: Mesh3d<R>> {
+    /// The mesh geometry itself
+    pub mesh: MeshT,
+    /// Data attached to each vertex or point of the mesh
+    pub point_attributes: Vec<MeshAttribute<R>>,
+    /// Data attached to each cell of the mesh
+    pub cell_attributes: Vec<MeshAttribute<R>>,
+}
+
+/// Returns an mesh data wrapper with a default mesh and without attached attributes
+impl<R: Real, MeshT: Mesh3d<R> + Default> Default for MeshWithData<R, MeshT> {
+    fn default() -> Self {
+        Self::new(MeshT::default())
+    }
+}
+
+impl<R: Real, MeshT: Mesh3d<R>> MeshWithData<R, MeshT> {
+    /// Wraps the given mesh such that point and cell data can be attached
+    pub fn new(mesh: MeshT) -> Self {
+        Self {
+            mesh,
+            point_attributes: Vec::new(),
+            cell_attributes: Vec::new(),
+        }
+    }
+
+    /// Attaches an attribute to the points of the mesh, panics if the length of the data does not match the mesh's number of points
+    pub fn with_point_data(self, point_attribute: impl Into<MeshAttribute<R>>) -> Self {
+        self.try_with_point_data(point_attribute)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Attaches an attribute to the cells of the mesh, panics if the length of the data does not match the mesh's number of cells
+    pub fn with_cell_data(self, cell_attribute: impl Into<MeshAttribute<R>>) -> Self {
+        self.try_with_cell_data(cell_attribute)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Attaches an attribute to the points of the mesh, returns an error if the length of the data does not match the mesh's number of points
+    pub fn try_with_point_data(
+        mut self,
+        point_attribute: impl Into<MeshAttribute<R>>,
+    ) -> Result<Self, MeshAttributeLengthError> {
+        let point_attribute = point_attribute.into();
+        point_attribute.check_len(self.mesh.vertices().len(), "points")?;
+        self.point_attributes.push(point_attribute);
+        Ok(self)
+    }
+
+    /// Attaches an attribute to the cells of the mesh, returns an error if the length of the data does not match the mesh's number of cells
+    pub fn try_with_cell_data(
+        mut self,
+        cell_attribute: impl Into<MeshAttribute<R>>,
+    ) -> Result<Self, MeshAttributeLengthError> {
+        let cell_attribute = cell_attribute.into();
+        cell_attribute.check_len(self.mesh.cells().len(), "cells")?;
+        self.cell_attributes.push(cell_attribute);
+        Ok(self)
+    }
+}
+
+impl<R: Real> MeshAttribute<R> {
+    /// Creates a new named mesh attribute with the given data
+    pub fn new(name: &'static str, data: impl Into<AttributeData<R>>) -> Self {
+        Self {
+            name,
+            data: data.into(),
+        }
+    }
+
+    /// Creates a new named mesh attribute with scalar values implementing the [`Real`](crate::Real) trait
+    pub fn new_real_scalar(name: &'static str, data: impl Into<Vec<R>>) -> Self {
+        Self {
+            name,
+            data: AttributeData::ScalarReal(data.into()),
+        }
+    }
 
     /// Creates a new named mesh attribute with scalar values implementing the [`Real`](crate::Real) trait
     pub fn new_real_vector3(name: &'static str, data: impl Into<Vec<Vector3<R>>>) -> Self {
@@ -543,6 +2206,25 @@ impl<R: Real> MeshAttribute<R> {
         }
     }
 
+    /// Returns an error if the number of entries of the attribute does not match the expected length
+    fn check_len(
+        &self,
+        expected: usize,
+        kind: &'static str,
+    ) -> Result<(), MeshAttributeLengthError> {
+        let len = self.data.len();
+        if len == expected {
+            Ok(())
+        } else {
+            Err(MeshAttributeLengthError {
+                name: self.name,
+                len,
+                expected,
+                kind,
+            })
+        }
+    }
+
     /// Converts the mesh attribute to a [`vtkio::model::Attribute`](https://docs.rs/vtkio/0.6.*/vtkio/model/enum.Attribute.html)
     #[cfg(feature = "vtk_extras")]
     #[cfg_attr(doc_cfg, doc(cfg(feature = "vtk_extras")))]
@@ -625,8 +2307,8 @@ pub mod vtk_helper {
     use vtkio::IOBuffer;
 
     use super::{
-        CellConnectivity, HexCell, HexMesh3d, Mesh3d, PointCell, PointCloud3d, Real, TriMesh3d,
-        TriangleCell,
+        CellConnectivity, HexCell, HexMesh3d, Mesh3d, PointCell, PointCloud3d, QuadMesh3d,
+        QuadMeshCell, Real, TriMesh3d, TriangleCell,
     };
 
     /// Trait that can be implemented by mesh cells to return the corresponding [`vtkio::model::CellType`](https://docs.rs/vtkio/0.6.*/vtkio/model/enum.CellType.html)
@@ -634,6 +2316,10 @@ pub mod vtk_helper {
     pub trait HasVtkCellType {
         /// Returns the corresponding [`vtkio::model::CellType`](https://docs.rs/vtkio/0.6.*/vtkio/model/enum.CellType.html) of the cell
         fn vtk_cell_type() -> CellType;
+        /// Returns the [`vtkio::model::CellType`](https://docs.rs/vtkio/0.6.*/vtkio/model/enum.CellType.html) of this cell, differs from [`vtk_cell_type`](Self::vtk_cell_type) only for mixed cell types
+        fn cell_vtk_type(&self) -> CellType {
+            Self::vtk_cell_type()
+        }
     }
 
     #[cfg_attr(doc_cfg, doc(cfg(feature = "vtk_extras")))]
@@ -650,6 +2336,20 @@ pub mod vtk_helper {
         }
     }
 
+    #[cfg_attr(doc_cfg, doc(cfg(feature = "vtk_extras")))]
+    impl HasVtkCellType for QuadMeshCell {
+        fn vtk_cell_type() -> CellType {
+            CellType::Quad
+        }
+
+        fn cell_vtk_type(&self) -> CellType {
+            match self {
+                QuadMeshCell::Triangle(_) => CellType::Triangle,
+                QuadMeshCell::Quad(_) => CellType::Quad,
+            }
+        }
+    }
+
     #[cfg_attr(doc_cfg, doc(cfg(feature = "vtk_extras")))]
     impl HasVtkCellType for PointCell {
         fn vtk_cell_type() -> CellType {
@@ -677,13 +2377,17 @@ pub mod vtk_helper {
         let vertices = {
             let mut vertices = Vec::with_capacity(mesh.cells().len() * (vertices_per_cell + 1));
             for cell in mesh.cells().iter() {
-                vertices.push(3);
+                vertices.push(cell.cell_num_vertices() as u32);
                 cell.for_each_vertex(|v| vertices.push(v as u32));
             }
             vertices
         };
 
-        let cell_types = vec![<MeshT::Cell as HasVtkCellType>::vtk_cell_type(); mesh.cells().len()];
+        let cell_types = mesh
+            .cells()
+            .iter()
+            .map(|cell| cell.cell_vtk_type())
+            .collect();
 
         new_unstructured_grid_piece(points, vertices, cell_types)
     }
@@ -710,6 +2414,17 @@ pub mod vtk_helper {
         }
     }
 
+    /// Creates a [`vtkio::model::UnstructuredGridPiece`](https://docs.rs/vtkio/0.6.*/vtkio/model/struct.UnstructuredGridPiece.html) representing this mesh
+    #[cfg_attr(doc_cfg, doc(cfg(feature = "vtk_extras")))]
+    impl<'a, R> From<&'a QuadMesh3d<R>> for UnstructuredGridPiece
+    where
+        R: Real,
+    {
+        fn from(mesh: &'a QuadMesh3d<R>) -> Self {
+            mesh_to_unstructured_grid(mesh)
+        }
+    }
+
     /// Creates a [`vtkio::model::UnstructuredGridPiece`](https://docs.rs/vtkio/0.6.*/vtkio/model/struct.UnstructuredGridPiece.html) representing this point cloud
     #[cfg_attr(doc_cfg, doc(cfg(feature = "vtk_extras")))]
     impl<'a, R> From<&'a PointCloud3d<R>> for UnstructuredGridPiece
@@ -737,6 +2452,14 @@ pub mod vtk_helper {
         }
     }
 
+    /// Creates a [`vtkio::model::UnstructuredGridPiece`](https://docs.rs/vtkio/0.6.*/vtkio/model/struct.UnstructuredGridPiece.html) representing this mesh and wraps it into a [`vtkio::model::DataSet`](https://docs.rs/vtkio/0.6.*/vtkio/model/enum.DataSet.html)
+    #[cfg_attr(doc_cfg, doc(cfg(feature = "vtk_extras")))]
+    impl<R: Real> From<&QuadMesh3d<R>> for DataSet {
+        fn from(mesh: &QuadMesh3d<R>) -> Self {
+            DataSet::inline(UnstructuredGridPiece::from(mesh))
+        }
+    }
+
     /// Creates a [`vtkio::model::UnstructuredGridPiece`](https://docs.rs/vtkio/0.6.*/vtkio/model/struct.UnstructuredGridPiece.html) representing this point cloud and wraps it into a [`vtkio::model::DataSet`](https://docs.rs/vtkio/0.6.*/vtkio/model/enum.DataSet.html)
     #[cfg_attr(doc_cfg, doc(cfg(feature = "vtk_extras")))]
     impl<R: Real> Into<DataSet> for &PointCloud3d<R> {
@@ -763,3 +2486,138 @@ pub mod vtk_helper {
         }
     }
 }
+
+#[test]
+fn test_par_concat() {
+    let meshes: Vec<_> = (0..50)
+        .map(|i| {
+            let n = i % 7;
+            TriMesh3d::<f64> {
+                vertices: (0..n + 3)
+                    .map(|j| Vector3::new(i as f64, j as f64, 0.0))
+                    .collect(),
+                triangles: (0..n + 1).map(|j| [j, j + 1, j + 2]).collect(),
+            }
+        })
+        .collect();
+
+    let mut serial = TriMesh3d::default();
+    for mesh in meshes.iter() {
+        serial.append(&mut mesh.clone());
+    }
+
+    let parallel = par_concat(meshes);
+    assert_eq!(parallel.vertices, serial.vertices);
+    assert_eq!(parallel.triangles, serial.triangles);
+
+    let empty = par_concat::<f64>(Vec::new());
+    assert!(empty.vertices.is_empty() && empty.triangles.is_empty());
+}
+
+#[test]
+fn test_vertex_adjacency() {
+    // Two triangles sharing the edge (1, 2) and an unreferenced vertex
+    let mesh = TriMesh3d::<f64> {
+        vertices: vec![Vector3::zeros(); 5],
+        triangles: vec![[0, 1, 2], [2, 1, 3]],
+    };
+
+    let adjacency = mesh.vertex_adjacency();
+    assert_eq!(adjacency.num_vertices(), 5);
+    assert_eq!(adjacency.neighbors(0), &[1, 2]);
+    assert_eq!(adjacency.neighbors(1), &[0, 2, 3]);
+    assert_eq!(adjacency.neighbors(2), &[0, 1, 3]);
+    assert_eq!(adjacency.neighbors(3), &[1, 2]);
+    assert!(adjacency.neighbors(4).is_empty());
+    assert_eq!(adjacency.degree(1), 3);
+    assert_eq!(adjacency.iter().map(|n| n.len()).sum::<usize>(), 10);
+    assert_eq!(adjacency.offsets(), &[0, 2, 5, 8, 10, 10]);
+}
+
+#[test]
+fn test_connected_components() {
+    // Closed tetrahedron with the given offset
+    let tetrahedron = |offset: f64| TriMesh3d::<f64> {
+        vertices: vec![
+            Vector3::new(offset, 0.0, 0.0),
+            Vector3::new(offset + 1.0, 0.0, 0.0),
+            Vector3::new(offset, 1.0, 0.0),
+            Vector3::new(offset, 0.0, 1.0),
+        ],
+        triangles: vec![[0, 2, 1], [0, 1, 3], [0, 3, 2], [1, 2, 3]],
+    };
+
+    let mut mesh = TriMesh3d::default();
+    for component in [tetrahedron(0.0), tetrahedron(5.0)].iter_mut() {
+        mesh.append(component);
+    }
+    // A single triangle sharing no vertex with the tetrahedra
+    mesh.append(&mut TriMesh3d {
+        vertices: vec![Vector3::new(-3.0, 0.0, 0.0), Vector3::y(), Vector3::z()],
+        triangles: vec![[0, 1, 2]],
+    });
+
+    let components = mesh.connected_components();
+    assert_eq!(components.len(), 3);
+    for (component, offset) in components.iter().take(2).zip([0.0, 5.0].iter()) {
+        // Vertices are numbered in the order of their first occurrence in the triangles
+        let mut expected = tetrahedron(*offset);
+        expected.remove_unreferenced_vertices();
+        assert_eq!(component.vertices, expected.vertices);
+        assert_eq!(component.triangles, expected.triangles);
+    }
+    assert_eq!(components[2].triangles, vec![[0, 1, 2]]);
+
+    assert_eq!(mesh.remove_small_components(2), 1);
+    assert_eq!(mesh.triangles.len(), 8);
+    assert_eq!(mesh.vertices.len(), 8);
+    assert_eq!(mesh.connected_components().len(), 2);
+    assert_eq!(mesh.remove_small_components(2), 0);
+}
+
+#[test]
+fn test_keep_largest_n_components() {
+    // Closed tetrahedron with the given scale and offset
+    let tetrahedron = |scale: f64, offset: f64| TriMesh3d::<f64> {
+        vertices: vec![
+            Vector3::new(offset, 0.0, 0.0),
+            Vector3::new(offset + scale, 0.0, 0.0),
+            Vector3::new(offset, scale, 0.0),
+            Vector3::new(offset, 0.0, scale),
+        ],
+        triangles: vec![[0, 2, 1], [0, 1, 3], [0, 3, 2], [1, 2, 3]],
+    };
+    // Open strip of five triangles with a small area, i.e. more triangles but less volume than a tetrahedron
+    let strip = TriMesh3d::<f64> {
+        vertices: (0..6)
+            .map(|i| Vector3::new(10.0 + (i / 2) as f64, 0.0, (i % 2) as f64) * 0.01)
+            .collect(),
+        triangles: vec![[0, 1, 2], [2, 1, 3], [2, 3, 4], [4, 3, 5], [0, 1, 3]],
+    };
+
+    let mut mesh = TriMesh3d::default();
+    for component in [tetrahedron(1.0, 0.0), strip, tetrahedron(2.0, 5.0)].iter_mut() {
+        mesh.append(component);
+    }
+
+    let mut by_triangles = mesh.clone();
+    by_triangles.keep_largest_n_components(1, ComponentSize::Triangles);
+    assert_eq!(by_triangles.triangles.len(), 5);
+    assert_eq!(by_triangles.vertices.len(), 6);
+
+    // Ties are broken by the order of the components
+    let mut by_triangles = mesh.clone();
+    by_triangles.keep_largest_n_components(2, ComponentSize::Triangles);
+    assert_eq!(by_triangles.triangles.len(), 9);
+    assert!(by_triangles.vertices.iter().all(|v| v.x <= 1.0));
+
+    let mut by_volume = mesh.clone();
+    by_volume.keep_largest_n_components(2, ComponentSize::Volume);
+    assert_eq!(by_volume.triangles.len(), 8);
+    assert_eq!(by_volume.vertices.len(), 8);
+    assert!(by_volume.vertices.iter().any(|v| v.x >= 5.0));
+
+    let mut all = mesh.clone();
+    all.keep_largest_n_components(3, ComponentSize::Volume);
+    assert_eq!(all.triangles, mesh.triangles);
+}
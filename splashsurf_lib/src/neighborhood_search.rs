@@ -367,6 +367,62 @@ pub fn compute_neigborhood_stats(neighborhood_list: &Vec<Vec<usize>>) -> Neighbo
     }
 }
 
+/// Relative neighbor count below which a particle is classified as a surface particle by [`classify_surface_particles`]
+///
+/// Particles with fewer neighbors than this fraction of the average neighbor count (of all particles with neighbors) are classified as surface particles.
+pub const SURFACE_PARTICLE_NEIGHBOR_FRACTION: f64 = 0.5;
+
+/// Center of mass offset above which a particle is classified as a surface particle by [`classify_surface_particles`] (relative to the support radius)
+///
+/// For a homogeneous neighborhood the offset vanishes, for a particle on a flat free surface it is approximately `3/8` of the support radius.
+pub const SURFACE_PARTICLE_COM_OFFSET_THRESHOLD: f64 = 0.15;
+
+/// Classifies all particles as surface (`true`) or interior (`false`) particles using a neighbor deficiency criterion
+///
+/// The neighborhood of every particle is computed with a spatial hashing neighborhood search on the domain of the given grid
+/// using the support radius as search radius. A particle is classified as a surface particle if
+///  - it has fewer than [`SURFACE_PARTICLE_NEIGHBOR_FRACTION`] times the average number of neighbors, or
+///  - the distance of the center of mass of its neighbors to the particle exceeds [`SURFACE_PARTICLE_COM_OFFSET_THRESHOLD`] times the support radius.
+///
+/// Particles without any neighbors are always classified as surface particles.
+pub fn classify_surface_particles<I: Index, R: Real>(
+    grid: &UniformGrid<I, R>,
+    particle_positions: &[Vector3<R>],
+    support_radius: R,
+) -> Vec<bool> {
+    profile!("classify_surface_particles");
+
+    let mut neighborhood_list = Vec::new();
+    neighborhood_search_spatial_hashing_parallel::<I, R>(
+        grid.aabb(),
+        particle_positions,
+        support_radius,
+        &mut neighborhood_list,
+    );
+
+    let neighbor_count_threshold = SURFACE_PARTICLE_NEIGHBOR_FRACTION
+        * compute_neigborhood_stats(&neighborhood_list).avg_neighbors;
+    let com_offset_threshold =
+        support_radius * R::from_f64(SURFACE_PARTICLE_COM_OFFSET_THRESHOLD).unwrap();
+    let com_offset_threshold_squared = com_offset_threshold * com_offset_threshold;
+
+    particle_positions
+        .par_iter()
+        .zip(neighborhood_list.par_iter())
+        .map(|(x_i, neighbors)| {
+            if neighbors.is_empty() || (neighbors.len() as f64) < neighbor_count_threshold {
+                return true;
+            }
+
+            let offset_sum = neighbors.iter().fold(Vector3::zeros(), |sum, &j| {
+                sum + (particle_positions[j] - x_i)
+            });
+            let com_offset = offset_sum / R::from_usize(neighbors.len()).unwrap();
+            com_offset.norm_squared() > com_offset_threshold_squared
+        })
+        .collect()
+}
+
 // Generates a map for spatially hashed indices of all particles (map from cell -> enclosed particles)
 #[inline(never)]
 fn sequential_generate_cell_to_particle_map<I: Index, R: Real>(
@@ -1,10 +1,10 @@
 //! Octree for spatially partitioning particle sets
 
 use crate::generic_tree::*;
-use crate::marching_cubes::SurfacePatch;
+use crate::marching_cubes::{EdgeInterpolation, SurfacePatch};
 use crate::mesh::{HexMesh3d, MeshAttribute, MeshWithData, TriMesh3d};
-use crate::topology::{Axis, Direction};
-use crate::uniform_grid::{PointIndex, UniformGrid};
+use crate::topology::{Axis, DirectedAxis, Direction};
+use crate::uniform_grid::{CellIndex, PointIndex, Rounding, UniformGrid};
 use crate::utils::{ChunkSize, ParallelPolicy};
 use crate::{
     marching_cubes, new_map, profile, AxisAlignedBoundingBox3d, GridConstructionError, Index,
@@ -16,9 +16,13 @@ use nalgebra::Vector3;
 use octant_helper::{HalfspaceFlags, Octant, OctantAxisDirections};
 use rayon::prelude::*;
 use smallvec::SmallVec;
-use split_criterion::{default_split_criterion, LeafSplitCriterion};
+use split_criterion::{default_split_criterion, LeafSplitCriterion, MaxDepthSplitCriterion};
 use std::cell::RefCell;
+use std::fmt;
+use std::iter::FusedIterator;
+use std::ops::DerefMut;
 use std::sync::atomic::{AtomicUsize, Ordering};
+use thiserror::Error as ThisError;
 use thread_local::ThreadLocal;
 
 // TODO: Make margin an Option
@@ -32,20 +36,61 @@ pub enum SubdivisionCriterion {
     MaxParticleCount(usize),
 }
 
+/// Strategy used to choose the point where an octree node is split into its eight octants
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde_crate::Serialize, serde_crate::Deserialize),
+    serde(crate = "serde_crate")
+)]
+pub enum SplitStrategy {
+    /// Split at the grid point closest to the geometric center of the node
+    GeometricMidpoint,
+    /// Split at the grid point closest to the per-axis median of the coordinates of the particles inside of the node
+    ///
+    /// Results in more balanced numbers of particles per child for non-uniform particle distributions.
+    ParticleMedian,
+}
+
+impl Default for SplitStrategy {
+    /// Returns the geometric midpoint strategy
+    fn default() -> Self {
+        Self::GeometricMidpoint
+    }
+}
+
 /// Data structure for octree based spatial subdivision of particles sets, for tree iteration/visitation use the [`root`](Self::root) [`OctreeNode`]
 #[derive(Clone, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde_crate::Serialize, serde_crate::Deserialize),
+    serde(crate = "serde_crate")
+)]
 pub struct Octree<I: Index, R: Real> {
     /// Root node of the tree
     root: OctreeNode<I, R>,
     /// Counter for assigning ids to subdivided nodes
     next_id: usize,
+    /// Maximum number of non-ghost particles per leaf used by the last subdivision of the tree
+    max_particles_per_leaf: Option<usize>,
+    /// Optional maximum depth of the nodes created by a subdivision of the tree
+    max_depth: Option<usize>,
+    /// Strategy used to choose the split points of nodes when subdividing the tree
+    split_strategy: SplitStrategy,
 }
 
 /// Represents a node in the octree hierarchy and stores child nodes, implements tree iteration/visitation from the [`generic_tree`](crate::generic_tree) module
 #[derive(Clone, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde_crate::Serialize, serde_crate::Deserialize),
+    serde(crate = "serde_crate")
+)]
 pub struct OctreeNode<I: Index, R: Real> {
     /// Id of the node used to identify it for debugging
     id: usize,
+    /// Depth of the node in the tree, zero for the root node
+    depth: usize,
     /// All child nodes of this octree node
     children: ArrayVec<Box<Self>, 8>,
     /// Lower corner point of the octree node on the background grid
@@ -56,6 +101,28 @@ pub struct OctreeNode<I: Index, R: Real> {
     aabb: AxisAlignedBoundingBox3d<R>,
     /// Additional data associated to this octree node
     data: NodeData<I, R>,
+    /// Cached centroid of the non-ghost particles in the subtree of this node, see [`Octree::cache_centroids`]
+    centroid: Option<ParticleCentroid<R>>,
+    /// Number of particles in the subtree of this node, see [`OctreeNode::particle_count`]
+    particle_count: usize,
+    /// Additional particles within a margin around the AABB of this leaf, see [`Octree::with_margin`]
+    ghost_particles: Vec<usize>,
+    /// Tight AABB of the particles in the subtree of this node, see [`Octree::compute_tight_bounds`]
+    tight_aabb: Option<AxisAlignedBoundingBox3d<R>>,
+}
+
+/// Centroid and number of the non-ghost particles in the subtree of an [`OctreeNode`]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde_crate::Serialize, serde_crate::Deserialize),
+    serde(crate = "serde_crate")
+)]
+pub struct ParticleCentroid<R: Real> {
+    /// Average position of the particles, the center of the node's AABB if it does not contain any particles
+    pub centroid: Vector3<R>,
+    /// Number of particles
+    pub count: usize,
 }
 
 impl<I: Index, R: Real> TreeNode for OctreeNode<I, R> {
@@ -73,13 +140,21 @@ impl<I: Index, R: Real> TreeNodeMut for OctreeNode<I, R> {
 }
 
 /// Optional data that may be stored in [`OctreeNode`]s
+///
+/// With the `serde` feature, nodes storing a surface patch cannot be serialized.
 #[derive(Clone, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde_crate::Serialize, serde_crate::Deserialize),
+    serde(crate = "serde_crate")
+)]
 pub enum NodeData<I: Index, R: Real> {
     /// Empty variant
     None,
     /// Storage for a set of SPH particles
     ParticleSet(ParticleSet),
     /// A patch that was already meshed
+    #[cfg_attr(feature = "serde", serde(skip))]
     SurfacePatch(SurfacePatchWrapper<I, R>),
 }
 
@@ -92,6 +167,11 @@ impl<I: Index, R: Real> Default for NodeData<I, R> {
 
 /// Stores the particle ids and the number of ghost particles inside an octree leaf
 #[derive(Clone, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde_crate::Serialize, serde_crate::Deserialize),
+    serde(crate = "serde_crate")
+)]
 pub struct ParticleSet {
     // The particles belonging to this set
     pub particles: OctreeNodeParticleStorage,
@@ -119,20 +199,275 @@ impl<I: Index, R: Real> SurfacePatchWrapper<I, R> {
 
 type OctreeNodeParticleStorage = SmallVec<[usize; 6]>;
 
+/// Error type returned by [`Octree::verify_partition`] if the particle sets of the leaves do not partition the particles
+#[derive(Clone, Debug, Eq, PartialEq, ThisError)]
+pub enum PartitionError {
+    /// A leaf contains a particle index that is not in the range of particles
+    #[error("leaf {leaf_id} contains the particle {particle} which is out of range for {n_particles} particles")]
+    ParticleOutOfRange {
+        /// Id of the leaf containing the particle
+        leaf_id: usize,
+        /// Index of the particle
+        particle: usize,
+        /// Number of particles the partition was checked against
+        n_particles: usize,
+    },
+    /// A particle is contained more than once in the leaves
+    #[error("the particle {particle} is contained more than once in the leaves (again in leaf {leaf_id})")]
+    DuplicateParticle {
+        /// Id of the leaf where the particle was encountered again
+        leaf_id: usize,
+        /// Index of the particle
+        particle: usize,
+    },
+    /// Particles are not contained in any leaf
+    #[error("{count} particles are not contained in any leaf (first missing particle: {first_particle})")]
+    MissingParticles {
+        /// Index of the first particle that is missing
+        first_particle: usize,
+        /// Number of missing particles
+        count: usize,
+    },
+}
+
+/// Borrowed view of the particles and the world space AABB of a single octree leaf, see [`Octree::leaf_views`]
+#[derive(Clone, Debug)]
+pub struct LeafView<'a, I: Index, R: Real> {
+    /// The leaf node this view refers to
+    node: &'a OctreeNode<I, R>,
+    /// Indices of all particles (including ghost particles) stored in the leaf
+    particles: &'a [usize],
+    /// AABB of the leaf in world coordinates
+    aabb: AxisAlignedBoundingBox3d<R>,
+}
+
+impl<'a, I: Index, R: Real> LeafView<'a, I, R> {
+    /// Returns a reference to the octree leaf node of this view
+    pub fn node(&self) -> &'a OctreeNode<I, R> {
+        self.node
+    }
+
+    /// Returns the indices of all particles stored in the leaf (including ghost particles)
+    pub fn particles(&self) -> &'a [usize] {
+        self.particles
+    }
+
+    /// Returns the indices of the additional ghost particles recorded for the leaf by [`Octree::with_margin`]
+    pub fn ghost_particles(&self) -> &'a [usize] {
+        self.node.ghost_particles.as_slice()
+    }
+
+    /// Returns the number of ghost particles stored in the leaf
+    pub fn ghost_particle_count(&self) -> usize {
+        self.node
+            .data
+            .particle_set()
+            .map(|ps| ps.ghost_particle_count)
+            .unwrap_or(0)
+    }
+
+    /// Returns the world space AABB of the leaf
+    pub fn aabb(&self) -> &AxisAlignedBoundingBox3d<R> {
+        &self.aabb
+    }
+}
+
+/// Summary of the structure of an octree and the distribution of particles to its leaves, see [`Octree::statistics`]
+///
+/// The particle counts of the leaves include ghost particles. Leaves that do not store a particle set
+/// (e.g. because they were already replaced by a surface patch) are counted as empty leaves.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OctreeStatistics {
+    /// Total number of nodes of the tree including the root node
+    pub num_nodes: usize,
+    /// Number of leaf nodes
+    pub num_leaves: usize,
+    /// Maximum depth of all nodes, zero if the tree only consists of the root node
+    pub max_depth: usize,
+    /// Minimum number of particles of a leaf
+    pub min_leaf_particles: usize,
+    /// Maximum number of particles of a leaf
+    pub max_leaf_particles: usize,
+    /// Average number of particles per leaf
+    pub mean_leaf_particles: f64,
+    /// Number of leaves without any particles
+    pub num_empty_leaves: usize,
+}
+
+impl fmt::Display for OctreeStatistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Octree statistics:")?;
+        writeln!(f, "  nodes: {}", self.num_nodes)?;
+        writeln!(
+            f,
+            "  leaves: {} ({} empty)",
+            self.num_leaves, self.num_empty_leaves
+        )?;
+        writeln!(f, "  max depth: {}", self.max_depth)?;
+        write!(
+            f,
+            "  particles per leaf: min {}, max {}, mean {:.2}",
+            self.min_leaf_particles, self.max_leaf_particles, self.mean_leaf_particles
+        )
+    }
+}
+
+/// Mutable view of a single octree node that allows to modify its data, see [`Octree::dfs_iter_mut`]
+///
+/// The children of the node cannot be accessed through the view, as they are yielded separately by the iterator.
+#[derive(Debug)]
+pub struct OctreeNodeMut<'a, I: Index, R: Real> {
+    /// Id of the node
+    id: usize,
+    /// Depth of the node in the tree
+    depth: usize,
+    /// Number of children of the node
+    num_children: usize,
+    /// Lower corner point of the node on the background grid
+    min_corner: &'a PointIndex<I>,
+    /// Upper corner point of the node on the background grid
+    max_corner: &'a PointIndex<I>,
+    /// AABB of the node
+    aabb: &'a AxisAlignedBoundingBox3d<R>,
+    /// Data associated to the node
+    data: &'a mut NodeData<I, R>,
+}
+
+impl<'a, I: Index, R: Real> OctreeNodeMut<'a, I, R> {
+    /// Returns the id of the node
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Returns the depth of the node in the tree, zero for the root node
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Returns whether the node is a leaf, i.e. has no children
+    pub fn is_leaf(&self) -> bool {
+        self.num_children == 0
+    }
+
+    /// Returns the [`PointIndex`] of the lower corner of the node
+    pub fn min_corner(&self) -> &'a PointIndex<I> {
+        self.min_corner
+    }
+
+    /// Returns the [`PointIndex`] of the upper corner of the node
+    pub fn max_corner(&self) -> &'a PointIndex<I> {
+        self.max_corner
+    }
+
+    /// Returns the world space AABB of the node
+    pub fn aabb(&self) -> &'a AxisAlignedBoundingBox3d<R> {
+        self.aabb
+    }
+
+    /// Returns a reference to the data stored in the node
+    pub fn data(&self) -> &NodeData<I, R> {
+        &*self.data
+    }
+
+    /// Returns a mutable reference to the data stored in the node
+    ///
+    /// Note that the cached [`particle_count`](OctreeNode::particle_count) of the node is not updated
+    /// when its particle set is modified.
+    pub fn data_mut(&mut self) -> &mut NodeData<I, R> {
+        &mut *self.data
+    }
+}
+
+/// Depth-first iterator over mutable views of the nodes of an octree returned by [`Octree::dfs_iter_mut`]
+pub struct DfsIterMut<'a, I: Index, R: Real> {
+    stack: Vec<&'a mut OctreeNode<I, R>>,
+}
+
+impl<'a, I: Index, R: Real> Iterator for DfsIterMut<'a, I, R> {
+    type Item = OctreeNodeMut<'a, I, R>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let OctreeNode {
+            id,
+            depth,
+            children,
+            min_corner,
+            max_corner,
+            aabb,
+            data,
+            ..
+        } = self.stack.pop()?;
+
+        // Borrowing the fields separately allows to hand out the data while the children stay on the stack
+        let num_children = children.len();
+        self.stack
+            .extend(children.iter_mut().rev().map(DerefMut::deref_mut));
+
+        Some(OctreeNodeMut {
+            id: *id,
+            depth: *depth,
+            num_children,
+            min_corner,
+            max_corner,
+            aabb,
+            data,
+        })
+    }
+}
+
+impl<'a, I: Index, R: Real> FusedIterator for DfsIterMut<'a, I, R> {}
+
 impl<I: Index, R: Real> Octree<I, R> {
     /// Creates a new octree with a single leaf node containing all vertices
     pub fn new(grid: &UniformGrid<I, R>, n_particles: usize) -> Self {
         Self {
             root: OctreeNode::new_root(grid, n_particles),
             next_id: 0,
+            max_particles_per_leaf: None,
+            max_depth: None,
+            split_strategy: SplitStrategy::default(),
         }
     }
 
+    /// Limits the depth of the nodes created by subsequent subdivisions of the tree
+    ///
+    /// Nodes at the maximum depth are not split any further and remain leaves holding all of their
+    /// particles, even if they contain more particles than allowed by the subdivision criterion. This
+    /// bounds the size of the tree for strongly clustered particle distributions. The root node has
+    /// a depth of zero, i.e. a maximum depth of zero prevents any subdivision.
+    pub fn with_max_depth(mut self, max_depth: Option<usize>) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Returns the maximum depth of the nodes created by subdivisions of the tree, see [`with_max_depth`](Self::with_max_depth)
+    pub fn max_depth(&self) -> Option<usize> {
+        self.max_depth
+    }
+
+    /// Sets the strategy used to choose the split points of the nodes for subsequent subdivisions of the tree
+    ///
+    /// The split points always lie on the background grid, so the children of a node are still aligned
+    /// with the grid cells regardless of the strategy.
+    pub fn with_split_strategy(mut self, split_strategy: SplitStrategy) -> Self {
+        self.split_strategy = split_strategy;
+        self
+    }
+
+    /// Returns the strategy used to choose the split points of the nodes, see [`with_split_strategy`](Self::with_split_strategy)
+    pub fn split_strategy(&self) -> SplitStrategy {
+        self.split_strategy
+    }
+
     /// Create a new octree and perform subdivision with the specified margin
     ///
     /// The margin is used to assign ghost particles to octree nodes. Each octant resulting
     /// from the subdivision gets assigned all particles that are directly inside it plus all
     /// particles from its parent that are within the given margin around the octant.
+    ///
+    /// If multi-threading is enabled, sibling nodes are subdivided concurrently and large nodes are
+    /// additionally split in parallel. The resulting tree is identical to the tree of a sequential
+    /// subdivision (including the order of the particles in every leaf), only the ids of the nodes may differ.
     pub fn new_subdivided(
         grid: &UniformGrid<I, R>,
         particle_positions: &[Vector3<R>],
@@ -164,6 +499,113 @@ impl<I: Index, R: Real> Octree<I, R> {
         tree
     }
 
+    /// Create a new octree and perform subdivision with the specified margin using a build path with a lower peak memory usage
+    ///
+    /// Results in the same tree as [`new_subdivided`](Self::new_subdivided) (up to the ids of the nodes)
+    /// but reduces the peak memory usage of the particle index storage to roughly half:
+    ///  - The particle list of the root node (containing all particles) is never stored, the children
+    ///    of the root are filled directly from the range of all particle indices.
+    ///  - No per-particle octant classification is stored during a split. Instead, the particles are
+    ///    classified again for every octant.
+    ///  - The particle list of a node is released as soon as the node is split.
+    ///
+    /// The tradeoff is that each particle is classified up to nine instead of one time per split and that
+    /// the build runs sequentially. The current in-memory build path of [`new_subdivided`](Self::new_subdivided)
+    /// keeps the root list and the classification of all particles alive during the first split, so its peak
+    /// memory usage is about two index lists of all particles. Prefer this function only for particle sets
+    /// where this overhead is significant compared to the available memory.
+    pub fn new_subdivided_low_memory(
+        grid: &UniformGrid<I, R>,
+        particle_positions: &[Vector3<R>],
+        subdivision_criterion: SubdivisionCriterion,
+        margin: R,
+        enable_stitching: bool,
+    ) -> Self {
+        profile!("octree new_subdivided_low_memory");
+
+        let n_particles = particle_positions.len();
+        let split_criterion =
+            default_split_criterion(subdivision_criterion, n_particles, enable_stitching);
+
+        let mut root = OctreeNode::new_root_with_data(grid, NodeData::None);
+        let next_id = AtomicUsize::new(0);
+
+        if split_criterion.split(&root, n_particles) {
+            root.subdivide_particles_low_memory(
+                grid,
+                particle_positions,
+                0..n_particles,
+                margin,
+                &next_id,
+            );
+            root.visit_mut_dfs(|node| {
+                // Stop recursion if split criterion is not fulfilled
+                if !split_criterion.split_leaf(node) {
+                    return;
+                }
+
+                // Take the particles out of the node to release them directly after the split
+                if let NodeData::ParticleSet(particle_set) = node.data.take() {
+                    let particles = particle_set.particles;
+                    node.subdivide_particles_low_memory(
+                        grid,
+                        particle_positions,
+                        particles.iter().copied(),
+                        margin,
+                        &next_id,
+                    );
+                }
+            });
+        } else {
+            root.data = NodeData::new_particle_set((0..n_particles).collect::<SmallVec<_>>(), 0);
+        }
+        root.update_particle_counts();
+
+        Self {
+            root,
+            next_id: next_id.into_inner(),
+            max_particles_per_leaf: Some(split_criterion.0.max_particles()),
+            max_depth: None,
+            split_strategy: SplitStrategy::GeometricMidpoint,
+        }
+    }
+
+    /// Create a new octree and perform subdivision using a precomputed assignment of the particles to the cells of the grid
+    ///
+    /// The given cell of each particle (e.g. from a previous neighborhood search) is used to route the particles to
+    /// the octants during the subdivision instead of classifying their positions relative to the split points. If
+    /// every particle lies inside of its cell (i.e. the cell is the [`enclosing_cell`](UniformGrid::enclosing_cell)
+    /// of its position), the leaves contain the same particles as a tree from [`new_subdivided`](Self::new_subdivided)
+    /// without margin. As the positions are not available, no ghost particles are assigned to the leaves and the
+    /// nodes are always split at their geometric midpoint.
+    pub fn from_cell_assignment(
+        grid: &UniformGrid<I, R>,
+        cell_indices: &[CellIndex<I>],
+        subdivision_criterion: SubdivisionCriterion,
+    ) -> Self {
+        profile!("octree from_cell_assignment");
+
+        let mut tree = Octree::new(grid, cell_indices.len());
+        let split_criterion =
+            default_split_criterion(subdivision_criterion, cell_indices.len(), false);
+        tree.max_particles_per_leaf = Some(split_criterion.0.max_particles());
+
+        let next_id = AtomicUsize::new(0);
+        tree.root.visit_mut_bfs(|node| {
+            // Stop recursion if split criterion is not fulfilled
+            if !split_criterion.split_leaf(node) {
+                return;
+            }
+
+            // Perform one octree split on the node
+            node.subdivide_with_cell_assignment(grid, cell_indices, &next_id);
+        });
+        tree.root.update_particle_counts();
+        tree.next_id = next_id.into_inner();
+
+        tree
+    }
+
     /// Returns a reference to the root node of the octree
     pub fn root(&self) -> &OctreeNode<I, R> {
         &self.root
@@ -174,6 +616,58 @@ impl<I: Index, R: Real> Octree<I, R> {
         &mut self.root
     }
 
+    /// Computes statistics about the structure of the tree and the number of particles per leaf in a single pass over the tree
+    pub fn statistics(&self) -> OctreeStatistics {
+        let mut stats = OctreeStatistics {
+            min_leaf_particles: usize::MAX,
+            ..Default::default()
+        };
+        let mut total_leaf_particles = 0;
+
+        for node in self.root.dfs_iter() {
+            stats.num_nodes += 1;
+            stats.max_depth = stats.max_depth.max(node.depth);
+
+            if node.children.is_empty() {
+                let leaf_particles = node
+                    .data
+                    .particle_set()
+                    .map(|particle_set| particle_set.particles.len())
+                    .unwrap_or(0);
+
+                stats.num_leaves += 1;
+                if leaf_particles == 0 {
+                    stats.num_empty_leaves += 1;
+                }
+                stats.min_leaf_particles = stats.min_leaf_particles.min(leaf_particles);
+                stats.max_leaf_particles = stats.max_leaf_particles.max(leaf_particles);
+                total_leaf_particles += leaf_particles;
+            }
+        }
+
+        // Every tree has at least one leaf
+        stats.mean_leaf_particles = total_leaf_particles as f64 / stats.num_leaves as f64;
+        stats
+    }
+
+    /// Returns an iterator over mutable views of all nodes in depth-first order, e.g. to modify the data of leaves in-place
+    ///
+    /// The nodes are yielded in the same order as by the [`dfs_iter`](crate::generic_tree::VisitableTree::dfs_iter)
+    /// of the root node. The structure of the tree cannot be changed through the iterator, use
+    /// [`visit_mut_dfs`](crate::generic_tree::MutVisitableTree::visit_mut_dfs) on the root node for this.
+    pub fn dfs_iter_mut(&mut self) -> DfsIterMut<'_, I, R> {
+        DfsIterMut {
+            stack: vec![&mut self.root],
+        }
+    }
+
+    /// Returns an iterator over all leaves of the tree in depth-first order
+    pub fn leaves(&self) -> impl Iterator<Item = &OctreeNode<I, R>> {
+        self.root
+            .dfs_iter()
+            .filter(|node| node.children().is_empty())
+    }
+
     /// Subdivide the octree recursively using the given splitting criterion and a margin to add ghost particles
     pub fn subdivide_recursively_margin(
         &mut self,
@@ -190,6 +684,9 @@ impl<I: Index, R: Real> Octree<I, R> {
             particle_positions.len(),
             enable_stitching,
         );
+        self.max_particles_per_leaf = Some(split_criterion.0.max_particles());
+        let split_criterion = (split_criterion, MaxDepthSplitCriterion::new(self.max_depth));
+        let split_strategy = self.split_strategy;
 
         let next_id = AtomicUsize::new(0);
         self.root.visit_mut_bfs(|node| {
@@ -199,8 +696,9 @@ impl<I: Index, R: Real> Octree<I, R> {
             }
 
             // Perform one octree split on the node
-            node.subdivide_with_margin(grid, particle_positions, margin, &next_id);
+            node.subdivide_with_margin(grid, particle_positions, margin, split_strategy, &next_id);
         });
+        self.root.update_particle_counts();
         self.next_id = next_id.into_inner();
     }
 
@@ -221,6 +719,9 @@ impl<I: Index, R: Real> Octree<I, R> {
             enable_stitching,
         );
         let parallel_policy = ParallelPolicy::default();
+        self.max_particles_per_leaf = Some(split_criterion.0.max_particles());
+        let split_criterion = (split_criterion, MaxDepthSplitCriterion::new(self.max_depth));
+        let split_strategy = self.split_strategy;
 
         let next_id = AtomicUsize::new(0);
         let visitor = {
@@ -240,12 +741,19 @@ impl<I: Index, R: Real> Octree<I, R> {
                     .len()
                     < parallel_policy.min_task_size
                 {
-                    node.subdivide_with_margin(grid, particle_positions, margin, &next_id);
+                    node.subdivide_with_margin(
+                        grid,
+                        particle_positions,
+                        margin,
+                        split_strategy,
+                        next_id,
+                    );
                 } else {
                     node.par_subdivide_with_margin(
                         grid,
                         particle_positions,
                         margin,
+                        split_strategy,
                         &parallel_policy,
                         &next_id,
                     );
@@ -254,69 +762,406 @@ impl<I: Index, R: Real> Octree<I, R> {
         };
 
         self.root.par_visit_mut_bfs(visitor);
+        self.root.update_particle_counts();
         self.next_id = next_id.into_inner();
     }
 
+    /// Merges the children of nodes back into a single leaf if the split resulted in under-full leaves, returns the number of merged nodes
+    ///
+    /// The eight children of a node are merged if all of them are leaves storing particle sets and their combined
+    /// number of non-ghost particles is below `min_fraction` times the maximum number of particles per leaf used
+    /// for the subdivision of the tree. The particle list of a merged leaf is the union of the particle lists of
+    /// its children (including ghost particles). The nodes are processed bottom-up, so merges can cascade upwards.
+    ///
+    /// As a node is only split if it contains more particles than the maximum per leaf, a `min_fraction` of one only
+    /// reverses splits that would not be performed by the current criterion. Larger fractions reverse splits of
+    /// nodes that only slightly exceeded the limit into many nearly empty leaves, at the cost of leaves with up to
+    /// `min_fraction` times the maximum number of particles. Does nothing if the tree was never subdivided.
+    pub fn balance_leaves(&mut self, min_fraction: R) -> usize {
+        profile!("octree balance_leaves");
+
+        if let Some(max_particles_per_leaf) = self.max_particles_per_leaf {
+            let min_particles = R::from_usize(max_particles_per_leaf)
+                .map(|max_particles| min_fraction * max_particles)
+                .unwrap_or_else(R::max_value);
+            let merged = self.root.merge_underfull_children(min_particles);
+            self.root.update_particle_counts();
+            merged
+        } else {
+            0
+        }
+    }
+
+    /// Merges sparse sibling leaves back into a single leaf in a bottom-up pass, returns the number of merged nodes
+    ///
+    /// Same as [`balance_leaves`](Self::balance_leaves) but with an absolute limit: the eight children of a node
+    /// are merged if all of them are leaves storing particle sets and their combined number of non-ghost particles
+    /// is below `min_particles`. The merged leaf keeps the corners of the node and stores the union of the
+    /// particle lists of its children, i.e. ghost particles contained in several children are only stored once.
+    pub fn coarsen(&mut self, min_particles: usize) -> usize {
+        profile!("octree coarsen");
+
+        let min_particles = R::from_usize(min_particles).unwrap_or_else(R::max_value);
+        let merged = self.root.merge_underfull_children(min_particles);
+        self.root.update_particle_counts();
+        merged
+    }
+
+    /// Subdivides leaves until the depths of all pairs of face-adjacent leaves differ by at most one (2:1 balancing), returns the number of subdivided leaves
+    ///
+    /// A leaf is split if one of the leaves sharing a face with it is more than one level deeper. As splitting
+    /// a leaf may violate the constraint for its own neighbors, this is repeated until no more leaves have to be
+    /// split. The depth of the tree does not increase, so the process is guaranteed to terminate. The particles
+    /// of the split leaves are distributed with the given ghost particle margin and the split strategy of the
+    /// tree, the subdivision criterion and maximum depth are not considered. Leaves that do not store a particle
+    /// set (e.g. after they were replaced by a surface patch) are not split.
+    pub fn balance(
+        &mut self,
+        grid: &UniformGrid<I, R>,
+        particle_positions: &[Vector3<R>],
+        margin: R,
+    ) -> usize {
+        profile!("octree balance");
+
+        let split_strategy = self.split_strategy;
+        let next_id = AtomicUsize::new(self.next_id);
+        let mut num_split = 0;
+
+        loop {
+            // Identify leaves by their corners, as the first child of a split leaf has the same lower corner
+            let mut unbalanced_leaves = self
+                .leaves()
+                .filter(|leaf| leaf.data.particle_set().is_some())
+                .filter(|leaf| {
+                    DirectedAxis::all_possible().iter().any(|face| {
+                        self.root
+                            .max_face_neighbor_depth(
+                                leaf.min_corner.index(),
+                                leaf.max_corner.index(),
+                                face,
+                            )
+                            .map(|depth| depth > leaf.depth + 1)
+                            .unwrap_or(false)
+                    })
+                })
+                .map(|leaf| (leaf.min_corner, leaf.max_corner))
+                .collect::<Vec<_>>();
+
+            if unbalanced_leaves.is_empty() {
+                break;
+            }
+
+            unbalanced_leaves.sort_unstable();
+            num_split += unbalanced_leaves.len();
+
+            self.root.visit_mut_dfs(|node| {
+                if node.children.is_empty()
+                    && unbalanced_leaves
+                        .binary_search(&(node.min_corner, node.max_corner))
+                        .is_ok()
+                {
+                    node.subdivide_with_margin(
+                        grid,
+                        particle_positions,
+                        margin,
+                        split_strategy,
+                        &next_id,
+                    );
+                }
+            });
+        }
+
+        self.root.update_particle_counts();
+        self.next_id = next_id.into_inner();
+        num_split
+    }
+
+    /// Computes and stores the tight AABB of the particles of every leaf and their union for every inner node, see [`OctreeNode::tight_aabb`]
+    ///
+    /// In contrast to the AABBs of the nodes given by their corners on the grid, the tight AABBs only enclose the
+    /// positions of the particles stored in the leaves (including ghost particles), e.g. for frustum culling. The
+    /// values are not updated when the tree is modified.
+    pub fn compute_tight_bounds(&mut self, particle_positions: &[Vector3<R>]) {
+        profile!("octree compute_tight_bounds");
+        self.root
+            .compute_tight_bounds_recursively(particle_positions);
+    }
+
+    /// Computes and caches the centroid and number of the non-ghost particles in the subtree of every node, see [`OctreeNode::centroid`]
+    ///
+    /// Every particle inside of the domain of the root node is a non-ghost particle of exactly one leaf, namely
+    /// the leaf it is assigned to without a margin. The cached values of an inner node therefore summarize all
+    /// particles in its subtree, e.g. for center-of-mass queries or Barnes-Hut-style approximations.
+    /// The values are not updated when the tree is modified. Nodes created by a later subdivision do not have
+    /// a cached centroid, while merging leaves with [`balance_leaves`](Self::balance_leaves) keeps the values valid.
+    pub fn cache_centroids(&mut self, grid: &UniformGrid<I, R>, particle_positions: &[Vector3<R>]) {
+        profile!("octree cache_centroids");
+
+        let root_particles = (0..particle_positions.len())
+            .filter(|&i| self.root.aabb.contains_point(&particle_positions[i]))
+            .collect();
+        self.root
+            .cache_centroids_recursively(grid, particle_positions, root_particles);
+    }
+
+    /// Returns the leaf of the octree that contains the given world space point, `None` if it lies outside of the root node's AABB
+    ///
+    /// The tree is traversed from the root by classifying the point relative to the split point of each inner node
+    /// in the same way as particles are classified during subdivision (without ghost particle margin). Therefore,
+    /// for a tree subdivided from a particle set, the returned leaf stores every particle at the queried position.
+    pub fn find_leaf_at(
+        &self,
+        grid: &UniformGrid<I, R>,
+        point: &Vector3<R>,
+    ) -> Option<&OctreeNode<I, R>> {
+        if !self.root.aabb.contains_point(point) {
+            return None;
+        }
+
+        let mut node = &self.root;
+        while !node.children.is_empty() {
+            let relative_pos = point - grid.point_coordinates(node.children_split_point());
+            let octant: Octant = OctantAxisDirections::classify(&relative_pos).into();
+            node = &node.children[octant as usize];
+        }
+
+        Some(node)
+    }
+
+    /// Records for every leaf the particles within the given margin around its AABB that are not stored in the leaf, see [`OctreeNode::ghost_particles`]
+    ///
+    /// In contrast to the margin of the subdivision, which only distributes the particles of a node to its
+    /// children, the particles are collected from all leaves of the tree, including neighboring leaves in other
+    /// branches of the tree. This allows to reconstruct each leaf independently with the full density field
+    /// inside of its AABB. A particle is within the margin if it is contained in the AABB of the leaf grown
+    /// by `margin` in every direction. Only particles inside of the domain of the root node are considered.
+    ///
+    /// The recorded particles replace the ones of a previous call and are not updated if the tree is modified.
+    pub fn with_margin(
+        &mut self,
+        grid: &UniformGrid<I, R>,
+        particle_positions: &[Vector3<R>],
+        margin: R,
+    ) {
+        profile!("octree with_margin");
+
+        let root_aabb = &self.root.aabb;
+        let ghost_particles: Vec<Vec<usize>> = self
+            .leaves()
+            .map(|leaf| {
+                let mut margin_aabb = AxisAlignedBoundingBox3d::new(
+                    grid.point_coordinates(&leaf.min_corner),
+                    grid.point_coordinates(&leaf.max_corner),
+                );
+                margin_aabb.grow_uniformly(margin);
+
+                let mut candidates = Vec::new();
+                self.root
+                    .collect_particles_in_aabb(&margin_aabb, &mut candidates);
+                candidates.retain(|&i| {
+                    let pos = &particle_positions[i];
+                    root_aabb.contains_point(pos) && margin_aabb.contains_point(pos)
+                });
+                candidates.sort_unstable();
+                candidates.dedup();
+
+                if let Some(particle_set) = leaf.data.particle_set() {
+                    let mut own_particles = particle_set.particles.to_vec();
+                    own_particles.sort_unstable();
+                    candidates.retain(|i| own_particles.binary_search(i).is_err());
+                }
+
+                candidates
+            })
+            .collect();
+
+        // The mutable visitation uses the same order as the depth-first iterator
+        let mut ghost_particles = ghost_particles.into_iter();
+        self.root.visit_mut_dfs(|node| {
+            if node.children.is_empty() {
+                node.ghost_particles = ghost_particles
+                    .next()
+                    .expect("Number of leaves changed during visitation");
+            }
+        });
+    }
+
+    /// Calls the given closure on every leaf of the tree in parallel, without collecting the leaves first
+    ///
+    /// The work is split recursively at the inner nodes using `rayon::join`. The order in which the leaves
+    /// are visited is unspecified.
+    pub fn par_visit_leaves<F>(&self, f: F)
+    where
+        F: Fn(&OctreeNode<I, R>) + Sync,
+    {
+        self.root.par_visit_leaves(&f);
+    }
+
+    /// Returns borrowed views of the particle indices and world space AABBs of all leaves that store a particle set (in depth-first order)
+    pub fn leaf_views<'a>(&'a self, grid: &'a UniformGrid<I, R>) -> Vec<LeafView<'a, I, R>> {
+        self.leaves()
+            .filter_map(|node| {
+                node.data().particle_set().map(|particle_set| LeafView {
+                    node,
+                    particles: particle_set.particles.as_slice(),
+                    aabb: AxisAlignedBoundingBox3d::new(
+                        grid.point_coordinates(&node.min_corner),
+                        grid.point_coordinates(&node.max_corner),
+                    ),
+                })
+            })
+            .collect()
+    }
+
+    /// Checks that every particle index in `0..n_particles` is contained in exactly one leaf of the octree
+    ///
+    /// Only the particle sets stored in the leaves are checked, i.e. the tree has to be subdivided without a ghost
+    /// particle margin (ghost particles are contained in several leaves and are reported as duplicates) and the leaves
+    /// must not yet be replaced by their surface patches. The indices are collected in a bitset, so in addition to
+    /// mismatching particle counts, duplicate and missing particles are detected.
+    pub fn verify_partition(&self, n_particles: usize) -> Result<(), PartitionError> {
+        const WORD_BITS: usize = u64::BITS as usize;
+        let is_set = |words: &[u64], particle: usize| {
+            words[particle / WORD_BITS] & (1 << (particle % WORD_BITS)) != 0
+        };
+
+        let mut contained = vec![0u64; n_particles.div_ceil(WORD_BITS)];
+        for leaf in self.leaves() {
+            let particle_set = match leaf.data().particle_set() {
+                Some(particle_set) => particle_set,
+                None => continue,
+            };
+
+            for &particle in particle_set.particles.iter() {
+                if particle >= n_particles {
+                    return Err(PartitionError::ParticleOutOfRange {
+                        leaf_id: leaf.id,
+                        particle,
+                        n_particles,
+                    });
+                }
+                if is_set(&contained, particle) {
+                    return Err(PartitionError::DuplicateParticle {
+                        leaf_id: leaf.id,
+                        particle,
+                    });
+                }
+                contained[particle / WORD_BITS] |= 1 << (particle % WORD_BITS);
+            }
+        }
+
+        let count = n_particles
+            - contained
+                .iter()
+                .map(|word| word.count_ones() as usize)
+                .sum::<usize>();
+        if count > 0 {
+            let first_particle = (0..n_particles).find(|&p| !is_set(&contained, p)).unwrap();
+            return Err(PartitionError::MissingParticles {
+                first_particle,
+                count,
+            });
+        }
+
+        Ok(())
+    }
+
     /// Constructs a hex mesh visualizing the cells of the octree, may contain hanging and duplicate vertices as cells are not connected
+    ///
+    /// The node id (`"node_id"`), the number of particles (`"particle_count"`) and the depth (`"depth"`) of every leaf
+    /// are attached as cell data, e.g. to visualize the subdivision structure of the tree in ParaView.
     pub fn hexmesh(
         &self,
         grid: &UniformGrid<I, R>,
         only_non_empty: bool,
     ) -> MeshWithData<R, HexMesh3d<R>> {
         profile!("convert octree into hexmesh");
+        self.hexmesh_impl(grid, only_non_empty, false)
+    }
 
+    /// Constructs a hex mesh visualizing the cells of the octree where cells share the vertices at identical grid points
+    ///
+    /// Same as [`hexmesh`](Self::hexmesh) but the vertices are deduplicated using their exact point index on the
+    /// background grid, which significantly reduces the size of the mesh for large trees. Hanging vertices
+    /// (e.g. on the face between a leaf and its smaller neighbors) are still present.
+    pub fn hexmesh_welded(
+        &self,
+        grid: &UniformGrid<I, R>,
+        only_non_empty: bool,
+    ) -> MeshWithData<R, HexMesh3d<R>> {
+        profile!("convert octree into welded hexmesh");
+        self.hexmesh_impl(grid, only_non_empty, true)
+    }
+
+    /// Constructs the hex mesh of all leaves, optionally reusing vertices of identical grid points
+    fn hexmesh_impl(
+        &self,
+        grid: &UniformGrid<I, R>,
+        only_non_empty: bool,
+        weld_vertices: bool,
+    ) -> MeshWithData<R, HexMesh3d<R>> {
         let mut mesh = HexMesh3d {
             vertices: Vec::new(),
             cells: Vec::new(),
         };
 
+        let mut vertex_map: MapType<[I; 3], usize> = new_map();
         let mut ids = Vec::new();
-        self.root.dfs_iter().for_each(|node| {
-            if node.children().is_empty() {
-                if only_non_empty
-                    && node
-                        .data()
-                        .particle_set()
-                        .map(|ps| ps.particles.is_empty())
-                        .unwrap_or(true)
-                {
-                    return;
-                }
+        let mut particle_counts = Vec::new();
+        let mut depths = Vec::new();
+        for node in self.leaves() {
+            if only_non_empty
+                && node
+                    .data()
+                    .particle_set()
+                    .map(|ps| ps.particles.is_empty())
+                    .unwrap_or(true)
+            {
+                continue;
+            }
+
+            let l = node.min_corner.index();
+            let u = node.max_corner.index();
+            let corners = [
+                [l[0], l[1], l[2]],
+                [u[0], l[1], l[2]],
+                [u[0], u[1], l[2]],
+                [l[0], u[1], l[2]],
+                [l[0], l[1], u[2]],
+                [u[0], l[1], u[2]],
+                [u[0], u[1], u[2]],
+                [l[0], u[1], u[2]],
+            ];
+
+            let mut cell = [0; 8];
+            for (vertex, corner) in cell.iter_mut().zip(corners.iter()) {
+                let mut push_vertex = || {
+                    mesh.vertices.push(grid.point_coordinates_array(corner));
+                    mesh.vertices.len() - 1
+                };
 
-                let lower_coords = grid.point_coordinates(&node.min_corner);
-                let upper_coords = grid.point_coordinates(&node.max_corner);
-
-                let vertices = vec![
-                    lower_coords,
-                    Vector3::new(upper_coords[0], lower_coords[1], lower_coords[2]),
-                    Vector3::new(upper_coords[0], upper_coords[1], lower_coords[2]),
-                    Vector3::new(lower_coords[0], upper_coords[1], lower_coords[2]),
-                    Vector3::new(lower_coords[0], lower_coords[1], upper_coords[2]),
-                    Vector3::new(upper_coords[0], lower_coords[1], upper_coords[2]),
-                    upper_coords,
-                    Vector3::new(lower_coords[0], upper_coords[1], upper_coords[2]),
-                ];
-
-                let offset = mesh.vertices.len();
-                let cell = [
-                    offset + 0,
-                    offset + 1,
-                    offset + 2,
-                    offset + 3,
-                    offset + 4,
-                    offset + 5,
-                    offset + 6,
-                    offset + 7,
-                ];
-
-                mesh.vertices.extend(vertices);
-                mesh.cells.push(cell);
-                ids.push(node.id as u64);
+                *vertex = if weld_vertices {
+                    *vertex_map.entry(*corner).or_insert_with(push_vertex)
+                } else {
+                    push_vertex()
+                };
             }
-        });
 
-        assert_eq!(mesh.cells.len(), ids.len());
-        MeshWithData::new(mesh).with_cell_data(MeshAttribute::new("node_id", ids))
+            mesh.cells.push(cell);
+            ids.push(node.id as u64);
+            particle_counts.push(
+                node.data()
+                    .particle_set()
+                    .map(|ps| ps.particles.len() as u64)
+                    .unwrap_or(0),
+            );
+            depths.push(node.depth as u64);
+        }
+
+        MeshWithData::new(mesh)
+            .with_cell_data(MeshAttribute::new("node_id", ids))
+            .with_cell_data(MeshAttribute::new("particle_count", particle_counts))
+            .with_cell_data(MeshAttribute::new("depth", depths))
     }
 }
 
@@ -327,10 +1172,17 @@ impl<I: Index, R: Real> OctreeNode<I, R> {
         max_corner: PointIndex<I>,
         aabb: AxisAlignedBoundingBox3d<R>,
     ) -> Self {
-        Self::with_data(id, min_corner, max_corner, aabb, NodeData::None)
+        Self::with_data(id, 0, min_corner, max_corner, aabb, NodeData::None)
     }
 
     fn new_root(grid: &UniformGrid<I, R>, n_particles: usize) -> Self {
+        Self::new_root_with_data(
+            grid,
+            NodeData::new_particle_set((0..n_particles).collect::<SmallVec<_>>(), 0),
+        )
+    }
+
+    fn new_root_with_data(grid: &UniformGrid<I, R>, data: NodeData<I, R>) -> Self {
         let n_points = grid.points_per_dim();
         let min_point = [I::zero(), I::zero(), I::zero()];
         let max_point = [
@@ -340,30 +1192,42 @@ impl<I: Index, R: Real> OctreeNode<I, R> {
         ];
 
         Self::with_data(
+            0,
             0,
             grid.get_point(min_point)
                 .expect("Cannot get lower corner of grid"),
             grid.get_point(max_point)
                 .expect("Cannot get upper corner of grid"),
             grid.aabb().clone(),
-            NodeData::new_particle_set((0..n_particles).collect::<SmallVec<_>>(), 0),
+            data,
         )
     }
 
     fn with_data(
         id: usize,
+        depth: usize,
         min_corner: PointIndex<I>,
         max_corner: PointIndex<I>,
         aabb: AxisAlignedBoundingBox3d<R>,
         data: NodeData<I, R>,
     ) -> Self {
+        let particle_count = data
+            .particle_set()
+            .map(|particle_set| particle_set.particles.len())
+            .unwrap_or(0);
+
         Self {
             id,
+            depth,
             children: Default::default(),
             min_corner,
             max_corner,
             aabb,
             data,
+            centroid: None,
+            particle_count,
+            ghost_particles: Vec::new(),
+            tight_aabb: None,
         }
     }
 
@@ -372,6 +1236,30 @@ impl<I: Index, R: Real> OctreeNode<I, R> {
         self.id
     }
 
+    /// Returns the depth of the node in the tree, zero for the root node
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Returns the number of particles in the subtree of this node
+    ///
+    /// For a leaf this is the length of its particle list (including ghost particles), for an inner node the sum of
+    /// the counts of its children. Therefore, ghost particles are counted once per leaf they are stored in, and
+    /// without a ghost particle margin the count of the root node equals the total number of particles. The count is
+    /// updated by the subdivision functions of the [`Octree`] and kept when the particles of a leaf are replaced
+    /// by a surface patch.
+    pub fn particle_count(&self) -> usize {
+        self.particle_count
+    }
+
+    /// Returns the indices of the additional ghost particles of this leaf recorded by [`Octree::with_margin`]
+    ///
+    /// These particles are not part of the particle set of the leaf but lie within the margin around its AABB,
+    /// the slice is empty if the margin was not computed.
+    pub fn ghost_particles(&self) -> &[usize] {
+        self.ghost_particles.as_slice()
+    }
+
     /// Returns a reference to the data stored in the node
     pub fn data(&self) -> &NodeData<I, R> {
         &self.data
@@ -393,10 +1281,30 @@ impl<I: Index, R: Real> OctreeNode<I, R> {
     }
 
     /// Returns the AABB represented by this octree node
+    ///
+    /// The AABB is given in world space coordinates, i.e. its corners are the coordinates of the
+    /// [`min_corner`](Self::min_corner) and [`max_corner`](Self::max_corner) points on the background grid
+    /// of the octree, e.g. for culling or visualization of the nodes.
     pub fn aabb(&self) -> &AxisAlignedBoundingBox3d<R> {
         &self.aabb
     }
 
+    /// Returns the cached centroid and number of the non-ghost particles in the subtree of this node
+    ///
+    /// Only populated by [`Octree::cache_centroids`], returns `None` if it was not called for the tree or if
+    /// this node was created by a subdivision afterwards.
+    pub fn centroid(&self) -> Option<&ParticleCentroid<R>> {
+        self.centroid.as_ref()
+    }
+
+    /// Returns the tight AABB of the particles in the subtree of this node
+    ///
+    /// Only populated by [`Octree::compute_tight_bounds`], returns `None` if it was not called for the tree,
+    /// if this node was created afterwards or if there are no particles in its subtree.
+    pub fn tight_aabb(&self) -> Option<&AxisAlignedBoundingBox3d<R>> {
+        self.tight_aabb.as_ref()
+    }
+
     /// Constructs a [`UniformGrid`](crate::UniformGrid) that represents the domain of this octree node
     pub fn grid(
         &self,
@@ -421,6 +1329,7 @@ impl<I: Index, R: Real> OctreeNode<I, R> {
         grid: &UniformGrid<I, R>,
         particle_positions: &[Vector3<R>],
         margin: R,
+        split_strategy: SplitStrategy,
         next_id: &AtomicUsize,
     ) {
         // Convert node body from Leaf to Children
@@ -428,8 +1337,8 @@ impl<I: Index, R: Real> OctreeNode<I, R> {
             let particles = particle_set.particles;
 
             // Obtain the point used as the octree split/pivot point
-            let split_point = get_split_point(grid, &self.min_corner, &self.max_corner)
-                .expect("Failed to get split point of octree node");
+            let split_point =
+                self.compute_split_point(grid, particle_positions, &particles, split_strategy);
             let split_coordinates = grid.point_coordinates(&split_point);
 
             let mut halfspace_flags = vec![HalfspaceFlags::empty(); particles.len()];
@@ -506,6 +1415,7 @@ impl<I: Index, R: Real> OctreeNode<I, R> {
 
                 let child = Box::new(OctreeNode::with_data(
                     next_id.fetch_add(1, Ordering::SeqCst),
+                    self.depth + 1,
                     min_corner,
                     max_corner,
                     child_aabb,
@@ -520,17 +1430,450 @@ impl<I: Index, R: Real> OctreeNode<I, R> {
 
             // Assign new children to the current node
             self.children = children;
+            self.particle_count = self.children_particle_count();
         } else {
             panic!("Only nodes with ParticleSet data can be subdivided");
         };
     }
 
+    /// Computes the tight AABBs of this node and its descendants, see [`Octree::compute_tight_bounds`]
+    fn compute_tight_bounds_recursively(
+        &mut self,
+        particle_positions: &[Vector3<R>],
+    ) -> Option<&AxisAlignedBoundingBox3d<R>> {
+        self.tight_aabb = if self.children.is_empty() {
+            self.data
+                .particle_set()
+                .filter(|particle_set| !particle_set.particles.is_empty())
+                .map(|particle_set| {
+                    let mut aabb = AxisAlignedBoundingBox3d::from_point(
+                        particle_positions[particle_set.particles[0]],
+                    );
+                    for &i in particle_set.particles.iter().skip(1) {
+                        aabb.join_with_point(&particle_positions[i]);
+                    }
+                    aabb
+                })
+        } else {
+            let mut union: Option<AxisAlignedBoundingBox3d<R>> = None;
+            for child in self.children.iter_mut() {
+                if let Some(child_aabb) = child.compute_tight_bounds_recursively(particle_positions)
+                {
+                    match union.as_mut() {
+                        Some(union) => union.join(child_aabb),
+                        None => union = Some(child_aabb.clone()),
+                    }
+                }
+            }
+            union
+        };
+
+        self.tight_aabb.as_ref()
+    }
+
+    /// Caches the centroids of this node and its descendants given the non-ghost particles of this node, see [`Octree::cache_centroids`]
+    fn cache_centroids_recursively(
+        &mut self,
+        grid: &UniformGrid<I, R>,
+        particle_positions: &[Vector3<R>],
+        particles: Vec<usize>,
+    ) -> (Vector3<R>, usize) {
+        let (sum, count) = if self.children.is_empty() {
+            let sum = particles
+                .iter()
+                .fold(Vector3::zeros(), |sum, &i| sum + particle_positions[i]);
+            (sum, particles.len())
+        } else {
+            assert_eq!(
+                self.children.len(),
+                8,
+                "octree nodes have to be split into eight octants"
+            );
+
+            // Assign the particles to the children like the subdivision without margin
+            let split_coordinates = grid.point_coordinates(self.children_split_point());
+
+            let mut octant_particles: [Vec<usize>; 8] = Default::default();
+            for i in particles {
+                let relative_pos = particle_positions[i] - split_coordinates;
+                let octant: Octant = OctantAxisDirections::classify(&relative_pos).into();
+                octant_particles[octant as usize].push(i);
+            }
+
+            // Children are stored in the order of their octants
+            self.children
+                .iter_mut()
+                .zip(octant_particles.iter_mut())
+                .map(|(child, particles)| {
+                    child.cache_centroids_recursively(
+                        grid,
+                        particle_positions,
+                        std::mem::take(particles),
+                    )
+                })
+                .fold(
+                    (Vector3::zeros(), 0),
+                    |(sum, count), (child_sum, child_count)| (sum + child_sum, count + child_count),
+                )
+        };
+
+        let centroid = if count > 0 {
+            sum / R::from_usize(count).unwrap()
+        } else {
+            self.aabb.centroid()
+        };
+        self.centroid = Some(ParticleCentroid { centroid, count });
+
+        (sum, count)
+    }
+
+    /// Returns the point used to split this node into its eight children according to the given strategy
+    fn compute_split_point(
+        &self,
+        grid: &UniformGrid<I, R>,
+        particle_positions: &[Vector3<R>],
+        particles: &[usize],
+        split_strategy: SplitStrategy,
+    ) -> PointIndex<I> {
+        let midpoint = get_split_point(grid, &self.min_corner, &self.max_corner)
+            .expect("Failed to get split point of octree node");
+
+        match split_strategy {
+            SplitStrategy::GeometricMidpoint => midpoint,
+            SplitStrategy::ParticleMedian => {
+                get_median_split_point(grid, self, particle_positions, particles)
+                    .unwrap_or(midpoint)
+            }
+        }
+    }
+
+    /// Returns the split point of an inner node, i.e. the corner shared by all of its children
+    fn children_split_point(&self) -> &PointIndex<I> {
+        // The upper corner of the first octant (negative in all directions) is the split point
+        &self.children[Octant::NegNegNeg as usize].max_corner
+    }
+
+    /// Calls the closure on every leaf of this subtree in parallel, see [`Octree::par_visit_leaves`]
+    fn par_visit_leaves<F>(&self, f: &F)
+    where
+        F: Fn(&Self) + Sync,
+    {
+        if self.children.is_empty() {
+            f(self);
+        } else {
+            Self::par_visit_leaves_of(self.children.as_slice(), f);
+        }
+    }
+
+    /// Recursively splits the given sibling nodes into two halves that are visited in parallel
+    fn par_visit_leaves_of<F>(nodes: &[Box<Self>], f: &F)
+    where
+        F: Fn(&Self) + Sync,
+    {
+        match nodes {
+            [] => {}
+            [node] => node.par_visit_leaves(f),
+            _ => {
+                let (left, right) = nodes.split_at(nodes.len() / 2);
+                rayon::join(
+                    || Self::par_visit_leaves_of(left, f),
+                    || Self::par_visit_leaves_of(right, f),
+                );
+            }
+        }
+    }
+
+    /// Returns the maximum depth of the leaves in this subtree that are adjacent to the given face of a box on the grid, see [`Octree::balance`]
+    ///
+    /// The box is given by its lower and upper point indices, its face by the directed axis pointing outwards.
+    /// Leaves are adjacent if they lie on the other side of the face and overlap with it in a non-zero area.
+    fn max_face_neighbor_depth(
+        &self,
+        min: &[I; 3],
+        max: &[I; 3],
+        face: &DirectedAxis,
+    ) -> Option<usize> {
+        let dim = face.axis.dim();
+        let face_coord = if face.direction.is_positive() {
+            max[dim]
+        } else {
+            min[dim]
+        };
+
+        let node_min = self.min_corner.index();
+        let node_max = self.max_corner.index();
+        let touches_face = node_min[dim] <= face_coord
+            && node_max[dim] >= face_coord
+            && face.axis.orthogonal_axes().iter().all(|axis| {
+                let d = axis.dim();
+                node_min[d] < max[d] && node_max[d] > min[d]
+            });
+        if !touches_face {
+            return None;
+        }
+
+        if self.children.is_empty() {
+            let is_neighbor = if face.direction.is_positive() {
+                node_min[dim] == face_coord
+            } else {
+                node_max[dim] == face_coord
+            };
+            if is_neighbor {
+                Some(self.depth)
+            } else {
+                None
+            }
+        } else {
+            self.children
+                .iter()
+                .filter_map(|child| child.max_face_neighbor_depth(min, max, face))
+                .max()
+        }
+    }
+
+    /// Appends the particles of all leaves of this subtree that overlap with the given AABB, see [`Octree::with_margin`]
+    fn collect_particles_in_aabb(
+        &self,
+        aabb: &AxisAlignedBoundingBox3d<R>,
+        particles: &mut Vec<usize>,
+    ) {
+        let overlaps = (0..3)
+            .all(|i| self.aabb.min()[i] < aabb.max()[i] && self.aabb.max()[i] > aabb.min()[i]);
+        if !overlaps {
+            return;
+        }
+
+        if self.children.is_empty() {
+            if let Some(particle_set) = self.data.particle_set() {
+                particles.extend(particle_set.particles.iter().copied());
+            }
+        } else {
+            for child in self.children.iter() {
+                child.collect_particles_in_aabb(aabb, particles);
+            }
+        }
+    }
+
+    /// Returns the sum of the particle counts of the children of this node
+    fn children_particle_count(&self) -> usize {
+        self.children.iter().map(|child| child.particle_count).sum()
+    }
+
+    /// Recomputes the particle counts of this node and its descendants bottom-up, see [`OctreeNode::particle_count`]
+    ///
+    /// Required after a recursive subdivision with margin, as the counts of the children can grow by their own
+    /// subdivision due to ghost particles.
+    fn update_particle_counts(&mut self) -> usize {
+        if !self.children.is_empty() {
+            self.particle_count = self
+                .children
+                .iter_mut()
+                .map(|child| child.update_particle_counts())
+                .sum();
+        }
+        self.particle_count
+    }
+
+    /// Recursively merges the children of this node and its descendants if they have less than the given number of non-ghost particles in total, see [`Octree::balance_leaves`]
+    fn merge_underfull_children(&mut self, min_particles: R) -> usize {
+        let mut merged = self
+            .children
+            .iter_mut()
+            .map(|child| child.merge_underfull_children(min_particles))
+            .sum();
+
+        if self.children.is_empty()
+            || !self
+                .children
+                .iter()
+                .all(|child| child.children.is_empty() && child.data.particle_set().is_some())
+        {
+            return merged;
+        }
+
+        let non_ghost_count: usize = self
+            .children
+            .iter()
+            .filter_map(|child| child.data.particle_set())
+            .map(|particle_set| particle_set.particles.len() - particle_set.ghost_particle_count)
+            .sum();
+
+        let below_limit = R::from_usize(non_ghost_count)
+            .map(|count| count < min_particles)
+            .unwrap_or(false);
+        if below_limit {
+            // Ghost particles may be stored in several children
+            let mut particles: Vec<usize> = self
+                .children
+                .iter()
+                .filter_map(|child| child.data.particle_set())
+                .flat_map(|particle_set| particle_set.particles.iter().copied())
+                .collect();
+            particles.sort_unstable();
+            particles.dedup();
+
+            let ghost_particle_count = particles.len() - non_ghost_count;
+            self.children.clear();
+            self.particle_count = particles.len();
+            self.data = NodeData::new_particle_set(particles, ghost_particle_count);
+            merged += 1;
+        }
+
+        merged
+    }
+
+    /// Subdivides this node into octants containing the given particles without storing a per-particle classification, see [`Octree::new_subdivided_low_memory`]
+    fn subdivide_particles_low_memory<P: Iterator<Item = usize> + Clone>(
+        &mut self,
+        grid: &UniformGrid<I, R>,
+        particle_positions: &[Vector3<R>],
+        particles: P,
+        margin: R,
+        next_id: &AtomicUsize,
+    ) {
+        // Obtain the point used as the octree split/pivot point
+        let split_point = get_split_point(grid, &self.min_corner, &self.max_corner)
+            .expect("Failed to get split point of octree node");
+        let split_coordinates = grid.point_coordinates(&split_point);
+
+        let mut counters: [usize; 8] = [0, 0, 0, 0, 0, 0, 0, 0];
+        let mut non_ghost_counters: [usize; 8] = [0, 0, 0, 0, 0, 0, 0, 0];
+
+        // Count the particles of all octants to allocate the exact storage for each child
+        for particle_idx in particles.clone() {
+            let pos = particle_positions[particle_idx];
+            let relative_pos = pos - split_coordinates;
+
+            if self.aabb.contains_point(&pos) {
+                let main_octant: Octant = OctantAxisDirections::classify(&relative_pos).into();
+                non_ghost_counters[main_octant as usize] += 1;
+            }
+
+            let particle_halfspace_flags =
+                HalfspaceFlags::classify_with_margin(&relative_pos, margin);
+            HalfspaceFlags::all_unique_octants()
+                .iter()
+                .zip(counters.iter_mut())
+                .filter(|(octant, _)| particle_halfspace_flags.contains(**octant))
+                .for_each(|(_, counter)| {
+                    *counter += 1;
+                });
+        }
+
+        // Construct the node for each octant, classifying the particles again
+        let mut children = ArrayVec::new();
+        for (&current_octant, (&octant_particle_count, &octant_non_ghost_count)) in Octant::all()
+            .iter()
+            .zip(counters.iter().zip(non_ghost_counters.iter()))
+        {
+            let current_octant_dir = OctantAxisDirections::from(current_octant);
+            let current_octant_flags = HalfspaceFlags::from(current_octant);
+
+            let min_corner = current_octant_dir
+                .combine_point_index(grid, &self.min_corner, &split_point)
+                .expect("Failed to get corner point of octree subcell");
+            let max_corner = current_octant_dir
+                .combine_point_index(grid, &split_point, &self.max_corner)
+                .expect("Failed to get corner point of octree subcell");
+
+            let child_aabb = AxisAlignedBoundingBox3d::new(
+                grid.point_coordinates(&min_corner),
+                grid.point_coordinates(&max_corner),
+            );
+
+            let mut octant_particles = SmallVec::with_capacity(octant_particle_count);
+            octant_particles.extend(particles.clone().filter(|&particle_idx| {
+                let relative_pos = particle_positions[particle_idx] - split_coordinates;
+                HalfspaceFlags::classify_with_margin(&relative_pos, margin)
+                    .contains(current_octant_flags)
+            }));
+            assert_eq!(octant_particles.len(), octant_particle_count);
+
+            children.push(Box::new(OctreeNode::with_data(
+                next_id.fetch_add(1, Ordering::SeqCst),
+                self.depth + 1,
+                min_corner,
+                max_corner,
+                child_aabb,
+                NodeData::new_particle_set(
+                    octant_particles,
+                    octant_particle_count - octant_non_ghost_count,
+                ),
+            )));
+        }
+
+        self.children = children;
+        self.particle_count = self.children_particle_count();
+    }
+
+    /// Subdivides this node by routing its particles to the octants using their given grid cells, see [`Octree::from_cell_assignment`]
+    fn subdivide_with_cell_assignment(
+        &mut self,
+        grid: &UniformGrid<I, R>,
+        cell_indices: &[CellIndex<I>],
+        next_id: &AtomicUsize,
+    ) {
+        // Convert node body from Leaf to Children
+        if let NodeData::ParticleSet(particle_set) = self.data.take() {
+            let split_point = get_split_point(grid, &self.min_corner, &self.max_corner)
+                .expect("Failed to get split point of octree node");
+            let split_index = split_point.index();
+
+            // A cell belongs to the positive side of the split point if its lower corner is not below it
+            let mut octant_particles: [OctreeNodeParticleStorage; 8] = Default::default();
+            for &particle_idx in particle_set.particles.iter() {
+                let cell = cell_indices[particle_idx].index();
+                let octant: Octant = OctantAxisDirections::from_bool(
+                    cell[0] >= split_index[0],
+                    cell[1] >= split_index[1],
+                    cell[2] >= split_index[2],
+                )
+                .into();
+                octant_particles[octant as usize].push(particle_idx);
+            }
+
+            let mut children = ArrayVec::new();
+            for (&current_octant, particles) in
+                Octant::all().iter().zip(octant_particles.iter_mut())
+            {
+                let current_octant_dir = OctantAxisDirections::from(current_octant);
+
+                let min_corner = current_octant_dir
+                    .combine_point_index(grid, &self.min_corner, &split_point)
+                    .expect("Failed to get corner point of octree subcell");
+                let max_corner = current_octant_dir
+                    .combine_point_index(grid, &split_point, &self.max_corner)
+                    .expect("Failed to get corner point of octree subcell");
+
+                let child_aabb = AxisAlignedBoundingBox3d::new(
+                    grid.point_coordinates(&min_corner),
+                    grid.point_coordinates(&max_corner),
+                );
+
+                children.push(Box::new(OctreeNode::with_data(
+                    next_id.fetch_add(1, Ordering::SeqCst),
+                    self.depth + 1,
+                    min_corner,
+                    max_corner,
+                    child_aabb,
+                    NodeData::new_particle_set(std::mem::take(particles), 0),
+                )));
+            }
+
+            self.children = children;
+            self.particle_count = self.children_particle_count();
+        } else {
+            panic!("Only nodes with ParticleSet data can be subdivided");
+        }
+    }
+
     /// Parallel subdivision of this node while considering a margin for "ghost particles" around each octant
     pub fn par_subdivide_with_margin(
         &mut self,
         grid: &UniformGrid<I, R>,
         particle_positions: &[Vector3<R>],
         margin: R,
+        split_strategy: SplitStrategy,
         parallel_policy: &ParallelPolicy,
         next_id: &AtomicUsize,
     ) {
@@ -539,8 +1882,8 @@ impl<I: Index, R: Real> OctreeNode<I, R> {
             let particles = particle_set.particles;
 
             // Obtain the point used as the octree split/pivot point
-            let split_point = get_split_point(grid, &self.min_corner, &self.max_corner)
-                .expect("Failed to get split point of octree node");
+            let split_point =
+                self.compute_split_point(grid, particle_positions, &particles, split_strategy);
             let split_coordinates = grid.point_coordinates(&split_point);
 
             let mut octant_flags = vec![HalfspaceFlags::empty(); particles.len()];
@@ -651,6 +1994,7 @@ impl<I: Index, R: Real> OctreeNode<I, R> {
 
                         let child = Box::new(OctreeNode::with_data(
                             next_id.fetch_add(1, Ordering::SeqCst),
+                            self.depth + 1,
                             min_corner,
                             max_corner,
                             child_aabb,
@@ -667,6 +2011,7 @@ impl<I: Index, R: Real> OctreeNode<I, R> {
 
             // Assign children to this node
             self.children = children.into_iter().collect::<ArrayVec<_, 8>>();
+            self.particle_count = self.children_particle_count();
         } else {
             panic!("Only nodes with ParticleSet data can be subdivided");
         };
@@ -677,6 +2022,7 @@ impl<I: Index, R: Real> OctreeNode<I, R> {
         children_map: &mut MapType<OctantAxisDirections, SurfacePatch<I, R>>,
         stitching_axis: Axis,
         iso_surface_threshold: R,
+        edge_interpolation: EdgeInterpolation,
     ) -> Result<(), ReconstructionError<I, R>> {
         profile!("stitch_children_orthogonal_to");
 
@@ -699,6 +2045,7 @@ impl<I: Index, R: Real> OctreeNode<I, R> {
 
             let stitched_patch = marching_cubes::stitch_surface_patches(
                 iso_surface_threshold,
+                edge_interpolation,
                 stitching_axis,
                 negative_side,
                 positive_side,
@@ -715,6 +2062,7 @@ impl<I: Index, R: Real> OctreeNode<I, R> {
     pub(crate) fn stitch_surface_patches(
         &mut self,
         iso_surface_threshold: R,
+        edge_interpolation: EdgeInterpolation,
     ) -> Result<(), ReconstructionError<I, R>> {
         profile!("stitch_surface_patches");
 
@@ -749,9 +2097,24 @@ impl<I: Index, R: Real> OctreeNode<I, R> {
             children_map
         };
 
-        self.stitch_children_orthogonal_to(&mut children_map, Axis::X, iso_surface_threshold)?;
-        self.stitch_children_orthogonal_to(&mut children_map, Axis::Y, iso_surface_threshold)?;
-        self.stitch_children_orthogonal_to(&mut children_map, Axis::Z, iso_surface_threshold)?;
+        self.stitch_children_orthogonal_to(
+            &mut children_map,
+            Axis::X,
+            iso_surface_threshold,
+            edge_interpolation,
+        )?;
+        self.stitch_children_orthogonal_to(
+            &mut children_map,
+            Axis::Y,
+            iso_surface_threshold,
+            edge_interpolation,
+        )?;
+        self.stitch_children_orthogonal_to(
+            &mut children_map,
+            Axis::Z,
+            iso_surface_threshold,
+            edge_interpolation,
+        )?;
 
         assert_eq!(
             children_map.len(),
@@ -853,13 +2216,151 @@ fn get_split_point<I: Index, R: Real>(
     grid.get_point(mid_indices)
 }
 
+/// Returns the grid point closest to the per-axis median of the particles inside of the node, restricted to the interior of the node
+///
+/// Returns `None` if the node does not contain any (non-ghost) particles.
+fn get_median_split_point<I: Index, R: Real>(
+    grid: &UniformGrid<I, R>,
+    node: &OctreeNode<I, R>,
+    particle_positions: &[Vector3<R>],
+    particles: &[usize],
+) -> Option<PointIndex<I>> {
+    let mut inner_positions = particles
+        .iter()
+        .map(|&i| particle_positions[i])
+        .filter(|pos| node.aabb.contains_point(pos))
+        .collect::<Vec<_>>();
+    if inner_positions.is_empty() {
+        return None;
+    }
+
+    let mid = inner_positions.len() / 2;
+    let mut median = Vector3::zeros();
+    for dim in 0..3 {
+        let (_, median_pos, _) = inner_positions.select_nth_unstable_by(mid, |a, b| {
+            a[dim]
+                .partial_cmp(&b[dim])
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        median[dim] = median_pos[dim];
+    }
+
+    let median_point = grid.clamped_point_index_for_coordinate(&median, Rounding::Nearest);
+    let lower = node.min_corner.index();
+    let upper = node.max_corner.index();
+    let mut split_index = *median_point.index();
+    for (dim, index) in split_index.iter_mut().enumerate() {
+        // Keep the split point strictly inside of the node, otherwise some children would be degenerate
+        if upper[dim] - lower[dim] > I::one() {
+            *index = (*index)
+                .max(lower[dim] + I::one())
+                .min(upper[dim] - I::one());
+        } else {
+            *index = (lower[dim] + upper[dim]) / (I::one() + I::one());
+        }
+    }
+
+    grid.get_point(split_index)
+}
+
+#[test]
+fn test_verify_partition_corrupted() {
+    use rand::Rng;
+
+    let mut rng = crate::random::rng_from_seed(3);
+    let particles = (0..100)
+        .map(|_| Vector3::new(rng.gen_range(0.0..1.0), rng.gen_range(0.0..0.5), 0.5))
+        .collect::<Vec<_>>();
+    let grid = crate::grid_for_reconstruction::<i64, _>(
+        &particles, 0.01, 0.04, 0.02, None, 0, None, false,
+    )
+    .unwrap();
+    let octree = Octree::new_subdivided(
+        &grid,
+        &particles,
+        SubdivisionCriterion::MaxParticleCount(10),
+        0.0,
+        false,
+        false,
+    );
+    assert_eq!(octree.verify_partition(particles.len()), Ok(()));
+
+    /// Applies the function to the particles of the first non-empty leaf in depth-first order
+    fn modify_first_leaf<F: FnOnce(&mut OctreeNodeParticleStorage)>(
+        octree: &mut Octree<i64, f64>,
+        f: F,
+    ) {
+        let mut f = Some(f);
+        octree.root.visit_mut_dfs(|node| {
+            if !node.children().is_empty() {
+                return;
+            }
+            if let NodeData::ParticleSet(particle_set) = node.data_mut() {
+                if !particle_set.particles.is_empty() {
+                    if let Some(f) = f.take() {
+                        f(&mut particle_set.particles);
+                    }
+                }
+            }
+        });
+    }
+
+    // Remove a particle from a leaf
+    let mut missing = octree.clone();
+    let mut removed = None;
+    modify_first_leaf(&mut missing, |particles| removed = particles.pop());
+    assert_eq!(
+        missing.verify_partition(particles.len()),
+        Err(PartitionError::MissingParticles {
+            first_particle: removed.unwrap(),
+            count: 1
+        })
+    );
+
+    // Replace a particle by a particle of another leaf, the particle count stays the same
+    let mut duplicate = octree.clone();
+    let other = octree
+        .leaves()
+        .filter_map(|n| n.data().particle_set())
+        .filter(|ps| !ps.particles.is_empty())
+        .nth(1)
+        .unwrap()
+        .particles[0];
+    modify_first_leaf(&mut duplicate, |particles| particles[0] = other);
+    assert!(matches!(
+        duplicate.verify_partition(particles.len()),
+        Err(PartitionError::DuplicateParticle { particle, .. }) if particle == other
+    ));
+
+    // Insert a particle index that does not exist
+    let mut out_of_range = octree.clone();
+    let n_particles = particles.len();
+    modify_first_leaf(&mut out_of_range, |particles| particles.push(n_particles));
+    assert!(matches!(
+        out_of_range.verify_partition(n_particles),
+        Err(PartitionError::ParticleOutOfRange { particle, .. }) if particle == n_particles
+    ));
+}
+
 mod split_criterion {
     use super::*;
 
     /// Trait that is used by an octree to decide whether an octree node should be further split or subdivided
     pub(super) trait LeafSplitCriterion<I: Index, R: Real> {
-        /// Returns whether the specified node should be split
-        fn split_leaf(&self, node: &OctreeNode<I, R>) -> bool;
+        /// Returns whether the specified node with the given number of non-ghost particles should be split
+        fn split(&self, node: &OctreeNode<I, R>, non_ghost_particle_count: usize) -> bool;
+
+        /// Returns whether the specified node should be split, always returns false for nodes without a particle set
+        fn split_leaf(&self, node: &OctreeNode<I, R>) -> bool {
+            match &node.data {
+                NodeData::ParticleSet(particle_set) => self.split(
+                    node,
+                    particle_set.particles.len() - particle_set.ghost_particle_count,
+                ),
+                // Early out if called on a non-leaf node
+                _ => false,
+            }
+        }
     }
 
     /// Split criterion that decides based on whether the number of non-ghost particles in a node is above a limit
@@ -871,20 +2372,37 @@ mod split_criterion {
         fn new(max_particles: usize) -> Self {
             Self { max_particles }
         }
+
+        /// Returns the maximum number of non-ghost particles per leaf
+        pub(super) fn max_particles(&self) -> usize {
+            self.max_particles
+        }
     }
 
     impl<I: Index, R: Real> LeafSplitCriterion<I, R> for MaxNonGhostParticleLeafSplitCriterion {
         /// Returns true if the number of non-ghost particles in a node is above a limit
-        fn split_leaf(&self, node: &OctreeNode<I, R>) -> bool {
-            match &node.data {
-                NodeData::ParticleSet(particle_set) => {
-                    // Check if this leaf is already below the limit of particles per cell
-                    return particle_set.particles.len() - particle_set.ghost_particle_count
-                        > self.max_particles;
-                }
-                // Early out if called on a non-leaf node
-                _ => return false,
-            };
+        fn split(&self, _node: &OctreeNode<I, R>, non_ghost_particle_count: usize) -> bool {
+            // Check if this leaf is already below the limit of particles per cell
+            non_ghost_particle_count > self.max_particles
+        }
+    }
+
+    /// Split criterion that prevents splitting nodes at or below an optional maximum depth
+    pub(super) struct MaxDepthSplitCriterion {
+        max_depth: Option<usize>,
+    }
+
+    impl MaxDepthSplitCriterion {
+        pub(super) fn new(max_depth: Option<usize>) -> Self {
+            Self { max_depth }
+        }
+    }
+
+    impl<I: Index, R: Real> LeafSplitCriterion<I, R> for MaxDepthSplitCriterion {
+        /// Returns true if the children of the node do not exceed the maximum depth
+        fn split(&self, node: &OctreeNode<I, R>, _non_ghost_particle_count: usize) -> bool {
+            self.max_depth
+                .is_none_or(|max_depth| node.depth < max_depth)
         }
     }
 
@@ -901,7 +2419,7 @@ mod split_criterion {
 
     impl<I: Index, R: Real> LeafSplitCriterion<I, R> for MinimumExtentSplitCriterion<I> {
         /// Only returns true if a splitting of the node does not result in a node that is smaller than the allowed minimum extent
-        fn split_leaf(&self, node: &OctreeNode<I, R>) -> bool {
+        fn split(&self, node: &OctreeNode<I, R>, _non_ghost_particle_count: usize) -> bool {
             let lower = node.min_corner.index();
             let upper = node.max_corner.index();
 
@@ -916,8 +2434,9 @@ mod split_criterion {
         A: LeafSplitCriterion<I, R>,
         B: LeafSplitCriterion<I, R>,
     {
-        fn split_leaf(&self, node: &OctreeNode<I, R>) -> bool {
-            self.0.split_leaf(node) && self.1.split_leaf(node)
+        fn split(&self, node: &OctreeNode<I, R>, non_ghost_particle_count: usize) -> bool {
+            self.0.split(node, non_ghost_particle_count)
+                && self.1.split(node, non_ghost_particle_count)
         }
     }
 
@@ -964,6 +2483,11 @@ mod octant_helper {
 
     /// All octants of a 3D cartesian coordinate system
     #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+    #[cfg_attr(
+        feature = "serde",
+        derive(serde_crate::Serialize, serde_crate::Deserialize),
+        serde(crate = "serde_crate")
+    )]
     #[repr(u8)]
     pub enum Octant {
         NegNegNeg = 0,
@@ -985,6 +2509,11 @@ mod octant_helper {
     }
 
     bitflags! {
+        #[cfg_attr(
+            feature = "serde",
+            derive(serde_crate::Serialize, serde_crate::Deserialize),
+            serde(crate = "serde_crate")
+        )]
         pub struct HalfspaceFlags: u8 {
             const X_NEG = 0b00000001;
             const X_POS = 0b00000010;
@@ -1230,7 +2759,7 @@ mod octant_helper {
             for (i, octant) in Octant::all().iter().copied().enumerate() {
                 assert_eq!(octant as usize, i);
                 assert_eq!(octant, unsafe {
-                    std::mem::transmute::<u8, Octant>(i as u8)
+                    core::mem::transmute::<u8, Octant>(i as u8)
                 });
             }
         }
@@ -0,0 +1,245 @@
+//! Builder for the parameters of the surface reconstruction
+//!
+//! The [`ReconstructionParametersBuilder`] specifies the kernel size and the cube size relative to the
+//! particle radius like the command line interface, starts from defaults for all other parameters and
+//! validates the configuration when the [`Parameters`] are built, e.g.:
+//! ```
+//! use splashsurf_lib::ReconstructionParametersBuilder;
+//!
+//! let parameters = ReconstructionParametersBuilder::new(0.025)
+//!     .smoothing_length(2.0)
+//!     .cube_size(0.5)
+//!     .iso_surface_threshold(0.6)
+//!     .build()
+//!     .unwrap();
+//! assert_eq!(parameters.compact_support_radius, 0.1);
+//! ```
+
+use crate::kernel::KernelType;
+use crate::marching_cubes::EdgeInterpolation;
+use crate::{
+    AxisAlignedBoundingBox3d, Parameters, PeriodicAxes, Real, SpatialDecompositionParameters,
+};
+use thiserror::Error as ThisError;
+
+/// Error type returned when the [`ReconstructionParametersBuilder`] is configured with invalid values
+#[derive(Debug, ThisError)]
+pub enum ParametersBuilderError<R: Real> {
+    /// A length or density parameter that has to be positive is zero, negative or not finite
+    #[error("the {name} has to be positive and finite, got {value}")]
+    NonPositive {
+        /// Name of the invalid parameter
+        name: &'static str,
+        /// The invalid value
+        value: R,
+    },
+    /// The iso-surface threshold is not a finite number
+    #[error("the iso-surface threshold has to be finite, got {0}")]
+    NonFiniteIsoSurfaceThreshold(R),
+}
+
+/// Builder for [`Parameters`] of the surface reconstruction with chained setters, see the [module level documentation](self)
+#[derive(Clone, Debug)]
+pub struct ReconstructionParametersBuilder<R: Real> {
+    particle_radius: R,
+    rest_density: R,
+    smoothing_length: R,
+    cube_size: R,
+    iso_surface_threshold: R,
+    domain_aabb: Option<AxisAlignedBoundingBox3d<R>>,
+    grid_margin_cells: usize,
+    enable_multi_threading: bool,
+    spatial_decomposition: Option<SpatialDecompositionParameters<R>>,
+    periodic_axes: PeriodicAxes,
+    edge_interpolation: Option<EdgeInterpolation>,
+    max_grid_cells: Option<u128>,
+    kernel: Option<KernelType>,
+}
+
+impl<R: Real> ReconstructionParametersBuilder<R> {
+    /// Creates a builder for the given particle radius, all other parameters are initialized with defaults
+    ///
+    /// The defaults are a rest density of `1000.0`, a smoothing length of `2.0`, a cube size of `0.5`,
+    /// an iso-surface threshold of `0.6`, no grid margin, enabled multi-threading and a global reconstruction
+    /// without spatial decomposition or periodic boundaries.
+    pub fn new(particle_radius: R) -> Self {
+        Self {
+            particle_radius,
+            rest_density: R::from_f64(1000.0).unwrap(),
+            smoothing_length: R::from_f64(2.0).unwrap(),
+            cube_size: R::from_f64(0.5).unwrap(),
+            iso_surface_threshold: R::from_f64(0.6).unwrap(),
+            domain_aabb: None,
+            grid_margin_cells: 0,
+            enable_multi_threading: true,
+            spatial_decomposition: None,
+            periodic_axes: PeriodicAxes::none(),
+            edge_interpolation: None,
+            max_grid_cells: None,
+            kernel: None,
+        }
+    }
+
+    /// Sets the rest density of the fluid
+    pub fn rest_density(mut self, rest_density: R) -> Self {
+        self.rest_density = rest_density;
+        self
+    }
+
+    /// Sets the smoothing length in multiples of the particle radius, the compact support radius of the kernel is twice the smoothing length
+    pub fn smoothing_length(mut self, smoothing_length: R) -> Self {
+        self.smoothing_length = smoothing_length;
+        self
+    }
+
+    /// Sets the edge length of the marching cubes cells in multiples of the particle radius
+    pub fn cube_size(mut self, cube_size: R) -> Self {
+        self.cube_size = cube_size;
+        self
+    }
+
+    /// Sets the density threshold of the iso-surface
+    pub fn iso_surface_threshold(mut self, iso_surface_threshold: R) -> Self {
+        self.iso_surface_threshold = iso_surface_threshold;
+        self
+    }
+
+    /// Restricts the reconstruction to the given domain instead of the AABB of all particles
+    pub fn domain_aabb(mut self, domain_aabb: AxisAlignedBoundingBox3d<R>) -> Self {
+        self.domain_aabb = Some(domain_aabb);
+        self
+    }
+
+    /// Sets the number of additional empty marching cubes cells that pad the background grid on every side
+    pub fn grid_margin_cells(mut self, grid_margin_cells: usize) -> Self {
+        self.grid_margin_cells = grid_margin_cells;
+        self
+    }
+
+    /// Enables or disables multi-threading within the reconstruction
+    pub fn enable_multi_threading(mut self, enable_multi_threading: bool) -> Self {
+        self.enable_multi_threading = enable_multi_threading;
+        self
+    }
+
+    /// Enables the spatial decomposition (octree subdivision) of the particles with the given parameters
+    pub fn spatial_decomposition(
+        mut self,
+        spatial_decomposition: SpatialDecompositionParameters<R>,
+    ) -> Self {
+        self.spatial_decomposition = Some(spatial_decomposition);
+        self
+    }
+
+    /// Sets the axes along which the domain is periodic, requires a domain AABB
+    pub fn periodic_axes(mut self, periodic_axes: PeriodicAxes) -> Self {
+        self.periodic_axes = periodic_axes;
+        self
+    }
+
+    /// Sets the method used to place the iso-surface vertices on the edges of the marching cubes cells
+    pub fn edge_interpolation(mut self, edge_interpolation: EdgeInterpolation) -> Self {
+        self.edge_interpolation = Some(edge_interpolation);
+        self
+    }
+
+    /// Sets an upper limit for the number of cells of the background grid
+    pub fn max_grid_cells(mut self, max_grid_cells: u128) -> Self {
+        self.max_grid_cells = Some(max_grid_cells);
+        self
+    }
+
+    /// Sets the SPH kernel used to compute the particle densities and to evaluate the density map
+    pub fn kernel(mut self, kernel: KernelType) -> Self {
+        self.kernel = Some(kernel);
+        self
+    }
+
+    /// Validates the configuration and returns the parameters with absolute kernel and cube sizes
+    pub fn build(&self) -> Result<Parameters<R>, ParametersBuilderError<R>> {
+        let positive = |name: &'static str, value: R| {
+            if value.is_finite() && value > R::zero() {
+                Ok(value)
+            } else {
+                Err(ParametersBuilderError::NonPositive { name, value })
+            }
+        };
+
+        let particle_radius = positive("particle radius", self.particle_radius)?;
+        let rest_density = positive("rest density", self.rest_density)?;
+        let smoothing_length = positive("smoothing length", self.smoothing_length)?;
+        let cube_size = positive("cube size", self.cube_size)?;
+        if !self.iso_surface_threshold.is_finite() {
+            return Err(ParametersBuilderError::NonFiniteIsoSurfaceThreshold(
+                self.iso_surface_threshold,
+            ));
+        }
+
+        Ok(Parameters {
+            particle_radius,
+            rest_density,
+            compact_support_radius: particle_radius
+                * (R::from_f64(2.0).unwrap() * smoothing_length),
+            cube_size: particle_radius * cube_size,
+            iso_surface_threshold: self.iso_surface_threshold,
+            domain_aabb: self.domain_aabb.clone(),
+            grid_margin_cells: self.grid_margin_cells,
+            enable_multi_threading: self.enable_multi_threading,
+            spatial_decomposition: self.spatial_decomposition.clone(),
+            periodic_axes: self.periodic_axes,
+            mask: None,
+            contour_cell_factors: None,
+            normalize_by_rest_density: None,
+            edge_interpolation: self.edge_interpolation,
+            max_grid_cells: self.max_grid_cells,
+            kernel: self.kernel,
+            anisotropy: None,
+        })
+    }
+}
+
+#[test]
+fn test_builder_rejects_zero_cube_size() {
+    let result = ReconstructionParametersBuilder::<f64>::new(0.025)
+        .cube_size(0.0)
+        .build();
+
+    match result {
+        Err(ParametersBuilderError::NonPositive { name, value }) => {
+            assert_eq!(name, "cube size");
+            assert_eq!(value, 0.0);
+        }
+        _ => panic!("a zero cube size has to be rejected"),
+    }
+}
+
+#[test]
+fn test_builder_rejects_invalid_values() {
+    assert!(ReconstructionParametersBuilder::<f64>::new(0.0)
+        .build()
+        .is_err());
+    assert!(ReconstructionParametersBuilder::<f64>::new(0.025)
+        .smoothing_length(-1.0)
+        .build()
+        .is_err());
+    assert!(ReconstructionParametersBuilder::<f64>::new(0.025)
+        .iso_surface_threshold(f64::NAN)
+        .build()
+        .is_err());
+}
+
+#[test]
+fn test_builder_minimal_configuration() {
+    let parameters = ReconstructionParametersBuilder::<f64>::new(0.025)
+        .build()
+        .unwrap();
+
+    assert_eq!(parameters.particle_radius, 0.025);
+    assert_eq!(parameters.rest_density, 1000.0);
+    assert!((parameters.compact_support_radius - 0.1).abs() < 1e-12);
+    assert!((parameters.cube_size - 0.0125).abs() < 1e-12);
+    assert_eq!(parameters.iso_surface_threshold, 0.6);
+    assert!(parameters.enable_multi_threading);
+    assert!(parameters.spatial_decomposition.is_none());
+    assert!(!parameters.periodic_axes.any());
+}
@@ -0,0 +1,339 @@
+//! Surface reconstruction with periodic boundary conditions
+//!
+//! Periodic boundaries are implemented on top of the regular reconstruction:
+//!  1. All particles are wrapped into the periodic domain.
+//!  2. Particles close to a periodic face are replicated as ghost particles on the opposite side
+//!     of the domain (repeatedly for all periodic axes, so that edge and corner regions are covered).
+//!  3. The density map is evaluated on a background grid that extends beyond the periodic faces by
+//!     a margin large enough such that the density field inside of the domain is periodic.
+//!  4. The density values of all grid points outside of the domain and on its upper faces are
+//!     replaced by the values of their periodic images inside of the domain, so that marching cubes
+//!     generates identical vertices on opposite faces.
+//!  5. The mesh is clipped to the periodic domain and the vertices on the upper periodic faces are
+//!     snapped to the translated vertices of the lower faces, so that the mesh can be tiled without
+//!     a seam.
+
+use crate::density_map::DensityMap;
+use crate::mesh::TriMesh3d;
+use crate::uniform_grid::UniformGrid;
+use crate::{density_map, new_map, profile, AxisAlignedBoundingBox3d, Index, Real};
+use log::info;
+use nalgebra::Vector3;
+use thiserror::Error as ThisError;
+
+/// Selects the coordinate axes along which the reconstruction domain is periodic
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct PeriodicAxes {
+    /// Whether the domain is periodic along the x-axis
+    pub x: bool,
+    /// Whether the domain is periodic along the y-axis
+    pub y: bool,
+    /// Whether the domain is periodic along the z-axis
+    pub z: bool,
+}
+
+impl PeriodicAxes {
+    /// Returns a value where no axis is periodic
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// Returns a value where the given axes are periodic
+    pub fn new(x: bool, y: bool, z: bool) -> Self {
+        Self { x, y, z }
+    }
+
+    /// Returns whether any axis is periodic
+    pub fn any(&self) -> bool {
+        self.x || self.y || self.z
+    }
+
+    /// Returns the periodicity flags as an array indexed by the dimension of the axis
+    pub fn as_array(&self) -> [bool; 3] {
+        [self.x, self.y, self.z]
+    }
+
+    /// Returns an iterator over the dimensions of all periodic axes
+    fn periodic_dims(&self) -> impl Iterator<Item = usize> {
+        let axes = self.as_array();
+        (0..3).filter(move |&dim| axes[dim])
+    }
+}
+
+/// Error type for invalid periodic boundary configurations
+#[derive(Debug, ThisError)]
+pub enum PeriodicBoundaryError<R: Real> {
+    /// Periodic boundaries require a user specified domain that defines the periods
+    #[error("periodic boundaries require a user specified domain AABB")]
+    MissingDomain,
+    /// The period along an axis has to be an integer multiple of the cube size
+    #[error("the domain extent ({extent}) along the periodic axis {dim} is not an integer multiple of the cube size ({cube_size})")]
+    IncompatibleCubeSize {
+        /// Dimension of the periodic axis
+        dim: usize,
+        /// Extent of the domain along the axis
+        extent: R,
+        /// The cube size of the reconstruction
+        cube_size: R,
+    },
+    /// A vertex on a periodic face is too far away from the origin to match it with the opposite face
+    #[error("the vertex coordinate {coordinate} on a periodic face is too large to be matched with the opposite face")]
+    UnrepresentableVertexCoordinate {
+        /// The coordinate that cannot be quantized
+        coordinate: R,
+    },
+    /// A vertex on an upper periodic face has no corresponding vertex on the lower face
+    #[error(
+        "the vertex {vertex:?} on a periodic face has no corresponding vertex on the opposite face"
+    )]
+    UnmatchedVertex {
+        /// Coordinates of the vertex on the upper face
+        vertex: Vector3<R>,
+    },
+}
+
+/// Particles and background domain prepared for a periodic reconstruction
+pub(crate) struct PeriodicSetup<R: Real> {
+    /// The periodic domain
+    pub domain: AxisAlignedBoundingBox3d<R>,
+    /// All wrapped particles followed by their ghost copies
+    pub particle_positions: Vec<Vector3<R>>,
+    /// The extended domain used for the reconstruction
+    pub extended_domain: AxisAlignedBoundingBox3d<R>,
+}
+
+/// Checks that the periodic domain is valid for the given cube size
+pub(crate) fn validate_periodic_domain<R: Real>(
+    domain: Option<&AxisAlignedBoundingBox3d<R>>,
+    periodic_axes: &PeriodicAxes,
+    cube_size: R,
+) -> Result<AxisAlignedBoundingBox3d<R>, PeriodicBoundaryError<R>> {
+    let domain = domain.ok_or(PeriodicBoundaryError::MissingDomain)?;
+    let extents = domain.extents();
+    let tolerance = R::from_f64(1e-6).unwrap();
+    for dim in periodic_axes.periodic_dims() {
+        let n_cells = extents[dim] / cube_size;
+        if (n_cells - n_cells.round()).abs() > tolerance * n_cells.max(R::one())
+            || n_cells.round() < R::one()
+        {
+            return Err(PeriodicBoundaryError::IncompatibleCubeSize {
+                dim,
+                extent: extents[dim],
+                cube_size,
+            });
+        }
+    }
+
+    Ok(domain.clone())
+}
+
+/// Wraps the particles into the periodic domain, adds ghost particles and computes the extended domain for the reconstruction
+pub(crate) fn setup_periodic_reconstruction<I: Index, R: Real>(
+    particle_positions: &[Vector3<R>],
+    domain: &AxisAlignedBoundingBox3d<R>,
+    periodic_axes: &PeriodicAxes,
+    compact_support_radius: R,
+    cube_size: R,
+) -> PeriodicSetup<R> {
+    profile!("setup periodic reconstruction");
+
+    let periods = domain.extents();
+    let kernel_evaluation_radius =
+        density_map::compute_kernel_evaluation_radius::<I, R>(compact_support_radius, cube_size)
+            .kernel_evaluation_radius;
+
+    // The density map generation ignores particles within the kernel evaluation radius of the grid
+    // boundary. With a margin of twice this radius all grid points inside of the periodic domain
+    // receive contributions of all particles (including ghost particles) in their support.
+    let margin_cells =
+        ((kernel_evaluation_radius + kernel_evaluation_radius) / cube_size).ceil() + R::one();
+    let margin = margin_cells * cube_size;
+
+    // Wrap all particles into the periodic domain
+    let mut positions: Vec<_> = particle_positions
+        .iter()
+        .map(|p| {
+            let mut p = *p;
+            for dim in periodic_axes.periodic_dims() {
+                let offset = p[dim] - domain.min()[dim];
+                p[dim] =
+                    domain.min()[dim] + offset - (offset / periods[dim]).floor() * periods[dim];
+            }
+            p
+        })
+        .collect();
+    let num_particles = positions.len();
+
+    // Replicate particles close to the periodic faces, previously added ghost particles are
+    // replicated as well to cover the edge and corner regions of the domain
+    for dim in periodic_axes.periodic_dims() {
+        let num_current = positions.len();
+        for i in 0..num_current {
+            let p = positions[i];
+            if p[dim] < domain.min()[dim] + margin {
+                let mut ghost = p;
+                ghost[dim] += periods[dim];
+                positions.push(ghost);
+            }
+            if p[dim] >= domain.max()[dim] - margin {
+                let mut ghost = p;
+                ghost[dim] -= periods[dim];
+                positions.push(ghost);
+            }
+        }
+    }
+
+    info!(
+        "Added {} ghost particles for periodic boundary conditions.",
+        positions.len() - num_particles
+    );
+
+    let extended_domain = {
+        let mut min = *domain.min();
+        let mut max = *domain.max();
+        for dim in periodic_axes.periodic_dims() {
+            min[dim] -= margin;
+            max[dim] += margin;
+        }
+        AxisAlignedBoundingBox3d::new(min, max)
+    };
+
+    PeriodicSetup {
+        domain: domain.clone(),
+        particle_positions: positions,
+        extended_domain,
+    }
+}
+
+/// Clips the mesh reconstructed on the extended domain to the periodic domain and snaps the vertices of opposite periodic faces
+pub(crate) fn finalize_periodic_mesh<R: Real>(
+    mesh: &mut TriMesh3d<R>,
+    domain: &AxisAlignedBoundingBox3d<R>,
+    periodic_axes: &PeriodicAxes,
+    cube_size: R,
+) -> Result<(), PeriodicBoundaryError<R>> {
+    profile!("finalize periodic mesh");
+
+    let tolerance = cube_size * R::from_f64(1e-4).unwrap();
+    let three = R::from_f64(3.0).unwrap();
+
+    // Marching cubes triangles never span multiple cells and the periodic faces coincide with
+    // grid planes, so the centroid is sufficient to decide whether a triangle is inside
+    {
+        let vertices = &mesh.vertices;
+        mesh.triangles.retain(|tri| {
+            let centroid = (vertices[tri[0]] + vertices[tri[1]] + vertices[tri[2]]) / three;
+            periodic_axes
+                .periodic_dims()
+                .all(|dim| centroid[dim] > domain.min()[dim] && centroid[dim] < domain.max()[dim])
+        });
+    }
+
+    mesh.remove_unreferenced_vertices();
+
+    // Snap the vertices on the upper faces to the translated vertices of the lower faces
+    for dim in periodic_axes.periodic_dims() {
+        let period = domain.max()[dim] - domain.min()[dim];
+        let other_dims = [(dim + 1) % 3, (dim + 2) % 3];
+        let quantize_coordinate = |coordinate: R, offset: i64| {
+            (coordinate / tolerance)
+                .round()
+                .to_i64()
+                .and_then(|q| q.checked_add(offset))
+                .ok_or(PeriodicBoundaryError::UnrepresentableVertexCoordinate { coordinate })
+        };
+        let quantize = |v: &Vector3<R>, offset: [i64; 2]| -> Result<[i64; 2], _> {
+            Ok([
+                quantize_coordinate(v[other_dims[0]], offset[0])?,
+                quantize_coordinate(v[other_dims[1]], offset[1])?,
+            ])
+        };
+
+        let mut lower_face_vertices = new_map();
+        for (i, v) in mesh.vertices.iter().enumerate() {
+            if (v[dim] - domain.min()[dim]).abs() < tolerance {
+                lower_face_vertices.insert(quantize(v, [0, 0])?, i);
+            }
+        }
+
+        // The density values on both faces are copied from the same lattice of grid points, so the
+        // coordinates of corresponding vertices along the face are exactly equal
+        for i in 0..mesh.vertices.len() {
+            let v = mesh.vertices[i];
+            if (v[dim] - domain.max()[dim]).abs() >= tolerance {
+                continue;
+            }
+
+            let j = *lower_face_vertices
+                .get(&quantize(&v, [0, 0])?)
+                .ok_or(PeriodicBoundaryError::UnmatchedVertex { vertex: v })?;
+            let mut snapped = mesh.vertices[j];
+            snapped[dim] += period;
+            mesh.vertices[i] = snapped;
+        }
+    }
+
+    Ok(())
+}
+
+/// Replaces the density values of all grid points outside of the periodic domain by the values of their periodic images inside of the domain
+///
+/// The density values evaluated at grid points on opposite periodic faces differ slightly due to rounding, which
+/// can change the topology of the contour close to the iso-surface threshold. After copying the values from the
+/// same lattice of grid points inside of the domain, marching cubes generates identical vertices on opposite faces.
+pub(crate) fn make_density_map_periodic<I: Index, R: Real>(
+    grid: &UniformGrid<I, R>,
+    domain: &AxisAlignedBoundingBox3d<R>,
+    periodic_axes: &PeriodicAxes,
+    density_map: &mut DensityMap<I, R>,
+) {
+    profile!("make density map periodic");
+
+    // Index of the grid points on the lower faces and the number of cells of the periods, the
+    // values along non-periodic axes are chosen such that every point is its only periodic image
+    let points_per_dim = *grid.points_per_dim();
+    let mut lower = [I::zero(); 3];
+    let mut period = points_per_dim;
+    for dim in periodic_axes.periodic_dims() {
+        lower[dim] = ((domain.min()[dim] - grid.aabb().min()[dim]) / grid.cell_size())
+            .round()
+            .to_index_unchecked();
+        period[dim] = (domain.extents()[dim] / grid.cell_size())
+            .round()
+            .to_index_unchecked();
+    }
+
+    // Returns an iterator over all indices of grid points along the axis that are periodic images of the given index
+    let images_along = |dim: usize, index: I| {
+        let mut first = index;
+        while first >= period[dim] {
+            first -= period[dim];
+        }
+        std::iter::successors(Some(first), move |&i| i.checked_add(&period[dim]))
+            .take_while(move |&i| i < points_per_dim[dim])
+    };
+
+    let mut periodic_map = new_map();
+    density_map.for_each(|flat_point_index, density| {
+        let point = match grid.try_unflatten_point_index(flat_point_index) {
+            Some(point) => point,
+            None => return,
+        };
+        let ijk = point.index();
+        let inside =
+            (0..3).all(|dim| ijk[dim] >= lower[dim] && ijk[dim] < lower[dim] + period[dim]);
+        if !inside {
+            return;
+        }
+
+        for i in images_along(0, ijk[0]) {
+            for j in images_along(1, ijk[1]) {
+                for k in images_along(2, ijk[2]) {
+                    periodic_map.insert(grid.flatten_point_indices(i, j, k), density);
+                }
+            }
+        }
+    });
+
+    *density_map = periodic_map.into();
+}
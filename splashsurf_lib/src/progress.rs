@@ -0,0 +1,86 @@
+//! Progress reporting for long running surface reconstructions
+//!
+//! The [`reconstruct_surface_with_progress`](crate::reconstruct_surface_with_progress) function reports the
+//! current [`ReconstructionStage`] together with the overall progress of the reconstruction to a callback,
+//! e.g. to drive a progress bar. The overall progress is a value in `[0, 1]` that never decreases between
+//! consecutive calls of the callback. For global reconstructions, the progress is reported at the beginning of
+//! every stage. For reconstructions with spatial decomposition, the progress is additionally reported after
+//! every processed leaf of the octree.
+
+use std::sync::Mutex;
+
+/// Major stages of a surface reconstruction
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ReconstructionStage {
+    /// Construction of the background grid and the octree (if spatial decomposition is enabled)
+    Initialization,
+    /// Neighborhood search and computation of the per-particle densities
+    NeighborhoodSearch,
+    /// Accumulation of the density map on the background grid
+    DensityMap,
+    /// Triangulation of the density map using marching cubes
+    MarchingCubes,
+    /// Reconstruction and stitching of the surface patches of the octree leaves (if spatial decomposition is enabled)
+    Subdomains,
+    /// The reconstruction is finished
+    Finished,
+}
+
+/// Progress of a surface reconstruction that is passed to a progress callback
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ReconstructionProgress {
+    /// The stage that is currently performed
+    pub stage: ReconstructionStage,
+    /// Overall progress of the reconstruction in `[0, 1]`
+    pub progress: f64,
+}
+
+/// Forwards the progress of a reconstruction to a callback, ensures that the reported progress never decreases
+///
+/// The reporter can be shared between threads, the callback is never called concurrently.
+pub(crate) struct ProgressReporter<'a> {
+    state: Mutex<(f64, &'a mut (dyn FnMut(ReconstructionProgress) + Send))>,
+}
+
+impl<'a> ProgressReporter<'a> {
+    /// Creates a new reporter for the given callback
+    pub(crate) fn new(callback: &'a mut (dyn FnMut(ReconstructionProgress) + Send)) -> Self {
+        Self {
+            state: Mutex::new((0.0, callback)),
+        }
+    }
+
+    /// Reports the given stage and overall progress to the callback, the progress is clamped to be at least the previously reported progress
+    pub(crate) fn report(&self, stage: ReconstructionStage, progress: f64) {
+        let mut state = self.state.lock().unwrap();
+        let progress = progress.min(1.0).max(state.0);
+        state.0 = progress;
+        (state.1)(ReconstructionProgress { stage, progress });
+    }
+}
+
+/// Reports the given stage and overall progress if a reporter is given
+pub(crate) fn report(
+    reporter: Option<&ProgressReporter>,
+    stage: ReconstructionStage,
+    progress: f64,
+) {
+    if let Some(reporter) = reporter {
+        reporter.report(stage, progress);
+    }
+}
+
+#[test]
+fn test_progress_reporter_monotonic() {
+    let mut reported = Vec::new();
+    {
+        let mut callback = |progress: ReconstructionProgress| reported.push(progress.progress);
+        let reporter = ProgressReporter::new(&mut callback);
+        reporter.report(ReconstructionStage::Initialization, 0.0);
+        reporter.report(ReconstructionStage::Subdomains, 0.5);
+        reporter.report(ReconstructionStage::Subdomains, 0.25);
+        reporter.report(ReconstructionStage::Finished, 2.0);
+    }
+
+    assert_eq!(reported, vec![0.0, 0.5, 0.5, 1.0]);
+}
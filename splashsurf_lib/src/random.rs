@@ -0,0 +1,41 @@
+//! Helpers for reproducible randomness
+//!
+//! The library itself currently does not contain any randomized algorithms. Randomized functions
+//! that are added in the future should not use a thread local or otherwise implicitly seeded RNG.
+//! Instead, they should accept an `rng: &mut impl Rng` argument provided by the caller.
+//!
+//! If the `random` feature is enabled, the crate re-exports its version of `rand` for this purpose
+//! and this module provides the [`DefaultRng`], a portable ChaCha based RNG from the
+//! [`rand_chacha`](https://docs.rs/rand_chacha) crate, that can be created from a `u64` seed using
+//! [`rng_from_seed`]. Its output only depends on the seed and not on the platform, so the same seed
+//! yields the same sequence of values on all platforms.
+
+#[cfg(any(test, feature = "random"))]
+use rand::SeedableRng;
+
+/// The portable RNG returned by [`rng_from_seed`]
+#[cfg(any(test, feature = "random"))]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "random")))]
+pub type DefaultRng = rand_chacha::ChaCha8Rng;
+
+/// Returns a new instance of the [`DefaultRng`] initialized with the given seed
+#[cfg(any(test, feature = "random"))]
+#[cfg_attr(doc_cfg, doc(cfg(feature = "random")))]
+pub fn rng_from_seed(seed: u64) -> DefaultRng {
+    DefaultRng::seed_from_u64(seed)
+}
+
+#[test]
+fn test_rng_from_seed_is_deterministic() {
+    use rand::Rng;
+
+    let mut rng_a = rng_from_seed(42);
+    let mut rng_b = rng_from_seed(42);
+    let values_a: Vec<u64> = (0..16).map(|_| rng_a.gen()).collect();
+    let values_b: Vec<u64> = (0..16).map(|_| rng_b.gen()).collect();
+    assert_eq!(values_a, values_b);
+
+    let mut rng_c = rng_from_seed(43);
+    let values_c: Vec<u64> = (0..16).map(|_| rng_c.gen()).collect();
+    assert_ne!(values_a, values_c);
+}
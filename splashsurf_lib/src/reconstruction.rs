@@ -1,18 +1,25 @@
 //! Helper functions calling the individual steps of the reconstruction pipeline
 
+use crate::anisotropy::{self, AnisotropicKernels};
+use crate::density_map::DensityMapParameters;
 use crate::generic_tree::*;
+use crate::kernel::SphKernel;
 use crate::marching_cubes::SurfacePatch;
 use crate::mesh::TriMesh3d;
 use crate::octree::{NodeData, Octree, OctreeNode};
+use crate::progress::{self, ProgressReporter, ReconstructionStage};
 use crate::uniform_grid::{OwningSubdomainGrid, Subdomain, UniformGrid};
-use crate::workspace::LocalReconstructionWorkspace;
+use crate::workspace::{record_marching_cubes_cases, LocalReconstructionWorkspace};
 use crate::{
-    density_map, marching_cubes, neighborhood_search, new_map, profile, utils, Index, Parameters,
-    ParticleDensityComputationStrategy, Real, ReconstructionError, SpatialDecompositionParameters,
+    density_map, marching_cubes, neighborhood_search, new_map, periodic, profile, utils,
+    DensityMap, Index, MapType, Parameters, ParticleDensityComputationStrategy, ParticleGroup,
+    Real, ReconstructionError, RestDensityNormalization, SpatialDecompositionParameters,
     SurfaceReconstruction,
 };
 use log::{debug, info, trace};
-use nalgebra::Vector3;
+use nalgebra::{Matrix3, Vector3};
+use rayon::prelude::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Mutex;
 
 /// Perform a global surface reconstruction without domain decomposition
@@ -20,18 +27,26 @@ pub(crate) fn reconstruct_surface_global<'a, I: Index, R: Real>(
     particle_positions: &[Vector3<R>],
     parameters: &Parameters<R>,
     output_surface: &'a mut SurfaceReconstruction<I, R>,
+    progress: Option<&ProgressReporter>,
 ) -> Result<(), ReconstructionError<I, R>> {
     profile!("reconstruct_surface_global");
 
+    let previous_density_map = output_surface
+        .workspace
+        .take_density_map(&output_surface.grid);
     let mut workspace = output_surface
         .workspace
         .get_local_with_capacity(particle_positions.len())
         .borrow_mut();
+    // Reuse the memory of the density map of a previous reconstruction on a grid with the same dimensions
+    if let Some(density_map) = previous_density_map {
+        workspace.density_map = density_map;
+    }
 
     // Clear the current mesh, as reconstruction will be appended to output
     output_surface.mesh.clear();
     // Perform global reconstruction without octree
-    reconstruct_single_surface_append(
+    let density_map = reconstruct_single_surface_append(
         &mut *workspace,
         &output_surface.grid,
         None,
@@ -39,10 +54,313 @@ pub(crate) fn reconstruct_surface_global<'a, I: Index, R: Real>(
         None,
         parameters,
         &mut output_surface.mesh,
+        progress,
+    )?;
+
+    output_surface.density_map = Some(density_map);
+
+    Ok(())
+}
+
+/// Perform a coarse preview and a global surface reconstruction sharing the per-particle densities, see [`crate::reconstruct_surface_with_preview`]
+pub(crate) fn reconstruct_surface_global_with_preview<I: Index, R: Real, F>(
+    particle_positions: &[Vector3<R>],
+    parameters: &Parameters<R>,
+    preview_parameters: &Parameters<R>,
+    preview_surface: &mut SurfaceReconstruction<I, R>,
+    output_surface: &mut SurfaceReconstruction<I, R>,
+    on_preview: F,
+) -> Result<(), ReconstructionError<I, R>>
+where
+    F: FnOnce(&SurfaceReconstruction<I, R>),
+{
+    profile!("reconstruct_surface_global_with_preview");
+
+    let mut workspace = output_surface
+        .workspace
+        .get_local_with_capacity(particle_positions.len())
+        .borrow_mut();
+
+    // The neighborhood search and the particle densities are only performed once for both passes
+    let mut particle_densities = Vec::new();
+    compute_particle_densities_and_neighbors(
+        &output_surface.grid,
+        particle_positions,
+        parameters,
+        &mut workspace.particle_neighbor_lists,
+        &mut particle_densities,
+    )?;
+
+    {
+        profile!("preview reconstruction");
+        preview_surface.mesh.clear();
+        let preview_density_map = reconstruct_single_surface_append(
+            &mut *workspace,
+            &preview_surface.grid,
+            None,
+            particle_positions,
+            Some(particle_densities.as_slice()),
+            preview_parameters,
+            &mut preview_surface.mesh,
+            None,
+        )?;
+        preview_surface.density_map = Some(preview_density_map);
+    }
+
+    info!(
+        "Preview reconstruction finished with {} triangles",
+        preview_surface.mesh.triangles.len()
+    );
+    on_preview(preview_surface);
+
+    output_surface.mesh.clear();
+    let density_map = reconstruct_single_surface_append(
+        &mut *workspace,
+        &output_surface.grid,
+        None,
+        particle_positions,
+        Some(particle_densities.as_slice()),
+        parameters,
+        &mut output_surface.mesh,
+        None,
+    )?;
+
+    output_surface.density_map = Some(density_map);
+
+    Ok(())
+}
+
+/// Perform a global surface reconstruction of several particle groups with individual kernels, see [`crate::reconstruct_multi_group`]
+pub(crate) fn reconstruct_multi_group_global<I: Index, R: Real>(
+    groups: &[ParticleGroup<R>],
+    parameters: &Parameters<R>,
+    output_surface: &mut SurfaceReconstruction<I, R>,
+) -> Result<(), ReconstructionError<I, R>> {
+    profile!("reconstruct_multi_group_global");
+
+    let grid = &output_surface.grid;
+
+    // Only particles that overlap the region of interest contribute to the density map
+    let mask = match &parameters.mask {
+        Some(mask) => Some(mask.aligned_to(grid)?),
+        None => None,
+    };
+
+    let mut particle_neighbor_lists = Vec::new();
+    let mut particle_densities = Vec::new();
+    let mut combined_density_map: MapType<I, R> = new_map();
+    for (group_index, group) in groups.iter().enumerate() {
+        if group.positions.is_empty() {
+            continue;
+        }
+
+        trace!(
+            "Computing density map of particle group {} ({} particles)...",
+            group_index,
+            group.positions.len()
+        );
+
+        // The densities of the particles are only computed from the particles of their own group
+        neighborhood_search::search_inplace::<I, R>(
+            grid.aabb(),
+            group.positions,
+            group.compact_support_radius,
+            parameters.enable_multi_threading,
+            &mut particle_neighbor_lists,
+        );
+        density_map::compute_particle_densities_inplace::<I, R>(
+            group.positions,
+            particle_neighbor_lists.as_slice(),
+            group.compact_support_radius,
+            parameters.kernel.unwrap_or_default(),
+            group.particle_mass,
+            parameters.enable_multi_threading,
+            &mut particle_densities,
+        )?;
+
+        let normalized_densities = normalization_rest_density(parameters, &particle_densities)
+            .map(|rest_density| vec![rest_density; particle_densities.len()]);
+        let group_densities = normalized_densities
+            .as_deref()
+            .unwrap_or(&particle_densities);
+
+        let active_particles = mask
+            .as_ref()
+            .map(|mask| mask.active_particles(group.positions, group.compact_support_radius));
+
+        let mut group_density_map = new_map().into();
+        density_map::generate_sparse_density_map(
+            grid,
+            None,
+            group.positions,
+            group_densities,
+            active_particles.as_deref(),
+            &DensityMapParameters {
+                particle_rest_mass: group.particle_mass,
+                compact_support_radius: group.compact_support_radius,
+                kernel: parameters.kernel.unwrap_or_default(),
+                cube_size: parameters.cube_size,
+                allow_threading: parameters.enable_multi_threading,
+                histogram: None,
+            },
+            &mut group_density_map,
+        )?;
+
+        // Accumulate the density contributions of all groups before contouring
+        group_density_map.for_each(|flat_point_index, density| {
+            *combined_density_map
+                .entry(flat_point_index)
+                .or_insert(R::zero()) += density;
+        });
+    }
+
+    let mut density_map = DensityMap::from(combined_density_map);
+    if let Some(mask) = &mask {
+        mask.apply_to_density_map(grid, &mut density_map);
+    }
+
+    output_surface.mesh.clear();
+    let mesh = &mut output_surface.mesh;
+    record_marching_cubes_cases(&mut output_surface.case_histogram, || {
+        marching_cubes::triangulate_density_map_with_interpolation_append(
+            grid,
+            None,
+            &density_map,
+            parameters.iso_surface_threshold,
+            parameters.edge_interpolation.unwrap_or_default(),
+            mesh,
+        )
+    })?;
+
+    output_surface.density_map = Some(density_map);
+
+    Ok(())
+}
+
+/// Perform a global surface reconstruction of particles with individual smoothing lengths, see [`crate::reconstruct_surface_with_smoothing_lengths`]
+pub(crate) fn reconstruct_surface_variable_support_global<I: Index, R: Real>(
+    particle_positions: &[Vector3<R>],
+    particle_smoothing_lengths: &[R],
+    parameters: &Parameters<R>,
+    output_surface: &mut SurfaceReconstruction<I, R>,
+) -> Result<(), ReconstructionError<I, R>> {
+    profile!("reconstruct_surface_variable_support_global");
+
+    let grid = &output_surface.grid;
+    let kernel = parameters.kernel.unwrap_or_default();
+    let max_smoothing_length = particle_smoothing_lengths
+        .iter()
+        .copied()
+        .fold(R::zero(), |max, h| max.max(h));
+
+    // The mass of a particle scales with its volume
+    let particle_rest_volume = R::from_f64((4.0 / 3.0) * std::f64::consts::PI).unwrap()
+        * parameters.particle_radius.powi(3);
+    let particle_rest_mass = particle_rest_volume * parameters.rest_density;
+    let particle_masses = particle_smoothing_lengths
+        .iter()
+        .map(|&h| particle_rest_mass * (h / parameters.compact_support_radius).powi(3))
+        .collect::<Vec<_>>();
+
+    // Search with the largest smoothing length, the neighbors are filtered by the support of each pair below
+    let mut particle_neighbor_lists = Vec::new();
+    neighborhood_search::search_inplace::<I, R>(
+        grid.aabb(),
+        particle_positions,
+        max_smoothing_length,
+        parameters.enable_multi_threading,
+        &mut particle_neighbor_lists,
+    );
+
+    let compute_density = |i: usize| {
+        let x_i = &particle_positions[i];
+        let h_i = particle_smoothing_lengths[i];
+        let mut density = particle_masses[i] * kernel.evaluate(R::zero(), h_i);
+        for &j in particle_neighbor_lists[i].iter() {
+            let h_ij = (h_i + particle_smoothing_lengths[j]) * R::from_f64(0.5).unwrap();
+            let r = (particle_positions[j] - x_i).norm();
+            density += particle_masses[j] * kernel.evaluate(r, h_ij);
+        }
+        density
+    };
+
+    let particle_densities: Vec<R> = {
+        profile!("compute particle densities");
+        if parameters.enable_multi_threading {
+            (0..particle_positions.len())
+                .into_par_iter()
+                .with_min_len(8)
+                .map(compute_density)
+                .collect()
+        } else {
+            (0..particle_positions.len()).map(compute_density).collect()
+        }
+    };
+
+    let particle_densities = match normalization_rest_density(parameters, &particle_densities) {
+        Some(rest_density) => vec![rest_density; particle_densities.len()],
+        None => particle_densities,
+    };
+
+    // Only particles that overlap the region of interest contribute to the density map
+    let mask = match &parameters.mask {
+        Some(mask) => Some(mask.aligned_to(grid)?),
+        None => None,
+    };
+    let active_particles = mask
+        .as_ref()
+        .map(|mask| mask.active_particles(particle_positions, max_smoothing_length));
+
+    // A kernel with smoothing length `h` is equivalent to the kernel with the largest smoothing length `H`
+    // compressed by the factor `H/h`, i.e. `W(r, h) = (H/h)^3 W(r H/h, H)`. The anisotropic density map
+    // accounts for the factor `(H/h)^3` and computes the particle volumes with the rest mass, so the
+    // densities are scaled to obtain the volumes `m_i/ρ_i` of the individual particles.
+    let anisotropic_kernels = AnisotropicKernels {
+        centers: particle_positions.to_vec(),
+        transforms: particle_smoothing_lengths
+            .iter()
+            .map(|&h| Matrix3::from_diagonal_element(max_smoothing_length / h))
+            .collect(),
+    };
+    let scaled_densities = particle_densities
+        .iter()
+        .zip(particle_masses.iter())
+        .map(|(&density, &mass)| density * particle_rest_mass / mass)
+        .collect::<Vec<_>>();
+
+    let mut density_map = density_map::generate_sparse_density_map_anisotropic(
+        grid,
+        &anisotropic_kernels,
+        &scaled_densities,
+        active_particles.as_deref(),
+        &DensityMapParameters {
+            particle_rest_mass,
+            compact_support_radius: max_smoothing_length,
+            kernel,
+            cube_size: parameters.cube_size,
+            allow_threading: parameters.enable_multi_threading,
+            histogram: None,
+        },
     )?;
 
-    // TODO: Set this correctly
-    output_surface.density_map = None;
+    if let Some(mask) = &mask {
+        mask.apply_to_density_map(grid, &mut density_map);
+    }
+
+    output_surface.mesh.clear();
+    let mesh = &mut output_surface.mesh;
+    record_marching_cubes_cases(&mut output_surface.case_histogram, || {
+        marching_cubes::triangulate_density_map_with_interpolation_append(
+            grid,
+            None,
+            &density_map,
+            parameters.iso_surface_threshold,
+            parameters.edge_interpolation.unwrap_or_default(),
+            mesh,
+        )
+    })?;
+
+    output_surface.density_map = Some(density_map);
 
     Ok(())
 }
@@ -52,12 +370,13 @@ pub(crate) fn reconstruct_surface_domain_decomposition<'a, I: Index, R: Real>(
     particle_positions: &[Vector3<R>],
     parameters: &Parameters<R>,
     output_surface: &'a mut SurfaceReconstruction<I, R>,
+    progress: Option<&ProgressReporter>,
 ) -> Result<(), ReconstructionError<I, R>> {
     profile!("reconstruct_surface_domain_decomposition");
 
     SurfaceReconstructionOctreeVisitor::new(particle_positions, parameters, output_surface)
         .expect("Unable to construct octree. Missing/invalid decomposition parameters?")
-        .run(particle_positions, output_surface)?;
+        .run(particle_positions, output_surface, progress)?;
 
     Ok(())
 }
@@ -115,31 +434,39 @@ impl<I: Index, R: Real> SurfaceReconstructionOctreeVisitor<I, R> {
     }
 
     fn run(
-        self,
+        mut self,
         global_particle_positions: &[Vector3<R>],
         output_surface: &mut SurfaceReconstruction<I, R>,
+        progress: Option<&ProgressReporter>,
     ) -> Result<(), ReconstructionError<I, R>> {
+        if !matches!(
+            self.spatial_decomposition.particle_density_computation,
+            ParticleDensityComputationStrategy::IndependentSubdomains
+        ) {
+            progress::report(progress, ReconstructionStage::NeighborhoodSearch, 0.05);
+        }
+
         let global_particle_densities_vec =
             match self.spatial_decomposition.particle_density_computation {
                 // Compute particle densities globally
                 ParticleDensityComputationStrategy::Global => {
-                    Some(Self::compute_particle_densities_global(
+                    Self::compute_particle_densities_global(
                         global_particle_positions,
                         &self.grid,
                         &self.parameters,
                         output_surface,
-                    ));
+                    )?;
                     Some(std::mem::take(output_surface.workspace.densities_mut()))
                 }
                 // Compute and merge particle densities per subdomain
                 ParticleDensityComputationStrategy::SynchronizeSubdomains => {
-                    Some(Self::compute_particle_densities_local(
+                    Self::compute_particle_densities_local(
                         global_particle_positions,
                         &self.grid,
                         &self.octree,
                         &self.parameters,
                         output_surface,
-                    ));
+                    )?;
                     Some(std::mem::take(output_surface.workspace.densities_mut()))
                 }
                 // Each subdomain will compute densities later on its own
@@ -149,18 +476,31 @@ impl<I: Index, R: Real> SurfaceReconstructionOctreeVisitor<I, R> {
         let global_particle_densities =
             global_particle_densities_vec.as_ref().map(|v| v.as_slice());
 
+        // Estimate the rest density once from all particles to obtain a consistent normalization for all subdomains
+        if let (Some(RestDensityNormalization::Estimated), Some(global_particle_densities)) = (
+            self.parameters.normalize_by_rest_density,
+            global_particle_densities,
+        ) {
+            let rest_density = estimate_rest_density(global_particle_densities)
+                .unwrap_or(self.parameters.rest_density);
+            self.parameters.normalize_by_rest_density =
+                Some(RestDensityNormalization::Supplied(rest_density));
+        }
+
         // Run surface reconstruction
         if self.spatial_decomposition.enable_stitching {
             self.run_with_stitching(
                 global_particle_positions,
                 global_particle_densities,
                 output_surface,
+                progress,
             )?;
         } else {
             self.run_inplace(
                 global_particle_positions,
                 global_particle_densities,
                 output_surface,
+                progress,
             )?;
         }
 
@@ -186,10 +526,10 @@ impl<I: Index, R: Real> SurfaceReconstructionOctreeVisitor<I, R> {
         grid: &UniformGrid<I, R>,
         parameters: &Parameters<R>,
         output_surface: &mut SurfaceReconstruction<I, R>,
-    ) {
+    ) -> Result<(), ReconstructionError<I, R>> {
         let mut densities = std::mem::take(output_surface.workspace.densities_mut());
 
-        {
+        let result = {
             let mut workspace = output_surface.workspace.get_local().borrow_mut();
             compute_particle_densities_and_neighbors(
                 grid,
@@ -197,10 +537,11 @@ impl<I: Index, R: Real> SurfaceReconstructionOctreeVisitor<I, R> {
                 parameters,
                 &mut workspace.particle_neighbor_lists,
                 &mut densities,
-            );
-        }
+            )
+        };
 
         *output_surface.workspace.densities_mut() = densities;
+        result.map_err(ReconstructionError::from)
     }
 
     fn compute_particle_densities_local(
@@ -209,7 +550,7 @@ impl<I: Index, R: Real> SurfaceReconstructionOctreeVisitor<I, R> {
         octree: &Octree<I, R>,
         parameters: &Parameters<R>,
         output_surface: &mut SurfaceReconstruction<I, R>,
-    ) {
+    ) -> Result<(), ReconstructionError<I, R>> {
         profile!(
             parent_scope,
             "parallel subdomain particle density computation"
@@ -228,9 +569,8 @@ impl<I: Index, R: Real> SurfaceReconstructionOctreeVisitor<I, R> {
 
         let tl_workspaces = &output_surface.workspace;
 
-        octree
-            .root()
-            .par_visit_bfs(|octree_node: &OctreeNode<I, R>| {
+        let result = octree.root().try_par_visit_bfs(
+            |octree_node: &OctreeNode<I, R>| -> Result<(), ReconstructionError<I, R>> {
                 profile!(
                     "visit octree node for density computation",
                     parent = parent_scope
@@ -240,7 +580,7 @@ impl<I: Index, R: Real> SurfaceReconstructionOctreeVisitor<I, R> {
                     &particle_set.particles
                 } else {
                     // Skip non-leaf nodes
-                    return;
+                    return Ok(());
                 };
 
                 let mut tl_workspace_ref_mut = tl_workspaces
@@ -260,7 +600,7 @@ impl<I: Index, R: Real> SurfaceReconstructionOctreeVisitor<I, R> {
                     parameters,
                     &mut tl_workspace.particle_neighbor_lists,
                     &mut tl_workspace.particle_densities,
-                );
+                )?;
 
                 {
                     profile!("update global density values");
@@ -278,10 +618,14 @@ impl<I: Index, R: Real> SurfaceReconstructionOctreeVisitor<I, R> {
                         }
                     }
                 }
-            });
+
+                Ok(())
+            },
+        );
 
         // Unpack densities from mutex and move back into workspace
         *output_surface.workspace.densities_mut() = global_densities.into_inner().unwrap();
+        result
     }
 
     fn run_inplace(
@@ -289,6 +633,7 @@ impl<I: Index, R: Real> SurfaceReconstructionOctreeVisitor<I, R> {
         global_particle_positions: &[Vector3<R>],
         global_particle_densities: Option<&[R]>,
         output_surface: &mut SurfaceReconstruction<I, R>,
+        progress: Option<&ProgressReporter>,
     ) -> Result<(), ReconstructionError<I, R>> {
         // Clear all local meshes
         {
@@ -308,6 +653,7 @@ impl<I: Index, R: Real> SurfaceReconstructionOctreeVisitor<I, R> {
 
             profile!(parent_scope, "parallel subdomain surf. rec.");
             info!("Starting triangulation of surface patches.");
+            let leaf_progress = LeafProgress::new(progress, &self.octree);
 
             self.octree
                 .root()
@@ -323,6 +669,7 @@ impl<I: Index, R: Real> SurfaceReconstructionOctreeVisitor<I, R> {
                     trace!("Processing octree leaf with {} particles", particles.len());
 
                     if particles.is_empty() {
+                        leaf_progress.leaf_processed();
                         return Ok(());
                     } else {
                         let subdomain_grid = self.extract_node_subdomain(octree_node);
@@ -361,6 +708,7 @@ impl<I: Index, R: Real> SurfaceReconstructionOctreeVisitor<I, R> {
                             node_particle_densities.as_ref().map(|v| v.as_slice()),
                             &self.parameters,
                             &mut node_mesh,
+                            None,
                         )?;
 
                         trace!("Surface patch successfully processed.");
@@ -372,6 +720,7 @@ impl<I: Index, R: Real> SurfaceReconstructionOctreeVisitor<I, R> {
                             tl_workspace.particle_densities = node_particle_densities;
                         }
 
+                        leaf_progress.leaf_processed();
                         Ok(())
                     }
                 })?;
@@ -397,6 +746,7 @@ impl<I: Index, R: Real> SurfaceReconstructionOctreeVisitor<I, R> {
         global_particle_positions: &[Vector3<R>],
         global_particle_densities: Option<&[R]>,
         output_surface: &mut SurfaceReconstruction<I, R>,
+        progress: Option<&ProgressReporter>,
     ) -> Result<(), ReconstructionError<I, R>> {
         let mut octree = self.octree.clone();
 
@@ -409,6 +759,7 @@ impl<I: Index, R: Real> SurfaceReconstructionOctreeVisitor<I, R> {
                 "parallel domain decomposed surf. rec. with stitching"
             );
             info!("Starting triangulation of surface patches.");
+            let leaf_progress = LeafProgress::new(progress, &self.octree);
 
             octree
                 .root_mut()
@@ -419,7 +770,12 @@ impl<I: Index, R: Real> SurfaceReconstructionOctreeVisitor<I, R> {
                         &particle_set.particles
                     } else {
                         // If node has no particle set, its children were already processed so it can be stitched
-                        octree_node.stitch_surface_patches(self.parameters.iso_surface_threshold)?;
+                        tl_workspaces.record_marching_cubes_cases(|| {
+                            octree_node.stitch_surface_patches(
+                                self.parameters.iso_surface_threshold,
+                                self.parameters.edge_interpolation.unwrap_or_default(),
+                            )
+                        })?;
                         return Ok(());
                     };
 
@@ -477,6 +833,7 @@ impl<I: Index, R: Real> SurfaceReconstructionOctreeVisitor<I, R> {
                         .data_mut()
                         .replace(NodeData::SurfacePatch(surface_patch.into()));
 
+                    leaf_progress.leaf_processed();
                     Ok(())
                 })?;
 
@@ -545,6 +902,41 @@ impl<I: Index, R: Real> SurfaceReconstructionOctreeVisitor<I, R> {
     }
 }
 
+/// Reports the progress of a reconstruction with spatial decomposition after every processed leaf of the octree
+struct LeafProgress<'a, 'b> {
+    progress: Option<&'a ProgressReporter<'b>>,
+    num_leaves: usize,
+    processed_leaves: AtomicUsize,
+}
+
+impl<'a, 'b> LeafProgress<'a, 'b> {
+    /// Reports the start of the reconstruction of the octree leaves
+    fn new<I: Index, R: Real>(
+        progress: Option<&'a ProgressReporter<'b>>,
+        octree: &Octree<I, R>,
+    ) -> Self {
+        progress::report(progress, ReconstructionStage::Subdomains, 0.1);
+        Self {
+            progress,
+            num_leaves: octree.leaves().count().max(1),
+            processed_leaves: AtomicUsize::new(0),
+        }
+    }
+
+    /// Reports that another leaf was processed
+    fn leaf_processed(&self) {
+        if self.progress.is_some() {
+            let processed = self.processed_leaves.fetch_add(1, Ordering::Relaxed) + 1;
+            let fraction = processed as f64 / self.num_leaves as f64;
+            progress::report(
+                self.progress,
+                ReconstructionStage::Subdomains,
+                0.1 + 0.85 * fraction,
+            );
+        }
+    }
+}
+
 /// Computes per particle densities into the workspace, also performs the required neighborhood search
 pub(crate) fn compute_particle_densities_and_neighbors<I: Index, R: Real>(
     grid: &UniformGrid<I, R>,
@@ -552,7 +944,7 @@ pub(crate) fn compute_particle_densities_and_neighbors<I: Index, R: Real>(
     parameters: &Parameters<R>,
     particle_neighbor_lists: &mut Vec<Vec<usize>>,
     densities: &mut Vec<R>,
-) {
+) -> Result<(), density_map::DensityMapError<R>> {
     profile!("compute_particle_densities_and_neighbors");
 
     let particle_rest_density = parameters.rest_density;
@@ -574,13 +966,45 @@ pub(crate) fn compute_particle_densities_and_neighbors<I: Index, R: Real>(
         particle_positions,
         particle_neighbor_lists.as_slice(),
         parameters.compact_support_radius,
+        parameters.kernel.unwrap_or_default(),
         particle_rest_mass,
         parameters.enable_multi_threading,
         densities,
-    );
+    )
 }
 
-/// Reconstruct a surface, appends triangulation to the given mesh
+/// Returns the rest density used to normalize the density field or `None` if normalization is disabled
+fn normalization_rest_density<R: Real>(
+    parameters: &Parameters<R>,
+    particle_densities: &[R],
+) -> Option<R> {
+    match parameters.normalize_by_rest_density? {
+        RestDensityNormalization::Supplied(rest_density) => Some(rest_density),
+        RestDensityNormalization::Estimated => {
+            Some(estimate_rest_density(particle_densities).unwrap_or(parameters.rest_density))
+        }
+    }
+}
+
+/// Estimates the rest density as the median of all positive per-particle densities, returns `None` if there are no such densities
+pub(crate) fn estimate_rest_density<R: Real>(particle_densities: &[R]) -> Option<R> {
+    let mut densities: Vec<R> = particle_densities
+        .iter()
+        .copied()
+        .filter(|&density| density > R::zero())
+        .collect();
+    if densities.is_empty() {
+        return None;
+    }
+
+    let median_index = densities.len() / 2;
+    let (_, median, _) = densities.select_nth_unstable_by(median_index, |a, b| {
+        a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal)
+    });
+    Some(*median)
+}
+
+/// Reconstruct a surface, appends triangulation to the given mesh and returns the density map used for the triangulation
 pub(crate) fn reconstruct_single_surface_append<'a, I: Index, R: Real>(
     workspace: &mut LocalReconstructionWorkspace<I, R>,
     grid: &UniformGrid<I, R>,
@@ -589,7 +1013,8 @@ pub(crate) fn reconstruct_single_surface_append<'a, I: Index, R: Real>(
     particle_densities: Option<&[R]>,
     parameters: &Parameters<R>,
     output_mesh: &'a mut TriMesh3d<R>,
-) -> Result<(), ReconstructionError<I, R>> {
+    progress: Option<&ProgressReporter>,
+) -> Result<DensityMap<I, R>, ReconstructionError<I, R>> {
     let particle_rest_density = parameters.rest_density;
     let particle_rest_volume = R::from_f64((4.0 / 3.0) * std::f64::consts::PI).unwrap()
         * parameters.particle_radius.powi(3);
@@ -599,41 +1024,126 @@ pub(crate) fn reconstruct_single_surface_append<'a, I: Index, R: Real>(
         assert_eq!(particle_densities.len(), particle_positions.len());
         particle_densities
     } else {
+        progress::report(progress, ReconstructionStage::NeighborhoodSearch, 0.05);
         compute_particle_densities_and_neighbors(
             grid,
             particle_positions,
             parameters,
             &mut workspace.particle_neighbor_lists,
             &mut workspace.particle_densities,
-        );
+        )?;
         workspace.particle_densities.as_slice()
     };
 
-    // Create a new density map, reusing memory with the workspace is bad for cache efficiency
-    // Alternatively one could reuse memory with a custom caching allocator
-    let mut density_map = new_map().into();
-    density_map::generate_sparse_density_map(
-        grid,
-        subdomain_grid,
-        particle_positions,
-        particle_densities,
-        None,
-        particle_rest_mass,
-        parameters.compact_support_radius,
-        parameters.cube_size,
-        parameters.enable_multi_threading,
-        &mut density_map,
-    )?;
+    // Evaluate the density field with the rest density instead of the per-particle densities to normalize it
+    let normalized_densities = normalization_rest_density(parameters, particle_densities)
+        .map(|rest_density| vec![rest_density; particle_densities.len()]);
+    let particle_densities = normalized_densities
+        .as_deref()
+        .unwrap_or(particle_densities);
+
+    // Only particles that overlap the region of interest contribute to the density map
+    let mask = match &parameters.mask {
+        Some(mask) => Some(mask.aligned_to(grid)?),
+        None => None,
+    };
+    let active_particles = mask
+        .as_ref()
+        .map(|mask| mask.active_particles(particle_positions, parameters.compact_support_radius));
+
+    // Take the density map from the workspace, it only holds memory of a previous reconstruction on
+    // a grid with the same dimensions, otherwise reusing memory with the workspace is bad for cache efficiency
+    progress::report(progress, ReconstructionStage::DensityMap, 0.4);
+    let mut density_map = std::mem::replace(&mut workspace.density_map, new_map().into());
+    match (subdomain_grid, &parameters.anisotropy) {
+        (None, Some(anisotropy)) => {
+            // The neighbor lists in the workspace were computed together with the particle densities
+            let anisotropic_kernels = anisotropy::compute_anisotropic_kernels(
+                particle_positions,
+                workspace.particle_neighbor_lists.as_slice(),
+                parameters.compact_support_radius,
+                anisotropy,
+                parameters.enable_multi_threading,
+            )
+            .map_err(density_map::DensityMapError::<R>::from)?;
 
-    marching_cubes::triangulate_density_map_append(
-        grid,
-        subdomain_grid,
-        &density_map,
-        parameters.iso_surface_threshold,
-        output_mesh,
-    )?;
+            density_map = density_map::generate_sparse_density_map_anisotropic(
+                grid,
+                &anisotropic_kernels,
+                particle_densities,
+                active_particles.as_deref(),
+                &DensityMapParameters {
+                    particle_rest_mass,
+                    compact_support_radius: parameters.compact_support_radius,
+                    kernel: parameters.kernel.unwrap_or_default(),
+                    cube_size: parameters.cube_size,
+                    allow_threading: parameters.enable_multi_threading,
+                    histogram: None,
+                },
+            )?;
+        }
+        _ => {
+            density_map::generate_sparse_density_map(
+                grid,
+                subdomain_grid,
+                particle_positions,
+                particle_densities,
+                active_particles.as_deref(),
+                &DensityMapParameters {
+                    particle_rest_mass,
+                    compact_support_radius: parameters.compact_support_radius,
+                    kernel: parameters.kernel.unwrap_or_default(),
+                    cube_size: parameters.cube_size,
+                    allow_threading: parameters.enable_multi_threading,
+                    histogram: None,
+                },
+                &mut density_map,
+            )?;
+        }
+    }
 
-    Ok(())
+    if let Some(mask) = &mask {
+        mask.apply_to_density_map(grid, &mut density_map);
+    }
+
+    // The grid of periodic reconstructions extends beyond the periodic domain given by the parameters
+    if parameters.periodic_axes.any() {
+        if let Some(domain) = &parameters.domain_aabb {
+            periodic::make_density_map_periodic(
+                grid,
+                domain,
+                &parameters.periodic_axes,
+                &mut density_map,
+            );
+        }
+    }
+
+    progress::report(progress, ReconstructionStage::MarchingCubes, 0.7);
+    let edge_interpolation = parameters.edge_interpolation.unwrap_or_default();
+    record_marching_cubes_cases(&mut workspace.case_histogram, || {
+        match (subdomain_grid, parameters.contour_cell_factors) {
+            (None, Some(contour_cell_factors)) => {
+                marching_cubes::triangulate_density_map_anisotropic_append(
+                    grid,
+                    &density_map,
+                    parameters.iso_surface_threshold,
+                    edge_interpolation,
+                    contour_cell_factors,
+                    output_mesh,
+                )
+            }
+            _ => marching_cubes::triangulate_density_map_with_interpolation_append(
+                grid,
+                subdomain_grid,
+                &density_map,
+                parameters.iso_surface_threshold,
+                edge_interpolation,
+                output_mesh,
+            ),
+        }
+    })?;
+
+    Ok(density_map)
 }
 
 /// Reconstruct a surface, appends triangulation to the given mesh
@@ -661,10 +1171,26 @@ pub(crate) fn reconstruct_surface_patch<I: Index, R: Real>(
             parameters,
             &mut workspace.particle_neighbor_lists,
             &mut workspace.particle_densities,
-        );
+        )?;
         workspace.particle_densities.as_slice()
     };
 
+    // Evaluate the density field with the rest density instead of the per-particle densities to normalize it
+    let normalized_densities = normalization_rest_density(parameters, particle_densities)
+        .map(|rest_density| vec![rest_density; particle_densities.len()]);
+    let particle_densities = normalized_densities
+        .as_deref()
+        .unwrap_or(particle_densities);
+
+    // Only particles that overlap the region of interest contribute to the density map
+    let mask = match &parameters.mask {
+        Some(mask) => Some(mask.aligned_to(subdomain_grid.global_grid())?),
+        None => None,
+    };
+    let active_particles = mask
+        .as_ref()
+        .map(|mask| mask.active_particles(particle_positions, parameters.compact_support_radius));
+
     // Create a new density map, reusing memory with the workspace is bad for cache efficiency
     // Alternatively, one could reuse memory with a custom caching allocator
     let mut density_map = new_map().into();
@@ -673,20 +1199,31 @@ pub(crate) fn reconstruct_surface_patch<I: Index, R: Real>(
         Some(subdomain_grid),
         particle_positions,
         particle_densities,
-        None,
-        particle_rest_mass,
-        parameters.compact_support_radius,
-        parameters.cube_size,
-        parameters.enable_multi_threading,
+        active_particles.as_deref(),
+        &DensityMapParameters {
+            particle_rest_mass,
+            compact_support_radius: parameters.compact_support_radius,
+            kernel: parameters.kernel.unwrap_or_default(),
+            cube_size: parameters.cube_size,
+            allow_threading: parameters.enable_multi_threading,
+            histogram: None,
+        },
         &mut density_map,
     )?;
 
+    if let Some(mask) = &mask {
+        mask.apply_to_density_map(subdomain_grid.global_grid(), &mut density_map);
+    }
+
     // Run marching cubes and get boundary data
-    let patch = marching_cubes::triangulate_density_map_to_surface_patch::<I, R>(
-        subdomain_grid,
-        &density_map,
-        parameters.iso_surface_threshold,
-    )?;
+    let patch = record_marching_cubes_cases(&mut workspace.case_histogram, || {
+        marching_cubes::triangulate_density_map_to_surface_patch::<I, R>(
+            subdomain_grid,
+            &density_map,
+            parameters.iso_surface_threshold,
+            parameters.edge_interpolation.unwrap_or_default(),
+        )
+    })?;
 
     Ok(patch)
 }
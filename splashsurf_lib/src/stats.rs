@@ -0,0 +1,68 @@
+//! Optional instrumentation of the reconstruction, e.g. the distribution of marching cubes cases
+//!
+//! The statistics are collected per reconstruction and returned together with its result, e.g. the
+//! histogram of the marching cubes cases can be queried using
+//! [`SurfaceReconstruction::marching_cubes_case_histogram`](crate::SurfaceReconstruction::marching_cubes_case_histogram).
+//! Statistics of concurrent reconstructions are therefore never mixed.
+
+use std::cell::RefCell;
+
+pub use crate::marching_cubes::{MarchingCubesCaseHistogram, MARCHING_CUBES_CASE_COUNT};
+
+thread_local! {
+    /// Histogram of the innermost [`collect_marching_cubes_cases`] scope that is active on the current thread
+    static CURRENT_CASE_HISTOGRAM: RefCell<Option<MarchingCubesCaseHistogram>> = const { RefCell::new(None) };
+}
+
+/// Adds all counts of the histogram to the histogram of the active [`collect_marching_cubes_cases`] scope of the current thread
+///
+/// The counts are discarded if no scope is active.
+pub(crate) fn add_to_current_scope(histogram: &MarchingCubesCaseHistogram) {
+    CURRENT_CASE_HISTOGRAM.with(|current| {
+        if let Some(current) = current.borrow_mut().as_mut() {
+            current.merge(histogram);
+        }
+    });
+}
+
+/// Runs the given function and returns the histogram of all marching cubes cases triangulated by it on the current thread
+///
+/// Scopes can be nested, e.g. if a job is stolen by a thread that waits for a parallel computation. The cases of
+/// an inner scope are only added to the inner histogram.
+pub(crate) fn collect_marching_cubes_cases<T>(
+    f: impl FnOnce() -> T,
+) -> (T, MarchingCubesCaseHistogram) {
+    let outer = CURRENT_CASE_HISTOGRAM.with(|current| {
+        current
+            .borrow_mut()
+            .replace(MarchingCubesCaseHistogram::default())
+    });
+    let result = f();
+    let histogram = CURRENT_CASE_HISTOGRAM
+        .with(|current| std::mem::replace(&mut *current.borrow_mut(), outer).unwrap_or_default());
+    (result, histogram)
+}
+
+#[test]
+fn test_collect_marching_cubes_cases_nested() {
+    let case = |c: u8| {
+        let mut histogram = MarchingCubesCaseHistogram::default();
+        histogram.increment(c);
+        histogram
+    };
+
+    // Cases outside of any scope are discarded
+    add_to_current_scope(&case(1));
+
+    let ((_, inner), outer) = collect_marching_cubes_cases(|| {
+        add_to_current_scope(&case(2));
+        let inner = collect_marching_cubes_cases(|| add_to_current_scope(&case(3)));
+        add_to_current_scope(&case(2));
+        inner
+    });
+
+    assert_eq!(inner.total(), 1);
+    assert_eq!(inner.count(3), 1);
+    assert_eq!(outer.total(), 2);
+    assert_eq!(outer.count(2), 2);
+}
@@ -0,0 +1,308 @@
+//! Out-of-core surface reconstruction of large domains in independent tiles
+//!
+//! [`reconstruct_surface_tiled`] splits the background grid of a reconstruction into a regular grid of tiles
+//! with a fixed number of marching cubes cells per axis. The tiles are reconstructed one after another from
+//! the particles inside of the tile and a surrounding ghost margin. The mesh of each tile is passed to a
+//! callback (e.g. to write it to a separate file) before the next tile is processed, so only the particles
+//! and the mesh of a single tile have to be kept in memory in addition to the input particles.
+//!
+//! The ghost margin ensures that the density values on the grid points of a tile are the same as in a global
+//! reconstruction of the whole domain. Every marching cubes cell of the global background grid belongs to
+//! exactly one tile and only the triangles of its own cells are kept in the mesh of a tile. Therefore, the
+//! union of all tile meshes is the surface of a global reconstruction.
+//!
+//! ## Welding the seams
+//!
+//! The meshes of neighboring tiles are not connected: vertices on a face shared by two tiles (or an edge shared
+//! by up to four tiles) are contained in the meshes of all of these tiles. To assemble a single closed mesh, a
+//! consumer has to concatenate the tile meshes and merge vertices that lie on the boundary of their
+//! [`Tile::aabb`] with the vertices of the neighboring tiles at the same position. As the vertex coordinates of
+//! different tiles are computed relative to different local grids, they may differ slightly due to floating
+//! point rounding, so positions should be compared with a small tolerance relative to the cube size
+//! (e.g. `1e-4 * cube_size`). Vertices in the interior of a tile never have to be merged.
+
+use crate::mesh::TriMesh3d;
+use crate::{
+    density_map, profile, reconstruct_surface, validate_parameters, AxisAlignedBoundingBox3d,
+    Index, Parameters, Real, ReconstructionError, RestDensityNormalization,
+};
+use log::info;
+use nalgebra::Vector3;
+
+/// Regular grid of tiles covering the background grid of a tiled reconstruction
+#[derive(Clone, Debug)]
+pub struct TileLayout<R: Real> {
+    /// Bounding box of the background grid of the whole domain, the lower corner is the lower corner of the first tile
+    pub domain_aabb: AxisAlignedBoundingBox3d<R>,
+    /// Edge length of the marching cubes cells
+    pub cube_size: R,
+    /// Number of marching cubes cells per tile along each axis (tiles at the upper domain boundary may be smaller)
+    pub cells_per_tile: usize,
+    /// Number of tiles along each axis
+    pub num_tiles: [usize; 3],
+    /// Margin around each tile in which ghost particles are collected for the reconstruction of the tile
+    pub ghost_margin: R,
+}
+
+/// A single tile of a tiled reconstruction
+#[derive(Clone, Debug)]
+pub struct Tile<R: Real> {
+    /// Index of the tile along each axis
+    pub index: [usize; 3],
+    /// Region of the domain covered by the marching cubes cells of the tile
+    pub aabb: AxisAlignedBoundingBox3d<R>,
+    /// Number of particles used for the reconstruction of the tile (including ghost particles)
+    pub num_particles: usize,
+}
+
+impl<R: Real> TileLayout<R> {
+    /// Returns the edge length of a (full) tile
+    pub fn tile_size(&self) -> R {
+        self.cube_size * R::from_usize(self.cells_per_tile).unwrap()
+    }
+
+    /// Returns the total number of tiles
+    pub fn num_tiles_total(&self) -> usize {
+        self.num_tiles[0] * self.num_tiles[1] * self.num_tiles[2]
+    }
+
+    /// Returns the region of the domain covered by the tile with the given index
+    pub fn tile_aabb(&self, index: &[usize; 3]) -> AxisAlignedBoundingBox3d<R> {
+        let tile_size = self.tile_size();
+        let domain_min = self.domain_aabb.min();
+        let domain_max = self.domain_aabb.max();
+
+        let min =
+            Vector3::from_fn(|d, _| domain_min[d] + tile_size * R::from_usize(index[d]).unwrap());
+        let max = Vector3::from_fn(|d, _| {
+            (domain_min[d] + tile_size * R::from_usize(index[d] + 1).unwrap()).min(domain_max[d])
+        });
+        AxisAlignedBoundingBox3d::new(min, max)
+    }
+
+    /// Returns the index of the tile containing the given point, points outside of the domain are assigned to the closest tile
+    pub fn tile_containing_point(&self, point: &Vector3<R>) -> [usize; 3] {
+        let tile_size = self.tile_size();
+        let domain_min = self.domain_aabb.min();
+        let index_along = |d: usize| {
+            clamped_tile_index((point[d] - domain_min[d]) / tile_size, self.num_tiles[d])
+        };
+        [index_along(0), index_along(1), index_along(2)]
+    }
+
+    /// Returns the flat index of the tile with the given index (the z-index is the fastest)
+    fn flatten_tile_index(&self, index: &[usize; 3]) -> usize {
+        (index[0] * self.num_tiles[1] + index[1]) * self.num_tiles[2] + index[2]
+    }
+}
+
+/// Returns the index of the tile containing the given coordinate in units of the tile size, clamped to the valid range
+fn clamped_tile_index<R: Real>(coordinate: R, num_tiles: usize) -> usize {
+    if coordinate <= R::zero() {
+        0
+    } else {
+        coordinate
+            .floor()
+            .to_usize()
+            .unwrap_or(usize::MAX)
+            .min(num_tiles - 1)
+    }
+}
+
+/// Performs a surface reconstruction in independent tiles and passes the mesh of each tile to the given callback
+///
+/// The background grid of the reconstruction is split into tiles of `cells_per_tile` marching cubes cells per axis,
+/// see the [module level documentation](self) for details and how to weld the seams of the tile meshes.
+/// The callback is called for every tile that contains particles (including ghost particles), even if its mesh
+/// is empty. An error returned by the callback aborts the reconstruction. The `max_grid_cells` limit of the
+/// parameters applies to the background grid of each tile (including its margin) instead of the whole domain.
+///
+/// Currently, only global reconstructions of the individual tiles without spatial decomposition, periodic
+/// boundaries, masks, per-axis marching cubes cell factors, an estimated rest density normalization
+/// or anisotropic kernels are supported.
+pub fn reconstruct_surface_tiled<I: Index, R: Real, F>(
+    particle_positions: &[Vector3<R>],
+    parameters: &Parameters<R>,
+    cells_per_tile: usize,
+    mut on_tile: F,
+) -> Result<TileLayout<R>, ReconstructionError<I, R>>
+where
+    F: FnMut(&Tile<R>, TriMesh3d<R>) -> Result<(), anyhow::Error>,
+{
+    profile!("reconstruct_surface_tiled");
+
+    if cells_per_tile == 0 {
+        return Err(ReconstructionError::InvalidParameters(
+            "the number of cells per tile has to be larger than zero".to_string(),
+        ));
+    }
+    if parameters.spatial_decomposition.is_some()
+        || parameters.periodic_axes.any()
+        || parameters.mask.is_some()
+        || parameters
+            .contour_cell_factors
+            .is_some_and(|factors| factors != [1, 1, 1])
+        || parameters.normalize_by_rest_density == Some(RestDensityNormalization::Estimated)
+        || parameters.anisotropy.is_some()
+    {
+        return Err(ReconstructionError::InvalidParameters(
+            "the tiled reconstruction does not support spatial decomposition, periodic boundaries, masks, per-axis marching cubes cell factors, an estimated rest density normalization or anisotropic kernels"
+                .to_string(),
+        ));
+    }
+    validate_parameters(parameters)?;
+
+    let cube_size = parameters.cube_size;
+    let compact_support_radius = parameters.compact_support_radius;
+    let kernel_evaluation_radius =
+        density_map::compute_kernel_evaluation_radius::<I, R>(compact_support_radius, cube_size)
+            .kernel_evaluation_radius;
+
+    // Domain of the reconstruction, equivalent to the domain of a global reconstruction
+    let domain_aabb = if let Some(domain_aabb) = &parameters.domain_aabb {
+        domain_aabb.clone()
+    } else {
+        if particle_positions.is_empty() {
+            return Err(ReconstructionError::InvalidParameters(
+                "the domain of a tiled reconstruction without particles has to be specified"
+                    .to_string(),
+            ));
+        }
+
+        let mut domain_aabb = if parameters.enable_multi_threading {
+            AxisAlignedBoundingBox3d::par_from_points(particle_positions)
+        } else {
+            AxisAlignedBoundingBox3d::from_points(particle_positions)
+        };
+        domain_aabb.grow_uniformly(parameters.particle_radius);
+        domain_aabb.grow_uniformly(kernel_evaluation_radius);
+        domain_aabb
+            .grow_uniformly(cube_size * R::from_usize(parameters.grid_margin_cells).unwrap());
+        domain_aabb
+    };
+
+    // Round the domain up to full marching cubes cells
+    let num_cells = domain_aabb
+        .extents()
+        .map(|extent| (extent / cube_size).ceil().to_usize().unwrap_or(0).max(1));
+    let layout = TileLayout {
+        domain_aabb: AxisAlignedBoundingBox3d::new(
+            *domain_aabb.min(),
+            domain_aabb.min() + num_cells.map(|n| R::from_usize(n).unwrap()) * cube_size,
+        ),
+        cube_size,
+        cells_per_tile,
+        num_tiles: [
+            num_cells[0].div_ceil(cells_per_tile),
+            num_cells[1].div_ceil(cells_per_tile),
+            num_cells[2].div_ceil(cells_per_tile),
+        ],
+        // Particles contributing to the tile and all of their neighbors
+        ghost_margin: kernel_evaluation_radius + compact_support_radius,
+    };
+
+    info!(
+        "Splitting the domain {:?} into {}x{}x{} tiles of {} cells per axis",
+        layout.domain_aabb,
+        layout.num_tiles[0],
+        layout.num_tiles[1],
+        layout.num_tiles[2],
+        cells_per_tile
+    );
+
+    // Particles close to the domain boundary are ignored, same as in a global reconstruction
+    let allowed_domain = {
+        let mut aabb = layout.domain_aabb.clone();
+        aabb.grow_uniformly(-kernel_evaluation_radius);
+        aabb
+    };
+
+    // Assign each particle to all tiles whose ghost margin contains the particle
+    let mut tile_particles = vec![Vec::new(); layout.num_tiles_total()];
+    {
+        profile!("assign particles to tiles");
+
+        let tile_size = layout.tile_size();
+        let domain_min = layout.domain_aabb.min();
+        for (particle_index, particle) in particle_positions.iter().enumerate() {
+            if !allowed_domain.contains_point(particle) {
+                continue;
+            }
+
+            let range_along = |d: usize| {
+                let relative = particle[d] - domain_min[d];
+                let lower = (relative - layout.ghost_margin) / tile_size;
+                let upper = (relative + layout.ghost_margin) / tile_size;
+                clamped_tile_index(lower, layout.num_tiles[d])
+                    ..=clamped_tile_index(upper, layout.num_tiles[d])
+            };
+
+            for i in range_along(0) {
+                for j in range_along(1) {
+                    for k in range_along(2) {
+                        tile_particles[layout.flatten_tile_index(&[i, j, k])].push(particle_index);
+                    }
+                }
+            }
+        }
+    }
+
+    // Margin of the background grid of a tile such that all particles of the tile are inside of the allowed domain of its density map
+    let grid_margin = cube_size
+        * (((kernel_evaluation_radius + layout.ghost_margin) / cube_size).ceil() + R::one());
+
+    for i in 0..layout.num_tiles[0] {
+        for j in 0..layout.num_tiles[1] {
+            for k in 0..layout.num_tiles[2] {
+                profile!("reconstruct tile");
+
+                let index = [i, j, k];
+                let particles =
+                    std::mem::take(&mut tile_particles[layout.flatten_tile_index(&index)]);
+                if particles.is_empty() {
+                    continue;
+                }
+
+                let tile_positions = particles
+                    .iter()
+                    .map(|&p| particle_positions[p])
+                    .collect::<Vec<_>>();
+
+                let tile_aabb = layout.tile_aabb(&index);
+                let tile_parameters = Parameters {
+                    domain_aabb: Some({
+                        let mut aabb = tile_aabb.clone();
+                        aabb.grow_uniformly(grid_margin);
+                        aabb
+                    }),
+                    ..parameters.clone()
+                };
+
+                let mut mesh: TriMesh3d<R> =
+                    reconstruct_surface::<I, R>(tile_positions.as_slice(), &tile_parameters)?
+                        .into();
+
+                // Only keep the triangles of the marching cubes cells of this tile
+                {
+                    let vertices = &mesh.vertices;
+                    let third = R::one() / R::from_usize(3).unwrap();
+                    mesh.triangles.retain(|tri| {
+                        let centroid =
+                            (vertices[tri[0]] + vertices[tri[1]] + vertices[tri[2]]) * third;
+                        layout.tile_containing_point(&centroid) == index
+                    });
+                }
+                mesh.remove_unreferenced_vertices();
+
+                let tile = Tile {
+                    index,
+                    aabb: tile_aabb,
+                    num_particles: tile_positions.len(),
+                };
+                on_tile(&tile, mesh)?;
+            }
+        }
+    }
+
+    Ok(layout)
+}
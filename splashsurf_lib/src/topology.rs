@@ -1,7 +1,7 @@
 //! Helper types for cartesian coordinate system topology
 
+use core::ops::{Add, Sub};
 use num::{CheckedAdd, CheckedSub, One};
-use std::ops::{Add, Sub};
 
 /// Direction on a number line/coordinate axis or identifiers for the end points of a line
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
@@ -1,8 +1,8 @@
-use std::fmt::{Debug, Display};
-use std::hash::Hash;
+use core::fmt::{Debug, Display};
+use core::hash::Hash;
 
-use bitflags::_core::ops::{AddAssign, MulAssign, SubAssign};
 use bytemuck::Pod;
+use core::ops::{AddAssign, MulAssign, SubAssign};
 use nalgebra::{RealField, SVector};
 use num::{Bounded, CheckedAdd, CheckedMul, CheckedSub, FromPrimitive, Integer, ToPrimitive};
 
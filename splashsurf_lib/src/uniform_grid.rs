@@ -3,11 +3,13 @@
 use crate::topology::{Axis, DirectedAxis, DirectedAxisArray, Direction};
 use crate::{AxisAlignedBoundingBox3d, Index, Real};
 use bitflags::bitflags;
+use core::cmp::Ordering;
+use core::fmt;
+use core::iter::Iterator;
 use itertools::iproduct;
+#[cfg(feature = "std")]
 use log::trace;
 use nalgebra::Vector3;
-use std::iter::Iterator;
-use thiserror::Error as ThisError;
 
 // TODO: Reduce mess with all array and scalar indexing functions
 
@@ -25,8 +27,35 @@ use thiserror::Error as ThisError;
  *   0          1
  */
 
+/// Rounding mode used to map a coordinate in space to the index of a point of a grid
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Rounding {
+    /// Round to the closest grid point with lower or equal index, e.g. for the lower corner of an AABB
+    Floor,
+    /// Round to the closest grid point with higher or equal index, e.g. for the upper corner of an AABB
+    Ceil,
+    /// Round to the closest grid point
+    Nearest,
+}
+
+impl Rounding {
+    /// Applies the rounding mode to a real value
+    fn apply<R: Real>(self, value: R) -> R {
+        match self {
+            Rounding::Floor => value.floor(),
+            Rounding::Ceil => value.ceil(),
+            Rounding::Nearest => value.round(),
+        }
+    }
+}
+
 /// Unique identifier for a point in a grid, represented by an index triplet on the 3D cartesian grid
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde_crate::Serialize, serde_crate::Deserialize),
+    serde(crate = "serde_crate")
+)]
 pub struct PointIndex<I: Index> {
     index: [I; 3],
 }
@@ -220,33 +249,62 @@ pub struct UniformCartesianCubeGrid3d<I: Index, R: Real> {
     n_cells_per_dim: [I; 3],
 }
 
+/// Returns the number of cells of a grid with the given cube size that encloses the AABB, without constructing the grid
+///
+/// The number of cells is computed in the same way as by [`UniformCartesianCubeGrid3d::from_aabb`] but independent of
+/// the index type, which allows to check the size of a grid before constructing it and allocating any storage.
+/// Returns `u128::MAX` if the cube size is not positive or the number of cells is not representable by a `u128`.
+pub fn grid_cell_count_for<R: Real>(aabb: &AxisAlignedBoundingBox3d<R>, cube_size: R) -> u128 {
+    // A NaN cube size is not positive either
+    if cube_size.partial_cmp(&R::zero()) != Some(Ordering::Greater) {
+        return u128::MAX;
+    }
+
+    let n_cells_real = aabb.extents() / cube_size;
+    n_cells_real.iter().fold(1, |count: u128, n| {
+        let n = n.ceil().to_u128().map_or(u128::MAX, |n| n.max(1));
+        count.saturating_mul(n)
+    })
+}
+
 /// Error type for the construction of a [`UniformGrid`]
-#[rustfmt::skip]
-#[derive(Copy, Clone, Eq, PartialEq, Debug, ThisError)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub enum GridConstructionError<I: Index, R: Real> {
     /// The cell size is invalid, it has to be larger than zero
-    #[error("invalid cell size `{0}` supplied, cell size has to be larger than zero")]
     InvalidCellSize(R),
     /// The AABB is degenerate, every dimension of the AABB has to have non-zero extents
-    #[error("degenerate AABB supplied, every dimension of the AABB has to have non-zero extents")]
     DegenerateAabb,
     /// The AABB is inconsistent, every dimension of the AABB has to have an extent larger than zero
-    #[error("inconsistent AABB supplied, every dimension of the AABB has to have an extent larger than zero")]
     InconsistentAabb,
     /// The index type is too small to index the number of cells in each dimension of the domain
-    #[error("index type is too small to index number of cells per dimension of the domain (max index: {})", I::max_value())]
     IndexTypeTooSmallCellsPerDim,
     /// The index type is too small to index the number of points in each dimension of the domain
-    #[error("index type is too small to index number of points per dimension of the domain (max index: {})", I::max_value())]
     IndexTypeTooSmallPointsPerDim,
     /// The index type is too small to index the total number of points in the whole domain (nx * ny * nz)
-    #[error("index type is too small to index the total number of points in the whole domain ({0}x{1}x{2}, max index: {})", I::max_value())]
     IndexTypeTooSmallTotalPoints(I, I, I),
     /// The real type is too small to store the coordinates of all possible points in the domain
-    #[error("real type is too small to store the coordinates of all points in the domain (max value: {})", R::max_value())]
     RealTypeTooSmallDomainSize,
 }
 
+// Implemented manually instead of using `thiserror` as the grid is part of the `no_std` core of the crate
+impl<I: Index, R: Real> fmt::Display for GridConstructionError<I, R> {
+    #[rustfmt::skip]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidCellSize(cell_size) => write!(f, "invalid cell size `{}` supplied, cell size has to be larger than zero", cell_size),
+            Self::DegenerateAabb => write!(f, "degenerate AABB supplied, every dimension of the AABB has to have non-zero extents"),
+            Self::InconsistentAabb => write!(f, "inconsistent AABB supplied, every dimension of the AABB has to have an extent larger than zero"),
+            Self::IndexTypeTooSmallCellsPerDim => write!(f, "index type is too small to index number of cells per dimension of the domain (max index: {})", I::max_value()),
+            Self::IndexTypeTooSmallPointsPerDim => write!(f, "index type is too small to index number of points per dimension of the domain (max index: {})", I::max_value()),
+            Self::IndexTypeTooSmallTotalPoints(nx, ny, nz) => write!(f, "index type is too small to index the total number of points in the whole domain ({}x{}x{}, max index: {})", nx, ny, nz, I::max_value()),
+            Self::RealTypeTooSmallDomainSize => write!(f, "real type is too small to store the coordinates of all points in the domain (max value: {})", R::max_value()),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<I: Index, R: Real> std::error::Error for GridConstructionError<I, R> {}
+
 impl<I: Index, R: Real> UniformCartesianCubeGrid3d<I, R> {
     /// Construct a new grid enclosing the given AABB
     ///
@@ -306,6 +364,7 @@ impl<I: Index, R: Real> UniformCartesianCubeGrid3d<I, R> {
     }
 
     /// Constructs a degenerate grid with zero extents, zero cells and zero points
+    #[cfg(feature = "std")]
     pub(crate) fn new_zero() -> Self {
         Self {
             aabb: AxisAlignedBoundingBox3d::new(Vector3::zeros(), Vector3::zeros()),
@@ -321,6 +380,12 @@ impl<I: Index, R: Real> UniformCartesianCubeGrid3d<I, R> {
         &self.aabb
     }
 
+    /// Returns the coordinates of the lower corner (the point with index `[0, 0, 0]`) of the grid
+    #[inline(always)]
+    pub fn origin(&self) -> &Vector3<R> {
+        self.aabb.min()
+    }
+
     /// Returns the cell size used by the grid
     #[inline(always)]
     pub fn cell_size(&self) -> R {
@@ -339,6 +404,20 @@ impl<I: Index, R: Real> UniformCartesianCubeGrid3d<I, R> {
         &self.n_cells_per_dim
     }
 
+    /// Returns the total number of grid points of the grid
+    #[inline(always)]
+    pub fn num_points(&self) -> I {
+        let np = &self.n_points_per_dim;
+        np[0] * np[1] * np[2]
+    }
+
+    /// Returns the total number of grid cells of the grid
+    #[inline(always)]
+    pub fn num_cells(&self) -> I {
+        let nc = &self.n_cells_per_dim;
+        nc[0] * nc[1] * nc[2]
+    }
+
     /// Converts a point index triplet into a strongly typed index, returns `None` if the corresponding point is not part of the grid
     #[inline(always)]
     pub fn get_point(&self, ijk: [I; 3]) -> Option<PointIndex<I>> {
@@ -488,6 +567,81 @@ impl<I: Index, R: Real> UniformCartesianCubeGrid3d<I, R> {
         self.point_coordinates_array(point.index())
     }
 
+    /// Returns the bounding box of a grid cell in space, i.e. the AABB spanned by its lowest and highest corner point
+    #[inline(always)]
+    pub fn cell_aabb(&self, cell: &CellIndex<I>) -> AxisAlignedBoundingBox3d<R> {
+        let min = self.point_coordinates_array(cell.index());
+        let max = min + Vector3::repeat(self.cell_size);
+        AxisAlignedBoundingBox3d::new(min, max)
+    }
+
+    /// Returns an iterator over all cells of the grid that overlap with the given sphere
+    ///
+    /// The candidates are the cells intersecting the AABB of the sphere (clamped to the grid), of which only the
+    /// cells with a closest point to the center of the sphere that is not farther away than the radius are returned.
+    pub fn cells_in_sphere<'a>(
+        &'a self,
+        center: &Vector3<R>,
+        radius: R,
+    ) -> impl Iterator<Item = CellIndex<I>> + 'a {
+        let center = *center;
+        let radius_squared = radius * radius;
+
+        // Range of cell indices per dimension overlapping with the AABB of the sphere
+        let mut lower = [0; 3];
+        let mut counts = [0; 3];
+        for dim in 0..3 {
+            let n_cells = self.n_cells_per_dim[dim].to_usize().unwrap_or(0);
+            let min = ((center[dim] - radius - self.aabb.min()[dim]) / self.cell_size).floor();
+            let max = ((center[dim] + radius - self.aabb.min()[dim]) / self.cell_size).floor();
+            // Spheres with a negative or NaN radius and spheres with a NaN center do not overlap any cell
+            let is_non_negative = |value: R| {
+                matches!(
+                    value.partial_cmp(&R::zero()),
+                    Some(Ordering::Greater | Ordering::Equal)
+                )
+            };
+            if n_cells == 0 || !is_non_negative(radius) || !is_non_negative(max) {
+                continue;
+            }
+
+            let min = min.max(R::zero()).to_usize().unwrap_or(usize::MAX);
+            let max = max.to_usize().unwrap_or(usize::MAX).min(n_cells - 1);
+            if min <= max {
+                lower[dim] = min;
+                counts[dim] = max - min + 1;
+            }
+        }
+
+        iproduct!(0..counts[0], 0..counts[1], 0..counts[2]).filter_map(move |(i, j, k)| {
+            let cell = CellIndex::from_ijk([
+                I::from_usize(lower[0] + i)?,
+                I::from_usize(lower[1] + j)?,
+                I::from_usize(lower[2] + k)?,
+            ]);
+
+            // Squared distance of the center to the closest point of the cell
+            let cell_aabb = self.cell_aabb(&cell);
+            let mut distance_squared = R::zero();
+            for dim in 0..3 {
+                let d = if center[dim] < cell_aabb.min()[dim] {
+                    cell_aabb.min()[dim] - center[dim]
+                } else if center[dim] > cell_aabb.max()[dim] {
+                    center[dim] - cell_aabb.max()[dim]
+                } else {
+                    R::zero()
+                };
+                distance_squared += d * d;
+            }
+
+            if distance_squared <= radius_squared {
+                Some(cell)
+            } else {
+                None
+            }
+        })
+    }
+
     /// Returns the grid cell index triplet of the cell enclosing a point with the given coordinates in space
     #[inline(always)]
     pub fn enclosing_cell(&self, coord: &Vector3<R>) -> [I; 3] {
@@ -499,6 +653,63 @@ impl<I: Index, R: Real> UniformCartesianCubeGrid3d<I, R> {
         ]
     }
 
+    /// Returns the index of the grid point corresponding to the given coordinates using the given rounding mode
+    ///
+    /// Returns `None` if the coordinates are outside of the grid's AABB. No tolerance is applied, i.e. coordinates
+    /// that are only slightly outside of the grid (e.g. due to round-off errors) are rejected as well.
+    /// See [`Self::clamped_point_index_for_coordinate`] for a variant that clamps the coordinates to the grid instead.
+    pub fn point_index_for_coordinate(
+        &self,
+        coord: &Vector3<R>,
+        rounding: Rounding,
+    ) -> Option<PointIndex<I>> {
+        let normalized_coord = (coord - self.aabb.min()) / self.cell_size;
+
+        let mut ijk = [I::zero(); 3];
+        for (dim, index) in ijk.iter_mut().enumerate() {
+            let n_cells = self.n_cells_per_dim[dim].to_real_unchecked::<R>();
+            let x = normalized_coord[dim];
+            let inside = x >= R::zero() && x <= n_cells;
+            if !inside {
+                return None;
+            }
+            *index = rounding.apply(x).to_index()?;
+        }
+
+        self.get_point(ijk)
+    }
+
+    /// Returns the index of the grid point corresponding to the given coordinates, clamped to the grid, using the given rounding mode
+    ///
+    /// Coordinates outside of the grid's AABB are clamped to the AABB before rounding,
+    /// i.e. they are mapped to the closest point on the boundary of the grid.
+    pub fn clamped_point_index_for_coordinate(
+        &self,
+        coord: &Vector3<R>,
+        rounding: Rounding,
+    ) -> PointIndex<I> {
+        let clamped_coord = Vector3::from_fn(|dim, _| {
+            coord[dim]
+                .max(self.aabb.min()[dim])
+                .min(self.aabb.max()[dim])
+        });
+
+        // Clamping to the AABB may still leave the normalized coordinate slightly outside due to round-off errors
+        let normalized_coord = (clamped_coord - self.aabb.min()) / self.cell_size;
+        let mut ijk = [I::zero(); 3];
+        for (dim, index) in ijk.iter_mut().enumerate() {
+            let n_cells = self.n_cells_per_dim[dim].to_real_unchecked::<R>();
+            let x = normalized_coord[dim].max(R::zero()).min(n_cells);
+            *index = rounding
+                .apply(x)
+                .to_index_unchecked::<I>()
+                .min(self.n_cells_per_dim[dim]);
+        }
+
+        self.get_point(ijk)
+            .expect("clamped point index has to be part of the grid")
+    }
+
     /// If part of the grid, returns the neighbor of a point following the given directed axis along the grid
     #[inline(always)]
     pub fn get_point_neighbor(
@@ -717,6 +928,7 @@ impl<I: Index, R: Real> UniformCartesianCubeGrid3d<I, R> {
     }
 
     /// Logs the information about the given grid
+    #[cfg(feature = "std")]
     pub(crate) fn log_grid_info(&self) {
         trace!(
             "Using a grid with {:?}x{:?}x{:?} points and {:?}x{:?}x{:?} cells of edge length {}.",
@@ -734,6 +946,7 @@ impl<I: Index, R: Real> UniformCartesianCubeGrid3d<I, R> {
 
 impl<I: Index, R: Real> OwningSubdomainGrid<I, R> {
     /// Creates a new subdomain grid
+    #[cfg(feature = "std")]
     pub(crate) fn new(
         global_grid: UniformGrid<I, R>,
         subdomain_grid: UniformGrid<I, R>,
@@ -931,6 +1144,118 @@ impl<I: Index> EdgeIndex<I> {
     }
 }
 
+#[test]
+fn test_grid_cell_count_for() {
+    let aabb = AxisAlignedBoundingBox3d::new(Vector3::zeros(), Vector3::new(1.0, 2.0, 3.5));
+    assert_eq!(grid_cell_count_for(&aabb, 1.0), 8);
+    assert_eq!(grid_cell_count_for(&aabb, 0.0), u128::MAX);
+
+    for &cube_size in &[0.3, 0.25, 0.01] {
+        let grid = UniformGrid::<i64, f64>::from_aabb(&aabb, cube_size).unwrap();
+        assert_eq!(
+            grid_cell_count_for(&aabb, cube_size),
+            grid.num_cells() as u128
+        );
+    }
+
+    // The count does not depend on the index type and saturates instead of overflowing
+    assert!(grid_cell_count_for(&aabb, 1e-9) > i32::MAX as u128);
+    assert_eq!(grid_cell_count_for(&aabb, 1e-300), u128::MAX);
+}
+
+#[test]
+fn test_grid_and_cell_aabb() {
+    let origin = Vector3::new(-1.0, 0.5, 2.0);
+    let grid = UniformGrid::<i64, f64>::new(&origin, &[3, 4, 5], 0.25).unwrap();
+
+    // The grid AABB is spanned by the first and last point of the grid
+    let np = grid.points_per_dim();
+    let max_point = grid.get_point([np[0] - 1, np[1] - 1, np[2] - 1]).unwrap();
+    assert_eq!(grid.aabb().min(), &grid.point_coordinates_array(&[0, 0, 0]));
+    assert!((grid.aabb().max() - grid.point_coordinates(&max_point)).norm() < 1e-12);
+
+    // The AABBs of all cells are contained in the grid AABB and sum up to its volume
+    let mut volume = 0.0;
+    for i in 0..3 {
+        for j in 0..4 {
+            for k in 0..5 {
+                let cell = grid.get_cell([i, j, k]).unwrap();
+                let cell_aabb = grid.cell_aabb(&cell);
+                assert_eq!(cell_aabb.min(), &grid.point_coordinates_array(&[i, j, k]));
+                assert!(
+                    (cell_aabb.max() - grid.point_coordinates_array(&[i + 1, j + 1, k + 1])).norm()
+                        < 1e-12
+                );
+                assert!(grid.aabb().contains_point(&cell_aabb.centroid()));
+                volume += cell_aabb.extents().iter().product::<f64>();
+            }
+        }
+    }
+    let grid_volume = grid.aabb().extents().iter().product::<f64>();
+    assert!((volume - grid_volume).abs() < 1e-10);
+}
+
+#[test]
+fn test_cells_in_sphere() {
+    let grid = UniformGrid::<i32, f64>::new(&Vector3::new(0.0, 0.0, 0.0), &[6, 5, 4], 0.5).unwrap();
+
+    let brute_force = |center: &Vector3<f64>, radius: f64| {
+        let mut cells = Vec::new();
+        for i in 0..6 {
+            for j in 0..5 {
+                for k in 0..4 {
+                    let cell = grid.get_cell([i, j, k]).unwrap();
+                    let aabb = grid.cell_aabb(&cell);
+                    let closest = Vector3::new(
+                        center.x.max(aabb.min().x).min(aabb.max().x),
+                        center.y.max(aabb.min().y).min(aabb.max().y),
+                        center.z.max(aabb.min().z).min(aabb.max().z),
+                    );
+                    if (closest - center).norm() <= radius {
+                        cells.push(*cell.index());
+                    }
+                }
+            }
+        }
+        cells
+    };
+
+    let spheres = [
+        (Vector3::new(1.3, 1.1, 0.9), 0.6),
+        (Vector3::new(1.3, 1.1, 0.9), 0.0),
+        (Vector3::new(0.1, 0.2, 0.3), 1.2),
+        (Vector3::new(-0.4, 1.0, 1.0), 0.5),
+        (Vector3::new(2.9, 2.4, 1.9), 0.75),
+        (Vector3::new(1.5, 1.25, 1.0), 10.0),
+        (Vector3::new(-2.0, -2.0, -2.0), 1.0),
+        (Vector3::new(10.0, 1.0, 1.0), 2.0),
+        (Vector3::new(1.3, 1.1, 0.9), -0.5),
+        (Vector3::new(1.3, 1.1, 0.9), f64::NAN),
+        (Vector3::new(f64::NAN, 1.1, 0.9), 0.5),
+    ];
+    for (center, radius) in spheres.iter() {
+        let mut cells = grid
+            .cells_in_sphere(center, *radius)
+            .map(|cell| *cell.index())
+            .collect::<Vec<_>>();
+        cells.sort();
+        let mut expected = brute_force(center, *radius);
+        expected.sort();
+        assert_eq!(
+            cells, expected,
+            "cells in sphere at {:?} with radius {}",
+            center, radius
+        );
+    }
+
+    // The whole grid is inside of a large sphere
+    assert_eq!(
+        grid.cells_in_sphere(&Vector3::new(1.5, 1.25, 1.0), 10.0)
+            .count(),
+        6 * 5 * 4
+    );
+}
+
 #[test]
 fn test_cube_cell_local_point_index() {
     let cube: CellIndex<i32> = CellIndex { index: [1, 1, 1] };
@@ -1239,6 +1564,9 @@ mod tests {
 
         assert_eq!(grid.aabb().max(), &Vector3::new(1.0, 1.0, 1.0));
         assert_eq!(grid.cell_size(), 1.0);
+        assert_eq!(grid.origin(), &Vector3::new(0.0, 0.0, 0.0));
+        assert_eq!(grid.num_points(), 8);
+        assert_eq!(grid.num_cells(), 1);
 
         let points = [
             [0, 0, 0],
@@ -1289,4 +1617,34 @@ mod tests {
             .get_point_neighbor(&origin, Axis::Z.with_direction(Direction::Negative))
             .is_none());
     }
+
+    #[test]
+    fn test_point_index_for_coordinate() {
+        let grid =
+            UniformGrid::<i32, f64>::new(&Vector3::new(-1.0, 0.0, 0.0), &[4, 2, 2], 0.5).unwrap();
+        let coord = Vector3::new(-0.2, 0.3, 1.0);
+
+        let index = |rounding| {
+            grid.point_index_for_coordinate(&coord, rounding)
+                .map(|p| *p.index())
+        };
+        assert_eq!(index(Rounding::Floor), Some([1, 0, 2]));
+        assert_eq!(index(Rounding::Ceil), Some([2, 1, 2]));
+        assert_eq!(index(Rounding::Nearest), Some([2, 1, 2]));
+
+        let outside = Vector3::new(1.1, 0.3, -0.1);
+        assert!(grid
+            .point_index_for_coordinate(&outside, Rounding::Floor)
+            .is_none());
+        assert_eq!(
+            grid.clamped_point_index_for_coordinate(&outside, Rounding::Floor)
+                .index(),
+            &[4, 0, 0]
+        );
+        assert_eq!(
+            grid.clamped_point_index_for_coordinate(&coord, Rounding::Ceil)
+                .index(),
+            &[2, 1, 2]
+        );
+    }
 }
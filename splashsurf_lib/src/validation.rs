@@ -0,0 +1,108 @@
+//! Validation of user supplied per-particle input data
+
+use crate::Real;
+use nalgebra::Vector3;
+use thiserror::Error as ThisError;
+
+/// Error type returned if the length of a per-particle input array does not match the number of particles
+#[derive(Clone, Debug, Eq, PartialEq, ThisError)]
+#[error("the number of values of the particle input \"{name}\" ({len}) does not match the number of particles ({num_particles})")]
+pub struct ParticleInputLengthError {
+    /// Name of the mismatched input array
+    pub name: &'static str,
+    /// Number of values of the input array
+    pub len: usize,
+    /// Number of particle positions
+    pub num_particles: usize,
+}
+
+/// Checks that all per-particle input arrays have one value per particle position
+///
+/// Every entry of `attribute_lengths` consists of the name of an input array (used for the error
+/// message) and its length. Optional inputs that were not provided can be passed as `None` and are
+/// skipped. Returns an error for the first array whose length does not match the number of particles.
+///
+/// ```
+/// use splashsurf_lib::nalgebra::Vector3;
+/// use splashsurf_lib::validate_particle_inputs;
+///
+/// let positions = vec![Vector3::new(0.0, 0.0, 0.0); 2];
+/// let masses = vec![1.0; 2];
+/// let densities: Option<&[f64]> = None;
+///
+/// assert!(validate_particle_inputs(
+///     &positions,
+///     &[("masses", Some(masses.len())), ("densities", densities.map(|d| d.len()))],
+/// )
+/// .is_ok());
+/// ```
+pub fn validate_particle_inputs<R: Real>(
+    particle_positions: &[Vector3<R>],
+    attribute_lengths: &[(&'static str, Option<usize>)],
+) -> Result<(), ParticleInputLengthError> {
+    let num_particles = particle_positions.len();
+    for &(name, len) in attribute_lengths {
+        if let Some(len) = len {
+            if len != num_particles {
+                return Err(ParticleInputLengthError {
+                    name,
+                    len,
+                    num_particles,
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_validate_particle_inputs() {
+    let positions = vec![Vector3::<f64>::zeros(); 3];
+
+    assert_eq!(validate_particle_inputs(&positions, &[]), Ok(()));
+    assert_eq!(
+        validate_particle_inputs(&positions, &[("masses", Some(3)), ("densities", None)]),
+        Ok(())
+    );
+
+    // Too few values
+    assert_eq!(
+        validate_particle_inputs(&positions, &[("masses", Some(2))]),
+        Err(ParticleInputLengthError {
+            name: "masses",
+            len: 2,
+            num_particles: 3
+        })
+    );
+
+    // Too many values
+    assert_eq!(
+        validate_particle_inputs(&positions, &[("masses", Some(4))]),
+        Err(ParticleInputLengthError {
+            name: "masses",
+            len: 4,
+            num_particles: 3
+        })
+    );
+
+    // Mismatch of a later input is reported with its name
+    assert_eq!(
+        validate_particle_inputs(
+            &positions,
+            &[
+                ("masses", Some(3)),
+                ("densities", None),
+                ("values", Some(0))
+            ]
+        ),
+        Err(ParticleInputLengthError {
+            name: "values",
+            len: 0,
+            num_particles: 3
+        })
+    );
+
+    // Any input is invalid for empty particle sets
+    assert!(validate_particle_inputs::<f64>(&[], &[("masses", Some(1))]).is_err());
+}
@@ -1,7 +1,11 @@
 //! Workspace for reusing allocated memory between multiple surface reconstructions
 
 use crate::mesh::TriMesh3d;
-use crate::{new_map, DensityMap, Index, Real};
+use crate::uniform_grid::UniformGrid;
+use crate::{
+    new_map, reconstruct_surface_inplace, DensityMap, Index, Parameters, Real, ReconstructionError,
+    SurfaceReconstruction,
+};
 use nalgebra::Vector3;
 use std::cell::RefCell;
 use std::fmt;
@@ -9,13 +13,101 @@ use std::fmt::Debug;
 use thread_local::ThreadLocal;
 
 /// Collection of all thread local workspaces used to reduce allocations on subsequent surface reconstructions
+///
+/// A workspace can be used to reconstruct e.g. all frames of a sequence with [`Self::reconstruct_into`],
+/// which reuses the buffers allocated by previous reconstructions.
 #[derive(Default)]
 pub struct ReconstructionWorkspace<I: Index, R: Real> {
     global_densities: Vec<R>,
     local_workspaces: ThreadLocal<RefCell<LocalReconstructionWorkspace<I, R>>>,
+    /// Density map of the previous reconstruction together with the background grid it was computed on
+    density_map: Option<(UniformGrid<I, R>, DensityMap<I, R>)>,
 }
 
 impl<I: Index, R: Real> ReconstructionWorkspace<I, R> {
+    /// Performs a surface reconstruction of the given particles into the given mesh, reusing the memory of the workspace
+    ///
+    /// The output mesh is cleared before the reconstruction. The density map of the previous reconstruction
+    /// is cleared and reused if the background grid of the reconstruction has the same dimensions.
+    pub fn reconstruct_into(
+        &mut self,
+        particle_positions: &[Vector3<R>],
+        parameters: &Parameters<R>,
+        output_mesh: &mut TriMesh3d<R>,
+    ) -> Result<(), ReconstructionError<I, R>> {
+        let mut reconstruction = SurfaceReconstruction {
+            mesh: std::mem::take(output_mesh),
+            workspace: std::mem::take(self),
+            ..SurfaceReconstruction::default()
+        };
+
+        let result =
+            reconstruct_surface_inplace(particle_positions, parameters, &mut reconstruction);
+
+        *output_mesh = reconstruction.mesh;
+        *self = reconstruction.workspace;
+        if let Some(density_map) = reconstruction.density_map {
+            self.density_map = Some((reconstruction.grid, density_map));
+        }
+
+        result
+    }
+
+    /// Returns a reference to the density map of the previous reconstruction performed with [`Self::reconstruct_into`]
+    pub fn density_map(&self) -> Option<&DensityMap<I, R>> {
+        self.density_map
+            .as_ref()
+            .map(|(_, density_map)| density_map)
+    }
+
+    /// Takes the density map of the previous reconstruction for reuse if it was computed on a grid with the same dimensions
+    pub(crate) fn take_density_map(
+        &mut self,
+        grid: &UniformGrid<I, R>,
+    ) -> Option<DensityMap<I, R>> {
+        match self.density_map.take() {
+            Some((previous_grid, density_map))
+                if previous_grid.points_per_dim() == grid.points_per_dim() =>
+            {
+                Some(density_map)
+            }
+            _ => None,
+        }
+    }
+
+    /// Takes the marching cubes case histograms of all thread local workspaces and returns their sum
+    #[cfg(feature = "stats")]
+    pub(crate) fn take_case_histogram(&mut self) -> CaseHistogram {
+        let mut case_histogram = CaseHistogram::default();
+        for local_workspace in self.local_workspaces.iter_mut() {
+            case_histogram.merge(&std::mem::take(
+                &mut local_workspace.get_mut().case_histogram,
+            ));
+        }
+        case_histogram
+    }
+
+    /// Takes the marching cubes case histograms of all thread local workspaces, nothing is collected without the `stats` feature
+    #[cfg(not(feature = "stats"))]
+    pub(crate) fn take_case_histogram(&mut self) -> CaseHistogram {}
+
+    /// Runs a marching cubes triangulation and adds the triangulated cases to the thread local workspace if the `stats` feature is enabled
+    ///
+    /// In contrast to [`record_marching_cubes_cases`], the thread local workspace is not borrowed during the triangulation.
+    pub(crate) fn record_marching_cubes_cases<T>(&self, triangulate: impl FnOnce() -> T) -> T {
+        #[cfg(feature = "stats")]
+        {
+            let (result, histogram) = crate::stats::collect_marching_cubes_cases(triangulate);
+            self.get_local()
+                .borrow_mut()
+                .case_histogram
+                .merge(&histogram);
+            result
+        }
+        #[cfg(not(feature = "stats"))]
+        triangulate()
+    }
+
     /// Returns a mutable reference to the global particle density vector
     pub(crate) fn densities_mut(&mut self) -> &mut Vec<R> {
         &mut self.global_densities
@@ -57,6 +149,33 @@ impl<I: Index, R: Real> Debug for ReconstructionWorkspace<I, R> {
     }
 }
 
+/// Histogram of the triangulated marching cubes cases if the `stats` feature is enabled, the unit type otherwise
+#[cfg(feature = "stats")]
+pub(crate) type CaseHistogram = crate::marching_cubes::MarchingCubesCaseHistogram;
+/// Histogram of the triangulated marching cubes cases if the `stats` feature is enabled, the unit type otherwise
+#[cfg(not(feature = "stats"))]
+pub(crate) type CaseHistogram = ();
+
+/// Runs a marching cubes triangulation and adds the triangulated cases to the given histogram
+#[cfg(feature = "stats")]
+pub(crate) fn record_marching_cubes_cases<T>(
+    case_histogram: &mut CaseHistogram,
+    triangulate: impl FnOnce() -> T,
+) -> T {
+    let (result, histogram) = crate::stats::collect_marching_cubes_cases(triangulate);
+    case_histogram.merge(&histogram);
+    result
+}
+
+/// Runs a marching cubes triangulation, the cases are only recorded with the `stats` feature
+#[cfg(not(feature = "stats"))]
+pub(crate) fn record_marching_cubes_cases<T>(
+    _case_histogram: &mut CaseHistogram,
+    triangulate: impl FnOnce() -> T,
+) -> T {
+    triangulate()
+}
+
 /// Workspace used by [crate::reconstruct_surface_inplace] internally to re-use allocated memory
 pub(crate) struct LocalReconstructionWorkspace<I: Index, R: Real> {
     /// Storage for the particle positions (only used in octree based approach)
@@ -69,6 +188,8 @@ pub(crate) struct LocalReconstructionWorkspace<I: Index, R: Real> {
     pub mesh: TriMesh3d<R>,
     /// Storage for the density level-set
     pub density_map: DensityMap<I, R>,
+    /// Histogram of the marching cubes cases triangulated with this workspace (only collected with the `stats` feature)
+    pub case_histogram: CaseHistogram,
 }
 
 impl<I: Index, R: Real> Default for LocalReconstructionWorkspace<I, R> {
@@ -87,6 +208,7 @@ impl<I: Index, R: Real> LocalReconstructionWorkspace<I, R> {
             particle_densities: Default::default(),
             mesh: Default::default(),
             density_map: new_map().into(),
+            case_histogram: Default::default(),
         }
     }
 
@@ -98,6 +220,7 @@ impl<I: Index, R: Real> LocalReconstructionWorkspace<I, R> {
             particle_densities: Vec::with_capacity(capacity),
             mesh: Default::default(),
             density_map: new_map().into(),
+            case_histogram: Default::default(),
         }
     }
 }
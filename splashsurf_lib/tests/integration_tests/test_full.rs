@@ -1,8 +1,17 @@
 use nalgebra::Vector3;
+use splashsurf_lib::anisotropy::AnisotropyParameters;
 use splashsurf_lib::marching_cubes::check_mesh_consistency;
+use splashsurf_lib::mesh::TriMesh3d;
+use splashsurf_lib::tiling::reconstruct_surface_tiled;
+use splashsurf_lib::uniform_grid::Rounding;
 use splashsurf_lib::{
-    reconstruct_surface, AxisAlignedBoundingBox3d, Parameters, ParticleDensityComputationStrategy,
-    Real, SpatialDecompositionParameters, SubdivisionCriterion,
+    grid_for_reconstruction, grid_for_reconstruction_anisotropic, reconstruct_file,
+    reconstruct_multi_group, reconstruct_surface, reconstruct_surface_with_preview,
+    reconstruct_surface_with_progress, reconstruct_surface_with_smoothing_lengths,
+    AxisAlignedBoundingBox3d, Parameters, ParticleDensityComputationStrategy, ParticleGroup,
+    PeriodicAxes, Real, ReconstructionError, ReconstructionMask, ReconstructionProgress,
+    ReconstructionStage, ReconstructionWorkspace, RestDensityNormalization,
+    SpatialDecompositionParameters, SubdivisionCriterion,
 };
 use std::path::Path;
 
@@ -35,8 +44,17 @@ fn params_with_aabb<R: Real>(
         cube_size,
         iso_surface_threshold,
         domain_aabb,
+        grid_margin_cells: 0,
         enable_multi_threading: false,
         spatial_decomposition: None,
+        periodic_axes: PeriodicAxes::none(),
+        mask: None,
+        contour_cell_factors: None,
+        normalize_by_rest_density: None,
+        edge_interpolation: None,
+        max_grid_cells: None,
+        kernel: None,
+        anisotropy: None,
     };
 
     match strategy {
@@ -169,3 +187,1094 @@ generate_test!(f32, surface_reconstruction_knot_stitching, "sailors_knot_19539_p
 
 generate_test!(f32, surface_reconstruction_free_particles_01, "free_particles_1000_particles.vtk" => "reconstruct_surface_free_particles_01_global.vtk", params(0.5, 4.0, 1.5, 0.45, Strategy::Global), 21000, 25000);
 generate_test!(f32, surface_reconstruction_free_particles_02, "free_particles_125_particles.vtk" => "reconstruct_surface_free_particles_02_global.vtk", params_with_aabb(0.5, 4.0, 1.5, 0.45, Some(AxisAlignedBoundingBox3d::new(Vector3::new(-10.0, -10.0, -10.0), Vector3::new(210.0, 210.0, 210.0))), Strategy::Global), 1450, 1550);
+
+#[test]
+fn surface_reconstruction_periodic_sine_wave() {
+    let particle_radius = 0.025;
+    let spacing = 2.0 * particle_radius;
+    let n = 20;
+
+    // Fluid slab on the periodic domain [0,1)x[0,1) with a sine wave surface
+    let mut particle_positions = Vec::new();
+    for i in 0..n {
+        for j in 0..n {
+            let x = (i as f64 + 0.5) * spacing;
+            let y = (j as f64 + 0.5) * spacing;
+            let height = 0.5
+                + 0.1 * (2.0 * std::f64::consts::PI * x).sin()
+                + 0.05 * (2.0 * std::f64::consts::PI * y).cos();
+            let mut z = 0.5 * spacing;
+            while z < height {
+                particle_positions.push(Vector3::new(x, y, z));
+                z += spacing;
+            }
+        }
+    }
+
+    let domain =
+        AxisAlignedBoundingBox3d::new(Vector3::new(0.0, 0.0, -0.5), Vector3::new(1.0, 1.0, 1.0));
+    let mut parameters = params_with_aabb(
+        particle_radius,
+        4.0,
+        1.0,
+        0.6,
+        Some(domain.clone()),
+        Strategy::Global,
+    );
+    parameters.periodic_axes = PeriodicAxes::new(true, true, false);
+
+    let reconstruction =
+        reconstruct_surface::<i64, _>(particle_positions.as_slice(), &parameters).unwrap();
+    let mesh = reconstruction.mesh();
+    assert!(!mesh.triangles.is_empty());
+
+    let tolerance = 1e-9;
+    for dim in 0..2 {
+        // The mesh has to be clipped to the periodic domain
+        assert!(mesh
+            .vertices
+            .iter()
+            .all(|v| v[dim] > domain.min()[dim] - tolerance
+                && v[dim] < domain.max()[dim] + tolerance));
+
+        let lower: Vec<_> = mesh
+            .vertices
+            .iter()
+            .filter(|v| (v[dim] - domain.min()[dim]).abs() < tolerance)
+            .collect();
+        let upper: Vec<_> = mesh
+            .vertices
+            .iter()
+            .filter(|v| (v[dim] - domain.max()[dim]).abs() < tolerance)
+            .collect();
+
+        // Every vertex on the upper face has to be an exact copy of a vertex on the lower face shifted by the period
+        assert!(!lower.is_empty());
+        assert_eq!(lower.len(), upper.len());
+        for u in upper {
+            let mut shifted = *u;
+            shifted[dim] -= domain.extents()[dim];
+            assert!(
+                lower.iter().any(|l| (*l - shifted).norm() < tolerance),
+                "No matching vertex on the opposite periodic face for {:?}",
+                u
+            );
+        }
+    }
+}
+
+#[test]
+fn reconstruct_file_cube() {
+    let input_file = Path::new("../data/cube_2366_particles.vtk");
+    let output_file = Path::new("../out/reconstruct_file_cube.vtk");
+
+    let parameters = params::<f64>(0.025, 4.0, 0.75, 0.6, Strategy::Global);
+    let stats = reconstruct_file::<i64, f64>(input_file, output_file, &parameters).unwrap();
+
+    assert_eq!(stats.num_particles, 2366);
+    assert!(stats.num_triangles > 0);
+
+    let mesh_positions = particles_from_vtk::<f64, _>(output_file).unwrap();
+    assert_eq!(mesh_positions.len(), stats.num_vertices);
+
+    assert!(reconstruct_file::<i64, f64>(
+        input_file,
+        Path::new("../out/reconstruct_file_cube.stl"),
+        &parameters
+    )
+    .is_err());
+}
+
+#[test]
+fn surface_reconstruction_with_mask() {
+    let particle_radius = 0.025;
+    let spacing = 2.0 * particle_radius;
+
+    // Block of fluid in [0, 0.6]^3
+    let mut particle_positions = Vec::new();
+    for i in 0..12 {
+        for j in 0..12 {
+            for k in 0..12 {
+                particle_positions.push(Vector3::new(
+                    (i as f64 + 0.5) * spacing,
+                    (j as f64 + 0.5) * spacing,
+                    (k as f64 + 0.5) * spacing,
+                ));
+            }
+        }
+    }
+
+    // Cube size is 0.025 and the mask voxels consist of 6x6x6 cells, only voxels with x < 0.25 are active
+    let domain = AxisAlignedBoundingBox3d::new(Vector3::repeat(-0.5), Vector3::repeat(1.0));
+    let mask = ReconstructionMask::from_fn(*domain.min(), 0.15, [10, 10, 10], |ijk| ijk[0] < 5);
+
+    for strategy in [Strategy::Global, Strategy::OctreeStitching] {
+        let mut parameters = params_with_aabb(
+            particle_radius,
+            4.0,
+            1.0,
+            0.6,
+            Some(domain.clone()),
+            strategy,
+        );
+
+        let full = reconstruct_surface::<i64, _>(particle_positions.as_slice(), &parameters)
+            .unwrap()
+            .mesh()
+            .clone();
+
+        parameters.mask = Some(mask.clone());
+        let reconstruction =
+            reconstruct_surface::<i64, _>(particle_positions.as_slice(), &parameters).unwrap();
+        let masked = reconstruction.mesh();
+
+        assert!(!masked.triangles.is_empty());
+        assert!(masked.triangles.len() < full.triangles.len());
+        assert!(full.vertices.iter().any(|v| v.x > 0.5));
+        assert!(masked.vertices.iter().all(|v| v.x < 0.25));
+
+        // The surface has to be closed at the boundary of the mask
+        if let Err(e) = check_mesh_consistency(reconstruction.grid(), masked) {
+            panic!("Masked mesh is not closed: {}", e);
+        }
+    }
+
+    // A mask that is not aligned to the background grid is rejected
+    let mut parameters = params_with_aabb(
+        particle_radius,
+        4.0,
+        1.0,
+        0.6,
+        Some(domain.clone()),
+        Strategy::Global,
+    );
+    parameters.mask = Some(ReconstructionMask::from_fn(
+        Vector3::repeat(-0.49),
+        0.15,
+        [10, 10, 10],
+        |_| true,
+    ));
+    assert!(reconstruct_surface::<i64, _>(particle_positions.as_slice(), &parameters).is_err());
+}
+
+#[test]
+fn surface_reconstruction_anisotropic_contouring() {
+    let particle_radius = 0.025;
+    let spacing = 2.0 * particle_radius;
+
+    // Cylinder along the z-axis, the surface is well resolved along the x- and y-axis but constant along the z-axis
+    let mut particle_positions = Vec::new();
+    for i in 0..12 {
+        for j in 0..12 {
+            let x = (i as f64 + 0.5) * spacing;
+            let y = (j as f64 + 0.5) * spacing;
+            if (x - 0.3).powi(2) + (y - 0.3).powi(2) > 0.3f64.powi(2) {
+                continue;
+            }
+            for k in 0..20 {
+                particle_positions.push(Vector3::new(x, y, (k as f64 + 0.5) * spacing));
+            }
+        }
+    }
+
+    let mut parameters = params::<f64>(particle_radius, 4.0, 0.75, 0.6, Strategy::Global);
+    let cube_size = parameters.cube_size;
+
+    let isotropic = reconstruct_surface::<i64, _>(particle_positions.as_slice(), &parameters)
+        .unwrap()
+        .mesh()
+        .clone();
+
+    parameters.contour_cell_factors = Some([1, 1, 4]);
+    let reconstruction =
+        reconstruct_surface::<i64, _>(particle_positions.as_slice(), &parameters).unwrap();
+    let anisotropic = reconstruction.mesh();
+
+    assert!(!anisotropic.triangles.is_empty());
+    assert!(anisotropic.triangles.len() < isotropic.triangles.len());
+    if let Err(e) = check_mesh_consistency(reconstruction.grid(), anisotropic) {
+        panic!("Anisotropic mesh is not closed: {}", e);
+    }
+
+    // The vertices have to be interpolated with the correct edge lengths per axis
+    let isotropic_aabb = AxisAlignedBoundingBox3d::from_points(&isotropic.vertices);
+    let anisotropic_aabb = AxisAlignedBoundingBox3d::from_points(&anisotropic.vertices);
+    for dim in 0..3 {
+        let tolerance = if dim < 2 { 1e-3 } else { 4.0 * cube_size };
+        assert!((isotropic_aabb.min()[dim] - anisotropic_aabb.min()[dim]).abs() < tolerance);
+        assert!((isotropic_aabb.max()[dim] - anisotropic_aabb.max()[dim]).abs() < tolerance);
+    }
+
+    // Per-axis factors are not supported with spatial decomposition
+    let mut parameters = params::<f64>(particle_radius, 4.0, 0.75, 0.6, Strategy::Octree);
+    parameters.contour_cell_factors = Some([1, 1, 4]);
+    assert!(reconstruct_surface::<i64, _>(particle_positions.as_slice(), &parameters).is_err());
+}
+
+#[test]
+fn surface_reconstruction_anisotropic_kernels() {
+    let particle_radius = 0.025;
+    let spacing = 2.0 * particle_radius;
+
+    // A single layer of particles in the xy-plane
+    let mut particle_positions = Vec::new();
+    for i in 0..12 {
+        for j in 0..12 {
+            particle_positions.push(Vector3::new(i as f64, j as f64, 0.0) * spacing);
+        }
+    }
+
+    let mut parameters = params::<f64>(particle_radius, 4.0, 0.25, 0.6, Strategy::Global);
+    let isotropic = reconstruct_surface::<i64, _>(particle_positions.as_slice(), &parameters)
+        .unwrap()
+        .mesh()
+        .clone();
+
+    parameters.anisotropy = Some(AnisotropyParameters::default());
+    let reconstruction =
+        reconstruct_surface::<i64, _>(particle_positions.as_slice(), &parameters).unwrap();
+    let anisotropic = reconstruction.mesh();
+
+    assert!(!anisotropic.triangles.is_empty());
+    if let Err(e) = check_mesh_consistency(reconstruction.grid(), anisotropic) {
+        panic!("Anisotropic mesh is not closed: {}", e);
+    }
+
+    // The kernels are compressed along the normal of the sheet, the reconstructed sheet is thinner
+    let isotropic_aabb = AxisAlignedBoundingBox3d::from_points(&isotropic.vertices);
+    let anisotropic_aabb = AxisAlignedBoundingBox3d::from_points(&anisotropic.vertices);
+    let isotropic_thickness = isotropic_aabb.extents()[2];
+    let anisotropic_thickness = anisotropic_aabb.extents()[2];
+    assert!(anisotropic_thickness > 0.0);
+    assert!(
+        anisotropic_thickness < 0.75 * isotropic_thickness,
+        "anisotropic thickness {} vs. isotropic thickness {}",
+        anisotropic_thickness,
+        isotropic_thickness
+    );
+
+    // Anisotropic kernels are not supported with spatial decomposition
+    let mut parameters = params::<f64>(particle_radius, 4.0, 0.75, 0.6, Strategy::Octree);
+    parameters.anisotropy = Some(AnisotropyParameters::default());
+    assert!(reconstruct_surface::<i64, _>(particle_positions.as_slice(), &parameters).is_err());
+}
+
+#[test]
+fn surface_reconstruction_with_smoothing_lengths() {
+    let particle_radius = 0.025;
+    let compact_support_radius = 4.0 * particle_radius;
+
+    // A block of small particles and a block of particles with twice the size and spacing
+    let mut particle_positions = Vec::new();
+    let mut particle_smoothing_lengths = Vec::new();
+    for i in 0..8 {
+        for j in 0..8 {
+            for k in 0..8 {
+                let spacing = 2.0 * particle_radius;
+                particle_positions.push(Vector3::new(i as f64, j as f64, k as f64) * spacing);
+                particle_smoothing_lengths.push(compact_support_radius);
+            }
+        }
+    }
+    for i in 0..4 {
+        for j in 0..4 {
+            for k in 0..4 {
+                let spacing = 4.0 * particle_radius;
+                particle_positions.push(
+                    Vector3::new(i as f64, j as f64, k as f64) * spacing
+                        + Vector3::new(1.0, 0.0, 0.0),
+                );
+                particle_smoothing_lengths.push(2.0 * compact_support_radius);
+            }
+        }
+    }
+
+    let parameters = params::<f64>(particle_radius, 4.0, 0.5, 0.6, Strategy::Global);
+    let reconstruction = reconstruct_surface_with_smoothing_lengths::<i64, _>(
+        particle_positions.as_slice(),
+        Some(particle_smoothing_lengths.as_slice()),
+        &parameters,
+    )
+    .unwrap();
+    let mesh = reconstruction.mesh();
+
+    assert!(!mesh.triangles.is_empty());
+    if let Err(e) = check_mesh_consistency(reconstruction.grid(), mesh) {
+        panic!("Mesh is not closed: {}", e);
+    }
+
+    // Both blocks are reconstructed as separate closed surfaces
+    let components = mesh.connected_components();
+    assert_eq!(components.len(), 2);
+
+    // The surface bulges out further around the larger particles, proportional to their size
+    let top_offset = |x_range: std::ops::Range<f64>, top_particle_layer: f64| {
+        mesh.vertices
+            .iter()
+            .filter(|v| x_range.contains(&v.x))
+            .map(|v| v.z - top_particle_layer)
+            .fold(f64::MIN, f64::max)
+    };
+    let small_offset = top_offset(-1.0..0.75, 7.0 * 2.0 * particle_radius);
+    let large_offset = top_offset(0.75..2.0, 3.0 * 4.0 * particle_radius);
+    assert!(small_offset > 0.0);
+    assert!(
+        large_offset > 1.5 * small_offset,
+        "surface offset around large particles {} vs. small particles {}",
+        large_offset,
+        small_offset
+    );
+
+    // Without smoothing lengths, this is a regular reconstruction
+    let uniform = reconstruct_surface_with_smoothing_lengths::<i64, _>(
+        particle_positions.as_slice(),
+        None,
+        &parameters,
+    )
+    .unwrap();
+    let reference =
+        reconstruct_surface::<i64, _>(particle_positions.as_slice(), &parameters).unwrap();
+    assert_eq!(
+        uniform.mesh().vertices.len(),
+        reference.mesh().vertices.len()
+    );
+
+    // The number of smoothing lengths has to match the number of particles
+    assert!(reconstruct_surface_with_smoothing_lengths::<i64, _>(
+        particle_positions.as_slice(),
+        Some(&particle_smoothing_lengths[1..]),
+        &parameters,
+    )
+    .is_err());
+}
+
+#[test]
+fn surface_reconstruction_parallel_density_map_matches_sequential() {
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    // Randomly perturbed block of particles, such that many grid points receive contributions from several particles
+    let particle_radius = 0.025;
+    let spacing = 2.0 * particle_radius;
+    let mut rng = ChaCha8Rng::seed_from_u64(5);
+    let mut particle_positions = Vec::new();
+    for i in 0..16 {
+        for j in 0..16 {
+            for k in 0..16 {
+                let jitter = Vector3::new(
+                    rng.gen_range(-0.2..0.2),
+                    rng.gen_range(-0.2..0.2),
+                    rng.gen_range(-0.2..0.2),
+                );
+                particle_positions
+                    .push((Vector3::new(i as f64, j as f64, k as f64) + jitter) * spacing);
+            }
+        }
+    }
+
+    let mut parameters = params::<f64>(particle_radius, 4.0, 0.75, 0.6, Strategy::Global);
+    parameters.enable_multi_threading = false;
+    let sequential =
+        reconstruct_surface::<i64, _>(particle_positions.as_slice(), &parameters).unwrap();
+    parameters.enable_multi_threading = true;
+    let parallel =
+        reconstruct_surface::<i64, _>(particle_positions.as_slice(), &parameters).unwrap();
+
+    // The thread local density maps only change the order of the summation
+    let sequential_map = sequential.density_map().unwrap();
+    let parallel_map = parallel.density_map().unwrap();
+    assert_eq!(sequential_map.len(), parallel_map.len());
+    sequential_map.for_each(|flat_point_index, value| {
+        let parallel_value = parallel_map.get(flat_point_index).unwrap();
+        assert!(
+            (value - parallel_value).abs() <= 1e-10,
+            "density map values differ at point {}: {} vs. {}",
+            flat_point_index,
+            value,
+            parallel_value
+        );
+    });
+
+    assert_eq!(
+        sequential.mesh().vertices.len(),
+        parallel.mesh().vertices.len()
+    );
+}
+
+#[test]
+fn surface_reconstruction_iso_surface_threshold() {
+    let particle_radius = 0.025;
+    let spacing = 2.0 * particle_radius;
+
+    let mut particle_positions = Vec::new();
+    for i in 0..10 {
+        for j in 0..10 {
+            for k in 0..10 {
+                particle_positions.push(Vector3::new(i as f64, j as f64, k as f64) * spacing);
+            }
+        }
+    }
+
+    // The same density field contoured at increasing thresholds encloses decreasing volumes
+    let volumes = [0.4, 0.6, 0.8]
+        .iter()
+        .map(|&threshold| {
+            let parameters = params::<f64>(particle_radius, 4.0, 0.5, threshold, Strategy::Global);
+            let reconstruction =
+                reconstruct_surface::<i64, _>(particle_positions.as_slice(), &parameters).unwrap();
+            if let Err(e) = check_mesh_consistency(reconstruction.grid(), reconstruction.mesh()) {
+                panic!("Mesh for threshold {} is not closed: {}", threshold, e);
+            }
+            reconstruction.mesh().signed_volume().abs()
+        })
+        .collect::<Vec<_>>();
+
+    assert!(volumes[0] > volumes[1]);
+    assert!(volumes[1] > volumes[2]);
+
+    // The volume is in the order of the volume of the particle block
+    let block_volume = (10.0 * spacing).powi(3);
+    assert!(volumes[1] > 0.5 * block_volume && volumes[1] < 2.0 * block_volume);
+}
+
+#[test]
+fn surface_reconstruction_normalized_by_rest_density() {
+    let particle_radius = 0.025;
+    let spacing = 2.0 * particle_radius;
+
+    let mut particle_positions = Vec::new();
+    for i in 0..20 {
+        for j in 0..20 {
+            for k in 0..20 {
+                particle_positions.push(Vector3::new(i as f64, j as f64, k as f64) * spacing);
+            }
+        }
+    }
+
+    // The domain is fixed, otherwise the grid would depend on the particle radius
+    let domain = AxisAlignedBoundingBox3d::new(Vector3::repeat(-0.25), Vector3::repeat(1.2));
+    let mut parameters = params_with_aabb::<f64>(
+        particle_radius,
+        4.0,
+        0.75,
+        0.5,
+        Some(domain),
+        Strategy::Global,
+    );
+    parameters.normalize_by_rest_density = Some(RestDensityNormalization::Estimated);
+    let reconstruction =
+        reconstruct_surface::<i64, _>(particle_positions.as_slice(), &parameters).unwrap();
+    assert!(!reconstruction.mesh().triangles.is_empty());
+
+    // The normalized field is one in the interior of the fluid
+    let grid = reconstruction.grid();
+    let center = grid
+        .point_index_for_coordinate(&Vector3::repeat(9.5 * spacing), Rounding::Nearest)
+        .map(|point| grid.flatten_point_index(&point))
+        .unwrap();
+    let center_value = reconstruction.density_map().unwrap().get(center).unwrap();
+    assert!((center_value - 1.0).abs() < 0.1);
+
+    // The estimated normalization does not depend on the particle radius used for the particle masses
+    let mut scaled_parameters = parameters.clone();
+    scaled_parameters.particle_radius *= 1.5;
+    let scaled =
+        reconstruct_surface::<i64, _>(particle_positions.as_slice(), &scaled_parameters).unwrap();
+    assert_eq!(
+        scaled.mesh().vertices.len(),
+        reconstruction.mesh().vertices.len()
+    );
+    for (a, b) in scaled
+        .mesh()
+        .vertices
+        .iter()
+        .zip(reconstruction.mesh().vertices.iter())
+    {
+        assert!((a - b).norm() < 1e-10);
+    }
+
+    let mut parameters = params::<f64>(particle_radius, 4.0, 0.75, 0.5, Strategy::Global);
+    parameters.normalize_by_rest_density = Some(RestDensityNormalization::Supplied(0.0));
+    assert!(reconstruct_surface::<i64, _>(particle_positions.as_slice(), &parameters).is_err());
+}
+
+#[test]
+fn surface_reconstruction_grid_cell_limit() {
+    let particle_radius = 0.025;
+    let spacing = 2.0 * particle_radius;
+
+    let mut particle_positions = Vec::new();
+    for i in 0..10 {
+        for j in 0..10 {
+            for k in 0..10 {
+                particle_positions.push(Vector3::new(i as f64, j as f64, k as f64) * spacing);
+            }
+        }
+    }
+
+    let mut parameters = params::<f64>(particle_radius, 4.0, 0.75, 0.6, Strategy::Global);
+    let reconstruction =
+        reconstruct_surface::<i64, _>(particle_positions.as_slice(), &parameters).unwrap();
+    let num_cells = reconstruction.grid().num_cells() as u128;
+
+    // A limit equal to the number of cells is accepted
+    parameters.max_grid_cells = Some(num_cells);
+    let limited =
+        reconstruct_surface::<i64, _>(particle_positions.as_slice(), &parameters).unwrap();
+    assert_eq!(
+        limited.mesh().triangles.len(),
+        reconstruction.mesh().triangles.len()
+    );
+
+    // Exceeding the limit is reported before the reconstruction starts
+    parameters.max_grid_cells = Some(num_cells - 1);
+    match reconstruct_surface::<i64, _>(particle_positions.as_slice(), &parameters) {
+        Err(ReconstructionError::GridCellLimitExceeded(required, max)) => {
+            assert_eq!(required, num_cells);
+            assert_eq!(max, num_cells - 1);
+        }
+        _ => panic!("expected an error because of the grid cell limit"),
+    }
+
+    // Too small cube size on a large domain
+    parameters.max_grid_cells = Some(100_000_000);
+    parameters.cube_size = 1e-5;
+    parameters.domain_aabb = Some(AxisAlignedBoundingBox3d::new(
+        Vector3::repeat(-1.0),
+        Vector3::repeat(1.0),
+    ));
+    assert!(matches!(
+        reconstruct_surface::<i64, _>(particle_positions.as_slice(), &parameters),
+        Err(ReconstructionError::GridCellLimitExceeded(_, _))
+    ));
+}
+
+#[test]
+fn surface_reconstruction_grid_margin_cells() {
+    let particle_radius = 0.025;
+    let compact_support_radius = 4.0 * particle_radius;
+    let cube_size = 0.75 * particle_radius;
+    let spacing = 2.0 * particle_radius;
+
+    let mut particle_positions = Vec::new();
+    for i in 0..8 {
+        for j in 0..8 {
+            for k in 0..8 {
+                particle_positions.push(Vector3::new(i as f64, j as f64, k as f64) * spacing);
+            }
+        }
+    }
+
+    let margin_cells = 3;
+    let grid = grid_for_reconstruction::<i64, f64>(
+        particle_positions.as_slice(),
+        particle_radius,
+        compact_support_radius,
+        cube_size,
+        None,
+        margin_cells,
+        None,
+        false,
+    )
+    .unwrap();
+
+    // The support regions of all particles (in particular at the boundary) padded by the margin cells are contained in the grid
+    let mut support_region = AxisAlignedBoundingBox3d::from_points(particle_positions.as_slice());
+    support_region.grow_uniformly(compact_support_radius + margin_cells as f64 * cube_size);
+    assert!((support_region.min() - grid.aabb().min()).min() >= 0.0);
+    assert!((grid.aabb().max() - support_region.max()).min() >= 0.0);
+
+    // A given domain is not padded
+    let domain = grid.aabb().clone();
+    let fixed_grid = grid_for_reconstruction::<i64, f64>(
+        particle_positions.as_slice(),
+        particle_radius,
+        compact_support_radius,
+        cube_size,
+        Some(&domain),
+        margin_cells,
+        None,
+        false,
+    )
+    .unwrap();
+    assert_eq!(fixed_grid.cells_per_dim(), grid.cells_per_dim());
+
+    // The padded reconstruction results in a closed surface
+    let mut parameters = params::<f64>(particle_radius, 4.0, 0.75, 0.6, Strategy::Global);
+    parameters.grid_margin_cells = margin_cells as usize;
+    let reconstruction =
+        reconstruct_surface::<i64, _>(particle_positions.as_slice(), &parameters).unwrap();
+    assert_eq!(reconstruction.grid().cells_per_dim(), grid.cells_per_dim());
+    assert!(check_mesh_consistency(reconstruction.grid(), reconstruction.mesh()).is_ok());
+}
+
+#[test]
+fn grid_for_reconstruction_anisotropic_margin() {
+    let particle_radius = 0.025;
+    let compact_support_radius = 4.0 * particle_radius;
+    let cube_size = 0.75 * particle_radius;
+    let spacing = 2.0 * particle_radius;
+
+    // A thin sheet of particles
+    let mut particle_positions = Vec::new();
+    for i in 0..8 {
+        for j in 0..8 {
+            particle_positions.push(Vector3::new(i as f64, j as f64, 0.0) * spacing);
+        }
+    }
+
+    let grid = |margin_cells: [i64; 3]| {
+        grid_for_reconstruction_anisotropic::<i64, f64>(
+            particle_positions.as_slice(),
+            particle_radius,
+            compact_support_radius,
+            cube_size,
+            margin_cells,
+            None,
+            false,
+        )
+        .unwrap()
+    };
+
+    let uniform = grid([0, 0, 0]);
+    let anisotropic = grid([4, 2, 0]);
+    let uniform_cells = uniform.cells_per_dim();
+    let anisotropic_cells = anisotropic.cells_per_dim();
+
+    // Every side of an axis is padded by the given number of cells (up to rounding of the cell count)
+    assert!((anisotropic_cells[0] - uniform_cells[0] - 8).abs() <= 1);
+    assert!((anisotropic_cells[1] - uniform_cells[1] - 4).abs() <= 1);
+    assert_eq!(anisotropic_cells[2], uniform_cells[2]);
+    assert!(anisotropic_cells[0] - uniform_cells[0] > anisotropic_cells[1] - uniform_cells[1]);
+
+    // The scalar version uses the same margin for every axis
+    let isotropic = grid_for_reconstruction::<i64, f64>(
+        particle_positions.as_slice(),
+        particle_radius,
+        compact_support_radius,
+        cube_size,
+        None,
+        2,
+        None,
+        false,
+    )
+    .unwrap();
+    assert_eq!(isotropic.cells_per_dim(), grid([2, 2, 2]).cells_per_dim());
+}
+
+#[test]
+fn surface_reconstruction_multi_group() {
+    // Particles on a lattice inside of a sphere
+    let sphere_particles = |center: Vector3<f64>, radius: f64, spacing: f64| {
+        let n = (radius / spacing).ceil() as i32;
+        let mut particles = Vec::new();
+        for i in -n..=n {
+            for j in -n..=n {
+                for k in -n..=n {
+                    let offset = Vector3::new(i as f64, j as f64, k as f64) * spacing;
+                    if offset.norm() <= radius {
+                        particles.push(center + offset);
+                    }
+                }
+            }
+        }
+        particles
+    };
+
+    // A finely resolved blob overlapping with a coarsely resolved blob
+    let (fine_center, fine_radius, fine_particle_radius) = (Vector3::zeros(), 0.4, 0.025);
+    let (coarse_center, coarse_radius, coarse_particle_radius) =
+        (Vector3::new(0.7, 0.0, 0.0), 0.5, 0.05);
+    let fine_particles = sphere_particles(fine_center, fine_radius, 2.0 * fine_particle_radius);
+    let coarse_particles =
+        sphere_particles(coarse_center, coarse_radius, 2.0 * coarse_particle_radius);
+
+    let mass = |particle_radius: f64| 1000.0 * (2.0 * particle_radius).powi(3);
+    let groups = [
+        ParticleGroup::new(
+            &fine_particles,
+            4.0 * fine_particle_radius,
+            mass(fine_particle_radius),
+        ),
+        ParticleGroup::new(
+            &coarse_particles,
+            4.0 * coarse_particle_radius,
+            mass(coarse_particle_radius),
+        ),
+    ];
+
+    let parameters = params::<f64>(fine_particle_radius, 4.0, 1.0, 0.6, Strategy::Global);
+    let reconstruction = reconstruct_multi_group::<i64, _>(&groups, &parameters).unwrap();
+    let mesh = reconstruction.mesh();
+
+    // The blobs merge into a single closed surface without holes
+    check_mesh_consistency(reconstruction.grid(), mesh).unwrap();
+    assert_eq!(mesh.genus(), Some(0));
+
+    // There are no surfaces left inside of the union of the blobs, i.e. the overlap is merged smoothly,
+    // and all vertices are close to the surface of the union
+    let signed_distance = |p: &Vector3<f64>| {
+        ((p - fine_center).norm() - fine_radius).min((p - coarse_center).norm() - coarse_radius)
+    };
+    for vertex in mesh.vertices.iter() {
+        let distance = signed_distance(vertex);
+        assert!(distance > -2.0 * coarse_particle_radius);
+        assert!(distance < 4.0 * coarse_particle_radius);
+    }
+
+    // The surface passes through the region where the blobs overlap
+    let junction_x = 0.35;
+    assert!(mesh
+        .vertices
+        .iter()
+        .any(|v| (v.x - junction_x).abs() < 0.05 && v.yz().norm() > 0.2));
+
+    // Spatial decomposition is not supported for multiple groups
+    let parameters = params::<f64>(fine_particle_radius, 4.0, 1.0, 0.6, Strategy::Octree);
+    assert!(matches!(
+        reconstruct_multi_group::<i64, _>(&groups, &parameters),
+        Err(ReconstructionError::InvalidParameters(_))
+    ));
+}
+
+#[test]
+fn surface_reconstruction_with_preview() {
+    let particle_positions: &Vec<Vector3<f32>> =
+        &particles_from_vtk("../data/cube_2366_particles.vtk").unwrap();
+    let parameters = params::<f32>(0.025, 4.0, 0.75, 0.6, Strategy::Global);
+
+    let mut preview_cell_size = None;
+    let mut preview_triangles = 0;
+    let reconstruction = reconstruct_surface_with_preview::<i64, _, _>(
+        particle_positions.as_slice(),
+        &parameters,
+        3,
+        |preview| {
+            preview_cell_size = Some(preview.grid().cell_size());
+            preview_triangles = preview.mesh().triangles.len();
+            check_mesh_consistency(preview.grid(), preview.mesh()).unwrap();
+        },
+    )
+    .unwrap();
+
+    // The preview is a coarser version of the surface
+    assert_eq!(preview_cell_size, Some(3.0 * parameters.cube_size));
+    assert!(preview_triangles > 0);
+    assert!(preview_triangles < reconstruction.mesh().triangles.len());
+
+    // The final surface is the same as the surface of a separate reconstruction
+    let reference =
+        reconstruct_surface::<i64, _>(particle_positions.as_slice(), &parameters).unwrap();
+    assert_eq!(reconstruction.grid().cell_size(), parameters.cube_size);
+    assert_eq!(
+        reconstruction.mesh().vertices.len(),
+        reference.mesh().vertices.len()
+    );
+    assert_eq!(
+        reconstruction.mesh().triangles.len(),
+        reference.mesh().triangles.len()
+    );
+    check_mesh_consistency(reconstruction.grid(), reconstruction.mesh()).unwrap();
+
+    // A preview factor of zero is rejected
+    assert!(matches!(
+        reconstruct_surface_with_preview::<i64, _, _>(
+            particle_positions.as_slice(),
+            &parameters,
+            0,
+            |_| {}
+        ),
+        Err(ReconstructionError::InvalidParameters(_))
+    ));
+}
+
+#[test]
+fn surface_reconstruction_tiled() {
+    let particle_positions: &Vec<Vector3<f32>> =
+        &particles_from_vtk("../data/cube_2366_particles.vtk").unwrap();
+    let parameters = params::<f32>(0.025, 4.0, 0.75, 0.6, Strategy::Global);
+
+    let reference =
+        reconstruct_surface::<i64, _>(particle_positions.as_slice(), &parameters).unwrap();
+
+    let mut num_tiles_with_particles = 0;
+    let mut num_triangles = 0;
+    let layout = reconstruct_surface_tiled::<i64, _, _>(
+        particle_positions.as_slice(),
+        &parameters,
+        16,
+        |tile, mesh| {
+            // All vertices of a tile are located in the cells of the tile
+            let mut aabb = tile.aabb.clone();
+            aabb.grow_uniformly(0.01 * parameters.cube_size);
+            assert!(mesh.vertices.iter().all(|v| aabb.contains_point(v)));
+
+            num_tiles_with_particles += 1;
+            num_triangles += mesh.triangles.len();
+            Ok(())
+        },
+    )
+    .unwrap();
+
+    assert!(layout.num_tiles.iter().all(|&n| n > 1));
+    assert!(num_tiles_with_particles > 1);
+    assert_eq!(
+        layout.domain_aabb.min(),
+        reference.grid().aabb().min(),
+        "tiles have to be aligned to the background grid of a global reconstruction"
+    );
+
+    // The union of all tiles is the global surface (up to rounding of the accumulated densities)
+    let reference_triangles = reference.mesh().triangles.len();
+    assert!(
+        (num_triangles as i64 - reference_triangles as i64).abs()
+            <= reference_triangles as i64 / 1000,
+        "tiled reconstruction has {} triangles, global reconstruction has {} triangles",
+        num_triangles,
+        reference_triangles
+    );
+
+    // Errors of the callback abort the reconstruction
+    assert!(reconstruct_surface_tiled::<i64, _, _>(
+        particle_positions.as_slice(),
+        &parameters,
+        16,
+        |_, _| Err(anyhow::anyhow!("failed to write tile"))
+    )
+    .is_err());
+}
+
+#[test]
+fn surface_reconstruction_stitching_matches_global() {
+    let particle_positions: &Vec<Vector3<f32>> =
+        &particles_from_vtk("../data/hilbert2_7954_particles.vtk").unwrap();
+
+    let global_parameters = params::<f32>(0.025, 4.0, 1.1, 0.6, Strategy::Global);
+    let stitching_parameters = params::<f32>(0.025, 4.0, 1.1, 0.6, Strategy::OctreeStitching);
+
+    let global =
+        reconstruct_surface::<i64, _>(particle_positions.as_slice(), &global_parameters).unwrap();
+    let stitched =
+        reconstruct_surface::<i64, _>(particle_positions.as_slice(), &stitching_parameters)
+            .unwrap();
+
+    // The per-leaf patches are welded into a single closed mesh
+    assert!(stitched.octree().is_some());
+    check_mesh_consistency(stitched.grid(), stitched.mesh()).unwrap();
+
+    // Both reconstructions describe the same surface
+    let global_aabb = AxisAlignedBoundingBox3d::from_points(&global.mesh().vertices);
+    let stitched_aabb = AxisAlignedBoundingBox3d::from_points(&stitched.mesh().vertices);
+    let tolerance = global_parameters.cube_size;
+    assert!((global_aabb.min() - stitched_aabb.min()).amax() <= tolerance);
+    assert!((global_aabb.max() - stitched_aabb.max()).amax() <= tolerance);
+
+    let global_area = global.mesh().surface_area();
+    let stitched_area = stitched.mesh().surface_area();
+    assert!(
+        (global_area - stitched_area).abs() <= 0.01 * global_area,
+        "stitched surface area {} deviates from global surface area {}",
+        stitched_area,
+        global_area
+    );
+}
+
+#[test]
+fn surface_reconstruction_with_progress() {
+    let particle_positions: &Vec<Vector3<f32>> =
+        &particles_from_vtk("../data/hilbert2_7954_particles.vtk").unwrap();
+
+    for strategy in [Strategy::Global, Strategy::OctreeStitching] {
+        let expected_stage = match strategy {
+            Strategy::Global => ReconstructionStage::MarchingCubes,
+            _ => ReconstructionStage::Subdomains,
+        };
+        let parameters = params::<f32>(0.025, 4.0, 1.1, 0.6, strategy);
+
+        let mut reported: Vec<ReconstructionProgress> = Vec::new();
+        let reconstruction = reconstruct_surface_with_progress::<i64, _, _>(
+            particle_positions.as_slice(),
+            &parameters,
+            |progress| reported.push(progress),
+        )
+        .unwrap();
+
+        assert!(!reconstruction.mesh().triangles.is_empty());
+        assert_eq!(
+            reported.first().unwrap().stage,
+            ReconstructionStage::Initialization
+        );
+        assert_eq!(
+            *reported.last().unwrap(),
+            ReconstructionProgress {
+                stage: ReconstructionStage::Finished,
+                progress: 1.0
+            }
+        );
+        assert!(reported.windows(2).all(|w| w[0].progress <= w[1].progress));
+        assert!(reported
+            .iter()
+            .all(|p| p.progress >= 0.0 && p.progress <= 1.0));
+
+        assert!(reported.iter().any(|p| p.stage == expected_stage));
+    }
+}
+
+#[test]
+fn surface_reconstruction_periodic_with_progress() {
+    let particle_radius = 0.025;
+    let spacing = 2.0 * particle_radius;
+
+    // Flat fluid slab on the periodic domain [0,1)x[0,1)
+    let mut particle_positions = Vec::new();
+    for i in 0..20 {
+        for j in 0..20 {
+            for k in 0..4 {
+                particle_positions.push(
+                    (Vector3::new(i as f64, j as f64, k as f64) + Vector3::repeat(0.5)) * spacing,
+                );
+            }
+        }
+    }
+
+    let domain =
+        AxisAlignedBoundingBox3d::new(Vector3::new(0.0, 0.0, -0.5), Vector3::new(1.0, 1.0, 1.0));
+    let mut parameters = params_with_aabb(
+        particle_radius,
+        4.0,
+        1.0,
+        0.6,
+        Some(domain),
+        Strategy::Global,
+    );
+    parameters.periodic_axes = PeriodicAxes::new(true, true, false);
+
+    let mut reported: Vec<ReconstructionProgress> = Vec::new();
+    let reconstruction = reconstruct_surface_with_progress::<i64, _, _>(
+        particle_positions.as_slice(),
+        &parameters,
+        |progress| reported.push(progress),
+    )
+    .unwrap();
+
+    assert!(!reconstruction.mesh().triangles.is_empty());
+    assert_eq!(
+        reported.first().unwrap().stage,
+        ReconstructionStage::Initialization
+    );
+    assert!(reported
+        .iter()
+        .any(|p| p.stage == ReconstructionStage::MarchingCubes));
+    assert_eq!(
+        *reported.last().unwrap(),
+        ReconstructionProgress {
+            stage: ReconstructionStage::Finished,
+            progress: 1.0
+        }
+    );
+    assert!(reported.windows(2).all(|w| w[0].progress <= w[1].progress));
+}
+
+#[test]
+fn surface_reconstruction_workspace_reuses_density_map() {
+    let particle_radius = 0.025;
+    let spacing = 2.0 * particle_radius;
+    let mut particle_positions = Vec::new();
+    for i in 0..12 {
+        for j in 0..12 {
+            for k in 0..12 {
+                particle_positions.push(Vector3::new(i as f64, j as f64, k as f64) * spacing);
+            }
+        }
+    }
+
+    for &enable_multi_threading in &[false, true] {
+        let mut parameters = params::<f64>(particle_radius, 4.0, 0.75, 0.6, Strategy::Global);
+        parameters.enable_multi_threading = enable_multi_threading;
+
+        let reference =
+            reconstruct_surface::<i64, _>(particle_positions.as_slice(), &parameters).unwrap();
+
+        let mut workspace = ReconstructionWorkspace::<i64, f64>::default();
+        let mut mesh = TriMesh3d::default();
+
+        workspace
+            .reconstruct_into(particle_positions.as_slice(), &parameters, &mut mesh)
+            .unwrap();
+        let first_capacity = workspace.density_map().unwrap().capacity();
+        assert_eq!(mesh.triangles.len(), reference.mesh().triangles.len());
+
+        // The second reconstruction on the same grid clears and refills the density map of the first one
+        workspace
+            .reconstruct_into(particle_positions.as_slice(), &parameters, &mut mesh)
+            .unwrap();
+        let density_map = workspace.density_map().unwrap();
+        assert_eq!(density_map.capacity(), first_capacity);
+        assert_eq!(density_map.len(), reference.density_map().unwrap().len());
+        assert_eq!(mesh.vertices.len(), reference.mesh().vertices.len());
+        assert_eq!(mesh.triangles.len(), reference.mesh().triangles.len());
+    }
+}
+
+#[cfg(feature = "stats")]
+#[test]
+fn surface_reconstruction_case_histogram_per_reconstruction() {
+    use splashsurf_lib::marching_cubes::marching_cubes_case_counts;
+
+    let particle_radius = 0.025;
+    let spacing = 2.0 * particle_radius;
+    let mut particle_positions = Vec::new();
+    for i in 0..10 {
+        for j in 0..10 {
+            for k in 0..10 {
+                particle_positions.push(Vector3::new(i as f64, j as f64, k as f64) * spacing);
+            }
+        }
+    }
+    let parameters = params::<f64>(particle_radius, 4.0, 0.75, 0.6, Strategy::Global);
+
+    let reference =
+        reconstruct_surface::<i64, _>(particle_positions.as_slice(), &parameters).unwrap();
+    let histogram = reference.marching_cubes_case_histogram();
+
+    // All cells with corners on both sides of the iso-surface are triangulated exactly once
+    let case_counts = marching_cubes_case_counts(
+        reference.grid(),
+        reference.density_map().unwrap(),
+        parameters.iso_surface_threshold,
+    );
+    assert!(histogram.total() > 0);
+    for case in 1..255 {
+        assert_eq!(histogram.count(case), case_counts.count(case));
+    }
+    assert_eq!(histogram.count(0), 0);
+    assert_eq!(histogram.count(255), 0);
+
+    // Concurrent reconstructions do not mix their statistics
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            let particle_positions = particle_positions.clone();
+            let parameters = parameters.clone();
+            std::thread::spawn(move || {
+                reconstruct_surface::<i64, _>(particle_positions.as_slice(), &parameters)
+                    .unwrap()
+                    .marching_cubes_case_histogram()
+                    .clone()
+            })
+        })
+        .collect();
+    for thread in threads {
+        assert_eq!(&thread.join().unwrap(), histogram);
+    }
+
+    // Every cell is counted once with stitching of the subdomains
+    let stitching_parameters =
+        params::<f64>(particle_radius, 4.0, 0.75, 0.6, Strategy::OctreeStitching);
+    let stitched =
+        reconstruct_surface::<i64, _>(particle_positions.as_slice(), &stitching_parameters)
+            .unwrap();
+    assert_eq!(
+        stitched.marching_cubes_case_histogram().total(),
+        histogram.total()
+    );
+}
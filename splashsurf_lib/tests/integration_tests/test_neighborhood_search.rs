@@ -1,6 +1,6 @@
 use nalgebra::Vector3;
 use splashsurf_lib::neighborhood_search::*;
-use splashsurf_lib::AxisAlignedBoundingBox3d;
+use splashsurf_lib::{AxisAlignedBoundingBox3d, UniformGrid};
 
 fn sort_neighborhood_lists(neighborhood_list: &mut Vec<Vec<usize>>) {
     for neighbors in neighborhood_list.iter_mut() {
@@ -121,6 +121,45 @@ fn test_neighborhood_search_spatial_hashing_simple() {
     }
 }
 
+#[test]
+fn test_classify_surface_particles_cube() {
+    let n = 8;
+    let spacing = 0.05;
+    let support_radius = 1.8 * spacing;
+
+    let mut particles = Vec::new();
+    for i in 0..n {
+        for j in 0..n {
+            for k in 0..n {
+                particles.push(Vector3::new(i as f64, j as f64, k as f64) * spacing);
+            }
+        }
+    }
+
+    let mut domain = AxisAlignedBoundingBox3d::from_points(particles.as_slice());
+    domain.grow_uniformly(support_radius);
+    let grid = UniformGrid::<i64, f64>::from_aabb(&domain, spacing).unwrap();
+
+    let is_surface = classify_surface_particles(&grid, particles.as_slice(), support_radius);
+    assert_eq!(is_surface.len(), particles.len());
+
+    // All particles on the faces of the cube are surface particles, all others are interior particles
+    let mut index = 0;
+    for i in 0..n {
+        for j in 0..n {
+            for k in 0..n {
+                let on_face = [i, j, k].iter().any(|&c| c == 0 || c == n - 1);
+                assert_eq!(
+                    is_surface[index], on_face,
+                    "wrong classification of particle ({}, {}, {})",
+                    i, j, k
+                );
+                index += 1;
+            }
+        }
+    }
+}
+
 #[cfg(feature = "vtk_extras")]
 mod tests_from_files {
     use super::super::io;
@@ -1,7 +1,13 @@
-use splashsurf_lib::generic_tree::VisitableTree;
+use rand_chacha::ChaCha8Rng;
+use splashsurf_lib::generic_tree::{TreeNode, VisitableTree};
+use splashsurf_lib::mesh::AttributeData;
 use splashsurf_lib::nalgebra::Vector3;
-use splashsurf_lib::octree::Octree;
-use splashsurf_lib::{grid_for_reconstruction, Index, Real, SubdivisionCriterion, UniformGrid};
+use splashsurf_lib::octree::{NodeData, Octree, PartitionError, SplitStrategy};
+use splashsurf_lib::vtkio::model::VertexNumbers;
+use splashsurf_lib::{
+    grid_for_reconstruction, AxisAlignedBoundingBox3d, Index, Real, SubdivisionCriterion,
+    UniformGrid,
+};
 use std::path::Path;
 
 use super::io;
@@ -86,6 +92,9 @@ fn build_octree() {
         false,
     );
 
+    // Without a margin, every particle is contained in exactly one leaf
+    octree.verify_partition(particles.len()).unwrap();
+
     /*
     let root = octree.root();
 
@@ -101,124 +110,1329 @@ fn build_octree() {
     //octree_to_file(&octree, &grid, "U:\\octree.vtk");
 }
 
-/// Loads particles from a VTK file and checks that octree contains all particles and that each particle is in the correct leaf
+/// Loads particles from a VTK file and checks that octree contains all particles and that each particle is in the correct leaf
+#[test]
+fn build_octree_from_vtk() {
+    let file = "../data/double_dam_break_frame_26_4732_particles.vtk";
+    let particles = io::vtk::particles_from_vtk::<f64, _>(file).unwrap();
+    //println!("Loaded {} particles from {}", particles.len(), file);
+
+    let grid = grid_for_reconstruction::<i64, _>(
+        particles.as_slice(),
+        0.025,
+        4.0 * 0.025,
+        0.2,
+        None,
+        0,
+        None,
+        true,
+    )
+    .unwrap();
+    //println!("{:?}", grid);
+
+    let mut octree = Octree::new(&grid, particles.as_slice().len());
+    octree.subdivide_recursively_margin(
+        &grid,
+        particles.as_slice(),
+        SubdivisionCriterion::MaxParticleCount(60),
+        0.0,
+        false,
+    );
+
+    // Sum the number of particles per leaf
+    let mut particle_count = 0;
+    for node in octree.root().dfs_iter() {
+        if let Some(particle_set) = node.data().particle_set() {
+            let node_particles = &particle_set.particles;
+            //println!("Leaf with: {} particles", node_particles.len());
+            particle_count += node_particles.len();
+
+            // Ensure that all particles are within extents of octree cell
+            let aabb = node.aabb();
+            for &idx in node_particles.iter() {
+                let particle = particles[idx];
+                assert!(aabb.contains_point(&particle));
+            }
+        }
+    }
+    // Ensure that every particle was exactly in one cell
+    assert_eq!(particle_count, particles.len());
+
+    //octree_to_file(&octree, &grid, "U:\\double_dam_break_frame_26_4732_particles_octree.vtk");
+}
+
+/// Checks that the leaf views of an octree cover all particles exactly once and that particles are inside of the view AABBs
+#[test]
+fn octree_leaf_views() {
+    let file = "../data/double_dam_break_frame_26_4732_particles.vtk";
+    let particles = io::vtk::particles_from_vtk::<f64, _>(file).unwrap();
+
+    let grid = grid_for_reconstruction::<i64, _>(
+        particles.as_slice(),
+        0.025,
+        4.0 * 0.025,
+        0.2,
+        None,
+        0,
+        None,
+        true,
+    )
+    .unwrap();
+
+    let mut octree = Octree::new(&grid, particles.as_slice().len());
+    octree.subdivide_recursively_margin(
+        &grid,
+        particles.as_slice(),
+        SubdivisionCriterion::MaxParticleCount(60),
+        0.0,
+        false,
+    );
+
+    let leaf_views = octree.leaf_views(&grid);
+    assert!(leaf_views.len() > 1);
+
+    let mut particle_counts = vec![0; particles.len()];
+    for leaf in leaf_views.iter() {
+        for &idx in leaf.particles() {
+            assert!(leaf.aabb().contains_point(&particles[idx]));
+            particle_counts[idx] += 1;
+        }
+    }
+
+    assert!(particle_counts.into_iter().all(|count| count == 1));
+}
+
+/// Checks that balancing merges under-full leaves into leaves with the union of the particles of the children
+#[test]
+fn octree_balance_leaves() {
+    let file = "../data/double_dam_break_frame_26_4732_particles.vtk";
+    let particles = io::vtk::particles_from_vtk::<f64, _>(file).unwrap();
+
+    let grid = grid_for_reconstruction::<i64, _>(
+        particles.as_slice(),
+        0.025,
+        4.0 * 0.025,
+        0.2,
+        None,
+        0,
+        None,
+        true,
+    )
+    .unwrap();
+
+    let max_particles = 60;
+    let octree = Octree::new_subdivided(
+        &grid,
+        particles.as_slice(),
+        SubdivisionCriterion::MaxParticleCount(max_particles),
+        0.1,
+        false,
+        false,
+    );
+
+    // Splits of the original subdivision are never reverted with a fraction of one
+    let mut balanced = octree.clone();
+    assert_eq!(balanced.balance_leaves(1.0), 0);
+    assert_tree_equivalence(&octree, &balanced);
+
+    let min_fraction = 4.0;
+    let mut balanced = octree.clone();
+    assert!(balanced.balance_leaves(min_fraction) > 0);
+    assert!(balanced.root().dfs_iter().count() < octree.root().dfs_iter().count());
+    assert_unique_node_per_particle(particles.as_slice(), &balanced);
+
+    for node in balanced.root().dfs_iter() {
+        // Remaining splits have enough particles in their children
+        if !node.children().is_empty() && node.children().iter().all(|c| c.children().is_empty()) {
+            let non_ghost_count: usize = node
+                .children()
+                .iter()
+                .filter_map(|c| c.data().particle_set())
+                .map(|ps| ps.particles.len() - ps.ghost_particle_count)
+                .sum();
+            assert!(non_ghost_count as f64 >= min_fraction * max_particles as f64);
+        }
+
+        // Each leaf contains the union of all particles of the corresponding subtree of the original tree
+        if let Some(particle_set) = node.data().particle_set() {
+            let original = octree
+                .root()
+                .dfs_iter()
+                .find(|n| {
+                    n.min_corner() == node.min_corner() && n.max_corner() == node.max_corner()
+                })
+                .expect("Leaf of balanced tree is not part of the original tree");
+
+            let mut expected: Vec<usize> = original
+                .dfs_iter()
+                .filter_map(|n| n.data().particle_set())
+                .flat_map(|ps| ps.particles.iter().copied())
+                .collect();
+            expected.sort_unstable();
+            expected.dedup();
+
+            let mut actual = particle_set.particles.to_vec();
+            actual.sort_unstable();
+            assert_eq!(actual, expected);
+
+            let ghost_count = actual
+                .iter()
+                .filter(|&&idx| !node.aabb().contains_point(&particles[idx]))
+                .count();
+            assert_eq!(particle_set.ghost_particle_count, ghost_count);
+        }
+    }
+
+    // Merging everything results in a single leaf with all particles
+    let mut balanced = octree.clone();
+    balanced.balance_leaves(f64::MAX);
+    assert!(balanced.root().children().is_empty());
+    let root_particles = balanced.root().data().particle_set().unwrap();
+    assert_eq!(
+        root_particles.particles.to_vec(),
+        (0..particles.len()).collect::<Vec<_>>()
+    );
+    assert_eq!(root_particles.ghost_particle_count, 0);
+}
+
+#[test]
+fn octree_coarsen() {
+    let file = "../data/double_dam_break_frame_26_4732_particles.vtk";
+    let particles = io::vtk::particles_from_vtk::<f64, _>(file).unwrap();
+
+    let grid = grid_for_reconstruction::<i64, _>(
+        particles.as_slice(),
+        0.025,
+        4.0 * 0.025,
+        0.2,
+        None,
+        0,
+        None,
+        true,
+    )
+    .unwrap();
+
+    let octree = Octree::new_subdivided(
+        &grid,
+        particles.as_slice(),
+        SubdivisionCriterion::MaxParticleCount(30),
+        0.0,
+        false,
+        false,
+    );
+    let count_leaf_particles = |octree: &Octree<i64, f64>| -> usize {
+        octree
+            .root()
+            .dfs_iter()
+            .filter_map(|node| node.data().particle_set())
+            .map(|particle_set| particle_set.particles.len())
+            .sum()
+    };
+
+    let mut coarsened = octree.clone();
+    assert!(coarsened.coarsen(100) > 0);
+    assert!(coarsened.root().dfs_iter().count() < octree.root().dfs_iter().count());
+    assert_eq!(count_leaf_particles(&coarsened), particles.len());
+    assert_unique_node_per_particle(particles.as_slice(), &coarsened);
+
+    // No remaining group of sibling leaves has less particles than the limit
+    for node in coarsened.root().dfs_iter() {
+        if !node.children().is_empty() && node.children().iter().all(|c| c.children().is_empty()) {
+            let count: usize = node
+                .children()
+                .iter()
+                .filter_map(|c| c.data().particle_set())
+                .map(|ps| ps.particles.len())
+                .sum();
+            assert!(count >= 100);
+        }
+    }
+
+    // A limit of zero never merges any leaves
+    let mut unchanged = octree.clone();
+    assert_eq!(unchanged.coarsen(0), 0);
+    assert_tree_equivalence(&octree, &unchanged);
+}
+
+#[test]
+fn octree_hexmesh_cell_data() {
+    let file = "../data/double_dam_break_frame_26_4732_particles.vtk";
+    let particles = io::vtk::particles_from_vtk::<f64, _>(file).unwrap();
+
+    let grid = grid_for_reconstruction::<i64, _>(
+        particles.as_slice(),
+        0.025,
+        4.0 * 0.025,
+        0.2,
+        None,
+        0,
+        None,
+        true,
+    )
+    .unwrap();
+
+    let mut octree = Octree::new(&grid, particles.as_slice().len());
+    octree.subdivide_recursively_margin(
+        &grid,
+        particles.as_slice(),
+        SubdivisionCriterion::MaxParticleCount(60),
+        0.0,
+        false,
+    );
+
+    let mesh = octree.hexmesh(&grid, true);
+    assert_eq!(mesh.cell_attributes.len(), 3);
+
+    let particle_count = mesh
+        .cell_attributes
+        .iter()
+        .find(|attribute| attribute.name == "particle_count")
+        .unwrap();
+    match &particle_count.data {
+        AttributeData::ScalarU64(counts) => {
+            assert_eq!(counts.len(), mesh.mesh.cells.len());
+            assert_eq!(counts.iter().sum::<u64>(), particles.len() as u64);
+        }
+        _ => panic!("Unexpected data type of the particle count attribute"),
+    }
+
+    let depth = mesh
+        .cell_attributes
+        .iter()
+        .find(|attribute| attribute.name == "depth")
+        .unwrap();
+    match &depth.data {
+        AttributeData::ScalarU64(depths) => {
+            assert_eq!(depths.len(), mesh.mesh.cells.len());
+            assert_eq!(
+                depths.iter().copied().max().unwrap() as usize,
+                octree.statistics().max_depth
+            );
+        }
+        _ => panic!("Unexpected data type of the depth attribute"),
+    }
+
+    // A tree consisting only of the root node results in a single cell with depth zero
+    let root_mesh = Octree::new(&grid, particles.len()).hexmesh(&grid, false);
+    assert_eq!(root_mesh.mesh.cells.len(), 1);
+    match &root_mesh
+        .cell_attributes
+        .iter()
+        .find(|attribute| attribute.name == "depth")
+        .unwrap()
+        .data
+    {
+        AttributeData::ScalarU64(depths) => assert_eq!(depths, &vec![0]),
+        _ => panic!("Unexpected data type of the depth attribute"),
+    }
+
+    let grid_piece = mesh.to_unstructured_grid();
+    assert_eq!(grid_piece.data.cell.len(), 3);
+    match &grid_piece.cells.cell_verts {
+        VertexNumbers::Legacy { vertices, .. } => {
+            assert_eq!(vertices.len(), mesh.mesh.cells.len() * 9);
+            assert!(vertices.chunks(9).all(|cell| cell[0] == 8));
+        }
+        _ => panic!("Unexpected vertex number format"),
+    }
+}
+
+struct TestParameters<R: Real> {
+    particle_radius: R,
+    compact_support_radius: R,
+    cube_size: R,
+    margin: Option<R>,
+    max_particles_per_cell: Option<usize>,
+    compare_seq_par: bool,
+}
+
+impl<R: Real> Default for TestParameters<R> {
+    fn default() -> Self {
+        let particle_radius = R::from_f64(0.025).unwrap();
+        let compact_support_radius = particle_radius.times_f64(4.0);
+        let cube_size = particle_radius.times_f64(0.5);
+
+        Self {
+            particle_radius,
+            compact_support_radius,
+            cube_size,
+            margin: None,
+            max_particles_per_cell: None,
+            compare_seq_par: true,
+        }
+    }
+}
+
+impl<R: Real> TestParameters<R> {
+    fn new(particle_radius: f64, compact_support_factor: f64, cube_size_factor: f64) -> Self {
+        let params = Self::default();
+        params.with_parameters(particle_radius, compact_support_factor, cube_size_factor)
+    }
+
+    fn with_margin(mut self, margin: Option<f64>) -> Self {
+        self.margin = margin.map(|m| self.particle_radius.times_f64(m));
+        self
+    }
+    fn with_max_particles_per_cell(mut self, max_particles_per_cell: Option<usize>) -> Self {
+        self.max_particles_per_cell = max_particles_per_cell;
+        self
+    }
+
+    fn with_compare_seq_par(mut self, compare_seq_par: bool) -> Self {
+        self.compare_seq_par = compare_seq_par;
+        self
+    }
+
+    fn with_parameters(
+        mut self,
+        particle_radius: f64,
+        compact_support_factor: f64,
+        cube_size_factor: f64,
+    ) -> Self {
+        self.particle_radius = R::from_f64(particle_radius).unwrap();
+        self.compact_support_radius = self.particle_radius.times_f64(compact_support_factor);
+        self.cube_size = self.particle_radius.times_f64(cube_size_factor);
+        self
+    }
+
+    fn build_grid<I: Index>(&self, particle_positions: &[Vector3<R>]) -> UniformGrid<I, R> {
+        grid_for_reconstruction(
+            particle_positions,
+            self.particle_radius,
+            self.compact_support_radius,
+            self.cube_size,
+            None,
+            I::zero(),
+            None,
+            true,
+        )
+        .unwrap()
+    }
+}
+
+#[test]
+fn octree_cached_centroids() {
+    use rand::{Rng, SeedableRng};
+
+    // Random positions avoid particles that lie exactly on the boundaries of octree nodes
+    let mut rng = ChaCha8Rng::seed_from_u64(7);
+    let particles: Vec<Vector3<f64>> = (0..2000)
+        .map(|_| {
+            Vector3::new(
+                rng.gen_range(-0.5..0.5),
+                rng.gen_range(-0.3..0.3),
+                rng.gen_range(-0.2..0.2),
+            )
+        })
+        .collect();
+
+    let grid = grid_for_reconstruction::<i64, _>(
+        particles.as_slice(),
+        0.025,
+        0.1,
+        0.05,
+        None,
+        0,
+        None,
+        true,
+    )
+    .unwrap();
+    let mut octree = Octree::new_subdivided(
+        &grid,
+        particles.as_slice(),
+        SubdivisionCriterion::MaxParticleCount(100),
+        0.1,
+        false,
+        false,
+    );
+
+    assert!(octree.root().centroid().is_none());
+    octree.cache_centroids(&grid, particles.as_slice());
+
+    let root = octree.root().centroid().unwrap();
+    assert_eq!(root.count, particles.len());
+
+    for node in octree.root().dfs_iter() {
+        let cached = node
+            .centroid()
+            .expect("Centroid was not cached for all nodes");
+
+        // Direct computation from all particles inside of the node
+        let inside: Vec<_> = particles
+            .iter()
+            .filter(|p| node.aabb().contains_point(p))
+            .collect();
+        assert_eq!(cached.count, inside.len());
+        if !inside.is_empty() {
+            let centroid =
+                inside.iter().fold(Vector3::zeros(), |sum, &p| sum + p) / inside.len() as f64;
+            assert!((cached.centroid - centroid).norm() < 1e-12);
+        }
+
+        // The cached count of a leaf agrees with its number of non-ghost particles
+        if let Some(particle_set) = node.data().particle_set() {
+            assert_eq!(
+                cached.count,
+                particle_set.particles.len() - particle_set.ghost_particle_count
+            );
+        }
+    }
+}
+
+#[test]
+fn octree_verify_partition() {
+    use rand::{Rng, SeedableRng};
+
+    // Random positions avoid particles that lie exactly on the boundaries of octree nodes
+    let mut rng = ChaCha8Rng::seed_from_u64(11);
+    let particles: Vec<Vector3<f64>> = (0..500)
+        .map(|_| {
+            Vector3::new(
+                rng.gen_range(-0.5..0.5),
+                rng.gen_range(-0.3..0.3),
+                rng.gen_range(-0.2..0.2),
+            )
+        })
+        .collect();
+
+    let grid = grid_for_reconstruction::<i64, _>(
+        particles.as_slice(),
+        0.025,
+        0.1,
+        0.05,
+        None,
+        0,
+        None,
+        true,
+    )
+    .unwrap();
+    let build = |margin: f64| {
+        Octree::new_subdivided(
+            &grid,
+            particles.as_slice(),
+            SubdivisionCriterion::MaxParticleCount(20),
+            margin,
+            false,
+            false,
+        )
+    };
+
+    let octree = build(0.0);
+    assert_eq!(octree.verify_partition(particles.len()), Ok(()));
+    assert!(matches!(
+        octree.verify_partition(particles.len() + 3),
+        Err(PartitionError::MissingParticles { first_particle, count: 3 }) if first_particle == particles.len()
+    ));
+    assert!(matches!(
+        octree.verify_partition(particles.len() - 1),
+        Err(PartitionError::ParticleOutOfRange { .. })
+    ));
+
+    // Ghost particles are contained in more than one leaf
+    assert!(matches!(
+        build(0.1).verify_partition(particles.len()),
+        Err(PartitionError::DuplicateParticle { .. })
+    ));
+}
+
+#[test]
+fn octree_bfs_iter_level_order() {
+    use rand::{Rng, SeedableRng};
+
+    let mut rng = ChaCha8Rng::seed_from_u64(3);
+    let particles: Vec<Vector3<f64>> = (0..300)
+        .map(|_| {
+            // Denser particles in one corner to obtain leaves on different levels
+            let x: f64 = rng.gen_range(0.0..1.0);
+            Vector3::new(x * x, rng.gen_range(0.0..0.5), rng.gen_range(0.0..0.5))
+        })
+        .collect();
+
+    let grid = grid_for_reconstruction::<i64, _>(
+        particles.as_slice(),
+        0.025,
+        0.1,
+        0.05,
+        None,
+        0,
+        None,
+        false,
+    )
+    .unwrap();
+    let octree = Octree::new_subdivided(
+        &grid,
+        particles.as_slice(),
+        SubdivisionCriterion::MaxParticleCount(40),
+        0.0,
+        false,
+        false,
+    );
+
+    // Expected order: the root, then all nodes of each level with the children of every node in octant order
+    let mut expected = vec![octree.root()];
+    let mut level = vec![octree.root()];
+    let mut depth = 0;
+    loop {
+        level = level
+            .iter()
+            .flat_map(|node| node.children().iter().map(|child| child.as_ref()))
+            .collect();
+        if level.is_empty() {
+            break;
+        }
+        expected.extend(level.iter().copied());
+        depth += 1;
+    }
+    assert!(depth >= 2);
+
+    let bfs_nodes = octree.root().bfs_iter().collect::<Vec<_>>();
+    assert_eq!(bfs_nodes.len(), expected.len());
+    for (node, expected_node) in bfs_nodes.iter().zip(expected.iter()) {
+        assert!(std::ptr::eq(*node, *expected_node));
+    }
+
+    // The iterator can be stopped at a target depth, e.g. after the root and its children
+    let first_levels = octree.root().bfs_iter().take(9).collect::<Vec<_>>();
+    assert!(first_levels[1..]
+        .iter()
+        .zip(octree.root().children().iter())
+        .all(|(node, child)| std::ptr::eq(*node, child.as_ref())));
+}
+
+#[test]
+fn octree_max_depth() {
+    let particles = io::vtk::particles_from_vtk::<f64, _>(
+        "../data/double_dam_break_frame_26_4732_particles.vtk",
+    )
+    .unwrap();
+    let parameters = TestParameters::<f64>::default();
+    let grid = parameters.build_grid::<i64>(particles.as_slice());
+
+    for &enable_multi_threading in &[false, true] {
+        let mut octree = Octree::new(&grid, particles.len()).with_max_depth(Some(2));
+        // A tiny particle limit would result in a much deeper tree without the depth limit
+        if enable_multi_threading {
+            octree.par_subdivide_recursively_margin(
+                &grid,
+                particles.as_slice(),
+                SubdivisionCriterion::MaxParticleCount(5),
+                0.0,
+                false,
+            );
+        } else {
+            octree.subdivide_recursively_margin(
+                &grid,
+                particles.as_slice(),
+                SubdivisionCriterion::MaxParticleCount(5),
+                0.0,
+                false,
+            );
+        }
+
+        assert!(octree.root().dfs_iter().all(|node| node.depth() <= 2));
+        assert!(octree.root().dfs_iter().any(|node| node.depth() == 2));
+
+        // Leaves at the depth limit keep all of their particles
+        assert!(octree
+            .root()
+            .dfs_iter()
+            .filter(|node| node.depth() == 2)
+            .filter_map(|node| node.data().particle_set())
+            .any(|particle_set| particle_set.particles.len() > 5));
+        assert_unique_node_per_particle(particles.as_slice(), &octree);
+    }
+}
+
+#[test]
+fn octree_find_leaf_at() {
+    let particles = io::vtk::particles_from_vtk::<f64, _>(
+        "../data/double_dam_break_frame_26_4732_particles.vtk",
+    )
+    .unwrap();
+    let parameters = TestParameters::<f64>::default();
+    let grid = parameters.build_grid::<i64>(particles.as_slice());
+
+    let octree = Octree::new_subdivided(
+        &grid,
+        particles.as_slice(),
+        SubdivisionCriterion::MaxParticleCount(30),
+        0.0,
+        false,
+        false,
+    );
+
+    for particle_i in (0..particles.len()).step_by(97) {
+        let leaf = octree
+            .find_leaf_at(&grid, &particles[particle_i])
+            .expect("Particle should be inside of the root node");
+        assert!(leaf.children().is_empty());
+        assert!(leaf
+            .data()
+            .particle_set()
+            .expect("Leaf should store a particle set")
+            .particles
+            .contains(&particle_i));
+    }
+
+    let outside = grid.aabb().max() + Vector3::repeat(1.0);
+    assert!(octree.find_leaf_at(&grid, &outside).is_none());
+}
+
+#[test]
+fn octree_particle_counts() {
+    let particles = io::vtk::particles_from_vtk::<f64, _>(
+        "../data/double_dam_break_frame_26_4732_particles.vtk",
+    )
+    .unwrap();
+    let parameters = TestParameters::<f64>::default();
+    let grid = parameters.build_grid::<i64>(particles.as_slice());
+
+    let assert_consistent_counts = |octree: &Octree<i64, f64>| {
+        for node in octree.root().dfs_iter() {
+            if node.children().is_empty() {
+                let particle_set = node.data().particle_set().unwrap();
+                assert_eq!(node.particle_count(), particle_set.particles.len());
+            } else {
+                let children_count: usize = node
+                    .children()
+                    .iter()
+                    .map(|child| child.particle_count())
+                    .sum();
+                assert_eq!(node.particle_count(), children_count);
+            }
+        }
+    };
+
+    for &margin in &[0.0, 0.05] {
+        let mut octrees = [
+            Octree::new_subdivided_low_memory(
+                &grid,
+                particles.as_slice(),
+                SubdivisionCriterion::MaxParticleCount(30),
+                margin,
+                false,
+            ),
+            Octree::new_subdivided(
+                &grid,
+                particles.as_slice(),
+                SubdivisionCriterion::MaxParticleCount(30),
+                margin,
+                false,
+                false,
+            ),
+            Octree::new_subdivided(
+                &grid,
+                particles.as_slice(),
+                SubdivisionCriterion::MaxParticleCount(30),
+                margin,
+                true,
+                false,
+            ),
+        ];
+
+        for octree in octrees.iter_mut() {
+            assert_consistent_counts(octree);
+            if margin > 0.0 {
+                // Ghost particles are counted once for every leaf
+                assert!(octree.root().particle_count() > particles.len());
+            } else {
+                assert_eq!(octree.root().particle_count(), particles.len());
+            }
+
+            assert!(octree.coarsen(100) > 0);
+            assert_consistent_counts(octree);
+        }
+    }
+}
+
+#[test]
+fn octree_with_margin_ghost_particles() {
+    use rand::{Rng, SeedableRng};
+
+    let mut rng = ChaCha8Rng::seed_from_u64(7);
+    let particles = (0..200)
+        .map(|_| {
+            Vector3::new(
+                rng.gen_range(0.0..1.0),
+                rng.gen_range(0.0..1.0),
+                rng.gen_range(0.0..1.0),
+            )
+        })
+        .collect::<Vec<Vector3<f64>>>();
+    let grid =
+        grid_for_reconstruction::<i64, _>(&particles, 0.025, 0.1, 0.05, None, 0, None, false)
+            .unwrap();
+
+    // A single split of the root node into eight adjacent leaves
+    let mut octree = Octree::new_subdivided(
+        &grid,
+        &particles,
+        SubdivisionCriterion::MaxParticleCount(150),
+        0.0,
+        false,
+        false,
+    );
+    assert_eq!(octree.root().children().len(), 8);
+    assert!(octree
+        .root()
+        .children()
+        .iter()
+        .all(|c| c.children().is_empty()));
+
+    // Find the particle of the first leaf that is closest to the face shared with its neighbor in x-direction
+    let leaf = octree.find_leaf_at(&grid, &particles[0]).unwrap();
+    let face_x = if leaf.aabb().max().x < octree.root().aabb().max().x {
+        leaf.aabb().max().x
+    } else {
+        leaf.aabb().min().x
+    };
+    let leaf_particles = leaf.data().particle_set().unwrap().particles.clone();
+    let (near_face_particle, distance) = leaf_particles
+        .iter()
+        .map(|&i| (i, (particles[i].x - face_x).abs()))
+        .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+        .unwrap();
+
+    let margin = distance + 0.01;
+    octree.with_margin(&grid, &particles, margin);
+
+    let mut query_point = particles[near_face_particle];
+    query_point.x = face_x + (face_x - particles[near_face_particle].x).signum() * 0.5 * margin;
+    let neighbor = octree.find_leaf_at(&grid, &query_point).unwrap();
+    let leaf = octree
+        .find_leaf_at(&grid, &particles[near_face_particle])
+        .unwrap();
+
+    assert_ne!(neighbor.min_corner().index(), leaf.min_corner().index());
+    assert!(neighbor.ghost_particles().contains(&near_face_particle));
+    assert!(!leaf.ghost_particles().contains(&near_face_particle));
+
+    // All ghost particles are in the margin around their leaf but not stored in the leaf itself
+    for view in octree.leaf_views(&grid) {
+        let mut margin_aabb = view.aabb().clone();
+        margin_aabb.grow_uniformly(margin);
+        for i in view.ghost_particles() {
+            assert!(margin_aabb.contains_point(&particles[*i]));
+            assert!(!view.particles().contains(i));
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn octree_serde_roundtrip() {
+    let particles = io::vtk::particles_from_vtk::<f64, _>(
+        "../data/double_dam_break_frame_26_4732_particles.vtk",
+    )
+    .unwrap();
+    let parameters = TestParameters::<f64>::default();
+    let grid = parameters.build_grid::<i64>(particles.as_slice());
+
+    let octree = Octree::new_subdivided(
+        &grid,
+        particles.as_slice(),
+        SubdivisionCriterion::MaxParticleCount(60),
+        0.05,
+        false,
+        false,
+    );
+
+    let bytes = bincode::serialize(&octree).unwrap();
+    let deserialized: Octree<i64, f64> = bincode::deserialize(&bytes).unwrap();
+
+    assert_tree_identical(&octree, &deserialized);
+    for (node, deserialized_node) in octree.root().dfs_iter().zip(deserialized.root().dfs_iter()) {
+        assert_eq!(node.id(), deserialized_node.id());
+        assert_eq!(node.depth(), deserialized_node.depth());
+        assert_eq!(node.particle_count(), deserialized_node.particle_count());
+    }
+}
+
+#[test]
+fn octree_median_split_strategy() {
+    let particles = io::vtk::particles_from_vtk::<f64, _>(
+        "../data/double_dam_break_frame_26_4732_particles.vtk",
+    )
+    .unwrap();
+    let parameters = TestParameters::<f64>::default();
+    let grid = parameters.build_grid::<i64>(particles.as_slice());
+
+    let leaf_counts = |split_strategy: SplitStrategy, enable_multi_threading: bool| {
+        // Limit the depth to compare the distribution of the particles for the same number of splits
+        let mut octree = Octree::new(&grid, particles.len())
+            .with_max_depth(Some(2))
+            .with_split_strategy(split_strategy);
+        if enable_multi_threading {
+            octree.par_subdivide_recursively_margin(
+                &grid,
+                particles.as_slice(),
+                SubdivisionCriterion::MaxParticleCount(5),
+                0.0,
+                false,
+            );
+        } else {
+            octree.subdivide_recursively_margin(
+                &grid,
+                particles.as_slice(),
+                SubdivisionCriterion::MaxParticleCount(5),
+                0.0,
+                false,
+            );
+        }
+        assert_unique_node_per_particle(particles.as_slice(), &octree);
+
+        for (i, particle) in particles.iter().enumerate().step_by(101) {
+            let leaf = octree.find_leaf_at(&grid, particle).unwrap();
+            assert!(leaf.data().particle_set().unwrap().particles.contains(&i));
+        }
+
+        let counts = octree
+            .root()
+            .dfs_iter()
+            .filter_map(|node| node.data().particle_set())
+            .map(|particle_set| particle_set.particles.len())
+            .collect::<Vec<_>>();
+        let max = counts.iter().copied().max().unwrap();
+        let min = counts.iter().copied().min().unwrap();
+        (min, max)
+    };
+
+    for &enable_multi_threading in &[false, true] {
+        let (midpoint_min, midpoint_max) =
+            leaf_counts(SplitStrategy::GeometricMidpoint, enable_multi_threading);
+        let (median_min, median_max) =
+            leaf_counts(SplitStrategy::ParticleMedian, enable_multi_threading);
+
+        assert!(median_max < midpoint_max);
+        assert!(median_max - median_min < midpoint_max - midpoint_min);
+    }
+}
+
+#[test]
+fn octree_par_visit_leaves() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let particles = io::vtk::particles_from_vtk::<f64, _>(
+        "../data/double_dam_break_frame_26_4732_particles.vtk",
+    )
+    .unwrap();
+    let parameters = TestParameters::<f64>::default();
+    let grid = parameters.build_grid::<i64>(particles.as_slice());
+
+    let octree = Octree::new_subdivided(
+        &grid,
+        particles.as_slice(),
+        SubdivisionCriterion::MaxParticleCount(30),
+        0.0,
+        true,
+        false,
+    );
+
+    let particle_count = AtomicUsize::new(0);
+    let leaf_count = AtomicUsize::new(0);
+    octree.par_visit_leaves(|leaf| {
+        assert!(leaf.children().is_empty());
+        let particle_set = leaf.data().particle_set().unwrap();
+        particle_count.fetch_add(particle_set.particles.len(), Ordering::SeqCst);
+        leaf_count.fetch_add(1, Ordering::SeqCst);
+    });
+
+    assert_eq!(particle_count.into_inner(), particles.len());
+    assert_eq!(
+        leaf_count.into_inner(),
+        octree
+            .root()
+            .dfs_iter()
+            .filter(|node| node.children().is_empty())
+            .count()
+    );
+}
+
 #[test]
-fn build_octree_from_vtk() {
-    let file = "../data/double_dam_break_frame_26_4732_particles.vtk";
-    let particles = io::vtk::particles_from_vtk::<f64, _>(file).unwrap();
-    //println!("Loaded {} particles from {}", particles.len(), file);
+fn octree_node_aabb() {
+    let particles = io::vtk::particles_from_vtk::<f64, _>(
+        "../data/double_dam_break_frame_26_4732_particles.vtk",
+    )
+    .unwrap();
+    let parameters = TestParameters::<f64>::default();
+    let grid = parameters.build_grid::<i64>(particles.as_slice());
 
-    let grid = grid_for_reconstruction::<i64, _>(
+    let octree = Octree::new_subdivided(
+        &grid,
         particles.as_slice(),
-        0.025,
-        4.0 * 0.025,
-        0.2,
-        None,
-        true,
+        SubdivisionCriterion::MaxParticleCount(60),
+        0.0,
+        false,
+        false,
+    );
+
+    // The root node covers the whole grid
+    assert_eq!(octree.root().aabb(), grid.aabb());
+
+    for node in octree.root().dfs_iter() {
+        assert_eq!(
+            node.aabb().min(),
+            &grid.point_coordinates(node.min_corner())
+        );
+        assert_eq!(
+            node.aabb().max(),
+            &grid.point_coordinates(node.max_corner())
+        );
+    }
+}
+
+#[test]
+fn octree_dfs_iter_mut() {
+    let particles = io::vtk::particles_from_vtk::<f64, _>(
+        "../data/double_dam_break_frame_26_4732_particles.vtk",
     )
     .unwrap();
-    //println!("{:?}", grid);
+    let parameters = TestParameters::<f64>::default();
+    let grid = parameters.build_grid::<i64>(particles.as_slice());
 
-    let mut octree = Octree::new(&grid, particles.as_slice().len());
-    octree.subdivide_recursively_margin(
+    let mut octree = Octree::new_subdivided(
         &grid,
         particles.as_slice(),
         SubdivisionCriterion::MaxParticleCount(60),
         0.0,
         false,
+        false,
     );
 
-    // Sum the number of particles per leaf
-    let mut particle_count = 0;
+    // The mutable iterator visits the nodes in the same order as the immutable one
+    let ids = octree
+        .root()
+        .dfs_iter()
+        .map(|node| node.id())
+        .collect::<Vec<_>>();
+    let ids_mut = octree
+        .dfs_iter_mut()
+        .map(|node| node.id())
+        .collect::<Vec<_>>();
+    assert_eq!(ids, ids_mut);
+
+    // Clear the particles of all leaves in the upper half of the domain
+    let center_z = grid.aabb().centroid().z;
+    let mut cleared = 0;
+    for mut node in octree.dfs_iter_mut() {
+        if node.is_leaf() && node.aabb().min().z >= center_z {
+            if let NodeData::ParticleSet(particle_set) = node.data_mut() {
+                particle_set.particles.clear();
+                particle_set.ghost_particle_count = 0;
+                cleared += 1;
+            }
+        }
+    }
+    assert!(cleared > 0);
+
     for node in octree.root().dfs_iter() {
         if let Some(particle_set) = node.data().particle_set() {
-            let node_particles = &particle_set.particles;
-            //println!("Leaf with: {} particles", node_particles.len());
-            particle_count += node_particles.len();
-
-            // Ensure that all particles are within extents of octree cell
-            let aabb = node.aabb();
-            for &idx in node_particles.iter() {
-                let particle = particles[idx];
-                assert!(aabb.contains_point(&particle));
+            if node.aabb().min().z >= center_z {
+                assert!(particle_set.particles.is_empty());
+            } else {
+                assert_eq!(particle_set.particles.len(), node.particle_count());
             }
         }
     }
-    // Ensure that every particle was exactly in one cell
-    assert_eq!(particle_count, particles.len());
+}
 
-    //octree_to_file(&octree, &grid, "U:\\double_dam_break_frame_26_4732_particles_octree.vtk");
+#[test]
+fn octree_statistics() {
+    let particles = io::vtk::particles_from_vtk::<f64, _>(
+        "../data/double_dam_break_frame_26_4732_particles.vtk",
+    )
+    .unwrap();
+    let parameters = TestParameters::<f64>::default();
+    let grid = parameters.build_grid::<i64>(particles.as_slice());
+
+    let octree = Octree::new_subdivided(
+        &grid,
+        particles.as_slice(),
+        SubdivisionCriterion::MaxParticleCount(60),
+        0.0,
+        false,
+        false,
+    );
+    let stats = octree.statistics();
+
+    let leaf_counts = octree
+        .root()
+        .dfs_iter()
+        .filter(|node| node.children().is_empty())
+        .map(|node| node.data().particle_set().unwrap().particles.len())
+        .collect::<Vec<_>>();
+
+    assert_eq!(stats.num_nodes, octree.root().dfs_iter().count());
+    assert_eq!(stats.num_leaves, leaf_counts.len());
+    // Every inner node has eight children
+    assert_eq!(
+        stats.num_nodes - 1,
+        8 * (stats.num_nodes - stats.num_leaves)
+    );
+    assert_eq!(
+        stats.max_depth,
+        octree
+            .root()
+            .dfs_iter()
+            .map(|node| node.depth())
+            .max()
+            .unwrap()
+    );
+    assert_eq!(stats.min_leaf_particles, *leaf_counts.iter().min().unwrap());
+    assert_eq!(stats.max_leaf_particles, *leaf_counts.iter().max().unwrap());
+    assert_eq!(
+        stats.num_empty_leaves,
+        leaf_counts.iter().filter(|&&count| count == 0).count()
+    );
+    let expected_mean = particles.len() as f64 / leaf_counts.len() as f64;
+    assert!((stats.mean_leaf_particles - expected_mean).abs() < 1e-10);
+
+    let report = format!("{}", stats);
+    assert!(report.contains(&format!("nodes: {}", stats.num_nodes)));
+    assert!(report.contains(&format!("max depth: {}", stats.max_depth)));
 }
 
-struct TestParameters<R: Real> {
-    particle_radius: R,
-    compact_support_radius: R,
-    cube_size: R,
-    margin: Option<R>,
-    max_particles_per_cell: Option<usize>,
-    compare_seq_par: bool,
+#[test]
+fn octree_balance_2to1() {
+    use rand::{Rng, SeedableRng};
+
+    // A dense cluster of particles in one corner and a few particles spread over the whole domain
+    let mut rng = ChaCha8Rng::seed_from_u64(11);
+    let mut particles = (0..1000)
+        .map(|_| {
+            Vector3::new(
+                rng.gen_range(0.0..0.05),
+                rng.gen_range(0.0..0.05),
+                rng.gen_range(0.0..0.05),
+            )
+        })
+        .collect::<Vec<_>>();
+    particles.extend((0..20).map(|_| {
+        Vector3::new(
+            rng.gen_range(0.0..1.0),
+            rng.gen_range(0.0..1.0),
+            rng.gen_range(0.0..1.0),
+        )
+    }));
+    let grid =
+        grid_for_reconstruction::<i64, _>(&particles, 0.001, 0.004, 0.002, None, 0, None, false)
+            .unwrap();
+
+    let mut octree = Octree::new_subdivided(
+        &grid,
+        &particles,
+        SubdivisionCriterion::MaxParticleCount(10),
+        0.0,
+        false,
+        false,
+    );
+    let max_depth = octree.statistics().max_depth;
+    assert!(max_face_adjacent_depth_difference(&octree) > 1);
+
+    assert!(octree.balance(&grid, &particles, 0.0) > 0);
+    assert!(max_face_adjacent_depth_difference(&octree) <= 1);
+    assert_eq!(octree.statistics().max_depth, max_depth);
+    assert_unique_node_per_particle(particles.as_slice(), &octree);
+
+    // Balancing a balanced tree does not change it
+    assert_eq!(octree.balance(&grid, &particles, 0.0), 0);
 }
 
-impl<R: Real> Default for TestParameters<R> {
-    fn default() -> Self {
-        let particle_radius = R::from_f64(0.025).unwrap();
-        let compact_support_radius = particle_radius.times_f64(4.0);
-        let cube_size = particle_radius.times_f64(0.5);
+/// Returns the maximum depth difference of all pairs of leaves that share a face
+fn max_face_adjacent_depth_difference<I: Index, R: Real>(octree: &Octree<I, R>) -> usize {
+    let leaves = octree
+        .root()
+        .dfs_iter()
+        .filter(|node| node.children().is_empty())
+        .collect::<Vec<_>>();
 
-        Self {
-            particle_radius,
-            compact_support_radius,
-            cube_size,
-            margin: None,
-            max_particles_per_cell: None,
-            compare_seq_par: true,
+    let mut max_difference = 0;
+    for a in leaves.iter() {
+        for b in leaves.iter() {
+            let (a_min, a_max) = (a.min_corner().index(), a.max_corner().index());
+            let (b_min, b_max) = (b.min_corner().index(), b.max_corner().index());
+
+            let shares_face = (0..3).any(|dim| {
+                a_max[dim] == b_min[dim]
+                    && (0..3)
+                        .filter(|&other| other != dim)
+                        .all(|other| a_min[other] < b_max[other] && a_max[other] > b_min[other])
+            });
+            if shares_face {
+                let difference = if a.depth() > b.depth() {
+                    a.depth() - b.depth()
+                } else {
+                    b.depth() - a.depth()
+                };
+                max_difference = max_difference.max(difference);
+            }
         }
     }
+
+    max_difference
 }
 
-impl<R: Real> TestParameters<R> {
-    fn new(particle_radius: f64, compact_support_factor: f64, cube_size_factor: f64) -> Self {
-        let params = Self::default();
-        params.with_parameters(particle_radius, compact_support_factor, cube_size_factor)
-    }
+#[test]
+fn octree_from_cell_assignment() {
+    let particles = io::vtk::particles_from_vtk::<f64, _>(
+        "../data/double_dam_break_frame_26_4732_particles.vtk",
+    )
+    .unwrap();
+    let parameters = TestParameters::<f64>::default();
+    let grid = parameters.build_grid::<i64>(particles.as_slice());
 
-    fn with_margin(mut self, margin: Option<f64>) -> Self {
-        self.margin = margin.map(|m| self.particle_radius.times_f64(m));
-        self
-    }
-    fn with_max_particles_per_cell(mut self, max_particles_per_cell: Option<usize>) -> Self {
-        self.max_particles_per_cell = max_particles_per_cell;
-        self
-    }
+    let cell_indices = particles
+        .iter()
+        .map(|p| grid.get_cell(grid.enclosing_cell(p)).unwrap())
+        .collect::<Vec<_>>();
 
-    fn with_compare_seq_par(mut self, compare_seq_par: bool) -> Self {
-        self.compare_seq_par = compare_seq_par;
-        self
-    }
+    let octree = Octree::new_subdivided(
+        &grid,
+        particles.as_slice(),
+        SubdivisionCriterion::MaxParticleCount(60),
+        0.0,
+        false,
+        false,
+    );
+    let octree_from_cells = Octree::from_cell_assignment(
+        &grid,
+        cell_indices.as_slice(),
+        SubdivisionCriterion::MaxParticleCount(60),
+    );
 
-    fn with_parameters(
-        mut self,
-        particle_radius: f64,
-        compact_support_factor: f64,
-        cube_size_factor: f64,
-    ) -> Self {
-        self.particle_radius = R::from_f64(particle_radius).unwrap();
-        self.compact_support_radius = self.particle_radius.times_f64(compact_support_factor);
-        self.cube_size = self.particle_radius.times_f64(cube_size_factor);
-        self
-    }
+    assert_tree_identical(&octree, &octree_from_cells);
+    assert_eq!(octree_from_cells.root().particle_count(), particles.len());
+}
 
-    fn build_grid<I: Index>(&self, particle_positions: &[Vector3<R>]) -> UniformGrid<I, R> {
-        grid_for_reconstruction(
-            particle_positions,
-            self.particle_radius,
-            self.compact_support_radius,
-            self.cube_size,
-            None,
-            true,
-        )
-        .unwrap()
+#[test]
+fn octree_hexmesh_welded() {
+    let particles = io::vtk::particles_from_vtk::<f64, _>(
+        "../data/double_dam_break_frame_26_4732_particles.vtk",
+    )
+    .unwrap();
+    let parameters = TestParameters::<f64>::default();
+    let grid = parameters.build_grid::<i64>(particles.as_slice());
+
+    let octree = Octree::new_subdivided(
+        &grid,
+        particles.as_slice(),
+        SubdivisionCriterion::MaxParticleCount(60),
+        0.0,
+        false,
+        false,
+    );
+
+    let mesh = octree.hexmesh(&grid, false).mesh;
+    let welded_mesh = octree.hexmesh_welded(&grid, false).mesh;
+
+    assert_eq!(mesh.vertices.len(), 8 * mesh.cells.len());
+    assert!(welded_mesh.vertices.len() < mesh.vertices.len());
+    assert_eq!(welded_mesh.cells.len(), mesh.cells.len());
+    for (cell, welded_cell) in mesh.cells.iter().zip(welded_mesh.cells.iter()) {
+        for (&v, &welded_v) in cell.iter().zip(welded_cell.iter()) {
+            assert_eq!(mesh.vertices[v], welded_mesh.vertices[welded_v]);
+        }
     }
+
+    // Every vertex of the welded mesh is unique
+    let mut vertices = welded_mesh
+        .vertices
+        .iter()
+        .map(|v| [v.x.to_bits(), v.y.to_bits(), v.z.to_bits()])
+        .collect::<Vec<_>>();
+    vertices.sort_unstable();
+    vertices.dedup();
+    assert_eq!(vertices.len(), welded_mesh.vertices.len());
+}
+
+#[test]
+fn octree_tight_bounds() {
+    let particles = vec![
+        Vector3::new(0.1, 0.2, 0.3),
+        Vector3::new(0.15, 0.25, 0.2),
+        Vector3::new(0.12, 0.3, 0.35),
+        Vector3::new(0.8, 0.9, 0.7),
+        Vector3::new(0.85, 0.75, 0.9),
+    ];
+    let grid =
+        grid_for_reconstruction::<i64, _>(&particles, 0.01, 0.04, 0.02, None, 0, None, false)
+            .unwrap();
+
+    // Split the root once, so that the two clusters of particles end up in different leaves
+    let mut octree = Octree::new_subdivided(
+        &grid,
+        &particles,
+        SubdivisionCriterion::MaxParticleCount(3),
+        0.0,
+        false,
+        false,
+    );
+    assert!(octree.root().tight_aabb().is_none());
+    octree.compute_tight_bounds(&particles);
+
+    let first_leaf = octree.find_leaf_at(&grid, &particles[0]).unwrap();
+    assert_eq!(
+        first_leaf.tight_aabb(),
+        Some(&AxisAlignedBoundingBox3d::new(
+            Vector3::new(0.1, 0.2, 0.2),
+            Vector3::new(0.15, 0.3, 0.35)
+        ))
+    );
+
+    let last_leaf = octree.find_leaf_at(&grid, &particles[4]).unwrap();
+    assert_eq!(
+        last_leaf.tight_aabb(),
+        Some(&AxisAlignedBoundingBox3d::new(
+            Vector3::new(0.8, 0.75, 0.7),
+            Vector3::new(0.85, 0.9, 0.9)
+        ))
+    );
+
+    assert_eq!(
+        octree.root().tight_aabb(),
+        Some(&AxisAlignedBoundingBox3d::new(
+            Vector3::new(0.1, 0.2, 0.2),
+            Vector3::new(0.85, 0.9, 0.9)
+        ))
+    );
+
+    // Leaves without particles do not have a tight AABB
+    assert!(octree
+        .root()
+        .dfs_iter()
+        .filter(|node| node.particle_count() == 0)
+        .all(|node| node.tight_aabb().is_none()));
+}
+
+#[test]
+fn octree_leaves() {
+    let particles = io::vtk::particles_from_vtk::<f64, _>(
+        "../data/double_dam_break_frame_26_4732_particles.vtk",
+    )
+    .unwrap();
+    let parameters = TestParameters::<f64>::default();
+    let grid = parameters.build_grid::<i64>(particles.as_slice());
+
+    let octree = Octree::new_subdivided(
+        &grid,
+        particles.as_slice(),
+        SubdivisionCriterion::MaxParticleCount(60),
+        0.0,
+        false,
+        false,
+    );
+
+    let dfs_leaf_ids = octree
+        .root()
+        .dfs_iter()
+        .filter(|node| node.children().is_empty())
+        .map(|node| node.id())
+        .collect::<Vec<_>>();
+    let leaf_ids = octree.leaves().map(|node| node.id()).collect::<Vec<_>>();
+
+    assert!(leaf_ids.len() > 1);
+    assert_eq!(leaf_ids.len(), octree.statistics().num_leaves);
+    assert_eq!(leaf_ids, dfs_leaf_ids);
 }
 
 /// Returns a vector containing per particle how often it is a non-ghost particle in the octree
@@ -298,6 +1512,37 @@ fn assert_tree_equivalence<I: Index, R: Real>(left_tree: &Octree<I, R>, right_tr
     }
 }
 
+/// Asserts that the trees have the same structure and leaves with identical particle lists (including the order of the particles)
+fn assert_tree_identical<I: Index, R: Real>(left_tree: &Octree<I, R>, right_tree: &Octree<I, R>) {
+    let left_nodes = left_tree.root().dfs_iter().collect::<Vec<_>>();
+    let right_nodes = right_tree.root().dfs_iter().collect::<Vec<_>>();
+    assert_eq!(left_nodes.len(), right_nodes.len());
+
+    for (left, right) in left_nodes.into_iter().zip(right_nodes) {
+        assert_eq!(left.children().len(), right.children().len());
+        assert_eq!(left.min_corner(), right.min_corner());
+        assert_eq!(left.max_corner(), right.max_corner());
+        assert_eq!(left.aabb(), right.aabb());
+
+        match (left.data().particle_set(), right.data().particle_set()) {
+            (Some(left_particles), Some(right_particles)) => {
+                assert_eq!(
+                    left_particles.particles.as_slice(),
+                    right_particles.particles.as_slice()
+                );
+                assert_eq!(
+                    left_particles.ghost_particle_count,
+                    right_particles.ghost_particle_count
+                );
+            }
+            (None, None) => {}
+            _ => panic!(
+                "Encountered a node where one octree has a particle set but the other does not!"
+            ),
+        }
+    }
+}
+
 fn build_octree_par_consistency<I: Index, R: Real, P: AsRef<Path>>(
     file: P,
     parameters: TestParameters<R>,
@@ -340,6 +1585,18 @@ fn build_octree_par_consistency<I: Index, R: Real, P: AsRef<Path>>(
 
     assert_unique_node_per_particle(particles.as_slice(), &octree_par);
 
+    let octree_low_memory = Octree::new_subdivided_low_memory(
+        &grid,
+        particles.as_slice(),
+        parameters
+            .max_particles_per_cell
+            .map(SubdivisionCriterion::MaxParticleCount)
+            .unwrap_or(SubdivisionCriterion::MaxParticleCountAuto),
+        parameters.margin.unwrap_or(R::zero()),
+        false,
+    );
+    assert_tree_equivalence(&octree_par, &octree_low_memory);
+
     if let Some(octree_seq) = octree_seq {
         assert_tree_equivalence(&octree_seq, &octree_par)
     }
@@ -355,6 +1612,33 @@ fn build_octree_cube() {
     );
 }
 
+#[test]
+fn build_octree_seq_par_identical() {
+    let particles = io::vtk::particles_from_vtk::<f64, _>(
+        "../data/double_dam_break_frame_26_4732_particles.vtk",
+    )
+    .unwrap();
+    let parameters = TestParameters::<f64>::default();
+    let grid = parameters.build_grid::<i64>(particles.as_slice());
+    let margin = parameters.compact_support_radius;
+
+    let build = |enable_multi_threading: bool| {
+        Octree::new_subdivided(
+            &grid,
+            particles.as_slice(),
+            SubdivisionCriterion::MaxParticleCount(200),
+            margin,
+            enable_multi_threading,
+            false,
+        )
+    };
+
+    let octree_seq = build(false);
+    let octree_par = build(true);
+    assert!(octree_seq.root().dfs_iter().count() > 1);
+    assert_tree_identical(&octree_seq, &octree_par);
+}
+
 #[test]
 fn build_octree_double_dam_break() {
     build_octree_par_consistency::<i64, f64, _>(
@@ -0,0 +1,106 @@
+//! Memory usage regression test of the octree construction
+//!
+//! This test is a separate test binary as it replaces the global allocator to track the peak memory
+//! usage, which would be disturbed by other tests running in parallel in the same process.
+
+use splashsurf_lib::generic_tree::VisitableTree;
+use splashsurf_lib::nalgebra::Vector3;
+use splashsurf_lib::octree::Octree;
+use splashsurf_lib::{SubdivisionCriterion, UniformGrid};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Allocator that keeps track of the current and peak number of allocated bytes
+struct PeakTrackingAllocator {
+    current: AtomicUsize,
+    peak: AtomicUsize,
+}
+
+unsafe impl GlobalAlloc for PeakTrackingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let current = self.current.fetch_add(layout.size(), Ordering::SeqCst) + layout.size();
+            self.peak.fetch_max(current, Ordering::SeqCst);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        self.current.fetch_sub(layout.size(), Ordering::SeqCst);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: PeakTrackingAllocator = PeakTrackingAllocator {
+    current: AtomicUsize::new(0),
+    peak: AtomicUsize::new(0),
+};
+
+/// Returns the result of the closure and the peak number of bytes allocated in addition to the memory allocated before the call
+fn measure_peak_memory<T, F: FnOnce() -> T>(f: F) -> (T, usize) {
+    let baseline = ALLOCATOR.current.load(Ordering::SeqCst);
+    ALLOCATOR.peak.store(baseline, Ordering::SeqCst);
+    let result = f();
+    (result, ALLOCATOR.peak.load(Ordering::SeqCst) - baseline)
+}
+
+#[test]
+fn octree_low_memory_build_peak() {
+    let n = 64;
+    let spacing = 0.01;
+    let mut particles = Vec::with_capacity(n * n * n);
+    for i in 0..n {
+        for j in 0..n {
+            for k in 0..n {
+                particles.push(Vector3::new(i as f64, j as f64, k as f64) * spacing);
+            }
+        }
+    }
+
+    let n_cells = (n + 2) as i64;
+    let grid = UniformGrid::<i64, f64>::new(
+        &Vector3::repeat(-spacing),
+        &[n_cells, n_cells, n_cells],
+        spacing,
+    )
+    .unwrap();
+    let margin = 0.5 * spacing;
+    let criterion = || SubdivisionCriterion::MaxParticleCount(10000);
+
+    let (octree, in_memory_peak) = measure_peak_memory(|| {
+        Octree::new_subdivided(&grid, &particles, criterion(), margin, false, false)
+    });
+    let num_leaf_particles = |octree: &Octree<i64, f64>| {
+        octree
+            .root()
+            .dfs_iter()
+            .filter_map(|node| node.data().particle_set())
+            .map(|particle_set| particle_set.particles.len())
+            .sum::<usize>()
+    };
+    let expected_leaf_particles = num_leaf_particles(&octree);
+    drop(octree);
+
+    let (octree, low_memory_peak) = measure_peak_memory(|| {
+        Octree::new_subdivided_low_memory(&grid, &particles, criterion(), margin, false)
+    });
+    assert_eq!(num_leaf_particles(&octree), expected_leaf_particles);
+
+    // The peak of the in-memory build is dominated by the root list, the classification of all
+    // particles and the lists of the first level of children, the low memory build mostly by the final leaves
+    let leaf_storage = expected_leaf_particles * std::mem::size_of::<usize>();
+    assert!(
+        low_memory_peak < in_memory_peak * 3 / 4,
+        "peak memory of the low memory build ({} bytes) is not significantly lower than of the in-memory build ({} bytes)",
+        low_memory_peak,
+        in_memory_peak
+    );
+    assert!(
+        low_memory_peak < leaf_storage * 3 / 2,
+        "peak memory of the low memory build ({} bytes) exceeds the leaf storage ({} bytes) by more than 50%",
+        low_memory_peak,
+        leaf_storage
+    );
+}